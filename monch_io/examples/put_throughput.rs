@@ -0,0 +1,48 @@
+//! Compare the throughput of writing objects straight to stdout with the buffered `put!`, like a
+//! `seq 1 1000000 | count` pipeline would.
+//!
+//! Run it with stdout piped to another process, which is the case that matters most, or pointed at
+//! a file:
+//!
+//! ```sh
+//! cargo run --release --example put_throughput | cat >/dev/null
+//! cargo run --release --example put_throughput >/tmp/items.cbor
+//! ```
+
+use monch_io::{ciborium, flush, put};
+use std::io::{self, Write};
+use std::time::Instant;
+
+const ITEMS: u64 = 1_000_000;
+
+fn main() {
+    // Before: a fresh, unbuffered stdout write for every item.
+    let start = Instant::now();
+    for i in 0..ITEMS {
+        ciborium::ser::into_writer(&i, io::stdout()).expect("write failed");
+    }
+    let unbuffered = start.elapsed();
+
+    // Buffered, but flushed after every item, which costs a write for each one all the same.
+    let start = Instant::now();
+    let mut stdout = io::BufWriter::new(io::stdout());
+    for i in 0..ITEMS {
+        ciborium::ser::into_writer(&i, &mut stdout).expect("write failed");
+        stdout.flush().expect("flush failed");
+    }
+    drop(stdout);
+    let flushed = start.elapsed();
+
+    // After: every item goes through the shared buffered stdout.
+    let start = Instant::now();
+    for i in 0..ITEMS {
+        put!(&i);
+    }
+    flush().expect("flush failed");
+    let buffered = start.elapsed();
+
+    eprintln!("{} items", ITEMS);
+    eprintln!("unbuffered:          {:?}", unbuffered);
+    eprintln!("flushed each item:   {:?}", flushed);
+    eprintln!("buffered (put!):     {:?}", buffered);
+}
@@ -0,0 +1,529 @@
+//! Async versions of the structured IO functions, for programs running on tokio.
+//!
+//! Reading only speaks CBOR. It works by buffering bytes from the reader, and decoding an item
+//! once the buffer holds all of it.
+
+use crate::header::HEADER_FIRST_BYTE;
+use crate::{closed_if_broken_pipe, write_to, Error, StreamHeader, Value, HEADER_TAG};
+use futures_core::Stream;
+use serde::{de::DeserializeOwned, Serialize};
+use std::io;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+/// The outcome of trying to decode an item from the front of a buffer.
+enum Decoded<T> {
+    /// A whole item, and how many bytes it took up.
+    Item(T, usize),
+
+    /// The buffer ends partway through an item, which needs at least this many more bytes.
+    Incomplete(usize),
+
+    /// The buffer starts with something which isn't valid CBOR.
+    Invalid(ciborium::de::Error<io::Error>),
+}
+
+/// The bytes of an item read so far, split in two so that neither part needs copying. When the
+/// decoder asks for more than is left, this notes how many bytes it was short by.
+struct Parts<'a> {
+    parts: [&'a [u8]; 2],
+    read: usize,
+    short: usize,
+}
+
+impl io::Read for Parts<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let part = if self.parts[0].is_empty() {
+            &mut self.parts[1]
+        } else {
+            &mut self.parts[0]
+        };
+        let len = io::Read::read(part, buf)?;
+        self.read += len;
+        Ok(len)
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let left = self.parts[0].len() + self.parts[1].len();
+        if buf.len() > left {
+            self.short = buf.len() - left;
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let (head, tail) = buf.split_at_mut(buf.len().min(self.parts[0].len()));
+        io::Read::read_exact(&mut self.parts[0], head)?;
+        io::Read::read_exact(&mut self.parts[1], tail)?;
+        self.read += buf.len();
+        Ok(())
+    }
+}
+
+/// Decode an item from the front of `first` followed by `second`.
+fn decode<T: DeserializeOwned>(first: &[u8], second: &[u8]) -> Decoded<T> {
+    let mut parts = Parts {
+        parts: [first, second],
+        read: 0,
+        short: 0,
+    };
+    match ciborium::de::from_reader(&mut parts) {
+        Ok(item) => Decoded::Item(item, parts.read),
+        Err(ciborium::de::Error::Io(e)) if e.kind() == io::ErrorKind::UnexpectedEof => {
+            Decoded::Incomplete(parts.short.max(1))
+        }
+        Err(e) => Decoded::Invalid(e),
+    }
+}
+
+/// Read a series of deserializable objects from an [`AsyncRead`], as a [`Stream`].
+///
+/// # Cancel safety
+///
+/// Polling the stream is cancel-safe: every byte read is kept in the parser's own buffer until
+/// it's part of a returned item. So if a `next()` future is dropped before it completes, as in a
+/// `tokio::select!` branch that loses, no input is lost and the next call picks up where it left
+/// off.
+///
+/// A partial item is decoded again from the start once enough bytes arrive to get further
+/// through it, so very large items which trickle in will take a while.
+pub struct AsyncInputParser<T, R> {
+    reader: R,
+
+    /// Bytes read but not yet returned as part of an item.
+    buffer: Vec<u8>,
+
+    /// How long the buffer has to be before it's worth trying to decode it again.
+    wanted: usize,
+
+    /// Set once the reader reaches the end of its input.
+    eof: bool,
+
+    /// Set when the last item was corrupt, and the reader is misaligned.
+    failed: bool,
+
+    /// The most bytes a single item can take up, if limited.
+    max_item_bytes: Option<usize>,
+
+    /// The header at the start of the stream, if there was one.
+    header: Option<StreamHeader>,
+
+    /// Set once we've looked for a header.
+    header_checked: bool,
+
+    // fn() -> T, so that the parser is Unpin whatever T is.
+    _phantom_type: PhantomData<fn() -> T>,
+}
+
+impl<T, R: AsyncRead + Unpin> AsyncInputParser<T, R> {
+    /// Create an AsyncInputParser reading a stream of CBOR items.
+    pub fn new(reader: R) -> Self {
+        AsyncInputParser {
+            reader,
+            buffer: Vec::new(),
+            wanted: 1,
+            eof: false,
+            failed: false,
+            max_item_bytes: None,
+            header: None,
+            header_checked: false,
+            _phantom_type: Default::default(),
+        }
+    }
+
+    /// Fail with [`Error::ItemTooLarge`] on any item larger than `limit` bytes, instead of
+    /// buffering it in memory.
+    pub fn with_max_item_size(mut self, limit: usize) -> Self {
+        self.max_item_bytes = Some(limit);
+        self
+    }
+
+    /// The [`StreamHeader`] at the start of the stream, if there is one. This is only known once
+    /// the first item has been read.
+    pub fn header(&self) -> Option<&StreamHeader> {
+        self.header.as_ref()
+    }
+
+    fn too_large(&self, len: usize) -> bool {
+        matches!(self.max_item_bytes, Some(limit) if len > limit)
+    }
+
+    fn item_too_large(&self) -> Error {
+        Error::ItemTooLarge {
+            limit: self.max_item_bytes.unwrap_or_default(),
+        }
+    }
+
+    /// Read more input into the buffer. Returns `Ok(false)` at the end of the input.
+    fn poll_fill(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<bool>> {
+        let mut chunk = [0u8; 4096];
+        let mut read_buf = ReadBuf::new(&mut chunk);
+        match Pin::new(&mut self.reader).poll_read(cx, &mut read_buf) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+            Poll::Ready(Ok(())) => {
+                let filled = read_buf.filled();
+                self.buffer.extend_from_slice(filled);
+                Poll::Ready(Ok(!filled.is_empty()))
+            }
+        }
+    }
+}
+
+impl<T: DeserializeOwned, R> AsyncInputParser<T, R> {
+    /// Decode the item at the front of the buffer. The first item might be a header, so it's
+    /// decoded as a [`Value`] first, in case `T` can't hold one.
+    fn decode_front(&self) -> Decoded<Result<T, Value>> {
+        if self.header_checked || self.buffer[0] != HEADER_FIRST_BYTE {
+            return match decode(&self.buffer, &[]) {
+                Decoded::Item(item, len) => Decoded::Item(Ok(item), len),
+                Decoded::Incomplete(needed) => Decoded::Incomplete(needed),
+                Decoded::Invalid(e) => Decoded::Invalid(e),
+            };
+        }
+
+        match decode(&self.buffer, &[]) {
+            Decoded::Item(value, len) => Decoded::Item(Err(value), len),
+            Decoded::Incomplete(needed) => Decoded::Incomplete(needed),
+            Decoded::Invalid(e) => Decoded::Invalid(e),
+        }
+    }
+
+    /// Take the first item of the stream, which was decoded as a [`Value`]. Returns `None` if it
+    /// was a header, which isn't an item itself.
+    fn take_first(&mut self, value: Value) -> Option<Result<T, Error>> {
+        match value {
+            Value::Tag(HEADER_TAG, inner) => match StreamHeader::from_inner(*inner) {
+                Some(header) => {
+                    self.header = Some(header);
+                    None
+                }
+                None => {
+                    self.failed = true;
+                    Some(Err(Error::InvalidHeader))
+                }
+            },
+
+            // Something else which happens to start the same way.
+            value => Some(value.deserialized().map_err(Error::ConstructValue)),
+        }
+    }
+}
+
+impl<T: DeserializeOwned, R: AsyncRead + Unpin> Stream for AsyncInputParser<T, R> {
+    type Item = Result<T, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.failed {
+            return Poll::Ready(None);
+        }
+
+        loop {
+            if this.buffer.len() >= this.wanted || (this.eof && !this.buffer.is_empty()) {
+                match this.decode_front() {
+                    Decoded::Item(_, len) if this.too_large(len) => {
+                        this.failed = true;
+                        return Poll::Ready(Some(Err(this.item_too_large())));
+                    }
+
+                    Decoded::Item(item, len) => {
+                        this.buffer.drain(..len);
+                        this.wanted = 1;
+                        this.header_checked = true;
+
+                        match item {
+                            Ok(item) => return Poll::Ready(Some(Ok(item))),
+                            Err(first) => match this.take_first(first) {
+                                Some(item) => return Poll::Ready(Some(item)),
+                                None => continue,
+                            },
+                        }
+                    }
+
+                    Decoded::Invalid(e) => {
+                        this.failed = true;
+                        return Poll::Ready(Some(Err(Error::Deserialize(e))));
+                    }
+
+                    Decoded::Incomplete(_) if this.eof => {
+                        // The input ended partway through an item.
+                        this.failed = true;
+                        let e = io::Error::from(io::ErrorKind::UnexpectedEof);
+                        return Poll::Ready(Some(Err(Error::Deserialize(e.into()))));
+                    }
+
+                    Decoded::Incomplete(_) if this.too_large(this.buffer.len()) => {
+                        this.failed = true;
+                        return Poll::Ready(Some(Err(this.item_too_large())));
+                    }
+
+                    Decoded::Incomplete(needed) => this.wanted = this.buffer.len() + needed,
+                }
+            } else if this.eof {
+                return Poll::Ready(None);
+            }
+
+            match this.poll_fill(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Ok(more)) => this.eof = !more,
+                Poll::Ready(Err(e)) => {
+                    this.failed = true;
+                    return Poll::Ready(Some(Err(Error::Io(e))));
+                }
+            }
+        }
+    }
+}
+
+/// Read a deserializable object from structured stdin, asynchronously.
+///
+/// This reads stdin a byte at a time, so that nothing past the object is consumed. To read many
+/// objects, use an [`AsyncInputParser`] instead.
+pub async fn read_one_async<T: DeserializeOwned>() -> Result<T, Error> {
+    let mut stdin = tokio::io::BufReader::with_capacity(1, tokio::io::stdin());
+    read_one_async_from(&mut stdin).await
+}
+
+/// Read a deserializable object from any [`AsyncBufRead`], asynchronously. Only the bytes making
+/// up the object are consumed from the reader.
+pub async fn read_one_async_from<R: AsyncBufRead + Unpin, T: DeserializeOwned>(
+    reader: &mut R,
+) -> Result<T, Error> {
+    // The part of the object which has already been consumed from the reader.
+    let mut partial = Vec::new();
+
+    // How many more bytes the object needs, at least.
+    let mut needed = 0;
+
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            let e = io::Error::from(io::ErrorKind::UnexpectedEof);
+            return Err(Error::Deserialize(e.into()));
+        }
+
+        if available.len() >= needed {
+            match decode(&partial, available) {
+                Decoded::Item(item, len) => {
+                    reader.consume(len - partial.len());
+                    return Ok(item);
+                }
+                Decoded::Invalid(e) => return Err(Error::Deserialize(e)),
+                Decoded::Incomplete(more) => needed = available.len() + more,
+            }
+        }
+
+        // All of these bytes are part of the object, so they're safe to consume.
+        let len = available.len();
+        partial.extend_from_slice(available);
+        reader.consume(len);
+        needed -= len;
+    }
+}
+
+/// Write a serializable object to structured stdout, asynchronously, and flush it.
+///
+/// Anything buffered by the sync [`write`](crate::write) is flushed first, so that output stays
+/// in order.
+pub async fn write_async<T: Serialize>(object: &T) -> Result<(), Error> {
+    crate::flush()?;
+
+    let mut stdout = tokio::io::stdout();
+    write_async_to(&mut stdout, object).await?;
+    stdout
+        .flush()
+        .await
+        .map_err(|e| closed_if_broken_pipe(e.into()))
+}
+
+/// Write a serializable object to any [`AsyncWrite`], in the process's
+/// [`output_format`](crate::output_format). This doesn't flush the writer.
+pub async fn write_async_to<W: AsyncWrite + Unpin, T: Serialize>(
+    writer: &mut W,
+    object: &T,
+) -> Result<(), Error> {
+    let mut buf = Vec::new();
+    write_to(&mut buf, object)?;
+    writer
+        .write_all(&buf)
+        .await
+        .map_err(|e| closed_if_broken_pipe(e.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cbor, Value};
+    use std::future::Future;
+    use std::time::Duration;
+
+    fn cbor_bytes(items: &[Value]) -> Vec<u8> {
+        let mut buf = vec![];
+        for item in items {
+            ciborium::ser::into_writer(item, &mut buf).unwrap();
+        }
+        buf
+    }
+
+    /// A future for the next item in a stream, like `StreamExt::next`.
+    struct Next<'a, S>(&'a mut S);
+
+    impl<S: Stream + Unpin> Future for Next<'_, S> {
+        type Output = Option<S::Item>;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            Pin::new(&mut *self.0).poll_next(cx)
+        }
+    }
+
+    fn next<S: Stream + Unpin>(stream: &mut S) -> Next<'_, S> {
+        Next(stream)
+    }
+
+    #[tokio::test]
+    async fn reads_items() {
+        let items = vec![
+            Value::Integer(1.into()),
+            Value::Text("two".into()),
+            Value::Array(vec![Value::Bool(true), Value::Null]),
+        ];
+        let bytes = cbor_bytes(&items);
+
+        let mut parser = AsyncInputParser::<Value, _>::new(&bytes[..]);
+        let mut read = vec![];
+        while let Some(item) = next(&mut parser).await {
+            read.push(item.unwrap());
+        }
+
+        assert_eq!(read, items);
+    }
+
+    #[tokio::test]
+    async fn truncated_item() {
+        let bytes = cbor_bytes(&[Value::Integer(1.into()), Value::Text("truncated".into())]);
+        let mut parser = AsyncInputParser::<Value, _>::new(&bytes[..bytes.len() - 2]);
+
+        assert_eq!(
+            next(&mut parser).await.unwrap().unwrap(),
+            Value::Integer(1.into())
+        );
+        assert!(next(&mut parser).await.unwrap().is_err());
+        assert!(next(&mut parser).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn item_too_large() {
+        let bytes = cbor_bytes(&[Value::Bytes(vec![0; 100])]);
+        let mut parser = AsyncInputParser::<Value, _>::new(&bytes[..]).with_max_item_size(10);
+
+        assert!(matches!(
+            next(&mut parser).await,
+            Some(Err(Error::ItemTooLarge { limit: 10 }))
+        ));
+    }
+
+    #[tokio::test]
+    async fn header_is_not_an_item() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Entry {
+            name: String,
+        }
+
+        let header = StreamHeader::new("entry", &["name"]);
+        let items = vec![
+            cbor!({"name" => "a"}).unwrap(),
+            cbor!({"name" => "b"}).unwrap(),
+        ];
+        let mut bytes = cbor_bytes(&[header.to_value()]);
+        bytes.extend(cbor_bytes(&items));
+
+        let mut parser = AsyncInputParser::<Value, _>::new(&bytes[..]);
+        assert_eq!(parser.header(), None);
+        assert_eq!(next(&mut parser).await.unwrap().unwrap(), items[0]);
+        assert_eq!(parser.header(), Some(&header));
+        assert_eq!(next(&mut parser).await.unwrap().unwrap(), items[1]);
+        assert!(next(&mut parser).await.is_none());
+
+        // Items which can't hold a header are read just the same.
+        let mut parser = AsyncInputParser::<Entry, _>::new(&bytes[..]);
+        let mut names = vec![];
+        while let Some(entry) = next(&mut parser).await {
+            names.push(entry.unwrap().name);
+        }
+        assert_eq!(names, ["a", "b"]);
+        assert_eq!(parser.header(), Some(&header));
+
+        // Other tags which look like headers at first are items.
+        let tagged = Value::Tag(0x1234_5678, Box::new(Value::Null));
+        let bytes = cbor_bytes(std::slice::from_ref(&tagged));
+        let mut parser = AsyncInputParser::<Value, _>::new(&bytes[..]);
+        assert_eq!(next(&mut parser).await.unwrap().unwrap(), tagged);
+        assert_eq!(parser.header(), None);
+    }
+
+    #[tokio::test]
+    async fn next_is_cancel_safe() {
+        let items = vec![Value::Text("first".into()), Value::Text("second".into())];
+        let bytes = cbor_bytes(&items);
+        let (mut tx, rx) = tokio::io::duplex(64);
+        let mut parser = AsyncInputParser::<Value, _>::new(rx);
+
+        // Send the first item and half of the second.
+        let split = bytes.len() - 3;
+        tx.write_all(&bytes[..split]).await.unwrap();
+
+        assert_eq!(next(&mut parser).await.unwrap().unwrap(), items[0]);
+
+        // Start reading the second item, then give up on it while it's incomplete.
+        let timed_out = tokio::time::timeout(Duration::from_millis(10), next(&mut parser)).await;
+        assert!(timed_out.is_err());
+
+        // The rest of the second item arrives, and none of the first half was lost.
+        tx.write_all(&bytes[split..]).await.unwrap();
+        drop(tx);
+
+        assert_eq!(next(&mut parser).await.unwrap().unwrap(), items[1]);
+        assert!(next(&mut parser).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn read_one_leaves_the_rest() {
+        let bytes = cbor_bytes(&[Value::Integer(1.into()), Value::Integer(2.into())]);
+        let mut reader = tokio::io::BufReader::with_capacity(3, &bytes[..]);
+
+        let first: u32 = read_one_async_from(&mut reader).await.unwrap();
+        let second: u32 = read_one_async_from(&mut reader).await.unwrap();
+        assert_eq!((first, second), (1, 2));
+        assert!(read_one_async_from::<_, u32>(&mut reader).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn items_a_byte_at_a_time() {
+        let items = vec![Value::Bytes(vec![7; 10_000]), Value::Text("after".into())];
+        let bytes = cbor_bytes(&items);
+
+        let mut reader = tokio::io::BufReader::with_capacity(1, &bytes[..]);
+        let first: Value = read_one_async_from(&mut reader).await.unwrap();
+        let second: Value = read_one_async_from(&mut reader).await.unwrap();
+        assert_eq!(vec![first, second], items);
+
+        let (mut tx, rx) = tokio::io::duplex(1);
+        let mut parser = AsyncInputParser::<Value, _>::new(rx);
+        tokio::spawn(async move { tx.write_all(&bytes).await.unwrap() });
+
+        assert_eq!(next(&mut parser).await.unwrap().unwrap(), items[0]);
+        assert_eq!(next(&mut parser).await.unwrap().unwrap(), items[1]);
+        assert!(next(&mut parser).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn write_then_read() {
+        let mut buf = vec![];
+        write_async_to(&mut buf, &"hello").await.unwrap();
+
+        let mut reader = &buf[..];
+        let read: String = read_one_async_from(&mut reader).await.unwrap();
+        assert_eq!(read, "hello");
+    }
+}
@@ -0,0 +1,83 @@
+//! Deterministic CBOR encoding, following the "core deterministic encoding" rules of RFC 8949
+//! section 4.2.1.
+//!
+//! ciborium already writes integers and lengths in their shortest form, and uses definite lengths
+//! for [`Value`]s, so all that's left for us is to sort map keys by the bytes of their encodings.
+
+use ciborium::value::Value;
+
+/// Rearrange a value so that it has a deterministic encoding, by recursively sorting its map keys.
+pub fn canonicalize(value: Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.into_iter().map(canonicalize).collect()),
+
+        Value::Map(pairs) => {
+            // Sort by the encoded bytes of each key, which themselves need to be deterministic.
+            let mut keyed: Vec<(Vec<u8>, (Value, Value))> = pairs
+                .into_iter()
+                .map(|(k, v)| {
+                    let k = canonicalize(k);
+                    let v = canonicalize(v);
+                    (encode(&k), (k, v))
+                })
+                .collect();
+            keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            Value::Map(keyed.into_iter().map(|(_bytes, pair)| pair).collect())
+        }
+
+        Value::Tag(tag, inner) => Value::Tag(tag, Box::new(canonicalize(*inner))),
+
+        other => other,
+    }
+}
+
+/// Encode a value into CBOR bytes.
+fn encode(value: &Value) -> Vec<u8> {
+    let mut bytes = vec![];
+    ciborium::ser::into_writer(value, &mut bytes).expect("writing to a Vec can't fail");
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ciborium::cbor;
+
+    #[test]
+    fn key_order_does_not_matter() {
+        let a = cbor!({
+            "name" => "file",
+            "kind" => "File",
+            "meta" => { "size" => 12, "mode" => 420, 10 => "ten", 1 => "one" },
+        })
+        .unwrap();
+
+        let b = cbor!({
+            "meta" => { 1 => "one", "mode" => 420, 10 => "ten", "size" => 12 },
+            "kind" => "File",
+            "name" => "file",
+        })
+        .unwrap();
+
+        assert_ne!(encode(&a), encode(&b));
+        assert_eq!(encode(&canonicalize(a)), encode(&canonicalize(b)));
+    }
+
+    #[test]
+    fn keys_sorted_by_encoding() {
+        // Shorter encodings sort first, then bytewise: integers (major type 0) before text.
+        let value = cbor!({ "bb" => 1, "a" => 2, 100 => 3, 1 => 4, "c" => 5 }).unwrap();
+        let expected = cbor!({ 1 => 4, 100 => 3, "a" => 2, "c" => 5, "bb" => 1 }).unwrap();
+
+        assert_eq!(canonicalize(value), expected);
+    }
+
+    #[test]
+    fn nested_in_arrays_and_tags() {
+        let value = Value::Tag(7, Box::new(cbor!([{ "b" => 1, "a" => 2 }]).unwrap()));
+        let expected = Value::Tag(7, Box::new(cbor!([{ "a" => 2, "b" => 1 }]).unwrap()));
+
+        assert_eq!(canonicalize(value), expected);
+    }
+}
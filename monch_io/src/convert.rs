@@ -0,0 +1,274 @@
+//! Conversions between CBOR [`Value`]s and JSON values.
+//!
+//! JSON can represent less than CBOR, so converting to JSON can fail:
+//!
+//! - Integers must fit in an `i64` or a `u64`.
+//! - Byte strings are an error, rather than being encoded into some text representation.
+//! - Floats must be finite, because JSON has no NaN or infinity.
+//! - Tags are dropped, leaving just the tagged value.
+//! - Map keys which aren't strings are rendered into strings. Integers, floats, bools, and null
+//!   are written just like their JSON representation (so `1` becomes `"1"`), and arrays and maps
+//!   are written as compact JSON text.
+//! - Maps keep their order. If several keys render to the same string, the last value wins, in
+//!   the place of the first.
+//!
+//! Every JSON value can be converted to CBOR.
+
+use ciborium::value::Value;
+use serde_json::Value as JsonValue;
+use thiserror::Error;
+
+/// An error converting a CBOR value into JSON.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum ConvertError {
+    #[error("integer {0} is too large for JSON")]
+    IntegerOutOfRange(i128),
+
+    #[error("cannot convert binary data to JSON")]
+    Bytes,
+
+    #[error("cannot convert non-finite float {0} to JSON")]
+    NonFiniteFloat(f64),
+
+    #[error("cannot convert unknown CBOR value to JSON")]
+    Unknown,
+}
+
+/// Convert a CBOR value into a JSON value. See the [module docs](self) for what can't be
+/// converted.
+pub fn cbor_to_json(value: Value) -> Result<JsonValue, ConvertError> {
+    let json = match value {
+        Value::Null => JsonValue::Null,
+        Value::Bool(b) => JsonValue::Bool(b),
+        Value::Text(s) => JsonValue::String(s),
+        Value::Integer(i) => JsonValue::Number(integer_to_json(i)?),
+        Value::Float(f) => JsonValue::Number(float_to_json(f)?),
+        Value::Bytes(_) => return Err(ConvertError::Bytes),
+
+        // Drop tags, keeping the inner value.
+        Value::Tag(_tag, inner) => cbor_to_json(*inner)?,
+
+        Value::Array(items) => JsonValue::Array(
+            items
+                .into_iter()
+                .map(cbor_to_json)
+                .collect::<Result<_, _>>()?,
+        ),
+
+        Value::Map(pairs) => {
+            let mut object = serde_json::Map::new();
+            for (k, v) in pairs {
+                object.insert(cbor_to_text(k)?, cbor_to_json(v)?);
+            }
+            JsonValue::Object(object)
+        }
+
+        // Value is non-exhaustive.
+        _ => return Err(ConvertError::Unknown),
+    };
+
+    Ok(json)
+}
+
+/// Convert a JSON value into a CBOR value. This always succeeds.
+pub fn json_to_cbor(value: JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(b) => Value::Bool(b),
+        JsonValue::String(s) => Value::Text(s),
+
+        JsonValue::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                Value::Integer(u.into())
+            } else if let Some(i) = n.as_i64() {
+                Value::Integer(i.into())
+            } else {
+                // Every JSON number fits in one of u64, i64, or f64.
+                Value::Float(n.as_f64().unwrap_or(f64::NAN))
+            }
+        }
+
+        JsonValue::Array(items) => Value::Array(items.into_iter().map(json_to_cbor).collect()),
+
+        JsonValue::Object(object) => Value::Map(
+            object
+                .into_iter()
+                .map(|(k, v)| (Value::Text(k), json_to_cbor(v)))
+                .collect(),
+        ),
+    }
+}
+
+fn integer_to_json(i: ciborium::value::Integer) -> Result<serde_json::Number, ConvertError> {
+    if let Ok(u) = u64::try_from(i) {
+        return Ok(u.into());
+    }
+
+    if let Ok(signed) = i64::try_from(i) {
+        return Ok(signed.into());
+    }
+
+    Err(ConvertError::IntegerOutOfRange(i128::from(i)))
+}
+
+fn float_to_json(f: f64) -> Result<serde_json::Number, ConvertError> {
+    serde_json::Number::from_f64(f).ok_or(ConvertError::NonFiniteFloat(f))
+}
+
+/// Render a CBOR value as plain text, like for a JSON object key or a line of `to text`. Text is
+/// used as it is, and everything else is written as compact JSON.
+pub fn cbor_to_text(value: Value) -> Result<String, ConvertError> {
+    match value {
+        // Strings are used as-is.
+        Value::Text(s) => Ok(s),
+
+        // Tags are dropped, just like for values.
+        Value::Tag(_tag, inner) => cbor_to_text(*inner),
+
+        // Everything else uses its compact JSON representation.
+        other => Ok(cbor_to_json(other)?.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ciborium::cbor;
+    use serde_json::json;
+
+    #[test]
+    fn json_round_trip() {
+        let values = vec![
+            json!(null),
+            json!(true),
+            json!(false),
+            json!(0),
+            json!(-1),
+            json!(u64::MAX),
+            json!(i64::MIN),
+            json!(1.5),
+            json!(-0.25),
+            json!("text"),
+            json!(""),
+            json!([]),
+            json!({}),
+            json!([1, "two", [3.5, null], {"four": false}]),
+            json!({"outer": {"inner": [1, 2, 3], "other": "x"}, "b": null}),
+        ];
+
+        for value in values {
+            let converted = json_to_cbor(value.clone());
+            assert_eq!(cbor_to_json(converted).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn cbor_round_trip() {
+        let values = vec![
+            Value::Null,
+            Value::Bool(true),
+            Value::Integer(u64::MAX.into()),
+            Value::Integer(i64::MIN.into()),
+            Value::Float(2.75),
+            Value::Text("text".into()),
+            cbor!([1, "two", [3.5, null]]).unwrap(),
+            cbor!({"outer" => {"inner" => [1, 2, 3]}, "b" => null}).unwrap(),
+        ];
+
+        for value in values {
+            let converted = cbor_to_json(value.clone()).unwrap();
+            assert_eq!(json_to_cbor(converted), value);
+        }
+    }
+
+    #[test]
+    fn key_order() {
+        let text = r#"{"z":1,"a":{"y":2,"b":3}}"#;
+        let converted = json_to_cbor(serde_json::from_str(text).unwrap());
+        let keys: Vec<_> = converted
+            .as_map()
+            .unwrap()
+            .iter()
+            .map(|(k, _)| k.as_text().unwrap())
+            .collect();
+        assert_eq!(keys, ["z", "a"]);
+        assert_eq!(cbor_to_json(converted).unwrap().to_string(), text);
+
+        // Keys which render the same keep the first one's place.
+        let map = Value::Map(vec![
+            (Value::Integer(1.into()), Value::Text("first".into())),
+            (Value::Text("x".into()), Value::Text("middle".into())),
+            (Value::Text("1".into()), Value::Text("last".into())),
+        ]);
+        assert_eq!(
+            cbor_to_json(map).unwrap().to_string(),
+            r#"{"1":"last","x":"middle"}"#
+        );
+    }
+
+    #[test]
+    fn unrepresentable_values() {
+        let too_small = Value::Integer(ciborium::value::Integer::try_from(-(1i128 << 64)).unwrap());
+        assert_eq!(
+            cbor_to_json(too_small),
+            Err(ConvertError::IntegerOutOfRange(-(1i128 << 64)))
+        );
+
+        assert_eq!(
+            cbor_to_json(Value::Bytes(vec![1, 2])),
+            Err(ConvertError::Bytes)
+        );
+
+        assert!(matches!(
+            cbor_to_json(Value::Float(f64::NAN)),
+            Err(ConvertError::NonFiniteFloat(_))
+        ));
+        assert_eq!(
+            cbor_to_json(Value::Float(f64::INFINITY)),
+            Err(ConvertError::NonFiniteFloat(f64::INFINITY))
+        );
+
+        // Errors are found inside containers, too.
+        assert_eq!(
+            cbor_to_json(cbor!({"a" => [Value::Bytes(vec![])]}).unwrap()),
+            Err(ConvertError::Bytes)
+        );
+    }
+
+    #[test]
+    fn tags_are_dropped() {
+        let tagged = Value::Tag(1, Box::new(Value::Integer(1_600_000_000.into())));
+        assert_eq!(cbor_to_json(tagged).unwrap(), json!(1_600_000_000));
+    }
+
+    #[test]
+    fn non_string_keys() {
+        let map = Value::Map(vec![
+            (Value::Integer(1.into()), Value::Text("int".into())),
+            (Value::Integer((-2).into()), Value::Text("negative".into())),
+            (Value::Bool(true), Value::Text("bool".into())),
+            (Value::Null, Value::Text("null".into())),
+            (Value::Float(1.5), Value::Text("float".into())),
+            (cbor!([1, "a"]).unwrap(), Value::Text("array".into())),
+            (cbor!({"k" => 1}).unwrap(), Value::Text("map".into())),
+            (
+                Value::Tag(7, Box::new(Value::Text("tagged".into()))),
+                Value::Text("tag".into()),
+            ),
+        ]);
+
+        assert_eq!(
+            cbor_to_json(map).unwrap(),
+            json!({
+                "1": "int",
+                "-2": "negative",
+                "true": "bool",
+                "null": "null",
+                "1.5": "float",
+                "[1,\"a\"]": "array",
+                "{\"k\":1}": "map",
+                "tagged": "tag",
+            })
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Structural differences between two values.
+
+use crate::DataPath;
+use ciborium::value::Value;
+
+/// The kind of difference found at a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    /// A map key or array element exists only in the new value.
+    Added,
+
+    /// A map key or array element exists only in the old value.
+    Removed,
+
+    /// The value changed from one type to another, like from a string to a map.
+    TypeChanged,
+
+    /// The value changed, but kept the same type.
+    Changed,
+}
+
+impl DiffKind {
+    /// A short lowercase name for this kind, like `added`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiffKind::Added => "added",
+            DiffKind::Removed => "removed",
+            DiffKind::TypeChanged => "type changed",
+            DiffKind::Changed => "changed",
+        }
+    }
+}
+
+/// One difference between two values.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffEntry {
+    /// Where the difference is.
+    pub path: DataPath,
+
+    pub kind: DiffKind,
+
+    /// The value in the old side, unless it was [`DiffKind::Added`].
+    pub old: Option<Value>,
+
+    /// The value in the new side, unless it was [`DiffKind::Removed`].
+    pub new: Option<Value>,
+}
+
+/// Find the differences between two values, by walking their maps and arrays together.
+///
+/// Map entries are matched up by key, and array elements by index. Equal values produce no
+/// entries at all.
+pub fn diff(a: &Value, b: &Value) -> Vec<DiffEntry> {
+    let mut entries = vec![];
+    diff_into(&mut entries, DataPath::parse(""), a, b);
+    entries
+}
+
+fn diff_into(entries: &mut Vec<DiffEntry>, path: DataPath, a: &Value, b: &Value) {
+    if a == b {
+        return;
+    }
+
+    match (a, b) {
+        // Compare maps key-by-key, in the order they appear.
+        (Value::Map(a_pairs), Value::Map(b_pairs)) => {
+            for (key, a_val) in a_pairs {
+                match lookup(b_pairs, key) {
+                    Some(b_val) => diff_into(entries, path.child(key.clone()), a_val, b_val),
+                    None => push(
+                        entries,
+                        path.child(key.clone()),
+                        DiffKind::Removed,
+                        Some(a_val),
+                        None,
+                    ),
+                }
+            }
+
+            for (key, b_val) in b_pairs {
+                if lookup(a_pairs, key).is_none() {
+                    push(
+                        entries,
+                        path.child(key.clone()),
+                        DiffKind::Added,
+                        None,
+                        Some(b_val),
+                    );
+                }
+            }
+        }
+
+        // Compare arrays element-by-element.
+        (Value::Array(a_items), Value::Array(b_items)) => {
+            let longest = a_items.len().max(b_items.len());
+            for i in 0..longest {
+                let child = path.child(Value::Integer(i.into()));
+                match (a_items.get(i), b_items.get(i)) {
+                    (Some(a_val), Some(b_val)) => diff_into(entries, child, a_val, b_val),
+                    (Some(a_val), None) => {
+                        push(entries, child, DiffKind::Removed, Some(a_val), None)
+                    }
+                    (None, Some(b_val)) => push(entries, child, DiffKind::Added, None, Some(b_val)),
+                    (None, None) => unreachable!("index within the longest array"),
+                }
+            }
+        }
+
+        // Look inside matching tags, since paths pass through them.
+        (Value::Tag(a_tag, a_inner), Value::Tag(b_tag, b_inner)) if a_tag == b_tag => {
+            diff_into(entries, path, a_inner, b_inner)
+        }
+
+        // Otherwise, the value changed, and maybe its type too.
+        _ if type_name(a) != type_name(b) => {
+            push(entries, path, DiffKind::TypeChanged, Some(a), Some(b))
+        }
+        _ => push(entries, path, DiffKind::Changed, Some(a), Some(b)),
+    }
+}
+
+/// Record a difference.
+fn push(
+    entries: &mut Vec<DiffEntry>,
+    path: DataPath,
+    kind: DiffKind,
+    old: Option<&Value>,
+    new: Option<&Value>,
+) {
+    entries.push(DiffEntry {
+        path,
+        kind,
+        old: old.cloned(),
+        new: new.cloned(),
+    });
+}
+
+/// Find the value for the first matching key in a map.
+fn lookup<'a>(pairs: &'a [(Value, Value)], key: &Value) -> Option<&'a Value> {
+    pairs.iter().find(|(k, _v)| k == key).map(|(_k, v)| v)
+}
+
+/// The name of the type of a value.
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Integer(_) => "integer",
+        Value::Bytes(_) => "bytes",
+        Value::Float(_) => "float",
+        Value::Text(_) => "text",
+        Value::Bool(_) => "bool",
+        Value::Null => "null",
+        Value::Tag(_, _) => "tag",
+        Value::Array(_) => "array",
+        Value::Map(_) => "map",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ciborium::cbor;
+
+    fn entry(path: &str, kind: DiffKind, old: Option<Value>, new: Option<Value>) -> DiffEntry {
+        DiffEntry {
+            path: DataPath::parse(path),
+            kind,
+            old,
+            new,
+        }
+    }
+
+    #[test]
+    fn equal_values() {
+        let value = cbor!({"a" => [1, 2, {"b" => null}]}).unwrap();
+        assert!(diff(&value, &value.clone()).is_empty());
+    }
+
+    #[test]
+    fn nested_maps() {
+        let a = cbor!({
+            "same" => 1,
+            "changed" => "old",
+            "removed" => true,
+            "retyped" => "12",
+            "inner" => { "deep" => { "x" => 1 } },
+        })
+        .unwrap();
+
+        let b = cbor!({
+            "same" => 1,
+            "changed" => "new",
+            "retyped" => 12,
+            "inner" => { "deep" => { "x" => 2, "y" => 3 } },
+            "added" => [],
+        })
+        .unwrap();
+
+        let text = |s: &str| Some(Value::Text(s.into()));
+        let int = |i: u64| Some(Value::Integer(i.into()));
+
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                entry(".changed", DiffKind::Changed, text("old"), text("new")),
+                entry(".removed", DiffKind::Removed, Some(Value::Bool(true)), None),
+                entry(".retyped", DiffKind::TypeChanged, text("12"), int(12)),
+                entry(".inner.deep.x", DiffKind::Changed, int(1), int(2)),
+                entry(".inner.deep.y", DiffKind::Added, None, int(3)),
+                entry(".added", DiffKind::Added, None, Some(Value::Array(vec![]))),
+            ]
+        );
+    }
+
+    #[test]
+    fn arrays_by_index() {
+        let a = cbor!([{"name" => "a"}, {"name" => "b"}, "gone"]).unwrap();
+        let b = cbor!([{"name" => "a"}, {"name" => "c"}]).unwrap();
+
+        assert_eq!(
+            diff(&a, &b),
+            vec![
+                entry(
+                    ".1.name",
+                    DiffKind::Changed,
+                    Some(Value::Text("b".into())),
+                    Some(Value::Text("c".into()))
+                ),
+                entry(
+                    ".2",
+                    DiffKind::Removed,
+                    Some(Value::Text("gone".into())),
+                    None
+                ),
+            ]
+        );
+
+        assert_eq!(
+            diff(&b, &a)[1],
+            entry(
+                ".2",
+                DiffKind::Added,
+                None,
+                Some(Value::Text("gone".into()))
+            )
+        );
+    }
+
+    #[test]
+    fn top_level_type_change() {
+        let a = Value::Text("a".into());
+        let b = cbor!({"a" => 1}).unwrap();
+
+        assert_eq!(
+            diff(&a, &b),
+            vec![entry(
+                "",
+                DiffKind::TypeChanged,
+                Some(a.clone()),
+                Some(b.clone())
+            )]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! An optional header at the start of a stream, describing the items which follow.
+//!
+//! The header is written as a CBOR item tagged with [`HEADER_TAG`], wrapping a map like
+//! `{"type": "entry", "fields": ["name", "kind"]}`. [`InputParser`](crate::InputParser) picks it
+//! up and makes it available through [`header`](crate::InputParser::header), rather than
+//! yielding it as an item, so readers which don't care never see it.
+
+use ciborium::value::Value;
+use std::fmt;
+
+/// The tag marking a stream header. This is "monc" in ASCII, which is in the range of tags
+/// anybody can use without registering them.
+pub const HEADER_TAG: u64 = 0x6d6f_6e63;
+
+/// The first byte of an encoded header: a tag with a 4-byte number.
+pub(crate) const HEADER_FIRST_BYTE: u8 = 0xda;
+
+/// A description of the items in a stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamHeader {
+    /// What each item is, like `"entry"` or `"string"`.
+    pub type_name: String,
+
+    /// The keys each item has, if they're maps.
+    pub fields: Vec<String>,
+}
+
+impl StreamHeader {
+    pub fn new(type_name: impl Into<String>, fields: &[&str]) -> Self {
+        StreamHeader {
+            type_name: type_name.into(),
+            fields: fields.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    /// The header as it's written to a stream, including its tag.
+    pub fn to_value(&self) -> Value {
+        let fields = self.fields.iter().cloned().map(Value::Text).collect();
+        let map = Value::Map(vec![
+            (
+                Value::Text("type".into()),
+                Value::Text(self.type_name.clone()),
+            ),
+            (Value::Text("fields".into()), Value::Array(fields)),
+        ]);
+
+        Value::Tag(HEADER_TAG, Box::new(map))
+    }
+
+    /// Parse the contents of a header's tag. Returns `None` if it isn't shaped like a header.
+    pub(crate) fn from_inner(inner: Value) -> Option<Self> {
+        let mut type_name = None;
+        let mut fields = vec![];
+
+        for (k, v) in inner.as_map()? {
+            match (k.as_text()?, v) {
+                ("type", Value::Text(name)) => type_name = Some(name.clone()),
+                ("fields", Value::Array(names)) => {
+                    for name in names {
+                        fields.push(name.as_text()?.to_string());
+                    }
+                }
+
+                // Leave room for more keys later.
+                _ => {}
+            }
+        }
+
+        Some(StreamHeader {
+            type_name: type_name?,
+            fields,
+        })
+    }
+}
+
+impl fmt::Display for StreamHeader {
+    /// A one-line summary, like `entry {name, kind}`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.type_name)?;
+        if !self.fields.is_empty() {
+            write!(f, " {{{}}}", self.fields.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let header = StreamHeader::new("entry", &["name", "kind"]);
+        let inner = match header.to_value() {
+            Value::Tag(HEADER_TAG, inner) => *inner,
+            other => panic!("header isn't tagged: {:?}", other),
+        };
+
+        assert_eq!(StreamHeader::from_inner(inner), Some(header));
+    }
+
+    #[test]
+    fn encoding_starts_with_tag() {
+        let mut bytes = vec![];
+        let header = StreamHeader::new("string", &[]);
+        ciborium::ser::into_writer(&header.to_value(), &mut bytes).unwrap();
+
+        assert_eq!(bytes[..5], [HEADER_FIRST_BYTE, 0x6d, 0x6f, 0x6e, 0x63]);
+    }
+
+    #[test]
+    fn summary() {
+        assert_eq!(
+            StreamHeader::new("entry", &["name", "kind"]).to_string(),
+            "entry {name, kind}"
+        );
+        assert_eq!(StreamHeader::new("string", &[]).to_string(), "string");
+    }
+}
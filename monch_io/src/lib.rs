@@ -1,14 +1,44 @@
-use serde::{Deserialize, Serialize};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{de::DeserializeOwned, Serialize};
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+use std::time::{Duration, Instant};
 use std::{io, iter};
 use thiserror::Error;
 
+// Let our exported macros refer to `::monch_io` from inside this crate, too.
+extern crate self as monch_io;
+
 /// Re-export the `cbor!` macro to implement our `put!` macro
 pub use ciborium;
 pub use ciborium::cbor;
 pub use ciborium::value::Value;
 
+pub mod convert;
+pub mod metadata;
+pub mod table;
+pub mod template;
+pub mod units;
+
+#[cfg(feature = "tokio")]
+mod async_io;
+#[cfg(feature = "tokio")]
+pub use async_io::{
+    read_one_async, read_one_async_from, write_async, write_async_to, AsyncInputParser,
+};
+
+mod canonical;
+pub use canonical::canonicalize;
+
+mod diff;
+pub use diff::{diff, DiffEntry, DiffKind};
+
+mod header;
+pub use header::{StreamHeader, HEADER_TAG};
+
 mod path;
-pub use path::DataPath;
+pub use path::{DataPath, DataPathError};
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -23,8 +53,61 @@ pub enum Error {
 
     #[error("reading object: {0}")]
     Deserialize(#[from] ciborium::de::Error<io::Error>),
+
+    #[error("reading object {index} at byte {offset}: {source}")]
+    DeserializeAt {
+        offset: u64,
+        index: u64,
+        source: ciborium::de::Error<io::Error>,
+    },
+
+    #[error("skipped {skipped} bytes of corrupt input at byte {offset}: {source}")]
+    Skipped {
+        offset: u64,
+        skipped: u64,
+        source: ciborium::de::Error<io::Error>,
+    },
+
+    /// Whatever was reading our output has gone away. Writes return this instead of a broken
+    /// pipe error, and most programs should treat it as a reason to quietly stop.
+    #[error("output closed by reader")]
+    Closed,
+
+    #[error("input item is larger than the limit of {limit} bytes")]
+    ItemTooLarge { limit: usize },
+
+    #[error("stream header is malformed")]
+    InvalidHeader,
+
+    #[error("writing JSON object: {0}")]
+    SerializeJson(serde_json::Error),
+
+    #[error("converting object to JSON: {0}")]
+    ConvertJson(#[from] convert::ConvertError),
+
+    #[error("reading JSON object: {0}")]
+    DeserializeJson(serde_json::Error),
 }
 
+impl Error {
+    /// Returns whether this error came from writing to a pipe with nothing on the other end.
+    pub fn is_broken_pipe(&self) -> bool {
+        match self {
+            Error::Closed => true,
+            Error::Io(ioe) | Error::Serialize(ciborium::ser::Error::Io(ioe)) => {
+                ioe.kind() == io::ErrorKind::BrokenPipe
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A sane default limit on the size of a single input item, used when reading from stdin.
+pub const DEFAULT_MAX_ITEM_SIZE: usize = 64 * 1024 * 1024;
+
+/// The environment variable which chooses the output [`Format`] of `put!` and [`write`].
+pub const FORMAT_VAR: &str = "MONCH_FORMAT";
+
 /// Logs a message for humans to standard error.
 #[macro_export]
 macro_rules! log {
@@ -32,6 +115,32 @@ macro_rules! log {
 }
 
 /// Writes an object for machines to standard out.
+///
+/// There are a few forms:
+///
+/// - `try_put!({"key" => value})` or `try_put!([a, b])` builds a value with [`cbor!`].
+/// - `try_put!(name: name, size: meta.len())` builds a map keyed by the identifiers, serializing
+///   each expression with serde.
+/// - `try_put!(@obj value)` writes any [`Serialize`] value, taking a reference to it.
+/// - `try_put!(&value)` writes a reference to a [`Serialize`] value.
+///
+/// ```
+/// use monch_io::try_put_to;
+///
+/// let mut buf = vec![];
+/// let name = "file.txt".to_string();
+/// let size: Option<u64> = None;
+/// try_put_to!(&mut buf, name: name, size: size).unwrap();
+/// ```
+///
+/// Keys in the inline form have to be identifiers:
+///
+/// ```compile_fail
+/// use monch_io::try_put_to;
+///
+/// let mut buf = vec![];
+/// try_put_to!(&mut buf, "name": 1).unwrap();
+/// ```
 #[macro_export]
 macro_rules! try_put {
     // Take invocations like `put!({"key" => "value"})` or `put!(["a", "list""])`
@@ -42,82 +151,978 @@ macro_rules! try_put {
         use monch_io::{cbor, Error, try_put};
 
         let result: Result<(), Error> = cbor!($($toks) *)
-            .map_err(|e| Error::ConstructValue(e))
+            .map_err(Error::ConstructValue)
             .and_then(|val| try_put!(&val));
 
         result
     }};
 
+    // Serialize each field straight into a map, without going through `cbor!`.
+    (@ fields $($key:ident : $value:expr),+) => {{
+        use monch_io::{Error, Value};
+
+        let fields: Result<Vec<(Value, Value)>, Error> = vec![$(
+            Value::serialized(&$value)
+                .map(|v| (Value::Text(stringify!($key).into()), v))
+                .map_err(Error::ConstructValue)
+        ),+].into_iter().collect();
+
+        fields.map(Value::Map)
+    }};
+
+    (@obj $value:expr) => { ::monch_io::write(&$value) };
+
+    ($($key:ident : $value:expr),+ $(,)?) => {
+        ::monch_io::try_put!(@ fields $($key: $value),+).and_then(|val| ::monch_io::write(&val))
+    };
+
     ($value:expr) => { ::monch_io::write($value) };
 }
 
+/// Writes an object for machines to the given writer, like `try_put_to!(&mut file, {"a" => 1})`.
+/// This takes the same forms as [`try_put!`].
+#[macro_export]
+macro_rules! try_put_to {
+    (@ cbor $writer:expr, $($toks:tt) *) => {{
+        use monch_io::{cbor, Error, write_to};
+
+        let result: Result<(), Error> = cbor!($($toks) *)
+            .map_err(Error::ConstructValue)
+            .and_then(|val| write_to($writer, &val));
+
+        result
+    }};
+
+    ($writer:expr, { $($toks:tt) * }) => { ::monch_io::try_put_to!(@ cbor $writer, { $($toks) * }) };
+    ($writer:expr, [ $($toks:tt) * ]) => { ::monch_io::try_put_to!(@ cbor $writer, [ $($toks) * ]) };
+
+    ($writer:expr, @obj $value:expr) => { ::monch_io::write_to($writer, &$value) };
+
+    ($writer:expr, $($key:ident : $value:expr),+ $(,)?) => {
+        ::monch_io::try_put!(@ fields $($key: $value),+)
+            .and_then(|val| ::monch_io::write_to($writer, &val))
+    };
+
+    ($writer:expr, $value:expr) => { ::monch_io::write_to($writer, $value) };
+}
+
 /// Writes an object for machines to standard out. If the write fails, panic.
 #[macro_export]
 macro_rules! put {
-    ( $($toks:tt) * ) => {{
-        let result = ::monch_io::try_put!( $($toks) * );
+    ( $($toks:tt) * ) => {
+        ::monch_io::exit_on_put_error(::monch_io::try_put!( $($toks) * ))
+    };
+}
 
-        if let Err(::monch_io::Error::Serialize(::monch_io::ciborium::ser::Error::Io(ref ioe))) = result {
-            if ioe.kind() == ::std::io::ErrorKind::BrokenPipe {
-                ::std::process::exit(0); // gracefully die on a SIGPIPE
-            }
-        }
+/// Writes an object for machines to the given writer. If the write fails, panic.
+#[macro_export]
+macro_rules! put_to {
+    ( $($toks:tt) * ) => {
+        ::monch_io::exit_on_put_error(::monch_io::try_put_to!( $($toks) * ))
+    };
+}
 
-        if let Err(e) = result {
-            eprintln!("failed to write object: {}", e);
-            ::std::process::exit(1);
+/// Handle the result of a `put!`, exiting the process if the write failed.
+#[doc(hidden)]
+pub fn exit_on_put_error(result: Result<(), Error>) {
+    if let Err(e) = result {
+        if e.is_broken_pipe() {
+            std::process::exit(0); // gracefully die on a SIGPIPE
         }
 
-    }};
+        eprintln!("failed to write object: {}", e);
+        std::process::exit(1);
+    }
+}
+
+/// Get the output format for this process, chosen by the `MONCH_FORMAT` environment variable.
+///
+/// This is `cbor` unless the variable is set to `json`, and is only read once per process.
+pub fn output_format() -> Format {
+    static OUTPUT_FORMAT: OnceCell<Format> = OnceCell::new();
+
+    *OUTPUT_FORMAT.get_or_init(|| requested_format().unwrap_or(Format::Cbor))
+}
+
+/// The format named by `MONCH_FORMAT`, if it's set to one.
+fn requested_format() -> Option<Format> {
+    std::env::var(FORMAT_VAR)
+        .ok()
+        .and_then(|name| Format::from_str(&name).ok())
 }
 
-/// Write a serializable object to structured stdout.
+/// The format to read input in, when `requested` is the format named by `MONCH_FORMAT`, or `None`
+/// to guess it from the input.
+///
+/// Guessing lets a utility read JSON piped in from anything, like `jq -c`. But some CBOR items
+/// start with bytes that look like JSON, so when CBOR was asked for, like the shell does for every
+/// utility it runs, the input is read as CBOR without guessing.
+fn input_format(requested: Option<Format>) -> Option<Format> {
+    match requested {
+        Some(Format::Cbor) => Some(Format::Cbor),
+        Some(Format::Json) | None => None,
+    }
+}
+
+/// How long objects can wait in the stdout buffer while more are being written, before they're
+/// flushed anyway, so that whoever is reading sees steady progress from a busy program.
+const FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Buffered standard output, shared by every `put!` in the process.
+///
+/// We hold the buffer behind our own lock rather than holding the stdout lock for the lifetime of
+/// the process, so that other threads can still print without deadlocking. Each object is written
+/// into the buffer whole, while the lock is held. It's flushed when it fills up, when it's been
+/// [`FLUSH_INTERVAL`] since the last flush, before reading from [`stdin`] might block, and after
+/// every object if stdout is a terminal: see [`flush_each_object`].
+static STDOUT: Lazy<Mutex<BufferedStdout>> = Lazy::new(|| {
+    // Nothing runs destructors for statics, so make sure we flush on the way out instead.
+    //
+    // SAFETY: `flush_at_exit` is a plain function that doesn't unwind.
+    unsafe {
+        libc::atexit(flush_at_exit);
+    }
+
+    Mutex::new(BufferedStdout {
+        writer: io::BufWriter::with_capacity(64 * 1024, io::stdout()),
+        flushed_at: Instant::now(),
+    })
+});
+
+struct BufferedStdout {
+    writer: io::BufWriter<io::Stdout>,
+
+    /// When the buffer was last flushed.
+    flushed_at: Instant,
+}
+
+impl BufferedStdout {
+    fn flush(&mut self) -> io::Result<()> {
+        self.flushed_at = Instant::now();
+        self.writer.flush()
+    }
+}
+
+/// Lock the buffered stdout, ignoring poisoning from other threads that panicked while writing.
+fn lock_stdout() -> MutexGuard<'static, BufferedStdout> {
+    STDOUT.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Write one object to the buffered stdout with `write`, flushing it afterwards if it's time to.
+fn write_stdout(
+    write: impl FnOnce(&mut io::BufWriter<io::Stdout>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut stdout = lock_stdout();
+    write(&mut stdout.writer)?;
+    if flush_each_object() || stdout.flushed_at.elapsed() >= FLUSH_INTERVAL {
+        stdout
+            .flush()
+            .map_err(|e| closed_if_broken_pipe(e.into()))?;
+    }
+
+    Ok(())
+}
+
+/// Returns whether objects written to stdout should be flushed straight away.
+///
+/// Someone watching a terminal wants to see each object as soon as it's written. Pipes and files
+/// are left to the buffer, since a program reading a pipe gets whatever was written before we
+/// wait on our own input, or go quiet for long: see [`STDOUT`].
+fn flush_each_object() -> bool {
+    static FLUSH_EACH: Lazy<bool> = Lazy::new(stdout_is_terminal);
+    *FLUSH_EACH
+}
+
+/// Returns whether stdout is a terminal.
+#[cfg(unix)]
+fn stdout_is_terminal() -> bool {
+    use std::os::unix::io::AsRawFd;
+
+    // SAFETY: isatty only looks at the file descriptor it's given.
+    unsafe { libc::isatty(io::stdout().as_raw_fd()) == 1 }
+}
+
+/// Returns whether stdout is a terminal. Elsewhere, assume it might be, and always flush.
+#[cfg(not(unix))]
+fn stdout_is_terminal() -> bool {
+    true
+}
+
+/// Flush any objects buffered by `put!` or [`write`] to stdout.
+///
+/// This happens automatically when the process exits, and before reading from [`stdin`] could
+/// block. Call it before waiting on anything else, like a timer or another process, so that
+/// objects already written don't wait with it. Also call it before writing to stdout by some
+/// other means.
+pub fn flush() -> Result<(), Error> {
+    lock_stdout()
+        .flush()
+        .map_err(|e| closed_if_broken_pipe(e.into()))
+}
+
+extern "C" fn flush_at_exit() {
+    // Don't wait on the lock here: if another thread is mid-write as we exit, its output is lost
+    // anyway, and blocking would hang the process.
+    let mut stdout = match STDOUT.try_lock() {
+        Ok(stdout) => stdout,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => return,
+    };
+
+    let result: Result<(), Error> = stdout.writer.flush().map_err(Error::Io);
+    if let Err(e) = result {
+        // There's nobody left to read our output, which is fine at this point. Otherwise, this
+        // mustn't panic like `eprintln!` can, because unwinding out of here is undefined.
+        if !e.is_broken_pipe() {
+            let _ = writeln!(io::stderr(), "failed to write object: {}", e);
+        }
+    }
+}
+
+/// Write a serializable object to structured stdout, in the process's [`output_format`].
+///
+/// Output is buffered unless stdout is a terminal: see [`flush`]. If whatever is reading our output
+/// goes away, this returns [`Error::Closed`].
 pub fn write<T: Serialize>(object: &T) -> Result<(), Error> {
-    ciborium::ser::into_writer(object, io::stdout())?;
+    write_stdout(|stdout| write_to(stdout, object))
+}
+
+/// Write a serializable object to any writer, in the process's [`output_format`].
+///
+/// This doesn't flush the writer. Broken pipes are returned as [`Error::Closed`].
+pub fn write_to<W: io::Write, T: Serialize>(writer: &mut W, object: &T) -> Result<(), Error> {
+    write_format(writer, object, output_format())
+}
+
+/// Write each item to structured stdout, as a separate object.
+///
+/// If whatever is reading our output goes away, this stops early with [`Error::Closed`]. Most
+/// programs should treat that as a reason to quietly exit, rather than as a failure.
+///
+/// ```no_run
+/// use monch_io::{write_all, Error};
+///
+/// let names = vec!["one", "two", "three"];
+/// match write_all(&names) {
+///     Ok(()) | Err(Error::Closed) => {}
+///     Err(e) => panic!("failed to write names: {}", e),
+/// }
+/// ```
+pub fn write_all<T: Serialize>(items: impl IntoIterator<Item = T>) -> Result<(), Error> {
+    for item in items {
+        write(&item)?;
+    }
+
+    Ok(())
+}
+
+/// Write each item to any writer, as a separate object. See [`write_all`].
+///
+/// ```
+/// use monch_io::{read_one_from, write_all_to};
+///
+/// let mut buf = vec![];
+/// write_all_to(&mut buf, [1, 2, 3]).unwrap();
+///
+/// // Each item is its own object, rather than one big array.
+/// let mut reader = &buf[..];
+/// let first: u32 = read_one_from(&mut reader).unwrap();
+/// assert_eq!(first, 1);
+/// ```
+pub fn write_all_to<W: io::Write, T: Serialize>(
+    writer: &mut W,
+    items: impl IntoIterator<Item = T>,
+) -> Result<(), Error> {
+    for item in items {
+        write_to(writer, &item)?;
+    }
+
+    Ok(())
+}
+
+/// Check the result of a write, returning `Ok(false)` if whatever was reading our output has gone
+/// away, so that a loop writing objects can quietly stop.
+///
+/// ```no_run
+/// use monch_io::{output_open, try_put, Error};
+///
+/// for i in 0.. {
+///     if !output_open(try_put!(&i))? {
+///         break;
+///     }
+/// }
+/// # Ok::<(), Error>(())
+/// ```
+pub fn output_open(written: Result<(), Error>) -> Result<bool, Error> {
+    match written {
+        Ok(()) => Ok(true),
+        Err(Error::Closed) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Convert broken pipe errors into [`Error::Closed`].
+fn closed_if_broken_pipe(e: Error) -> Error {
+    if e.is_broken_pipe() {
+        Error::Closed
+    } else {
+        e
+    }
+}
+
+/// Write a serializable object to structured stdout with a deterministic encoding, so that equal
+/// objects always produce the same bytes. See [`canonicalize`].
+pub fn write_canonical<T: Serialize>(object: &T) -> Result<(), Error> {
+    write(&canonicalize(Value::serialized(object)?))
+}
+
+/// Write a [`StreamHeader`] describing the items to follow, to structured stdout. This should
+/// come before any items.
+///
+/// Headers are only written in CBOR: when the [`output_format`] is JSON, this does nothing.
+pub fn write_header(header: &StreamHeader) -> Result<(), Error> {
+    write_stdout(|stdout| write_header_to(stdout, header))
+}
+
+/// Write a [`StreamHeader`] to any writer, in the process's [`output_format`]. See
+/// [`write_header`].
+pub fn write_header_to<W: io::Write>(writer: &mut W, header: &StreamHeader) -> Result<(), Error> {
+    match output_format() {
+        Format::Cbor => write_format(writer, &header.to_value(), Format::Cbor),
+        Format::Json => Ok(()),
+    }
+}
+
+/// Write a serializable object to stdout as one line of JSON.
+pub fn write_json<T: Serialize>(object: &T) -> Result<(), Error> {
+    write_stdout(|stdout| write_format(stdout, object, Format::Json))
+}
+
+/// Write a serializable object to a writer, in the given format.
+fn write_format<W: io::Write, T: Serialize>(
+    writer: W,
+    object: &T,
+    format: Format,
+) -> Result<(), Error> {
+    write_format_inner(writer, object, format).map_err(closed_if_broken_pipe)
+}
+
+fn write_format_inner<W: io::Write, T: Serialize>(
+    mut writer: W,
+    object: &T,
+    format: Format,
+) -> Result<(), Error> {
+    match format {
+        // ciborium flushes after every item, which would defeat any buffering in `writer`.
+        Format::Cbor => ciborium::ser::into_writer(object, NoFlush(writer))?,
+        Format::Json => {
+            // Convert through CBOR, so objects come out just like `get` and `save` write them.
+            let json = convert::cbor_to_json(Value::serialized(object)?)?;
+            serde_json::to_writer(&mut writer, &json).map_err(|e| {
+                // Surface IO errors as-is, so that broken pipes can be detected.
+                if e.is_io() {
+                    Error::Io(e.into())
+                } else {
+                    Error::SerializeJson(e)
+                }
+            })?;
+            writer.write_all(b"\n")?;
+        }
+    }
+
     Ok(())
 }
 
+/// A writer adapter that ignores flushes, leaving them to whoever owns the underlying writer.
+struct NoFlush<W>(W);
+
+impl<W: io::Write> io::Write for NoFlush<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Standard input, for reading objects from while writing others with `put!`.
+///
+/// Reads are buffered, and objects buffered for stdout are flushed before each read of the
+/// underlying stdin, since it could block waiting for more input. That way, a program downstream
+/// isn't kept waiting on objects we've already written, like matches from `grep` over a log that's
+/// still being written.
+pub fn stdin() -> impl io::Read {
+    io::BufReader::with_capacity(64 * 1024, FlushingStdin)
+}
+
+/// Stdin, flushing our stdout before each read. See [`stdin`].
+struct FlushingStdin;
+
+impl io::Read for FlushingStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // If this fails, so will the next write, which reports it.
+        let _ = flush();
+        io::Read::read(&mut io::stdin(), buf)
+    }
+}
+
 /// Read a deserializable object from structured stdin.
-pub fn read_one<'a, T: Deserialize<'a>>() -> Result<T, Error> {
-    let obj = ciborium::de::from_reader(io::stdin())?;
-    Ok(obj)
+pub fn read_one<T: DeserializeOwned>() -> Result<T, Error> {
+    // Reading this unbuffered leaves the rest of stdin for whatever reads it next.
+    let _ = flush();
+    read_one_from(io::stdin())
+}
+
+/// Read a deserializable object from any reader.
+///
+/// A [`StreamHeader`] in the way is skipped, since it isn't an object itself. Nothing past the
+/// object is read, so the rest is left for whatever reads from `reader` next.
+pub fn read_one_from<R: io::Read, T: DeserializeOwned>(mut reader: R) -> Result<T, Error> {
+    use std::io::Read;
+
+    loop {
+        // Look at the start of the item, reading no more than a header would take up.
+        let mut prefix = Vec::with_capacity(5);
+        (&mut reader).take(1).read_to_end(&mut prefix)?;
+        if prefix == [header::HEADER_FIRST_BYTE] {
+            (&mut reader).take(4).read_to_end(&mut prefix)?;
+        }
+
+        if prefix.len() == 5 && prefix[1..] == (HEADER_TAG as u32).to_be_bytes() {
+            let inner = ciborium::de::from_reader(&mut reader)?;
+            StreamHeader::from_inner(inner).ok_or(Error::InvalidHeader)?;
+            continue;
+        }
+
+        let obj = ciborium::de::from_reader((&prefix[..]).chain(&mut reader))?;
+        return Ok(obj);
+    }
 }
 
 /// Read a series of deserializable objects from structured stdin, stopping when stdin is closed.
-pub fn input_stream<T: Deserialize<'static>>() -> impl Iterator<Item = Result<T, Error>> {
-    InputParser::new(io::stdin())
+pub fn input_stream<T: DeserializeOwned>() -> impl Iterator<Item = Result<T, Error>> {
+    InputParser::new(stdin()).with_max_item_size(DEFAULT_MAX_ITEM_SIZE)
+}
+
+/// Read a series of deserializable objects from JSON Lines on stdin, stopping when stdin is closed.
+pub fn input_stream_json<T: DeserializeOwned>() -> impl Iterator<Item = Result<T, Error>> {
+    InputParser::new_json(stdin()).with_max_item_size(DEFAULT_MAX_ITEM_SIZE)
+}
+
+/// Read a series of deserializable objects from stdin, in CBOR or JSON Lines.
+///
+/// See [`InputParser::new_auto`]: this is CBOR when `MONCH_FORMAT` asks for it, and guessed from
+/// the first byte otherwise.
+pub fn input_stream_auto<T: DeserializeOwned>() -> impl Iterator<Item = Result<T, Error>> {
+    InputParser::new_auto(stdin()).with_max_item_size(DEFAULT_MAX_ITEM_SIZE)
+}
+
+/// A wire format for a stream of objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// A series of concatenated CBOR items.
+    Cbor,
+
+    /// JSON Lines: one JSON value per line of text.
+    Json,
+}
+
+impl FromStr for Format {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Format, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "cbor" => Ok(Format::Cbor),
+            "json" => Ok(Format::Json),
+            _ => Err(()),
+        }
+    }
+}
+
+impl Format {
+    /// Guess the format of a stream from its first byte.
+    ///
+    /// A `{`, `[`, digit, or `"` suggests JSON text, and everything else is CBOR. Plenty of CBOR
+    /// items start with those bytes too, like the integer -3 does, so this is only a guess for
+    /// input that's expected to be JSON.
+    pub fn sniff(first_byte: u8) -> Format {
+        match first_byte {
+            b'{' | b'[' | b'"' | b'0'..=b'9' => Format::Json,
+            _ => Format::Cbor,
+        }
+    }
+}
+
+/// Returns whether this byte could plausibly start a top-level CBOR item.
+///
+/// Used to find our place again after a corrupt item. Rules out reserved encodings, stray "break"
+/// codes, indefinite-length integers and tags, and unassigned simple values.
+fn is_plausible_header(byte: u8) -> bool {
+    let major = byte >> 5;
+    let info = byte & 0x1f;
+
+    match (major, info) {
+        // Reserved additional information values are never valid.
+        (_, 28..=30) => false,
+
+        // Integers and tags can't have an indefinite length.
+        (0 | 1 | 6, 31) => false,
+
+        // Simple values: only false, true, null, undefined, and floats are plausible.
+        (7, info) => matches!(info, 20..=23 | 25..=27),
+
+        _ => true,
+    }
+}
+
+/// Consume one CBOR item from `reader` without keeping any of it, however large it claims to be.
+///
+/// Used to find the end of an item that's too large to read. Fails with
+/// [`io::ErrorKind::InvalidData`] if the item is malformed.
+fn skip_item(reader: &mut impl io::Read) -> io::Result<()> {
+    use std::io::Read;
+
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed item");
+
+    // How many more items each enclosing array, map, or tag holds, or `None` for an
+    // indefinite-length one which lasts until a "break".
+    let mut open = vec![Some(1u64)];
+    while let Some(remaining) = open.last_mut() {
+        if *remaining == Some(0) {
+            open.pop();
+            continue;
+        }
+
+        let mut initial = [0];
+        reader.read_exact(&mut initial)?;
+
+        if initial[0] == 0xff {
+            match remaining {
+                None => open.pop(),
+                Some(_) => return Err(invalid()),
+            };
+            continue;
+        }
+
+        if let Some(n) = remaining {
+            *n -= 1;
+        }
+
+        let (major, info) = (initial[0] >> 5, initial[0] & 0x1f);
+        let argument = match info {
+            0..=23 => Some(info as u64),
+            24..=27 => {
+                let mut bytes = [0; 8];
+                let len = 1 << (info - 24);
+                reader.read_exact(&mut bytes[8 - len..])?;
+                Some(u64::from_be_bytes(bytes))
+            }
+            31 => None,
+            _ => return Err(invalid()),
+        };
+
+        match (major, argument) {
+            // Integers and simple values are all in the argument.
+            (0 | 1 | 7, Some(_)) => {}
+
+            (2 | 3, Some(len)) => {
+                let skipped = io::copy(&mut reader.take(len), &mut io::sink())?;
+                if skipped < len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+
+            (4, Some(len)) => open.push(Some(len)),
+            (5, Some(len)) => open.push(Some(len.saturating_mul(2))),
+            (6, Some(_)) => open.push(Some(1)),
+
+            // Indefinite-length strings are made of chunks, which are skipped like items.
+            (2..=5, None) => open.push(None),
+
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(())
+}
+
+/// A reader which fails once more than some number of bytes have been read through it.
+struct LimitedReader<'r, R> {
+    inner: &'r mut R,
+    remaining: usize,
+
+    /// Set if a read was cut off by the limit.
+    exceeded: bool,
+
+    /// The bytes read so far, if they're being kept.
+    record: Option<Vec<u8>>,
+}
+
+impl<'r, R: io::Read> io::Read for LimitedReader<'r, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 && !buf.is_empty() {
+            self.exceeded = true;
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "item too large"));
+        }
+
+        let max = buf.len().min(self.remaining);
+        let n = self.inner.read(&mut buf[..max])?;
+        self.remaining -= n;
+        if let Some(record) = &mut self.record {
+            record.extend_from_slice(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+/// A reader which counts the bytes read through it.
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R: io::Read> io::Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// A callback for [`InputParser::on_item`], which is passed the number of items read so far.
+struct ItemCallback {
+    every: u64,
+
+    /// How many more items until the callback runs next.
+    remaining: u64,
+
+    callback: fn(u64),
 }
 
 pub struct InputParser<T, R> {
-    buffer: io::BufReader<R>,
+    buffer: io::BufReader<CountingReader<R>>,
+
+    /// The format of the input, or `None` if we haven't sniffed it yet.
+    format: Option<Format>,
+
+    /// If we should try to skip past corrupt CBOR items, rather than giving up.
+    resilient: bool,
+
+    /// Set when the last item was corrupt, and the reader is misaligned.
+    failed: bool,
+
+    /// The index of the next item in the stream.
+    index: u64,
+
+    /// The most bytes a single item can take up, if limited.
+    max_item_bytes: Option<usize>,
+
+    /// The header at the start of the stream, if there was one.
+    header: Option<StreamHeader>,
+
+    /// Set once we've looked for a header.
+    header_checked: bool,
+
+    /// The number of items successfully read.
+    items_read: u64,
+
+    /// A callback to run every so many items.
+    on_item: Option<ItemCallback>,
 
     // so that we can use the T generic without storing a T
     _phantom_type: std::marker::PhantomData<T>,
 }
 
 impl<T, R: io::Read> InputParser<T, R> {
+    /// Create an InputParser reading a stream of CBOR items.
     pub fn new(reader: R) -> Self {
+        Self::with_format(reader, Some(Format::Cbor))
+    }
+
+    /// Create an InputParser reading JSON Lines.
+    pub fn new_json(reader: R) -> Self {
+        Self::with_format(reader, Some(Format::Json))
+    }
+
+    /// Create an InputParser reading CBOR if `MONCH_FORMAT` asks for it, as the shell does.
+    ///
+    /// Otherwise, like when a utility is run on its own with JSON piped in, the format is guessed
+    /// from the first byte: see [`InputParser::new_sniffing`].
+    pub fn new_auto(reader: R) -> Self {
+        Self::with_format(reader, input_format(requested_format()))
+    }
+
+    /// Create an InputParser which guesses whether its input is CBOR or JSON Lines, from its
+    /// first byte. See [`Format::sniff`] for why this is only a guess.
+    pub fn new_sniffing(reader: R) -> Self {
+        Self::with_format(reader, None)
+    }
+
+    /// Create an InputParser reading a stream of CBOR items, which skips past corrupt items.
+    pub fn new_resilient(reader: R) -> Self {
+        Self::new(reader).resilient()
+    }
+
+    /// Skip past corrupt CBOR items instead of giving up on the rest of the stream.
+    ///
+    /// After a corrupt item, the parser scans forward to the next byte that could plausibly start
+    /// an item, and yields an [`Error::Skipped`] saying how much it skipped. Iteration continues
+    /// from there. JSON Lines input always continues at the next line.
+    pub fn resilient(mut self) -> Self {
+        self.resilient = true;
+        self
+    }
+
+    /// Fail with [`Error::ItemTooLarge`] on any item larger than `limit` bytes, instead of
+    /// reading it into memory. When [`resilient`](Self::resilient), the rest of the item is
+    /// skipped, and iteration continues after it.
+    pub fn with_max_item_size(mut self, limit: usize) -> Self {
+        self.max_item_bytes = Some(limit);
+        self
+    }
+
+    fn with_format(reader: R, format: Option<Format>) -> Self {
         // 64-byte input buffer. Short because input lines are short.
-        let buffer = io::BufReader::with_capacity(64, reader);
+        let counter = CountingReader {
+            inner: reader,
+            count: 0,
+        };
+        let buffer = io::BufReader::with_capacity(64, counter);
 
         // Construct an input parser iterator
         InputParser {
             buffer,
+            format,
+            resilient: false,
+            failed: false,
+            index: 0,
+            max_item_bytes: None,
+            header: None,
+            header_checked: false,
+            items_read: 0,
+            on_item: None,
             _phantom_type: Default::default(),
         }
     }
-}
 
-impl<T: Deserialize<'static>, R: io::Read> iter::Iterator for InputParser<T, R> {
-    type Item = Result<T, Error>;
+    /// The [`StreamHeader`] at the start of the stream, if there is one. This is only known once
+    /// the first item has been read.
+    pub fn header(&self) -> Option<&StreamHeader> {
+        self.header.as_ref()
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Call `callback` after every `every` items, with the number of items read so far. Useful
+    /// for reporting progress.
+    pub fn on_item(mut self, every: u64, callback: fn(u64)) -> Self {
+        let every = every.max(1);
+        self.on_item = Some(ItemCallback {
+            every,
+            remaining: every,
+            callback,
+        });
+        self
+    }
+
+    /// The number of items successfully read so far. Corrupt items and the stream header don't
+    /// count.
+    pub fn items_read(&self) -> u64 {
+        self.items_read
+    }
+
+    /// The number of bytes of input consumed so far, which is the same as the
+    /// [`byte_offset`](Self::byte_offset). Bytes which have been read ahead into the buffer, but
+    /// aren't part of an item yet, don't count.
+    pub fn bytes_read(&self) -> u64 {
+        self.byte_offset()
+    }
+
+    /// The number of bytes of input consumed so far.
+    pub fn byte_offset(&self) -> u64 {
+        // Bytes sitting in the buffer have been read, but not consumed.
+        self.buffer.get_ref().count - self.buffer.buffer().len() as u64
+    }
+
+    /// Scan forward to the next plausible CBOR header, after a corrupt item which started at
+    /// `start`. Always skips at least one byte, to make progress.
+    fn resynchronize(&mut self, start: u64, source: ciborium::de::Error<io::Error>) -> Error {
+        use std::io::BufRead;
+
+        let mut first = self.byte_offset() == start;
+        loop {
+            let buf = match self.buffer.fill_buf() {
+                Ok(buf) => buf,
+                Err(e) => return Error::Io(e),
+            };
+
+            // Give up at EOF.
+            if buf.is_empty() {
+                break;
+            }
+
+            let skip = buf
+                .iter()
+                .enumerate()
+                .position(|(i, b)| !(first && i == 0) && is_plausible_header(*b));
+
+            match skip {
+                Some(n) => {
+                    self.buffer.consume(n);
+                    break;
+                }
+                None => {
+                    let n = buf.len();
+                    self.buffer.consume(n);
+                    first = false;
+                }
+            }
+        }
+
+        Error::Skipped {
+            offset: start,
+            skipped: self.byte_offset() - start,
+            source,
+        }
+    }
+
+    /// Read one CBOR item.
+    fn next_cbor<U: DeserializeOwned>(&mut self) -> Result<U, Error> {
+        // Attempt to read one object.
+        let start = self.byte_offset();
+        let index = self.index;
+        self.index += 1;
+
+        // When resilient, keep what's read of a limited item, so that we can find where it ends
+        // if it's too large.
+        let record = match (self.resilient, self.max_item_bytes) {
+            (true, Some(_)) => Some(vec![]),
+            _ => None,
+        };
+        let mut limited = LimitedReader {
+            inner: &mut self.buffer,
+            remaining: self.max_item_bytes.unwrap_or(usize::MAX),
+            exceeded: false,
+            record,
+        };
+        let read_result = ciborium::de::from_reader(&mut limited);
+        let exceeded = limited.exceeded;
+        let record = limited.record.take();
+
+        match read_result {
+            // The object read successfully.
+            Ok(obj) => Ok(obj),
+
+            // The object was too big, and we've stopped partway through it.
+            Err(_) if exceeded => {
+                self.failed = true;
+                let limit = self
+                    .max_item_bytes
+                    .expect("only limited readers are exceeded");
+
+                // Skip the rest of the item, so the next one is read from where it starts.
+                if let Some(record) = record {
+                    use std::io::Read;
+                    if let Err(e) = skip_item(&mut (&record[..]).chain(&mut self.buffer)) {
+                        // If the input ends partway through the item, or it's malformed, we'll
+                        // stop or resynchronize from here. Anything else is a real IO error.
+                        use io::ErrorKind::{InvalidData, UnexpectedEof};
+                        if !matches!(e.kind(), UnexpectedEof | InvalidData) {
+                            return Err(Error::Io(e));
+                        }
+                    }
+                }
+
+                Err(Error::ItemTooLarge { limit })
+            }
+
+            // Find our place again, if we can.
+            Err(e) if self.resilient => Err(self.resynchronize(start, e)),
+
+            // There was an error parsing, pass it through to userspace with its position.
+            Err(e) => {
+                self.failed = true;
+                Err(Error::DeserializeAt {
+                    offset: start,
+                    index,
+                    source: e,
+                })
+            }
+        }
+    }
+
+    /// Read one JSON line, skipping blank lines. Returns `None` at EOF.
+    fn next_json(&mut self) -> Option<Result<T, Error>>
+    where
+        T: DeserializeOwned,
+    {
+        use std::io::{BufRead, Read};
+
+        // Read at most one byte past the limit, so we can tell when a line is too long.
+        let limit = self.max_item_bytes;
+        let max_read = limit.map_or(u64::MAX, |l| l as u64 + 1);
+
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match (&mut self.buffer).take(max_read).read_line(&mut line) {
+                Err(e) => return Some(Err(Error::Io(e))),
+                Ok(0) => return None,
+                Ok(_) if line.trim().is_empty() => continue,
+                Ok(_) => break,
+            }
+        }
+
+        if let Some(limit) = limit {
+            if line.len() > limit {
+                self.failed = true;
+
+                // Skip the rest of the line, so the next item starts on the one after it.
+                if self.resilient && !line.ends_with('\n') {
+                    if let Err(e) = self.skip_line() {
+                        return Some(Err(Error::Io(e)));
+                    }
+                }
+                return Some(Err(Error::ItemTooLarge { limit }));
+            }
+        }
+
+        let parsed = serde_json::from_str(&line).map_err(Error::DeserializeJson);
+        Some(parsed)
+    }
+
+    /// Discard input up to and including the next newline, or up to EOF if there isn't one.
+    fn skip_line(&mut self) -> io::Result<()> {
+        use std::io::BufRead;
+
+        loop {
+            let buf = self.buffer.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(());
+            }
+            match buf.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    self.buffer.consume(end + 1);
+                    return Ok(());
+                }
+                None => {
+                    let len = buf.len();
+                    self.buffer.consume(len);
+                }
+            }
+        }
+    }
+
+    /// Read the next item, in whichever format.
+    fn next_item(&mut self) -> Option<Result<T, Error>>
+    where
+        T: DeserializeOwned,
+    {
         use std::io::BufRead;
 
         // Try to read the next 64 bytes of data into the buffer.
         // This also lets us check for valid EOFs.
         let readahead = self.buffer.fill_buf();
-        match readahead {
+        let first_byte = match readahead {
             // Pass through IO errors to the calling program.
             Err(e) => return Some(Err(Error::Io(e))),
 
@@ -125,17 +1130,547 @@ impl<T: Deserialize<'static>, R: io::Read> iter::Iterator for InputParser<T, R>
             Ok(buf) if buf.is_empty() => return None,
 
             // Everything went well, try to read the object.
-            Ok(_) => {}
+            Ok(buf) => buf[0],
+        };
+
+        // If we don't know the format yet, guess it from the first byte we see.
+        let format = *self.format.get_or_insert_with(|| Format::sniff(first_byte));
+
+        // Once we've lost our place in a stream, we can't read anything else from it.
+        if self.failed && !self.resilient {
+            return None;
         }
 
-        // Attempt to read one object.
-        let read_result = ciborium::de::from_reader(&mut self.buffer);
-        match read_result {
-            // The object read successfully.
-            Ok(obj) => Some(Ok(obj)),
+        if format == Format::Json {
+            return self.next_json();
+        }
+
+        // A header can only come first, and isn't an item itself.
+        if !self.header_checked {
+            self.header_checked = true;
+            if first_byte == header::HEADER_FIRST_BYTE {
+                return match self.next_cbor::<Value>() {
+                    Ok(Value::Tag(HEADER_TAG, inner)) => match StreamHeader::from_inner(*inner) {
+                        Some(header) => {
+                            self.header = Some(header);
+                            self.index = 0;
+                            self.next_item()
+                        }
+                        None => {
+                            self.failed = true;
+                            Some(Err(Error::InvalidHeader))
+                        }
+                    },
+
+                    // Something else which happens to start the same way.
+                    Ok(value) => Some(value.deserialized().map_err(Error::ConstructValue)),
+                    Err(e) => Some(Err(e)),
+                };
+            }
+        }
+
+        Some(self.next_cbor())
+    }
+}
+
+impl<T: DeserializeOwned, R: io::Read> iter::Iterator for InputParser<T, R> {
+    type Item = Result<T, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.next_item();
+
+        if let Some(Ok(_)) = item {
+            self.items_read += 1;
+            if let Some(on_item) = &mut self.on_item {
+                on_item.remaining -= 1;
+                if on_item.remaining == 0 {
+                    on_item.remaining = on_item.every;
+                    (on_item.callback)(self.items_read);
+                }
+            }
+        }
+
+        item
+    }
+}
 
-            // There was an error parsing, pass it through to userspace.
-            Err(e) => Some(Err(Error::Deserialize(e))),
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_all(format: Option<Format>, input: &[u8]) -> Vec<Result<Value, Error>> {
+        InputParser::with_format(input, format).collect()
+    }
+
+    fn cbor_bytes(values: &[Value]) -> Vec<u8> {
+        let mut bytes = vec![];
+        for val in values {
+            ciborium::ser::into_writer(val, &mut bytes).unwrap();
+        }
+        bytes
+    }
+
+    #[test]
+    fn write_in_each_format() {
+        let value = cbor!({"a" => [1, "two"]}).unwrap();
+
+        let mut json_out = vec![];
+        write_format(&mut json_out, &value, Format::Json).unwrap();
+        write_format(&mut json_out, &value, Format::Json).unwrap();
+        assert_eq!(
+            String::from_utf8(json_out).unwrap(),
+            "{\"a\":[1,\"two\"]}\n{\"a\":[1,\"two\"]}\n"
+        );
+
+        let mut cbor_out = vec![];
+        write_format(&mut cbor_out, &value, Format::Cbor).unwrap();
+        assert_eq!(cbor_out, cbor_bytes(&[value]));
+    }
+
+    #[test]
+    fn write_and_read_round_trip() {
+        let mut buf = vec![];
+        try_put_to!(&mut buf, {"name" => "file", "size" => 12}).unwrap();
+        try_put_to!(&mut buf, ["a", "b"]).unwrap();
+        put_to!(&mut buf, &"text");
+
+        let mut reader = &buf[..];
+        let first: Value = read_one_from(&mut reader).unwrap();
+        let second: Value = read_one_from(&mut reader).unwrap();
+        let third: String = read_one_from(&mut reader).unwrap();
+
+        assert_eq!(first, cbor!({"name" => "file", "size" => 12}).unwrap());
+        assert_eq!(second, cbor!(["a", "b"]).unwrap());
+        assert_eq!(third, "text");
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn put_inline_fields_and_objects() {
+        let name = String::from("file");
+        let names = vec![name.clone()];
+        let size: Option<u64> = None;
+
+        let mut buf = vec![];
+        try_put_to!(&mut buf, name: name.as_str(), size: size, first: names.first()).unwrap();
+        try_put_to!(&mut buf, @obj names).unwrap();
+        put_to!(&mut buf, @obj Some(3));
+
+        let mut reader = &buf[..];
+        let first: Value = read_one_from(&mut reader).unwrap();
+        let second: Vec<String> = read_one_from(&mut reader).unwrap();
+        let third: Value = read_one_from(&mut reader).unwrap();
+
+        assert_eq!(
+            first,
+            cbor!({"name" => "file", "size" => null, "first" => "file"}).unwrap()
+        );
+        assert_eq!(second, names);
+        assert_eq!(third, Value::Integer(3.into()));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn resilient_skips_corrupt_item() {
+        let mut input = cbor_bytes(&[Value::Text("first".into())]);
+        let corrupt_at = input.len() as u64;
+
+        // A reserved header, followed by a couple of stray "break" codes.
+        input.extend([0x1c, 0xff, 0xff]);
+        input.extend(cbor_bytes(&[
+            Value::Text("second".into()),
+            cbor!({"a" => 1}).unwrap(),
+        ]));
+
+        let mut parser = InputParser::<Value, _>::new_resilient(&input[..]);
+        assert_eq!(parser.next().unwrap().unwrap(), Value::Text("first".into()));
+        assert_eq!(parser.byte_offset(), corrupt_at);
+
+        match parser.next().unwrap() {
+            Err(Error::Skipped {
+                offset, skipped, ..
+            }) => {
+                assert_eq!(offset, corrupt_at);
+                assert_eq!(skipped, 3);
+            }
+            other => panic!("expected a skip, got {:?}", other),
+        }
+
+        let rest: Vec<Value> = parser.map(Result::unwrap).collect();
+        assert_eq!(
+            rest,
+            vec![Value::Text("second".into()), cbor!({"a" => 1}).unwrap()]
+        );
+    }
+
+    #[test]
+    fn non_resilient_stops_after_corrupt_item() {
+        let mut input = cbor_bytes(&[Value::Text("first".into())]);
+        input.extend([0x1c, 0xff, 0xff]);
+        input.extend(cbor_bytes(&[Value::Text("second".into())]));
+
+        let items = parse_all(Some(Format::Cbor), &input);
+        assert_eq!(items.len(), 2);
+        assert!(matches!(items[1], Err(Error::DeserializeAt { .. })));
+    }
+
+    #[test]
+    fn error_reports_position() {
+        let mut input = cbor_bytes(&[Value::Text("zero".into()), Value::Text("one".into())]);
+        let corrupt_at = input.len() as u64;
+        input.push(0x1c);
+
+        let items = parse_all(Some(Format::Cbor), &input);
+        let err = items[2].as_ref().unwrap_err();
+        match err {
+            Error::DeserializeAt { offset, index, .. } => {
+                assert_eq!(*offset, corrupt_at);
+                assert_eq!(*index, 2);
+            }
+            other => panic!("expected a positioned error, got {:?}", other),
+        }
+
+        let message = err.to_string();
+        assert!(message.contains("object 2"), "{}", message);
+        assert!(
+            message.contains(&format!("byte {}", corrupt_at)),
+            "{}",
+            message
+        );
+    }
+
+    #[test]
+    fn item_size_limit() {
+        let limit = 1024;
+        let parse_limited = |input: &[u8]| -> Vec<Result<Value, Error>> {
+            InputParser::new(input).with_max_item_size(limit).collect()
+        };
+
+        // A byte string claiming to be 1 GiB long, which is cut off well before then.
+        let mut huge = vec![0x5a, 0x40, 0x00, 0x00, 0x00];
+        huge.extend([0u8; 2048]);
+        let items = parse_limited(&huge);
+        assert_eq!(items.len(), 1);
+        assert!(matches!(items[0], Err(Error::ItemTooLarge { limit: 1024 })));
+
+        // When resilient, the rest of it is skipped, rather than read as items of its own.
+        let items: Vec<Result<Value, Error>> = InputParser::new_resilient(&huge[..])
+            .with_max_item_size(limit)
+            .collect();
+        assert!(
+            matches!(items[..], [Err(Error::ItemTooLarge { .. })]),
+            "{:?}",
+            items
+        );
+
+        // ...and the item after it is still read, even if the large one is nested.
+        let nested = Value::Array(vec![
+            Value::Map(vec![("data".into(), Value::Bytes(vec![0; 2048]))]),
+            Value::Tag(1, Box::new(Value::Text("a".repeat(2048)))),
+        ]);
+        let mut input = cbor_bytes(&[nested]);
+        // An indefinite-length array, holding a 2 KiB byte string and an empty one.
+        input.extend([0x9f, 0x59, 0x08, 0x00]);
+        input.extend([0u8; 2048]);
+        input.extend([0x40, 0xff]);
+        input.extend(cbor_bytes(&[Value::Text("next".into())]));
+        let items: Vec<Result<Value, Error>> = InputParser::new_resilient(&input[..])
+            .with_max_item_size(limit)
+            .collect();
+        assert_eq!(items.len(), 3, "{:?}", items);
+        assert!(matches!(items[0], Err(Error::ItemTooLarge { .. })));
+        assert!(matches!(items[1], Err(Error::ItemTooLarge { .. })));
+        assert_eq!(items[2].as_ref().unwrap(), &Value::Text("next".into()));
+
+        // Items just under and exactly at the limit are fine.
+        let under = Value::Bytes(vec![1; 1000]);
+        let exact = Value::Bytes(vec![2; 1021]); // plus a 3-byte header
+        let input = cbor_bytes(&[under.clone(), exact.clone()]);
+        let items: Vec<Value> = parse_limited(&input)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(items, vec![under, exact]);
+
+        // JSON lines are limited too.
+        let long_line = format!("\"{}\"\n", "a".repeat(2048));
+        let items: Vec<Result<Value, Error>> = InputParser::new_json(long_line.as_bytes())
+            .with_max_item_size(limit)
+            .collect();
+        assert!(matches!(items[..], [Err(Error::ItemTooLarge { .. })]));
+
+        // When resilient, the rest of a long line is skipped, and the next line still read.
+        let input = format!("{}{{\"name\": \"ok\"}}\n", long_line);
+        let items: Vec<Result<Value, Error>> = InputParser::new_json(input.as_bytes())
+            .with_max_item_size(limit)
+            .resilient()
+            .collect();
+        assert_eq!(items.len(), 2, "{:?}", items);
+        assert!(matches!(items[0], Err(Error::ItemTooLarge { .. })));
+        assert_eq!(
+            items[1].as_ref().unwrap(),
+            &Value::Map(vec![("name".into(), "ok".into())])
+        );
+    }
+
+    #[test]
+    fn write_all_items_separately() {
+        let mut buf = vec![];
+        write_all_to(&mut buf, vec!["a", "b"]).unwrap();
+        assert_eq!(
+            buf,
+            cbor_bytes(&[Value::Text("a".into()), Value::Text("b".into())])
+        );
+    }
+
+    #[test]
+    fn write_all_reports_closed() {
+        struct ClosedPipe;
+
+        impl io::Write for ClosedPipe {
+            fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let result = write_all_to(&mut ClosedPipe, [1, 2, 3]);
+        assert!(matches!(result, Err(Error::Closed)));
+    }
+
+    #[test]
+    fn closed_pipe_ends_output_loop() {
+        let (reader, mut writer) = os_pipe::pipe().unwrap();
+        drop(reader);
+
+        // Write like a utility would, stopping once nobody is listening.
+        let mut written = 0;
+        for item in 0..1000 {
+            if !output_open(write_to(&mut writer, &item)).unwrap() {
+                break;
+            }
+            written += 1;
+        }
+
+        assert_eq!(written, 0);
+    }
+
+    #[test]
+    fn header_is_not_an_item() {
+        let header = StreamHeader::new("entry", &["name"]);
+        let items = vec![
+            cbor!({"name" => "a"}).unwrap(),
+            cbor!({"name" => "b"}).unwrap(),
+        ];
+
+        let mut input = cbor_bytes(&[header.to_value()]);
+        input.extend(cbor_bytes(&items));
+
+        let mut parser = InputParser::<Value, _>::new(&input[..]);
+        assert_eq!(parser.header(), None);
+        assert_eq!(parser.next().unwrap().unwrap(), items[0]);
+        assert_eq!(parser.header(), Some(&header));
+        assert_eq!(parser.next().unwrap().unwrap(), items[1]);
+        assert!(parser.next().is_none());
+
+        // A header with nothing after it is an empty stream.
+        let input = cbor_bytes(&[header.to_value()]);
+        let mut parser = InputParser::<Value, _>::new(&input[..]);
+        assert!(parser.next().is_none());
+        assert_eq!(parser.header(), Some(&header));
+    }
+
+    #[test]
+    fn read_one_skips_header() {
+        let header = StreamHeader::new("entry", &["name"]);
+        let mut input = cbor_bytes(&[header.to_value()]);
+        input.extend(cbor_bytes(&[cbor!({"name" => "a"}).unwrap(), Value::Null]));
+
+        #[derive(serde::Deserialize)]
+        struct Entry {
+            name: String,
         }
+
+        let mut reader = &input[..];
+        let first: Entry = read_one_from(&mut reader).unwrap();
+        let second: Value = read_one_from(&mut reader).unwrap();
+        assert_eq!(first.name, "a");
+        assert_eq!(second, Value::Null);
+        assert!(reader.is_empty());
+
+        // Other tags which look like headers at first are objects.
+        let tagged = Value::Tag(0x1234_5678, Box::new(Value::Integer(1.into())));
+        let input = cbor_bytes(std::slice::from_ref(&tagged));
+        assert_eq!(read_one_from::<_, Value>(&input[..]).unwrap(), tagged);
+
+        let not_a_header = Value::Tag(HEADER_TAG, Box::new(Value::Integer(1.into())));
+        let input = cbor_bytes(&[not_a_header]);
+        assert!(matches!(
+            read_one_from::<_, Value>(&input[..]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn streams_without_headers() {
+        // Other tags with 4-byte numbers are items, even though they look like headers at first.
+        let items = vec![
+            Value::Tag(0x1234_5678, Box::new(Value::Integer(1.into()))),
+            Value::Integer(2.into()),
+        ];
+        let input = cbor_bytes(&items);
+        let mut parser = InputParser::<Value, _>::new_auto(&input[..]);
+        assert_eq!(parser.next().unwrap().unwrap(), items[0]);
+        assert_eq!(parser.next().unwrap().unwrap(), items[1]);
+        assert_eq!(parser.header(), None);
+
+        // A header anywhere but the start is just a tagged item.
+        let header = StreamHeader::new("entry", &[]).to_value();
+        let items = vec![Value::Null, header];
+        let read: Vec<Value> = parse_all(Some(Format::Cbor), &cbor_bytes(&items))
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read, items);
+    }
+
+    #[test]
+    fn malformed_header() {
+        let not_a_header = Value::Tag(HEADER_TAG, Box::new(Value::Integer(1.into())));
+        let results = parse_all(
+            Some(Format::Cbor),
+            &cbor_bytes(&[not_a_header, Value::Null]),
+        );
+        assert!(matches!(results[..], [Err(Error::InvalidHeader)]));
+    }
+
+    #[test]
+    fn counters_track_consumed_input() {
+        let items: Vec<Value> = (0..5000u32)
+            .map(|i| Value::Text(format!("item {}", i)))
+            .collect();
+        let input = cbor_bytes(&items);
+
+        let mut parser = InputParser::<Value, _>::new(&input[..]);
+        let mut expected_bytes = 0;
+        for (i, item) in items.iter().enumerate() {
+            assert_eq!(parser.next().unwrap().unwrap(), *item);
+
+            // Only bytes making up items count, even though the parser reads ahead.
+            expected_bytes += cbor_bytes(std::slice::from_ref(item)).len() as u64;
+            assert_eq!(parser.items_read(), i as u64 + 1);
+            assert_eq!(parser.bytes_read(), expected_bytes);
+        }
+
+        assert!(parser.next().is_none());
+        assert_eq!(parser.items_read(), 5000);
+        assert_eq!(parser.bytes_read(), input.len() as u64);
+    }
+
+    #[test]
+    fn on_item_callback() {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static CALLS: AtomicU64 = AtomicU64::new(0);
+        static LAST: AtomicU64 = AtomicU64::new(0);
+
+        fn record(count: u64) {
+            CALLS.fetch_add(1, Ordering::SeqCst);
+            LAST.store(count, Ordering::SeqCst);
+        }
+
+        let input = b"1\n2\n3\n4\n5\n6\n7\n";
+        let parser = InputParser::<u32, _>::new_json(&input[..]).on_item(3, record);
+        assert_eq!(parser.count(), 7);
+
+        assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+        assert_eq!(LAST.load(Ordering::SeqCst), 6);
+    }
+
+    #[test]
+    fn input_formats() {
+        assert_eq!(input_format(Some(Format::Cbor)), Some(Format::Cbor));
+        assert_eq!(input_format(Some(Format::Json)), None);
+        assert_eq!(input_format(None), None);
+
+        // With nothing set, like `jq -c | get .a` outside the shell, plain JSON is read as JSON.
+        let json = b"{\"a\":1}\n[2]\n";
+        let read: Vec<Value> = parse_all(input_format(None), json)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read, [cbor!({"a" => 1}).unwrap(), cbor!([2]).unwrap()]);
+
+        // ...and so is CBOR, including a stream header, whose tag doesn't look like JSON.
+        let items = vec![cbor!({"a" => 1}).unwrap()];
+        let mut input = vec![];
+        write_header_to(&mut input, &StreamHeader::new("thing", &["a"])).unwrap();
+        input.extend(cbor_bytes(&items));
+        let read: Vec<Value> = parse_all(input_format(None), &input)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read, items);
+
+        // -3 is encoded as `"`, and -17 as `0`, so they're only read right when CBOR was asked for.
+        let items = vec![Value::Integer((-3).into()), Value::Integer((-17).into())];
+        let input = cbor_bytes(&items);
+        assert_eq!(&input, b"\"0");
+        let read: Vec<Value> = parse_all(input_format(Some(Format::Cbor)), &input)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(read, items);
+        assert!(parse_all(input_format(None), &input)[0].is_err());
+    }
+
+    #[test]
+    fn format_names() {
+        assert_eq!(Format::from_str("json"), Ok(Format::Json));
+        assert_eq!(Format::from_str(" CBOR "), Ok(Format::Cbor));
+        assert_eq!(Format::from_str("yaml"), Err(()));
+    }
+
+    #[test]
+    fn json_lines_mixed_validity() {
+        let input = b"{\"a\": 1}\nnot json\n\n[1, 2]\n\"text\"";
+        let items = parse_all(Some(Format::Json), input);
+
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[0].as_ref().unwrap(), &cbor!({"a" => 1}).unwrap());
+        assert!(matches!(items[1], Err(Error::DeserializeJson(_))));
+        assert_eq!(items[2].as_ref().unwrap(), &cbor!([1, 2]).unwrap());
+        assert_eq!(items[3].as_ref().unwrap(), &Value::Text("text".into()));
+    }
+
+    #[test]
+    fn empty_input() {
+        assert!(parse_all(Some(Format::Json), b"").is_empty());
+        assert!(parse_all(Some(Format::Cbor), b"").is_empty());
+        assert!(parse_all(None, b"").is_empty());
+    }
+
+    #[test]
+    fn auto_detects_json() {
+        let items = parse_all(None, b"{\"a\": true}\n12\n");
+        let items: Vec<Value> = items.into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            items,
+            vec![cbor!({"a" => true}).unwrap(), Value::Integer(12.into())]
+        );
+    }
+
+    #[test]
+    fn auto_detects_cbor() {
+        let values = vec![
+            cbor!({"a" => true}).unwrap(),
+            Value::Text("text".into()),
+            Value::Integer(12.into()),
+        ];
+        let items = parse_all(None, &cbor_bytes(&values));
+        let items: Vec<Value> = items.into_iter().map(Result::unwrap).collect();
+        assert_eq!(items, values);
     }
 }
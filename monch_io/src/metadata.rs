@@ -0,0 +1,62 @@
+//! Describing files and times the same way in every utility.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fs::{self, FileType};
+use std::path::Path;
+use std::time::SystemTime;
+
+#[cfg(not(unix))]
+use std::path::PathBuf;
+
+/// Based on file metadata, come up with a type: `Dir`, `File`, `Symlink`, or `Unknown`.
+pub fn kind_name(file_type: FileType) -> &'static str {
+    if file_type.is_dir() {
+        "Dir"
+    } else if file_type.is_file() {
+        "File"
+    } else if file_type.is_symlink() {
+        "Symlink"
+    } else {
+        "Unknown"
+    }
+}
+
+/// Format a time like `2021-11-05T14:03:09Z`.
+pub fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Something that's the same for every path to a directory, including through symlinks. Useful
+/// for noticing when a walk comes back around to a directory it's already seen.
+#[cfg(unix)]
+pub fn dir_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = fs::metadata(path).ok()?;
+    Some((meta.dev(), meta.ino()))
+}
+
+/// Something that's the same for every path to a directory, including through symlinks. Useful
+/// for noticing when a walk comes back around to a directory it's already seen.
+#[cfg(not(unix))]
+pub fn dir_id(path: &Path) -> Option<PathBuf> {
+    fs::canonicalize(path).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn describe() {
+        let dir = env::temp_dir();
+        assert_eq!(kind_name(fs::metadata(&dir).unwrap().file_type()), "Dir");
+        assert!(dir_id(&dir).is_some());
+        assert_eq!(dir_id(&dir.join(".")), dir_id(&dir));
+
+        let time = UNIX_EPOCH + Duration::from_millis(1_636_121_589_500);
+        assert_eq!(rfc3339(time), "2021-11-05T14:13:09Z");
+    }
+}
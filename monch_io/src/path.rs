@@ -1,35 +1,86 @@
 use ciborium::value::Value;
 use std::fmt;
 use std::str::FromStr;
+use thiserror::Error;
 
 /// Represents a path to get some nested data, like `.outer.inner.12.field`.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct DataPath(Vec<Value>);
 
 impl DataPath {
     /// Parse a `jq`-like string path, in the form `outer.inner.1`.
     ///
     /// Works by converting each `.`-separated path segment into either a CBOR
-    /// [`Integer`](ciborium::value::Integer), or a String key.
+    /// [`Integer`](ciborium::value::Integer), or a String key. This is lenient: empty segments are
+    /// dropped, and nothing else is validated. See [`DataPath::parse_strict`] for a version that
+    /// reports errors.
     pub fn parse(path: &str) -> DataPath {
         let parsed_parts = path
             .split('.')
             .filter(|part| !part.is_empty())
-            .map(|part| {
-                // For each `.`-separated path segment...
+            .map(parse_segment)
+            .collect();
+
+        DataPath(parsed_parts)
+    }
 
-                // Try to parse it as an integer
-                if let Ok(int) = u64::from_str(part) {
-                    // return CBOR varint encoding
-                    return Value::Integer(int.into());
+    /// Parse a `jq`-like string path, in the form `.outer.inner.1`, rejecting malformed paths.
+    ///
+    /// The leading `.` is optional, and both `.` and the empty string are the empty path. Unlike
+    /// [`DataPath::parse`], empty segments (like in `..outer` or `outer.`), unbalanced quotes, and
+    /// slice syntax are all errors.
+    ///
+    /// Keys can be quoted with `'` or `"`, like `.'file.txt'`, so they can contain dots. The quotes
+    /// aren't part of the key, and a quoted key is always text, even if it looks like a number.
+    pub fn parse_strict(path: &str) -> Result<DataPath, DataPathError> {
+        // Skip the optional leading dot, keeping track of where we are in the input.
+        let (body, start) = match path.strip_prefix('.') {
+            Some(rest) => (rest, 1),
+            None => (path, 0),
+        };
+
+        // The identity path has no segments at all.
+        if body.is_empty() {
+            return Ok(DataPath(vec![]));
+        }
+
+        let mut parsed_parts = vec![];
+        let (mut key, mut quoted, mut key_start) = (String::new(), false, start);
+        let mut open_quote: Option<(usize, char)> = None;
+        for (i, c) in body.char_indices() {
+            let offset = start + i;
+            match (open_quote, c) {
+                (Some((_, q)), c) if c == q => open_quote = None,
+                (Some(_), c) => key.push(c),
+                (None, '"' | '\'') => {
+                    open_quote = Some((offset, c));
+                    quoted = true;
                 }
+                (None, '[' | ']') => return Err(DataPathError::InvalidSlice { offset }),
+                (None, '.') => {
+                    parsed_parts.push(strict_segment(key, quoted, key_start)?);
+                    key = String::new();
+                    quoted = false;
+                    key_start = offset + 1;
+                }
+                (None, c) => key.push(c),
+            }
+        }
 
-                // If it's not an integer, treat it as a string key.
-                Value::Text(part.to_string())
-            })
-            .collect();
+        // Every quote we open has to be closed.
+        if let Some((offset, _)) = open_quote {
+            return Err(DataPathError::UnbalancedQuote { offset });
+        }
+        parsed_parts.push(strict_segment(key, quoted, key_start)?);
 
-        DataPath(parsed_parts)
+        Ok(DataPath(parsed_parts))
+    }
+
+    /// Make a new path, which refers to the given key inside the value this path refers to.
+    pub fn child(&self, key: Value) -> DataPath {
+        let mut parts = self.0.clone();
+        parts.push(key);
+        DataPath(parts)
     }
 
     /// If this path is non-empty, split off the first element.
@@ -39,6 +90,28 @@ impl DataPath {
             .map(|(head, tail)| (head.clone(), DataPath(tail.into())))
     }
 
+    /// The last key in this path, or `None` if it's empty.
+    pub fn last(&self) -> Option<&Value> {
+        self.0.last()
+    }
+
+    /// Use this path to put a value inside another, replacing whatever was there.
+    ///
+    /// Maps are created where there's nothing to key into, and replace any other values in the
+    /// way, like text. Integer keys index into arrays, which are padded with nulls if they're
+    /// too short. Tags are passed through, like in [`DataPath::get_from`].
+    pub fn set_into(&self, target: &mut Value, value: Value) {
+        set_in(&self.0, target, value)
+    }
+
+    /// Use this path to take a value out of another, returning `None` if there's nothing there.
+    ///
+    /// Keys are removed from maps, but array elements are replaced with nulls, so that the ones
+    /// after them keep their indices. The empty path takes the whole value, leaving a null.
+    pub fn take_from(&self, target: &mut Value) -> Option<Value> {
+        take_in(&self.0, target)
+    }
+
     /// Use this path to get an inner value from a CBOR [`Value`].
     ///
     /// If the value is not found, returns [`Value::Null`] instead.
@@ -108,21 +181,207 @@ impl DataPath {
             _ => Value::Null,
         }
     }
+
+    /// Use this path to borrow an inner value from a CBOR [`Value`], without cloning it.
+    ///
+    /// This behaves just like [`DataPath::get_from`], but returns `None` where `get_from` would
+    /// return a [`Value::Null`] for a value that wasn't found.
+    pub fn get_ref<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        let mut current = untag(value);
+
+        for key in &self.0 {
+            let inner = match (current, key) {
+                // Index into arrays, bounds-checking the index.
+                (Value::Array(array), Value::Integer(key)) => {
+                    let key_usize = usize::try_from(*key).ok()?;
+                    array.get(key_usize)?
+                }
+
+                // Key into maps, taking the first matching element.
+                (Value::Map(map), key) => map.iter().find(|(k, _v)| k == key).map(|(_k, v)| v)?,
+
+                // Nothing else can be indexed into.
+                _ => return None,
+            };
+
+            current = untag(inner);
+        }
+
+        Some(current)
+    }
+}
+
+/// Put a value at the path given by `keys` inside `target`, for [`DataPath::set_into`].
+fn set_in(keys: &[Value], target: &mut Value, value: Value) {
+    let (key, rest) = match keys.split_first() {
+        Some(split) => split,
+        None => {
+            *target = value;
+            return;
+        }
+    };
+
+    if let Value::Tag(_tag, inner) = target {
+        return set_in(keys, inner, value);
+    }
+
+    let index = match key {
+        Value::Integer(i) => usize::try_from(*i).ok(),
+        _ => None,
+    };
+    match (target, index) {
+        (Value::Array(array), Some(index)) => {
+            if array.len() <= index {
+                array.resize(index + 1, Value::Null);
+            }
+            set_in(rest, &mut array[index], value)
+        }
+
+        // Take the first matching element, like everywhere else.
+        (Value::Map(map), _) => match map.iter().position(|(k, _v)| k == key) {
+            Some(i) => set_in(rest, &mut map[i].1, value),
+            None => {
+                let mut inner = Value::Null;
+                set_in(rest, &mut inner, value);
+                map.push((key.clone(), inner));
+            }
+        },
+
+        // Anything else is in the way, so replace it with a map.
+        (target, _) => {
+            *target = Value::Map(vec![]);
+            set_in(keys, target, value)
+        }
+    }
+}
+
+/// Take the value at the path given by `keys` out of `target`, for [`DataPath::take_from`].
+fn take_in(keys: &[Value], target: &mut Value) -> Option<Value> {
+    let (key, rest) = match keys.split_first() {
+        Some(split) => split,
+        None => return Some(std::mem::replace(target, Value::Null)),
+    };
+
+    match (target, key) {
+        (Value::Tag(_tag, inner), _) => take_in(keys, inner),
+
+        (Value::Array(array), Value::Integer(index)) => {
+            let inner = array.get_mut(usize::try_from(*index).ok()?)?;
+            take_in(rest, inner)
+        }
+
+        (Value::Map(map), _) => {
+            let i = map.iter().position(|(k, _v)| k == key)?;
+            if rest.is_empty() {
+                Some(map.remove(i).1)
+            } else {
+                take_in(rest, &mut map[i].1)
+            }
+        }
+
+        _ => None,
+    }
+}
+
+/// Convert a single `.`-separated path segment into a key.
+fn parse_segment(part: &str) -> Value {
+    // Try to parse it as an integer
+    if let Ok(int) = u64::from_str(part) {
+        // return CBOR varint encoding
+        return Value::Integer(int.into());
+    }
+
+    // If it's not an integer, treat it as a string key.
+    Value::Text(part.to_string())
+}
+
+/// Convert a path segment for [`DataPath::parse_strict`], with its quotes already removed, given
+/// its byte offset in the path.
+fn strict_segment(key: String, quoted: bool, offset: usize) -> Result<Value, DataPathError> {
+    match (quoted, key.is_empty()) {
+        (true, _) => Ok(Value::Text(key)),
+        (false, true) => Err(DataPathError::EmptySegment { offset }),
+        (false, false) => Ok(parse_segment(&key)),
+    }
+}
+
+/// An error found while parsing a [`DataPath`] with [`DataPath::parse_strict`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum DataPathError {
+    #[error("empty path segment at byte {offset}")]
+    EmptySegment { offset: usize },
+
+    #[error("unbalanced quote at byte {offset}")]
+    UnbalancedQuote { offset: usize },
+
+    #[error("invalid slice at byte {offset}: slices are not supported in paths")]
+    InvalidSlice { offset: usize },
+}
+
+/// Strip any CBOR tags from a value, because paths pass through tags transparently.
+fn untag(mut value: &Value) -> &Value {
+    while let Value::Tag(_tag, inner) = value {
+        value = inner;
+    }
+    value
 }
 
 impl fmt::Display for DataPath {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The empty path refers to the whole value, like `jq`'s identity filter.
+        if self.0.is_empty() {
+            return write!(f, ".");
+        }
+
         for part in &self.0 {
+            write!(f, ".")?;
             match part {
-                Value::Text(s) => write!(f, "{}", s)?,
-                Value::Integer(i) => write!(f, "{}", u64::try_from(*i).unwrap())?,
-                _ => unreachable!("format unknown path part"),
+                Value::Text(s) => write_key(f, s)?,
+                Value::Integer(i) => write!(f, "{}", i128::from(*i))?,
+
+                // We can't parse anything else, but paths built with `child` could contain it.
+                other => write!(f, "{:?}", other)?,
             }
         }
         Ok(())
     }
 }
 
+/// Write a text key so that [`DataPath::parse_strict`] reads it back as the same key. Keys with
+/// anything special in them, or that look like numbers, are quoted.
+fn write_key(f: &mut fmt::Formatter, key: &str) -> fmt::Result {
+    let special = |c: char| matches!(c, '.' | '\'' | '"' | '[' | ']') || c.is_whitespace();
+    if !key.is_empty() && !key.contains(special) && u64::from_str(key).is_err() {
+        return write!(f, "{}", key);
+    }
+
+    if !key.contains('\'') {
+        return write!(f, "'{}'", key);
+    }
+    if !key.contains('"') {
+        return write!(f, "\"{}\"", key);
+    }
+
+    // With both kinds of quote, quote the pieces between single quotes separately, and each
+    // single quote with double quotes. Quoted pieces next to each other are all one key.
+    for (i, piece) in key.split('\'').enumerate() {
+        if i > 0 {
+            write!(f, "\"'\"")?;
+        }
+        if !piece.is_empty() {
+            write!(f, "'{}'", piece)?;
+        }
+    }
+    Ok(())
+}
+
+impl FromIterator<Value> for DataPath {
+    /// Build a path out of its keys, outermost first.
+    fn from_iter<I: IntoIterator<Item = Value>>(keys: I) -> DataPath {
+        DataPath(keys.into_iter().collect())
+    }
+}
+
 impl From<&str> for DataPath {
     fn from(text: &str) -> DataPath {
         DataPath::parse(text)
@@ -130,10 +389,10 @@ impl From<&str> for DataPath {
 }
 
 impl FromStr for DataPath {
-    type Err = String; // never constructed
+    type Err = DataPathError;
 
     fn from_str(text: &str) -> Result<DataPath, Self::Err> {
-        Ok(DataPath::parse(text))
+        DataPath::parse_strict(text)
     }
 }
 
@@ -220,4 +479,268 @@ mod tests {
             Value::Null
         );
     }
+
+    #[test]
+    fn get_ref_matches_get_from() {
+        let value = cbor!({
+            "outer" => {
+                "tagged" => Value::Tag(1234, Box::new(Value::Text("inside".into()))),
+                "list" => ["zero", "one", null],
+            }
+        })
+        .unwrap();
+
+        assert_eq!(
+            DataPath::parse(".outer.tagged").get_ref(&value),
+            Some(&Value::Text("inside".into()))
+        );
+
+        assert_eq!(
+            DataPath::parse(".outer.list.1").get_ref(&value),
+            Some(&Value::Text("one".into()))
+        );
+
+        // Present nulls are still found, but missing values aren't.
+        assert_eq!(
+            DataPath::parse(".outer.list.2").get_ref(&value),
+            Some(&Value::Null)
+        );
+        assert_eq!(DataPath::parse(".outer.list.3").get_ref(&value), None);
+        assert_eq!(DataPath::parse(".outer.nope.deeper").get_ref(&value), None);
+        assert_eq!(DataPath::parse(".outer.list.one").get_ref(&value), None);
+
+        // The empty path refers to the whole value.
+        assert_eq!(DataPath::parse("").get_ref(&value), Some(&value));
+    }
+
+    #[test]
+    fn set_into_creates_and_replaces() {
+        let mut value = cbor!({
+            "outer" => {
+                "list" => ["zero"],
+                "text" => "in the way",
+            }
+        })
+        .unwrap();
+
+        let set = |value: &mut Value, path: &str, inner: &str| {
+            DataPath::parse(path).set_into(value, Value::Text(inner.into()))
+        };
+        set(&mut value, ".outer.list.0", "first");
+        set(&mut value, ".outer.list.2", "third");
+        set(&mut value, ".outer.text.deeper", "replaced");
+        set(&mut value, ".new.nested", "created");
+
+        let expected = cbor!({
+            "outer" => {
+                "list" => ["first", null, "third"],
+                "text" => { "deeper" => "replaced" },
+            },
+            "new" => { "nested" => "created" },
+        })
+        .unwrap();
+        assert_eq!(value, expected);
+
+        // Tags are passed through, and the empty path replaces the whole value.
+        let mut tagged = Value::Tag(1234, Box::new(Value::Map(vec![])));
+        set(&mut tagged, ".key", "inside");
+        assert_eq!(
+            tagged,
+            Value::Tag(1234, Box::new(cbor!({ "key" => "inside" }).unwrap()))
+        );
+        set(&mut tagged, "", "everything");
+        assert_eq!(tagged, Value::Text("everything".into()));
+    }
+
+    #[test]
+    fn take_from_removes() {
+        let mut value = cbor!({
+            "outer" => {
+                "list" => ["zero", "one"],
+                "keep" => true,
+                "gone" => "soon",
+            }
+        })
+        .unwrap();
+
+        let take = |value: &mut Value, path: &str| DataPath::parse(path).take_from(value);
+        assert_eq!(
+            take(&mut value, ".outer.gone"),
+            Some(Value::Text("soon".into()))
+        );
+        assert_eq!(
+            take(&mut value, ".outer.list.0"),
+            Some(Value::Text("zero".into()))
+        );
+        assert_eq!(take(&mut value, ".outer.list.5"), None);
+        assert_eq!(take(&mut value, ".outer.keep.deeper"), None);
+        assert_eq!(take(&mut value, ".nope"), None);
+
+        let expected = cbor!({
+            "outer" => {
+                "list" => [null, "one"],
+                "keep" => true,
+            }
+        })
+        .unwrap();
+        assert_eq!(value, expected);
+
+        assert_eq!(take(&mut value, ""), Some(expected));
+        assert_eq!(value, Value::Null);
+    }
+
+    #[test]
+    fn get_ref_does_not_clone() {
+        let big: Vec<Value> = (0..100_000u64)
+            .map(|i| Value::Array(vec![Value::Integer(i.into())]))
+            .collect();
+        let value = Value::Map(vec![(Value::Text("big".into()), Value::Array(big))]);
+
+        let found = DataPath::parse(".big.99999.0").get_ref(&value).unwrap();
+
+        // The returned reference points into the original value.
+        let original = match &value {
+            Value::Map(map) => match &map[0].1 {
+                Value::Array(outer) => match &outer[99_999] {
+                    Value::Array(inner) => &inner[0],
+                    _ => unreachable!(),
+                },
+                _ => unreachable!(),
+            },
+            _ => unreachable!(),
+        };
+        assert!(std::ptr::eq(found, original));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let inputs = [
+            "",
+            ".",
+            ".outer",
+            ".outer.inner.2",
+            "outer.inner.2",
+            "..outer...0..",
+            ".0.1.2",
+            ".with-dashes.and_underscores",
+        ];
+
+        for input in inputs {
+            let parsed = DataPath::parse(input);
+            let displayed = parsed.to_string();
+            assert_eq!(
+                DataPath::parse(&displayed),
+                parsed,
+                "round-trip of {:?}",
+                input
+            );
+        }
+
+        assert_eq!(
+            DataPath::parse("outer.inner.2").to_string(),
+            ".outer.inner.2"
+        );
+        assert_eq!(DataPath::parse("").to_string(), ".");
+
+        // Keys that wouldn't be read back the same are quoted.
+        let text = |s: &str| Value::Text(s.into());
+        let paths = [
+            (vec![text("file.txt"), text("0")], ".'file.txt'.'0'"),
+            (
+                vec![text("quoted key"), Value::Integer(3.into())],
+                ".'quoted key'.3",
+            ),
+            (vec![text("it's"), text("a[1]")], ".\"it's\".'a[1]'"),
+            (vec![text("+5"), text(""), text("plain")], ".'+5'.''.plain"),
+            (vec![text("\"it's\"")], ".'\"it'\"'\"'s\"'"),
+            (vec![text("'")], ".\"'\""),
+            (vec![text("say \"hi\"\t")], ".'say \"hi\"\t'"),
+        ];
+        for (keys, displayed) in paths {
+            let path: DataPath = keys.into_iter().collect();
+            assert_eq!(path.to_string(), displayed);
+            assert_eq!(DataPath::parse_strict(&path.to_string()), Ok(path));
+        }
+
+        // Strict paths with quoted keys survive the round trip too.
+        let quoted = [
+            ".outer.inner.2",
+            ".\"file.txt\".'0'.0",
+            ".'quoted key'.''",
+            ".a'b c'd",
+            ".\"it's\".'[x]'",
+            "'123'.'-1'",
+        ];
+        for input in quoted {
+            let parsed = DataPath::parse_strict(input).unwrap();
+            assert_eq!(DataPath::parse_strict(&parsed.to_string()), Ok(parsed));
+        }
+    }
+
+    #[test]
+    fn parse_strict_valid() {
+        assert_eq!(
+            DataPath::parse_strict(".outer.inner.200").unwrap(),
+            DataPath::parse(".outer.inner.200")
+        );
+    }
+
+    #[test]
+    fn parse_strict_quoted() {
+        let text = |s: &str| Value::Text(s.into());
+        let strict = |path| DataPath::parse_strict(path).unwrap().0;
+
+        // Quotes aren't part of the key, and let it contain dots, or look like a number.
+        assert_eq!(
+            strict("outer.'quoted key'"),
+            vec![text("outer"), text("quoted key")]
+        );
+        assert_eq!(
+            strict(".\"file.txt\".'0'.0"),
+            vec![text("file.txt"), text("0"), Value::Integer(0.into())]
+        );
+        assert_eq!(strict(".''"), vec![text("")]);
+        assert_eq!(strict(".a'b c'd"), vec![text("ab cd")]);
+        assert_eq!(strict(".\"it's\""), vec![text("it's")]);
+
+        let value = cbor!({ "quoted key" => { "file.txt" => 1 } }).unwrap();
+        assert_eq!(
+            DataPath::parse_strict(".'quoted key'.'file.txt'")
+                .unwrap()
+                .get_ref(&value),
+            Some(&Value::Integer(1.into()))
+        );
+        assert!(DataPath::parse_strict("").unwrap().0.is_empty());
+        assert!(DataPath::parse_strict(".").unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn parse_strict_errors() {
+        use DataPathError::*;
+
+        assert_eq!(
+            DataPath::parse_strict("..foo..bar"),
+            Err(EmptySegment { offset: 1 })
+        );
+        assert_eq!(
+            DataPath::parse_strict(".foo..bar"),
+            Err(EmptySegment { offset: 5 })
+        );
+        assert_eq!(
+            DataPath::parse_strict(".foo."),
+            Err(EmptySegment { offset: 5 })
+        );
+        assert_eq!(
+            DataPath::parse_strict(".foo.'bar"),
+            Err(UnbalancedQuote { offset: 5 })
+        );
+        assert_eq!(
+            DataPath::parse_strict("foo.b\"ar"),
+            Err(UnbalancedQuote { offset: 5 })
+        );
+        assert_eq!(
+            DataPath::parse_strict(".foo.bar[1:2]"),
+            Err(InvalidSlice { offset: 8 })
+        );
+    }
 }
@@ -0,0 +1,149 @@
+//! Writing streams of maps as CSV tables, with a row for each map.
+//!
+//! The columns are the keys of the first map, in its order, unless they're given up front. Maps
+//! without a column's key leave its field empty, and keys which aren't columns are left out.
+//! Values are written as text like [`cbor_to_text`], except that null is an empty field.
+
+use crate::convert::{cbor_to_text, ConvertError};
+use ciborium::value::Value;
+use std::collections::BTreeMap;
+use std::io;
+use thiserror::Error;
+
+/// An error writing a CSV table.
+#[derive(Error, Debug)]
+pub enum CsvError {
+    #[error("expected a stream of maps")]
+    NotAMap,
+
+    #[error(transparent)]
+    Convert(#[from] ConvertError),
+
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+/// Writes maps as the rows of a CSV table, after a header row naming the columns. See the
+/// [module docs](self) for how maps become rows.
+pub struct CsvWriter<W: io::Write> {
+    writer: csv::Writer<W>,
+
+    /// The columns of the table, once we know them.
+    columns: Option<Vec<String>>,
+}
+
+impl<W: io::Write> CsvWriter<W> {
+    /// Create a CsvWriter whose columns are the keys of the first map written. Nothing is written
+    /// until then, not even the header row.
+    pub fn new(out: W) -> Self {
+        CsvWriter {
+            writer: csv::Writer::from_writer(out),
+            columns: None,
+        }
+    }
+
+    /// Create a CsvWriter with the given columns, writing the header row straight away.
+    pub fn with_columns(out: W, columns: Vec<String>) -> Result<Self, CsvError> {
+        let mut writer = csv::Writer::from_writer(out);
+        writer.write_record(&columns)?;
+        Ok(CsvWriter {
+            writer,
+            columns: Some(columns),
+        })
+    }
+
+    /// Write a map as a row of the table.
+    pub fn write_map(&mut self, map: Value) -> Result<(), CsvError> {
+        let pairs = match map {
+            Value::Map(pairs) => pairs,
+            _ => return Err(CsvError::NotAMap),
+        };
+
+        let (mut keys, mut fields) = (vec![], BTreeMap::new());
+        for (key, value) in pairs {
+            let key = cbor_to_text(key)?;
+            let value = match value {
+                Value::Null => String::new(),
+                value => cbor_to_text(value)?,
+            };
+            if fields.insert(key.clone(), value).is_none() {
+                keys.push(key);
+            }
+        }
+
+        // Without any columns given, the first map decides them, in its order.
+        let columns = match &mut self.columns {
+            Some(columns) => columns,
+            None => {
+                self.writer.write_record(&keys)?;
+                self.columns.insert(keys)
+            }
+        };
+
+        let row = columns
+            .iter()
+            .map(|column| fields.get(column).map_or("", String::as_str));
+        self.writer.write_record(row)?;
+        Ok(())
+    }
+
+    /// Finish writing the table, returning the writer it was written to.
+    pub fn finish(self) -> Result<W, CsvError> {
+        // The error can't be taken out of csv's IntoInnerError, so copy what it says.
+        self.writer
+            .into_inner()
+            .map_err(|e| CsvError::Io(io::Error::new(e.error().kind(), e.error().to_string())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ciborium::cbor;
+
+    fn table(columns: Option<&[&str]>, maps: &[Value]) -> Result<String, CsvError> {
+        let mut writer = match columns {
+            Some(columns) => {
+                let columns = columns.iter().map(|c| c.to_string()).collect();
+                CsvWriter::with_columns(vec![], columns)?
+            }
+            None => CsvWriter::new(vec![]),
+        };
+        for map in maps {
+            writer.write_map(map.clone())?;
+        }
+        Ok(String::from_utf8(writer.finish()?).unwrap())
+    }
+
+    #[test]
+    fn rows() {
+        let maps = [
+            cbor!({"name" => "a", "size" => 1, "tags" => ["x"]}).unwrap(),
+            cbor!({"size" => null, "extra" => true, "name" => "b, c"}).unwrap(),
+        ];
+        assert_eq!(
+            table(None, &maps).unwrap(),
+            "name,size,tags\na,1,\"[\"\"x\"\"]\"\n\"b, c\",,\n"
+        );
+        assert_eq!(
+            table(Some(&["extra", "name"]), &maps).unwrap(),
+            "extra,name\n,a\ntrue,\"b, c\"\n"
+        );
+
+        // Without any maps, there's nothing to say what the columns are.
+        assert_eq!(table(None, &[]).unwrap(), "");
+        assert_eq!(table(Some(&["a"]), &[]).unwrap(), "a\n");
+
+        assert!(matches!(
+            table(None, &[cbor!([1]).unwrap()]),
+            Err(CsvError::NotAMap)
+        ));
+        assert!(matches!(
+            table(None, &[cbor!({"a" => Value::Bytes(vec![])}).unwrap()]),
+            Err(CsvError::Convert(ConvertError::Bytes))
+        ));
+    }
+}
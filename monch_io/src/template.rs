@@ -0,0 +1,220 @@
+//! Rendering text from items, with templates like `{.name} is {.size} bytes`.
+//!
+//! Each placeholder is a path in braces, parsed like [`DataPath::parse_strict`], so `{.name}` is
+//! the item's `name` field and `{.}` is the whole item. Quoted keys can contain braces, like
+//! `{.'a}b'}`. Outside of placeholders, `{{` and `}}` are a literal `{` and `}`, and a `}` on its
+//! own is just text.
+//!
+//! Values are rendered like [`cbor_to_text`], except that null is rendered as nothing.
+
+use crate::convert::{cbor_to_text, ConvertError};
+use crate::path::{DataPath, DataPathError};
+use ciborium::value::Value;
+use std::iter::Peekable;
+use std::mem;
+use std::str::CharIndices;
+use thiserror::Error;
+
+/// An error parsing a template.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    #[error("unterminated placeholder at byte {offset}: use `{{{{` for a literal brace")]
+    Unterminated { offset: usize },
+
+    #[error("nested brace at byte {offset}: placeholders can't contain other placeholders")]
+    NestedBrace { offset: usize },
+
+    #[error("invalid path in the placeholder at byte {offset}: {error}")]
+    InvalidPath { offset: usize, error: DataPathError },
+}
+
+/// A piece of a parsed template.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Piece {
+    /// Text which is rendered as it is, with its escapes already replaced.
+    Text(String),
+
+    /// A placeholder, replaced by the value at this path.
+    Field(DataPath),
+}
+
+/// Parse a template into its pieces, which alternate between text and placeholders, though
+/// placeholders can be next to each other. See the [module docs](self) for the syntax.
+pub fn parse(template: &str) -> Result<Vec<Piece>, TemplateError> {
+    let mut pieces = vec![];
+    let mut text = String::new();
+    let mut chars = template.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        match (c, next) {
+            ('{', Some('{')) | ('}', Some('}')) => {
+                chars.next();
+                text.push(c);
+            }
+            ('{', _) => {
+                let path = placeholder(template, offset, &mut chars)?;
+                if !text.is_empty() {
+                    pieces.push(Piece::Text(mem::take(&mut text)));
+                }
+                pieces.push(Piece::Field(path));
+            }
+            (c, _) => text.push(c),
+        }
+    }
+
+    if !text.is_empty() {
+        pieces.push(Piece::Text(text));
+    }
+    Ok(pieces)
+}
+
+/// Read the rest of the placeholder whose `{` is at `start`, up to and including its `}`.
+fn placeholder(
+    template: &str,
+    start: usize,
+    chars: &mut Peekable<CharIndices>,
+) -> Result<DataPath, TemplateError> {
+    let mut open_quote = None;
+    for (offset, c) in chars {
+        match (open_quote, c) {
+            (Some(q), c) if c == q => open_quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => open_quote = Some(c),
+            (None, '{') => return Err(TemplateError::NestedBrace { offset }),
+            (None, '}') => {
+                let path = template[start + 1..offset].trim();
+                return DataPath::parse_strict(path).map_err(|error| TemplateError::InvalidPath {
+                    offset: start,
+                    error,
+                });
+            }
+            (None, _) => {}
+        }
+    }
+
+    Err(TemplateError::Unterminated { offset: start })
+}
+
+/// Render a parsed template for an item. Placeholders for paths that aren't in the item are
+/// rendered as nothing, and their paths are added to `missing`.
+pub fn render(
+    pieces: &[Piece],
+    item: &Value,
+    missing: &mut Vec<DataPath>,
+) -> Result<String, ConvertError> {
+    let mut rendered = String::new();
+    for piece in pieces {
+        match piece {
+            Piece::Text(text) => rendered.push_str(text),
+            Piece::Field(path) => match path.get_ref(item) {
+                Some(Value::Null) => {}
+                Some(value) => rendered.push_str(&cbor_to_text(value.clone())?),
+                None => missing.push(path.clone()),
+            },
+        }
+    }
+    Ok(rendered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ciborium::cbor;
+
+    fn text(text: &str) -> Piece {
+        Piece::Text(text.to_string())
+    }
+
+    fn field(path: &str) -> Piece {
+        Piece::Field(DataPath::parse_strict(path).unwrap())
+    }
+
+    #[test]
+    fn parses() {
+        assert_eq!(parse(""), Ok(vec![]));
+        assert_eq!(parse("plain"), Ok(vec![text("plain")]));
+        assert_eq!(
+            parse("{.name} is {.size} bytes"),
+            Ok(vec![
+                field(".name"),
+                text(" is "),
+                field(".size"),
+                text(" bytes")
+            ])
+        );
+
+        // Placeholders can be right next to each other, and their paths can be spaced out.
+        assert_eq!(
+            parse("{.a}{ .b.0 }{.}"),
+            Ok(vec![field(".a"), field(".b.0"), field(".")])
+        );
+        assert_eq!(parse("{.'a}b'}"), Ok(vec![field(".'a}b'")]));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            parse("{{.name}} is {{{.name}}}"),
+            Ok(vec![text("{.name} is {"), field(".name"), text("}")])
+        );
+        assert_eq!(parse("a } b }}"), Ok(vec![text("a } b }")]));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(
+            parse("a {.name"),
+            Err(TemplateError::Unterminated { offset: 2 })
+        );
+        assert_eq!(
+            parse("{.'name}"),
+            Err(TemplateError::Unterminated { offset: 0 })
+        );
+        assert_eq!(
+            parse("{.a{.b}}"),
+            Err(TemplateError::NestedBrace { offset: 3 })
+        );
+        assert_eq!(
+            parse("x {.a..b}"),
+            Err(TemplateError::InvalidPath {
+                offset: 2,
+                error: DataPathError::EmptySegment { offset: 3 },
+            })
+        );
+    }
+
+    #[test]
+    fn renders() {
+        let item = cbor!({
+            "name" => "a.txt",
+            "size" => 512,
+            "tags" => ["x", "y"],
+            "owner" => null,
+        })
+        .unwrap();
+        let render = |template: &str| {
+            let mut missing = vec![];
+            let rendered = render(&parse(template).unwrap(), &item, &mut missing).unwrap();
+            let missing: Vec<_> = missing.iter().map(DataPath::to_string).collect();
+            (rendered, missing)
+        };
+
+        assert_eq!(
+            render("{.name} is {.size} bytes"),
+            ("a.txt is 512 bytes".to_string(), vec![])
+        );
+        assert_eq!(
+            render("{.tags} {.tags.1}"),
+            (r#"["x","y"] y"#.to_string(), vec![])
+        );
+        assert_eq!(render("[{.owner}]"), ("[]".to_string(), vec![]));
+        assert_eq!(
+            render("[{.group}] [{.tags.5}]"),
+            (
+                "[] []".to_string(),
+                vec![".group".to_string(), ".tags.5".to_string()]
+            )
+        );
+    }
+}
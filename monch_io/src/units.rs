@@ -0,0 +1,185 @@
+//! Parsing sizes and durations written on the command line, like `10M` or `1h30m`.
+//!
+//! Sizes are a whole number of bytes, optionally followed by a unit: `k`, `M`, `G`, or `T`, each
+//! 1024 times the last. Units aren't case sensitive, and can end in `b` or `ib`, so `1k`, `1KB` and
+//! `1KiB` are all 1024 bytes.
+//!
+//! Durations are one or more numbers, each followed by a unit: `ms`, `s`, `m`, `h`, `d`, or `w`,
+//! like `2d` or `1h30m`. The numbers can have a fractional part, like `1.5s`, and a number on its
+//! own is a number of seconds.
+
+use std::time::Duration;
+use thiserror::Error;
+
+/// An error parsing a size or a duration.
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum UnitError {
+    #[error("'{0}' isn't a size, like `512`, `1k` or `10M`")]
+    InvalidSize(String),
+
+    #[error("'{0}' isn't a duration, like `30s`, `2d` or `1h30m`")]
+    InvalidDuration(String),
+
+    #[error("'{0}' is too large")]
+    TooLarge(String),
+}
+
+/// Parse a size in bytes, like `512`, `1k`, or `10M`. See the [module docs](self).
+pub fn parse_size(size: &str) -> Result<u64, UnitError> {
+    let trimmed = size.trim();
+    let digits = trimmed
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or_else(|| trimmed.len());
+    let (number, unit) = trimmed.split_at(digits);
+
+    let number: u64 = match number.parse() {
+        Ok(number) => number,
+        Err(_) if number.is_empty() => return Err(UnitError::InvalidSize(size.to_string())),
+        Err(_) => return Err(UnitError::TooLarge(size.to_string())),
+    };
+
+    // A bare `ib` isn't a unit.
+    let unit = unit.to_ascii_lowercase();
+    if unit == "ib" {
+        return Err(UnitError::InvalidSize(size.to_string()));
+    }
+    let prefix = unit
+        .strip_suffix("ib")
+        .or_else(|| unit.strip_suffix('b'))
+        .unwrap_or(&unit);
+    let shift = match prefix {
+        "" => 0,
+        "k" => 10,
+        "m" => 20,
+        "g" => 30,
+        "t" => 40,
+        _ => return Err(UnitError::InvalidSize(size.to_string())),
+    };
+
+    number
+        .checked_mul(1 << shift)
+        .ok_or_else(|| UnitError::TooLarge(size.to_string()))
+}
+
+/// Parse a duration, like `30s`, `2d`, or `1h30m`. See the [module docs](self).
+pub fn parse_duration(duration: &str) -> Result<Duration, UnitError> {
+    let invalid = || UnitError::InvalidDuration(duration.to_string());
+    let trimmed = duration.trim();
+    if trimmed.is_empty() {
+        return Err(invalid());
+    }
+
+    // A number on its own is in seconds.
+    if let Ok(seconds) = trimmed.parse::<f64>() {
+        return seconds_to_duration(duration, seconds);
+    }
+
+    let mut seconds = 0.0;
+    let mut rest = trimmed;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(invalid)?;
+        let (number, after) = rest.split_at(digits);
+        let number: f64 = number.parse().map_err(|_| invalid())?;
+
+        let letters = after
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or_else(|| after.len());
+        let (unit, after) = after.split_at(letters);
+        seconds += match unit {
+            "ms" => number / 1000.0,
+            "s" => number,
+            "m" => number * 60.0,
+            "h" => number * 60.0 * 60.0,
+            "d" => number * 24.0 * 60.0 * 60.0,
+            "w" => number * 7.0 * 24.0 * 60.0 * 60.0,
+            _ => return Err(invalid()),
+        };
+
+        rest = after;
+    }
+
+    seconds_to_duration(duration, seconds)
+}
+
+/// Make a [`Duration`] out of a number of seconds, if it's one that can be represented.
+fn seconds_to_duration(duration: &str, seconds: f64) -> Result<Duration, UnitError> {
+    if seconds.is_nan() || seconds < 0.0 {
+        Err(UnitError::InvalidDuration(duration.to_string()))
+    } else if seconds >= u64::MAX as f64 {
+        Err(UnitError::TooLarge(duration.to_string()))
+    } else {
+        Ok(Duration::from_secs_f64(seconds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        let sizes = [
+            ("0", 0),
+            ("512", 512),
+            (" 512 ", 512),
+            ("1k", 1024),
+            ("1K", 1024),
+            ("1kb", 1024),
+            ("1KiB", 1024),
+            ("10M", 10 << 20),
+            ("3g", 3 << 30),
+            ("2T", 2 << 40),
+            ("7b", 7),
+        ];
+        for (size, bytes) in sizes.iter() {
+            assert_eq!(parse_size(size), Ok(*bytes), "{}", size);
+        }
+
+        for size in ["", "k", "1.5k", "-1", "1x", "1kk", "1ib", "1 k", "ten"] {
+            let err = parse_size(size).unwrap_err();
+            assert_eq!(err, UnitError::InvalidSize(size.to_string()), "{}", size);
+        }
+
+        let too_large = ["99999999999999999999", "16777216T"];
+        for size in too_large {
+            assert_eq!(parse_size(size), Err(UnitError::TooLarge(size.to_string())));
+        }
+    }
+
+    #[test]
+    fn durations() {
+        let durations = [
+            ("0", Duration::ZERO),
+            ("30", Duration::from_secs(30)),
+            ("0.5", Duration::from_millis(500)),
+            ("250ms", Duration::from_millis(250)),
+            ("30s", Duration::from_secs(30)),
+            ("1.5s", Duration::from_millis(1500)),
+            ("10m", Duration::from_secs(600)),
+            ("1h30m", Duration::from_secs(5400)),
+            ("2d", Duration::from_secs(2 * 86400)),
+            ("3d2h", Duration::from_secs(3 * 86400 + 7200)),
+            ("1w", Duration::from_secs(7 * 86400)),
+            ("1m1m", Duration::from_secs(120)),
+        ];
+        for (duration, expected) in durations.iter() {
+            assert_eq!(parse_duration(duration), Ok(*expected), "{}", duration);
+        }
+
+        for duration in [
+            "", "s", "2x", "2 d", "-2d", "-1", "1h 30m", "d2", "1..5s", "2m30", "1s-1ms", "nan",
+        ] {
+            let err = parse_duration(duration).unwrap_err();
+            let expected = UnitError::InvalidDuration(duration.to_string());
+            assert_eq!(err, expected, "{}", duration);
+        }
+
+        let too_large = "99999999999999999999999w";
+        assert_eq!(
+            parse_duration(too_large),
+            Err(UnitError::TooLarge(too_large.to_string()))
+        );
+    }
+}
@@ -0,0 +1,47 @@
+//! Objects written with `put!` come out as JSON lines when `MONCH_FORMAT=json`.
+//!
+//! The output format is only read once per process, so this runs itself again with the variable
+//! set, and checks what that wrote. It has no test harness, so nothing else is on its stdout.
+
+use monch_io::{cbor, put, try_put, write, Error, FORMAT_VAR};
+use std::env;
+use std::process::Command;
+
+/// Set when this is the run writing the objects.
+const CHILD_VAR: &str = "MONCH_IO_JSON_OUTPUT_CHILD";
+
+/// Write some objects, in whatever the output format is.
+fn write_objects() {
+    put!({"name" => "a.txt", "size" => 1});
+    put!(name: "b.txt", tags: vec!["x", "y"]);
+
+    // Keys which aren't strings are converted like everywhere else, rather than failing.
+    write(&cbor!({1 => "one", true => null}).unwrap()).unwrap();
+
+    // Bytes can't be converted.
+    let bytes = try_put!(&monch_io::Value::Bytes(vec![1, 2]));
+    assert!(matches!(bytes, Err(Error::ConvertJson(_))), "{:?}", bytes);
+}
+
+fn main() {
+    if env::var_os(CHILD_VAR).is_some() {
+        write_objects();
+        return;
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .env(CHILD_VAR, "1")
+        .env(FORMAT_VAR, "json")
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        concat!(
+            "{\"name\":\"a.txt\",\"size\":1}\n",
+            "{\"name\":\"b.txt\",\"tags\":[\"x\",\"y\"]}\n",
+            "{\"1\":\"one\",\"true\":null}\n",
+        )
+    );
+    println!("json_output: ok");
+}
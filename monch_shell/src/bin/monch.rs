@@ -1,3 +1,4 @@
+use clap::Parser as _;
 use monch_syntax::Parser;
 use owo_colors::OwoColorize;
 use rustyline::completion::Completer;
@@ -9,9 +10,36 @@ use rustyline::hint::Hinter;
 use rustyline::line_buffer::LineBuffer;
 use rustyline::validate::Validator;
 use rustyline::Context;
-use std::env;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::{env, fmt, fs, process};
 
-use monch_shell::{Exit, Interpreter, Streams};
+use monch_shell::{Exit, History, Interpreter, ShellAction, Streams};
+
+#[derive(Debug, clap::Parser)]
+struct Args {
+    /// Run this command, rather than starting an interactive shell.
+    #[clap(short('c'), conflicts_with("script"))]
+    command: Option<String>,
+
+    /// Describe what this command would do, without running it.
+    #[clap(long, value_name("COMMAND"), conflicts_with_all(&["command", "script"]))]
+    explain: Option<String>,
+
+    /// Run this script, rather than starting an interactive shell.
+    script: Option<PathBuf>,
+
+    /// Arguments for the script, available as `$1`, `$2`, and so on.
+    script_args: Vec<String>,
+
+    /// Don't run a startup file before the first prompt.
+    #[clap(long)]
+    norc: bool,
+
+    /// Run this startup file before the first prompt, instead of `$MONCH_RC` or `~/.monchrc`.
+    #[clap(long, value_name("PATH"), conflicts_with("norc"))]
+    rcfile: Option<PathBuf>,
+}
 
 fn main() {
     #[cfg(debug_assertions)]
@@ -24,19 +52,192 @@ fn main() {
             .canonicalize()
             .expect("the parent dir of this executable must be canonicalizable");
 
-        // If we're a debug build, add the Cargo target directory to MONCH_PATH.
-        env::set_var("MONCH_PATH", monch_path);
+        // If we're a debug build, search the Cargo target directory after anything else.
+        let mut dirs: Vec<PathBuf> = match env::var_os("MONCH_PATH") {
+            Some(path) => env::split_paths(&path).collect(),
+            None => vec![],
+        };
+        if !dirs.contains(&monch_path) {
+            dirs.push(monch_path);
+        }
+        let path = env::join_paths(dirs).expect("MONCH_PATH must be joinable");
+        env::set_var("MONCH_PATH", path);
     }
 
-    // Set up readline
-    let mut rl = rustyline::Editor::new();
-    rl.set_helper(Some(Helper::new()));
+    let args = Args::parse();
 
     // Set up the interpreter
     let stdio = Streams::stdio().expect("couldn't open stdio");
+    let interactive = stdio.stdin.is_terminal();
     let workdir = env::current_dir().expect("bad working directory");
     let mut interpreter = Interpreter::new(stdio, &workdir);
 
+    let exit = match (&args.explain, &args.command, &args.script) {
+        (Some(line), _, _) => explain(&interpreter, line),
+        (None, Some(command), _) => run_command(&mut interpreter, command),
+        (None, None, Some(script)) => {
+            interpreter.set_script(script.display().to_string(), args.script_args.clone());
+            run_script(&mut interpreter, script)
+        }
+        (None, None, None) if interactive => {
+            if !args.norc {
+                run_rc(&mut interpreter, args.rcfile.clone());
+            }
+
+            // The startup file can exit before there's even a prompt.
+            match take_action(&mut interpreter) {
+                Some(exit) => exit,
+                None => repl(&mut interpreter),
+            }
+        }
+
+        // If stdin isn't a terminal, someone's piping us a script.
+        (None, None, None) => run_stdin(&mut interpreter),
+    };
+
+    // A script which stopped for `exec` still has to do it.
+    let exit = take_action(&mut interpreter).unwrap_or(exit);
+
+    process::exit(exit.as_status() as i32);
+}
+
+/// Do what a builtin asked of the shell, like `exit` or `exec`. Returns the exit to leave the
+/// shell with, if it should be left.
+fn take_action(interpreter: &mut Interpreter) -> Option<Exit> {
+    match interpreter.take_action()? {
+        ShellAction::Exit(exit) => Some(exit),
+        ShellAction::Exec { program, args } => {
+            eprintln!("monch: exec: {}", interpreter.exec(&program, &args));
+            Some(Exit::COULD_NOT_EXECUTE)
+        }
+    }
+}
+
+/// Run a single command line.
+fn run_command(interpreter: &mut Interpreter, line: &str) -> Exit {
+    let cmd = match Parser::new().parse_command(line) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            eprintln!("monch: {}", e);
+            return Exit::BAD_SYNTAX;
+        }
+    };
+
+    match interpreter.eval_command(&cmd) {
+        Ok(exit) => exit,
+        Err(e) => {
+            eprintln!("monch: {}", e);
+            e.as_exit()
+        }
+    }
+}
+
+/// Describe what a command line would do, without running it.
+fn explain(interpreter: &Interpreter, line: &str) -> Exit {
+    let cmd = match Parser::new().parse_command(line) {
+        Ok(cmd) => cmd,
+        Err(e) => {
+            eprintln!("monch: {}", e);
+            return Exit::BAD_SYNTAX;
+        }
+    };
+
+    let plan = match interpreter.explain_command(&cmd) {
+        Ok(plan) => plan,
+        Err(e) => {
+            eprintln!("monch: {}", e);
+            return e.as_exit();
+        }
+    };
+
+    match plan.write_lines(&mut io::stdout(), "", interpreter.stdout_color()) {
+        Ok(()) => Exit::SUCCESS,
+        Err(e) => {
+            eprintln!("monch: {}", e);
+            Exit::FAILURE
+        }
+    }
+}
+
+/// Run each command in a script file.
+fn run_script(interpreter: &mut Interpreter, path: &Path) -> Exit {
+    match fs::read_to_string(path) {
+        Ok(source) => run_source(interpreter, &path.display().to_string(), &source),
+        Err(e) => {
+            eprintln!("monch: {}: {}", path.display(), e);
+            Exit::COMMAND_NOT_FOUND
+        }
+    }
+}
+
+/// Run the startup file, from `--rcfile`, `$MONCH_RC`, or `~/.monchrc`, in that order. Errors in
+/// it are reported, but the shell starts regardless.
+fn run_rc(interpreter: &mut Interpreter, rcfile: Option<PathBuf>) {
+    let from_env = env::var_os("MONCH_RC").filter(|p| !p.is_empty());
+    let path = match rcfile.or_else(|| from_env.map(PathBuf::from)) {
+        Some(path) => path,
+
+        // Unlike one we were asked for, there doesn't have to be a ~/.monchrc.
+        None => match env::var_os("HOME") {
+            Some(home) if Path::new(&home).join(".monchrc").exists() => {
+                Path::new(&home).join(".monchrc")
+            }
+            _ => return,
+        },
+    };
+
+    run_script(interpreter, &path);
+}
+
+/// Read all of stdin, and run it as a script.
+fn run_stdin(interpreter: &mut Interpreter) -> Exit {
+    let mut source = String::new();
+    match io::stdin().read_to_string(&mut source) {
+        Ok(_) => run_source(interpreter, "stdin", &source),
+        Err(e) => {
+            eprintln!("monch: stdin: {}", e);
+            Exit::FAILURE
+        }
+    }
+}
+
+/// Parse and run a script, where `name` says where it came from.
+fn run_source(interpreter: &mut Interpreter, name: &str, source: &str) -> Exit {
+    let script = match Parser::new().parse_script(source) {
+        Ok(script) => script,
+        Err(e) => {
+            eprintln!("monch: {}: {}", name, e);
+            return Exit::BAD_SYNTAX;
+        }
+    };
+
+    interpreter.eval_script(name, &script)
+}
+
+/// Read and run commands from the terminal, until the user's done. Returns the exit the shell
+/// should exit with.
+fn repl(interpreter: &mut Interpreter) -> Exit {
+    // Set up readline
+    let mut rl = rustyline::Editor::new();
+    rl.set_helper(Some(Helper::new()));
+
+    // Pass Ctrl-C on to whatever's running, rather than exiting.
+    if let Err(e) = monch_shell::install_interrupt_handler() {
+        eprintln!("monch: could not handle interrupts: {}", e);
+    }
+    monch_shell::claim_terminal();
+
+    // Pick up the history where the last shell left off.
+    if let Some(home) = env::var_os("HOME") {
+        match History::load(Path::new(&home).join(".monch_history")) {
+            Ok(history) => interpreter.set_history(history),
+            Err(e) => eprintln!("monch: could not load history: {}", e),
+        }
+    }
+    for line in interpreter.history().entries() {
+        rl.add_history_entry(line);
+    }
+
     // Make a parser
     let parser = Parser::new();
 
@@ -44,7 +245,8 @@ fn main() {
     let mut last_exit = Exit::SUCCESS;
 
     loop {
-        match rl.readline(&prompt(&interpreter, last_exit)) {
+        let use_color = interpreter.stdout_color();
+        match rl.readline(&prompt(interpreter, last_exit, use_color)) {
             Ok(line) => {
                 // Ignore empty inputs. Technically they don't parse.
                 if line.trim().is_empty() {
@@ -54,6 +256,9 @@ fn main() {
 
                 // Add the line as a history entry
                 rl.add_history_entry(&line);
+                if let Err(e) = interpreter.history_mut().add(&line) {
+                    eprintln!("monch: could not save history: {}", e);
+                }
 
                 // Parse the command line
                 let cmd = match parser.parse_command(&line) {
@@ -62,7 +267,7 @@ fn main() {
                     // Handle parse errors by printing them, setting last_exit, and skipping
                     // evaluation.
                     Err(e) => {
-                        eprintln!("monch: {}", e);
+                        print_error(&e, interpreter.stderr_color());
                         last_exit = Exit::BAD_SYNTAX;
                         continue;
                     }
@@ -74,10 +279,27 @@ fn main() {
 
                     // Handle errors by printing them and setting last_exit.
                     Err(e) => {
-                        eprintln!("monch: {}", e);
+                        print_error(&e, interpreter.stderr_color());
                         e.as_exit()
                     }
                 };
+
+                // Keep up with `history clear`.
+                if interpreter.history().entries().is_empty() {
+                    rl.clear_history();
+                }
+
+                match interpreter.take_action() {
+                    Some(ShellAction::Exit(exit)) => return exit,
+
+                    // If the program couldn't be run, we're still here, so carry on.
+                    Some(ShellAction::Exec { program, args }) => {
+                        let e = interpreter.exec(&program, &args);
+                        print_error(&format!("exec: {}", e), interpreter.stderr_color());
+                        last_exit = Exit::COULD_NOT_EXECUTE;
+                    }
+                    None => {}
+                }
             }
             Err(ReadlineError::Interrupted) => {
                 continue;
@@ -96,6 +318,8 @@ fn main() {
             eprintln!("monch: could not update working directory: {}", e);
         }
     }
+
+    Exit::SUCCESS
 }
 
 struct Helper {
@@ -136,19 +360,35 @@ impl Highlighter for Helper {}
 
 impl Validator for Helper {}
 
-/// Generate a shell prompt
-fn prompt(int: &Interpreter, last_exit: Exit) -> String {
+/// Print an error at the prompt, with a red `monch:` if `use_color` is set.
+fn print_error(e: &impl fmt::Display, use_color: bool) {
+    match use_color {
+        true => eprintln!("{} {}", "monch:".red(), e),
+        false => eprintln!("monch: {}", e),
+    }
+}
+
+/// Generate a shell prompt, colored if `use_color` is set.
+fn prompt(int: &Interpreter, last_exit: Exit, use_color: bool) -> String {
     // Generate the path segment
     let cwd = int.current_dir();
     let path_segment = cwd.to_string_lossy();
 
-    // Generate the error segment
-    let error_segment = if !last_exit.success() {
-        format!(" [{}]", last_exit)
-    } else {
-        "".to_string()
+    // Generate the error segment. For a pipeline, it also says which stage failed, like `[2:1]`.
+    let failed_stage = match int.last_pipeline_status() {
+        stages if stages.len() > 1 => stages.iter().rposition(|(_, exit)| *exit == last_exit),
+        _ => None,
+    };
+    let error_segment = match failed_stage {
+        _ if last_exit.success() => "".to_string(),
+        Some(stage) => format!(" [{}:{}]", stage + 1, last_exit),
+        None => format!(" [{}]", last_exit),
     };
 
+    if !use_color {
+        return format!("{}{} $ ", path_segment, error_segment);
+    }
+
     format!(
         "{}{}{}",
         path_segment.dimmed(),
@@ -0,0 +1,122 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+
+register_builtin!("cd", Cd, "change the current directory");
+
+pub struct Cd;
+
+impl exe::Execute for Cd {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        // Work out where we're going, and whether to say where that is.
+        let (dir, announce) = match args[..] {
+            // Bare `cd` goes home.
+            [] => match int.get_var("HOME") {
+                Some(home) => (home.to_string(), false),
+                None => {
+                    let _ = writeln!(ios.stderr, "monch: cd: HOME not set");
+                    exit!(Exit::FAILURE)
+                }
+            },
+
+            // `cd -` goes back to the previous directory.
+            [ref dash] if dash == "-" => match int.previous_dir() {
+                Some(previous) => (previous.to_string_lossy().to_string(), true),
+                None => {
+                    let _ = writeln!(ios.stderr, "monch: cd: OLDPWD not set");
+                    exit!(Exit::FAILURE)
+                }
+            },
+
+            [ref dir] => (dir.clone(), false),
+
+            _ => {
+                writeln!(ios.stderr, "monch: cd: too many arguments")?;
+                exit!(Exit::FAILURE)
+            }
+        };
+
+        let workdir = int.current_dir();
+        let new_workdir = workdir.join(&dir);
+
+        if !new_workdir.is_dir() {
+            writeln!(ios.stderr, "monch: cd: {}: no such file or directory", dir)?;
+            exit!(Exit::FAILURE)
+        }
+
+        let result = int.set_current_dir(new_workdir);
+
+        match result {
+            Ok(_) => {
+                if announce {
+                    let _ = writeln!(ios.stdout, "{}", int.current_dir().display());
+                }
+                exit!(Exit::SUCCESS)
+            }
+            Err(e) => exit!(e.as_exit()),
+        }
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        // `cd -` prints where it went.
+        match args[..] {
+            [ref dash] if dash == "-" => Ty::Text,
+            _ => Ty::Nothing,
+        }
+    }
+
+    fn is_synchronous(&self, _: &exe::Args) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_builtin, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn cd_back_and_home() {
+        let dir = scratch_dir("cd_back_and_home");
+        fs::create_dir(dir.join("a")).unwrap();
+        fs::create_dir(dir.join("home")).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_var("HOME", dir.join("home").to_string_lossy());
+
+        // There's nowhere to go back to yet.
+        let (_, err, exit) = run_builtin(&Cd, &mut int, &["-"], &[]);
+        assert_eq!(exit, Exit::FAILURE);
+        assert!(err.contains("OLDPWD not set"));
+
+        assert_eq!(run_builtin(&Cd, &mut int, &["a"], &[]).2, Exit::SUCCESS);
+        assert_eq!(int.current_dir(), dir.join("a"));
+
+        // `cd -` goes back, and says where it went.
+        let (out, _, exit) = run_builtin(&Cd, &mut int, &["-"], &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(out, format!("{}\n", dir.display()).into_bytes());
+        assert_eq!(int.current_dir(), dir);
+
+        // And again, back the other way.
+        run_builtin(&Cd, &mut int, &["-"], &[]);
+        assert_eq!(int.current_dir(), dir.join("a"));
+
+        // Bare `cd` goes home.
+        let (out, _, exit) = run_builtin(&Cd, &mut int, &[], &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert!(out.is_empty());
+        assert_eq!(int.current_dir(), dir.join("home"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
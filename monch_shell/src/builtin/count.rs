@@ -0,0 +1,205 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::DataPath;
+use std::io::{self, Write};
+use std::thread;
+
+register_builtin!("count", Count, "count the items in the input");
+
+/// `count` reads its whole input, and writes the number of items in it. With `--bytes`, it counts
+/// the bytes instead, without parsing anything, and with `--where PATH`, it only counts the items
+/// where the value at PATH is there, and isn't `null` or `false`.
+///
+/// Corrupt input stops the count, unless it's given `--lenient`, in which case the corrupt part
+/// is skipped and the rest is still counted.
+pub struct Count;
+
+/// What `count` was asked to count.
+#[derive(Debug, Default)]
+struct CountArgs {
+    bytes: bool,
+    filter: Option<DataPath>,
+    lenient: bool,
+}
+
+impl Count {
+    /// Parse the arguments of a `count` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<CountArgs, String> {
+        let mut parsed = CountArgs::default();
+
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            match arg {
+                "--bytes" => parsed.bytes = true,
+                "--lenient" => parsed.lenient = true,
+                "--where" => {
+                    let path = args.next().ok_or("`--where` needs a path")?;
+                    let path = DataPath::parse_strict(path).map_err(|e| e.to_string())?;
+                    parsed.filter = Some(path);
+                }
+                other => return Err(format!("unexpected argument `{}`", other)),
+            }
+        }
+
+        if parsed.bytes && (parsed.filter.is_some() || parsed.lenient) {
+            return Err("`--bytes` doesn't parse items, so it can't be combined".to_string());
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl exe::Execute for Count {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match Count::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: count: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let count = if args.bytes {
+                match io::copy(&mut ios.stdin, &mut io::sink()) {
+                    Ok(count) => count,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: count: {}", e);
+                        return Exit::FAILURE;
+                    }
+                }
+            } else {
+                let mut parser = monch_io::InputParser::<Value, _>::new(ios.stdin)
+                    .with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+                if args.lenient {
+                    parser = parser.resilient();
+                }
+
+                let mut count = 0;
+                while let Some(item) = parser.next() {
+                    let item = match item {
+                        Ok(item) => item,
+                        Err(e) => {
+                            let index = parser.items_read();
+                            let _ = writeln!(ios.stderr, "monch: count: item {}: {}", index, e);
+                            match e {
+                                monch_io::Error::Skipped { .. } => continue,
+                                _ => return Exit::FAILURE,
+                            }
+                        }
+                    };
+
+                    let counted = match &args.filter {
+                        None => true,
+                        Some(path) => !matches!(
+                            path.get_ref(&item),
+                            None | Some(Value::Null) | Some(Value::Bool(false))
+                        ),
+                    };
+                    if counted {
+                        count += 1;
+                    }
+                }
+
+                count
+            };
+
+            match ciborium::ser::into_writer(&Value::Integer(count.into()), &mut ios.stdout) {
+                Ok(()) => Exit::SUCCESS,
+                Err(_) => Exit::FAILURE,
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{encode, run_builtin, scratch_dir};
+    use monch_syntax::Parser;
+    use std::{env, fs};
+
+    #[test]
+    fn count_items() {
+        let dir = scratch_dir("count_items");
+        let number = |n: u64| Value::Integer(n.into());
+        let item = |done: Option<bool>| {
+            let done = done.map_or(vec![], |d| {
+                vec![(Value::Text("done".into()), Value::Bool(d))]
+            });
+            Value::Map(done)
+        };
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let mut count = |line: &str, input: &[u8]| {
+            fs::write(dir.join("input"), input).unwrap();
+            let cmd = Parser::new()
+                .parse_command(&format!("{} <input", line))
+                .unwrap();
+            int.eval_command_capture(&cmd).unwrap()
+        };
+
+        // Nothing at all is still counted.
+        assert_eq!(count("count", &[]), (encode(&[number(0)]), Exit::SUCCESS));
+
+        let items = encode(&[
+            item(Some(true)),
+            item(Some(false)),
+            item(None),
+            item(Some(true)),
+        ]);
+        assert_eq!(count("count", &items).0, encode(&[number(4)]));
+        assert_eq!(count("count --where .done", &items).0, encode(&[number(2)]));
+        assert_eq!(
+            count("count --bytes", &items).0,
+            encode(&[number(items.len() as u64)])
+        );
+
+        // Corrupt input stops the count, unless it's lenient.
+        let mut corrupt = encode(&[number(1)]);
+        corrupt.push(0x1c);
+        corrupt.extend(encode(&[number(2), number(3)]));
+        assert_eq!(count("count", &corrupt).1, Exit::FAILURE);
+        assert_eq!(count("count --lenient", &corrupt).0, encode(&[number(3)]));
+        assert_eq!(
+            count("count --bytes", &corrupt).0,
+            encode(&[number(corrupt.len() as u64)])
+        );
+
+        assert_eq!(count("count --where", &[]).1, Exit::BAD_SYNTAX);
+        assert_eq!(count("count --where ..x", &[]).1, Exit::BAD_SYNTAX);
+        assert_eq!(count("count --bytes --lenient", &[]).1, Exit::BAD_SYNTAX);
+        assert_eq!(count("count things", &[]).1, Exit::BAD_SYNTAX);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn count_reports_corrupt_item() {
+        let mut corrupt = encode(&[Value::Integer(1.into()), Value::Integer(2.into())]);
+        corrupt.push(0x1c);
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let (out, err, exit) = run_builtin(&Count, &mut int, &[], &corrupt);
+        assert_eq!((out, exit), (vec![], Exit::FAILURE));
+        assert!(err.starts_with("monch: count: item 2: "), "{}", err);
+
+        let (_, err, exit) = run_builtin(&Count, &mut int, &["--lenient"], &corrupt);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert!(err.starts_with("monch: count: item 2: "), "{}", err);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+use std::iter;
+use std::path::PathBuf;
+
+register_builtin!(
+    "dirs",
+    Dirs,
+    "list the current directory and the directory stack"
+);
+
+/// `dirs` outputs the current directory, followed by the directory stack from the most recently
+/// pushed, as an array of strings.
+pub struct Dirs;
+
+impl exe::Execute for Dirs {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        _args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let dirs: Vec<String> = iter::once(int.current_dir())
+            .chain(int.dir_stack().iter().rev().map(PathBuf::as_path))
+            .map(|dir| dir.to_string_lossy().to_string())
+            .collect();
+
+        if let Err(e) = ciborium::ser::into_writer(&dirs, &mut ios.stdout) {
+            let _ = writeln!(ios.stderr, "monch: dirs: {}", e);
+            exit!(Exit::FAILURE)
+        }
+
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{popd::Popd, pushd::Pushd};
+    use crate::testing::{run_builtin, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn directory_stack() {
+        let dir = scratch_dir("directory_stack");
+        fs::create_dir_all(dir.join("a/b")).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let dirs = |int: &mut Interpreter| -> Vec<String> {
+            let (out, _, exit) = run_builtin(&Dirs, int, &[], &[]);
+            assert_eq!(exit, Exit::SUCCESS);
+            ciborium::de::from_reader(&out[..]).unwrap()
+        };
+        let name = |path: PathBuf| path.to_string_lossy().to_string();
+
+        assert_eq!(dirs(&mut int), vec![name(dir.clone())]);
+
+        // Push relative to where we are.
+        assert_eq!(run_builtin(&Pushd, &mut int, &["a"], &[]).2, Exit::SUCCESS);
+        assert_eq!(run_builtin(&Pushd, &mut int, &["b"], &[]).2, Exit::SUCCESS);
+        assert_eq!(int.current_dir(), dir.join("a/b"));
+        assert_eq!(
+            dirs(&mut int),
+            vec![
+                name(dir.join("a/b")),
+                name(dir.join("a")),
+                name(dir.clone())
+            ]
+        );
+
+        // A bad directory isn't pushed.
+        let (_, err, exit) = run_builtin(&Pushd, &mut int, &["missing"], &[]);
+        assert_eq!(exit, Exit::FAILURE);
+        assert!(err.starts_with("monch: pushd: missing:"));
+        assert_eq!(int.dir_stack().len(), 2);
+
+        // Pop back out, in order.
+        assert_eq!(run_builtin(&Popd, &mut int, &[], &[]).2, Exit::SUCCESS);
+        assert_eq!(int.current_dir(), dir.join("a"));
+        assert_eq!(run_builtin(&Popd, &mut int, &[], &[]).2, Exit::SUCCESS);
+        assert_eq!(int.current_dir(), dir);
+
+        let (_, err, exit) = run_builtin(&Popd, &mut int, &[], &[]);
+        assert_eq!(exit, Exit::FAILURE);
+        assert!(err.contains("directory stack empty"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
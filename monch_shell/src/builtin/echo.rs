@@ -0,0 +1,132 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+
+register_builtin!("echo", Echo, "write each argument as a text item");
+
+/// `echo ARGS...` writes each argument as a CBOR text item. With `-s`, it writes them joined by
+/// spaces as a single item instead, and with `--raw`, it writes them as a line of plain text, like
+/// the `echo` program does.
+pub struct Echo;
+
+/// The options given to `echo`, and the arguments after them.
+struct EchoArgs<'a> {
+    single: bool,
+    raw: bool,
+    words: &'a [String],
+}
+
+impl<'a> EchoArgs<'a> {
+    fn parse(args: &'a [String]) -> EchoArgs<'a> {
+        let mut opts = EchoArgs {
+            single: false,
+            raw: false,
+            words: args,
+        };
+
+        // Options only count before the first word, so `echo a -s` writes `-s`.
+        while let Some((first, rest)) = opts.words.split_first() {
+            match first.as_str() {
+                "-s" => opts.single = true,
+                "--raw" => opts.raw = true,
+                _ => break,
+            }
+            opts.words = rest;
+        }
+
+        opts
+    }
+}
+
+impl exe::Execute for Echo {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let opts = EchoArgs::parse(args);
+
+        let written = if opts.raw {
+            writeln!(ios.stdout, "{}", opts.words.join(" ")).map_err(|e| e.to_string())
+        } else if opts.single {
+            ciborium::ser::into_writer(&opts.words.join(" "), &mut ios.stdout)
+                .map_err(|e| e.to_string())
+        } else {
+            opts.words.iter().try_for_each(|word| {
+                ciborium::ser::into_writer(word, &mut ios.stdout).map_err(|e| e.to_string())
+            })
+        };
+
+        if let Err(e) = written {
+            let _ = writeln!(ios.stderr, "monch: echo: {}", e);
+            exit!(Exit::FAILURE)
+        }
+
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        if EchoArgs::parse(args).raw {
+            Ty::Text
+        } else {
+            Ty::Cbor
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exe::Execute;
+    use crate::testing::{run, run_builtin};
+    use monch_syntax::Parser;
+    use std::env;
+
+    #[test]
+    fn echo_modes() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let texts = |out: &[u8]| {
+            let mut out = out;
+            let mut items = vec![];
+            while !out.is_empty() {
+                let item: String = ciborium::de::from_reader(&mut out).unwrap();
+                items.push(item);
+            }
+            items
+        };
+
+        // Each argument is an item of its own, unless `-s` joins them.
+        let (out, _, exit) = run_builtin(&Echo, &mut int, &["a b", "c"], &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(texts(&out), ["a b", "c"]);
+        let (out, _, _) = run_builtin(&Echo, &mut int, &["-s", "a b", "c"], &[]);
+        assert_eq!(texts(&out), ["a b c"]);
+        let (out, _, _) = run_builtin(&Echo, &mut int, &["a", "-s"], &[]);
+        assert_eq!(texts(&out), ["a", "-s"]);
+
+        // `--raw` writes a line of text instead.
+        let (out, _, _) = run_builtin(&Echo, &mut int, &["--raw", "a b", "c"], &[]);
+        assert_eq!(out, b"a b c\n");
+
+        // Each mode declares what it writes, which the pipeline is checked against.
+        let args = |a: &[&str]| a.iter().map(|a| a.to_string()).collect::<exe::Args>();
+        assert_eq!(Echo.output_type(&args(&["hello"])), Ty::Cbor);
+        assert_eq!(Echo.output_type(&args(&["-s", "hello"])), Ty::Cbor);
+        assert_eq!(Echo.output_type(&args(&["--raw", "hello"])), Ty::Text);
+
+        let (out, exit) = run(&mut int, &["echo hello | to tty"]);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert!(out.contains("hello"));
+        let cmd = Parser::new()
+            .parse_command("echo --raw hello | to tty")
+            .unwrap();
+        assert!(matches!(
+            int.eval_command_capture(&cmd),
+            Err(Error::TypeMismatch { .. })
+        ));
+    }
+}
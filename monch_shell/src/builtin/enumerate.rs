@@ -0,0 +1,150 @@
+use super::map_items;
+use crate::streams::WriteStream;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::StreamHeader;
+use std::io::Write;
+
+register_builtin!("enumerate", Enumerate, "number each item");
+
+/// `enumerate` passes on each item as an `{index, value}` map, with the item's index in the
+/// input, counting from 0, or from N with `--start N`. The keys can be changed with `--key NAME`
+/// and `--value NAME`.
+pub struct Enumerate;
+
+/// How `enumerate` was asked to number items.
+#[derive(Debug)]
+struct EnumerateArgs {
+    start: i64,
+    key: String,
+    value: String,
+}
+
+impl Enumerate {
+    /// Parse the arguments of an `enumerate` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<EnumerateArgs, String> {
+        let mut parsed = EnumerateArgs {
+            start: 0,
+            key: "index".to_string(),
+            value: "value".to_string(),
+        };
+
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("`{}` needs a value", arg))
+            };
+            match arg {
+                "--start" => {
+                    let start = value()?;
+                    parsed.start = start
+                        .parse()
+                        .map_err(|_| format!("`{}` isn't a number to start from", start))?;
+                }
+                "--key" => parsed.key = value()?.to_string(),
+                "--value" => parsed.value = value()?.to_string(),
+                other => return Err(format!("unexpected argument `{}`", other)),
+            }
+        }
+
+        if parsed.key == parsed.value {
+            return Err(format!(
+                "the key and value are both called `{}`",
+                parsed.key
+            ));
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl exe::Execute for Enumerate {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match Enumerate::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: enumerate: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let fields = [args.key.clone(), args.value.clone()];
+        let map_header = move |header: &StreamHeader| {
+            let fields = [fields[0].as_str(), fields[1].as_str()];
+            Some(StreamHeader::new(header.type_name.clone(), &fields))
+        };
+
+        let enumerate = move |index: u64, item, _: &mut WriteStream| {
+            let index = i128::from(args.start) + i128::from(index);
+            let index = ciborium::value::Integer::try_from(index)
+                .map_err(|_| "ran out of indices".to_string())?;
+            Ok(Some(Value::Map(vec![
+                (Value::Text(args.key.clone()), Value::Integer(index)),
+                (Value::Text(args.value.clone()), item),
+            ])))
+        };
+
+        Ok(map_items("enumerate", ios, map_header, enumerate))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_with_input, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn enumerate_items() {
+        let dir = scratch_dir("enumerate_items");
+        let input = br#""a" "b" "c""#;
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut enumerate = |args: &str| {
+            let line = format!("from json | enumerate {} | to json", args);
+            run_with_input(&mut int, &line, input)
+        };
+
+        assert_eq!(
+            enumerate(""),
+            (
+                "{\"index\":0,\"value\":\"a\"}\n{\"index\":1,\"value\":\"b\"}\n\
+                 {\"index\":2,\"value\":\"c\"}\n"
+                    .into(),
+                Exit::SUCCESS
+            )
+        );
+        assert_eq!(
+            enumerate("--start -1 --key i --value item").0,
+            "{\"i\":-1,\"item\":\"a\"}\n{\"i\":0,\"item\":\"b\"}\n{\"i\":1,\"item\":\"c\"}\n"
+        );
+
+        assert_eq!(enumerate("--start").1, Exit::BAD_SYNTAX);
+        assert_eq!(enumerate("--start first").1, Exit::BAD_SYNTAX);
+        assert_eq!(enumerate("--key value").1, Exit::BAD_SYNTAX);
+        assert_eq!(enumerate("things").1, Exit::BAD_SYNTAX);
+
+        // The indices can be used to pick out items.
+        let line = "from json | enumerate | where .index ge 1 | select .value | to text";
+        assert_eq!(
+            run_with_input(&mut int, line, input).0,
+            "{\"value\":\"b\"}\n{\"value\":\"c\"}\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, ShellAction, Streams};
+use std::io::Write;
+
+register_builtin!("exec", Exec, "replace the shell with a program");
+
+/// `exec COMMAND...` replaces the shell with a program. If it can't be run, the shell carries on.
+pub struct Exec;
+
+impl exe::Execute for Exec {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let (command, args) = match &args[..] {
+            [command, args @ ..] => (command, args.to_vec()),
+            [] => {
+                let _ = writeln!(ios.stderr, "monch: exec: expected COMMAND...");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        // Only programs can replace us: builtins need a shell to run in.
+        let program = match int.lookup_exe(command) {
+            Ok(exe::Resolved {
+                path: Some(path), ..
+            }) => path,
+            Ok(resolved) => {
+                let _ = writeln!(
+                    ios.stderr,
+                    "monch: exec: '{}' is a {}, not a program",
+                    command, resolved.kind
+                );
+                exit!(Exit::COULD_NOT_EXECUTE)
+            }
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: exec: {}", e);
+                exit!(Exit::COULD_NOT_EXECUTE)
+            }
+        };
+
+        // Whoever's running the shell does the replacing, once nothing else is running.
+        int.request_action(ShellAction::Exec { program, args });
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn is_synchronous(&self, _: &exe::Args) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_builtin, scratch_dir};
+    use std::fs;
+
+    #[test]
+    #[cfg(unix)]
+    fn exec_failures() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("exec_failures");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+
+        // Only programs which can be found can replace the shell.
+        let (_, err, exit) = run_builtin(&Exec, &mut int, &["nonexistent-command"], &[]);
+        assert_eq!(exit, Exit::COULD_NOT_EXECUTE);
+        assert!(err.contains("command not found"), "{}", err);
+        let (_, err, exit) = run_builtin(&Exec, &mut int, &["cd", "/"], &[]);
+        assert_eq!(exit, Exit::COULD_NOT_EXECUTE);
+        assert_eq!(err, "monch: exec: 'cd' is a builtin, not a program\n");
+        assert_eq!(run_builtin(&Exec, &mut int, &[], &[]).2, Exit::BAD_SYNTAX);
+        assert_eq!(int.take_action(), None);
+
+        // Otherwise, it's left for whoever's running the shell.
+        let broken = dir.join("broken");
+        fs::write(&broken, "#!/nonexistent/interpreter\n").unwrap();
+        fs::set_permissions(&broken, fs::Permissions::from_mode(0o755)).unwrap();
+        let (_, _, exit) = run_builtin(&Exec, &mut int, &["./broken", "arg"], &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+
+        let (program, args) = match int.take_action() {
+            Some(ShellAction::Exec { program, args }) => (program, args),
+            action => panic!("expected exec, not {:?}", action),
+        };
+        assert_eq!(program, broken);
+        assert_eq!(args, ["arg"]);
+
+        // If it can't be run, we're still here to say so, and still ignoring SIGPIPE.
+        let err = int.exec(&program, &args);
+        assert_eq!(err.as_exit(), Exit::COULD_NOT_EXECUTE);
+        let sigpipe = unsafe { libc::signal(libc::SIGPIPE, libc::SIG_IGN) };
+        assert_eq!(sigpipe, libc::SIG_IGN);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
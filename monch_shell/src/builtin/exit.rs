@@ -0,0 +1,50 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, ShellAction, Streams};
+use std::io::Write;
+
+register_builtin!("exit", ExitShell, "exit the shell");
+
+/// `exit [CODE]` exits the shell, with `CODE`, or otherwise with the exit of the last command.
+pub struct ExitShell;
+
+impl exe::Execute for ExitShell {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let exit = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => int.last_exit(),
+            [code] => match code.parse::<u8>() {
+                Ok(code) => Exit::Code(code.into()),
+                Err(_) => {
+                    let _ = writeln!(
+                        ios.stderr,
+                        "monch: exit: '{}': expected a number from 0 to 255",
+                        code
+                    );
+                    exit!(Exit::BAD_SYNTAX)
+                }
+            },
+            _ => {
+                let _ = writeln!(ios.stderr, "monch: exit: expected at most one argument");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        int.request_action(ShellAction::Exit(exit));
+        exit!(exit)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn is_synchronous(&self, _: &exe::Args) -> bool {
+        true
+    }
+}
@@ -0,0 +1,82 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use monch_syntax::Parser;
+use std::io::Write;
+
+register_builtin!(
+    "explain",
+    Explain,
+    "describe what a command would do, without running it"
+);
+
+/// `explain COMMAND` describes what a command would do, a line for each stage, without running
+/// it. Its arguments are joined with spaces, so pipelines need quoting, like
+/// `explain 'ls | to tty'`.
+pub struct Explain;
+
+impl exe::Execute for Explain {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let cmd = match Parser::new().parse_command(&args.join(" ")) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: explain: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let plan = match int.explain_command(&cmd) {
+            Ok(plan) => plan,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: explain: {}", e);
+                exit!(e.as_exit())
+            }
+        };
+
+        let use_color = int.use_color(&ios.stdout);
+        match plan.write_lines(&mut ios.stdout, "", use_color) {
+            Ok(()) => exit!(Exit::SUCCESS),
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: explain: {}", e);
+                exit!(Exit::FAILURE)
+            }
+        }
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Tty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::run_builtin;
+    use std::env;
+
+    #[test]
+    fn explain_describes_stages() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+
+        let (out, _, exit) = run_builtin(&Explain, &mut int, &["set", "| to", "tty"], &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out.lines().count(), 2);
+        assert!(out.lines().all(|l| l.contains("builtin")));
+
+        // Commands which wouldn't run fail like they would if they were run.
+        let (_, err, exit) = run_builtin(&Explain, &mut int, &["nonexistent-command"], &[]);
+        assert_eq!(exit, Exit::COMMAND_NOT_FOUND);
+        assert!(err.contains("command not found"));
+
+        let (_, _, exit) = run_builtin(&Explain, &mut int, &["cd |"], &[]);
+        assert_eq!(exit, Exit::BAD_SYNTAX);
+    }
+}
@@ -0,0 +1,99 @@
+use super::is_var_name;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+
+register_builtin!(
+    "export",
+    Export,
+    "pass variables on to programs the shell runs"
+);
+
+/// `export NAME=value` sets a variable and passes it on to child processes. `export NAME` just
+/// passes on an existing variable.
+pub struct Export;
+
+impl exe::Execute for Export {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        if args.is_empty() {
+            let _ = writeln!(ios.stderr, "monch: export: expected NAME=value");
+            exit!(Exit::BAD_SYNTAX)
+        }
+
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+
+            if !is_var_name(name) {
+                let _ = writeln!(ios.stderr, "monch: export: '{}': not a valid name", name);
+                exit!(Exit::FAILURE)
+            }
+
+            if let Some(value) = value {
+                int.set_var(name, value);
+            }
+            int.export_var(name);
+        }
+
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn is_synchronous(&self, _: &exe::Args) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::run;
+    use std::env;
+
+    #[test]
+    fn exported_variables_reach_children() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let (out, exit) = run(
+            &mut int,
+            &["export GREETING=hello", "set LOCAL secret", "env"],
+        );
+
+        assert_eq!(exit, Exit::SUCCESS);
+        assert!(out.lines().any(|l| l == "GREETING=hello"));
+        assert!(out.lines().any(|l| l == "MONCH_SHELL=1"));
+        assert!(out.lines().any(|l| l.starts_with("PWD=")));
+        assert!(!out.contains("LOCAL"));
+        assert_eq!(int.get_var("LOCAL"), Some("secret"));
+
+        // Exporting a shell-local variable passes it on.
+        let (out, _) = run(&mut int, &["export LOCAL", "env"]);
+        assert!(out.lines().any(|l| l == "LOCAL=secret"));
+
+        // And unsetting removes it entirely.
+        let (out, _) = run(&mut int, &["unset LOCAL GREETING", "env"]);
+        assert!(!out.contains("LOCAL") && !out.contains("GREETING"));
+        assert_eq!(int.get_var("LOCAL"), None);
+    }
+
+    #[test]
+    fn bad_variable_names() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        assert_eq!(run(&mut int, &["export 1X=a"]).1, Exit::FAILURE);
+        assert_eq!(run(&mut int, &["set a-b c"]).1, Exit::FAILURE);
+        assert_eq!(run(&mut int, &["set A"]).1, Exit::BAD_SYNTAX);
+        assert_eq!(int.get_var("1X"), None);
+    }
+}
@@ -0,0 +1,25 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+
+register_builtin!("false", False, "do nothing, and fail");
+
+/// `false` does nothing, and fails.
+pub struct False;
+
+impl exe::Execute for False {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        _ios: Streams,
+        _args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        exit!(Exit::FAILURE)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::io::{self, Write};
+use std::{iter, thread};
+
+register_builtin! {
+    ("first", First, "pass on only the first few items"),
+    ("head", First, "pass on only the first few items, like `first`"),
+}
+
+/// `first [N]` passes on the first N items of its input, 10 by default, and then stops reading,
+/// so that whatever's writing them stops too. The count can also be given like `first -n N`, and
+/// it's also called `head`.
+pub struct First;
+
+impl First {
+    /// The number of items to pass on, or `None` if the arguments don't make sense.
+    fn parse_args(args: &[String]) -> Option<usize> {
+        match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => Some(10),
+            [count] | ["-n", count] => count.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl exe::Execute for First {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let count = match First::parse_args(args) {
+            Some(count) => count,
+            None => {
+                let _ = writeln!(ios.stderr, "monch: first: expected a number of items");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        // Once this returns, its end of the input pipe is closed, which stops the writer.
+        let worker = thread::spawn(move || {
+            let mut parser = monch_io::InputParser::<Value, _>::new(ios.stdin)
+                .with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+
+            for i in 0..count {
+                let item = match parser.next() {
+                    None => break,
+                    Some(Ok(item)) => item,
+                    Some(Err(e)) => {
+                        let _ = writeln!(ios.stderr, "monch: first: {}", e);
+                        return Exit::FAILURE;
+                    }
+                };
+
+                // The header's read along with the first item, so pass it on before that.
+                let header = parser.header().filter(|_| i == 0).map(|h| h.to_value());
+                let written = header
+                    .iter()
+                    .chain(iter::once(&item))
+                    .try_for_each(|value| ciborium::ser::into_writer(value, &mut ios.stdout));
+                match written {
+                    Ok(()) => {}
+
+                    // Nothing wants the rest.
+                    Err(ciborium::ser::Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {
+                        break
+                    }
+                    Err(_) => return Exit::FAILURE,
+                }
+            }
+
+            Exit::SUCCESS
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exe::Execute;
+    use crate::streams::{stream_pipe, WriteStream};
+    use std::env;
+    use std::io::Read;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn first_items() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let numbers = |n: u32| {
+            let mut items = vec![];
+            for i in 0..n {
+                ciborium::ser::into_writer(&Value::Integer(i.into()), &mut items).unwrap();
+            }
+            items
+        };
+
+        // Feed the input through a pipe, like it'd come from another stage.
+        let mut first = |args: &[&str], input: Vec<u8>| {
+            let (stdin, mut stdin_w) = stream_pipe().unwrap();
+            let (mut stdout, stdout_w) = stream_pipe().unwrap();
+            let writer = thread::spawn(move || stdin_w.write_all(&input));
+            let ios = Streams {
+                stdin,
+                stdout: stdout_w,
+                stderr: WriteStream::Null,
+            };
+
+            let args: exe::Args = args.iter().map(|a| a.to_string()).collect();
+            let exit = First.execute(&mut int, ios, &args).unwrap().wait().unwrap();
+            let mut out = vec![];
+            stdout.read_to_end(&mut out).unwrap();
+            (out, exit, writer)
+        };
+
+        let (out, exit, _) = first(&["5"], numbers(100));
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(out, numbers(5));
+        assert_eq!(first(&[], numbers(100)).0, numbers(10));
+        assert_eq!(first(&["-n", "3"], numbers(100)).0, numbers(3));
+        assert_eq!(first(&["1000"], numbers(100)).0, numbers(100));
+        assert_eq!(first(&["0"], numbers(100)).0, numbers(0));
+
+        // It doesn't wait for the rest of the input, and the writer finds nobody's reading.
+        let start = Instant::now();
+        let (out, _, writer) = first(&["2"], numbers(1_000_000));
+        assert_eq!(out, numbers(2));
+        assert!(writer.join().unwrap().is_err());
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        assert_eq!(first(&["lots"], vec![]).1, Exit::BAD_SYNTAX);
+    }
+
+    #[test]
+    fn stops_when_output_closes() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+
+        // Each item is bigger than a pipe holds, so the second can't be written once the reader's
+        // gone.
+        let item = Value::Text("x".repeat(100_000));
+        let mut input = vec![];
+        for _ in 0..3 {
+            ciborium::ser::into_writer(&item, &mut input).unwrap();
+        }
+        let item_len = input.len() / 3;
+
+        let (stdin, mut stdin_w) = stream_pipe().unwrap();
+        let (mut stdout, stdout_w) = stream_pipe().unwrap();
+        let (mut stderr, stderr_w) = stream_pipe().unwrap();
+        thread::spawn(move || stdin_w.write_all(&input));
+        let ios = Streams {
+            stdin,
+            stdout: stdout_w,
+            stderr: stderr_w,
+        };
+        let args: exe::Args = ["3".to_string()].into_iter().collect();
+        let proc = First.execute(&mut int, ios, &args).unwrap();
+
+        // Read one item, then stop reading.
+        let mut out = vec![0; item_len];
+        stdout.read_exact(&mut out).unwrap();
+        drop(stdout);
+
+        assert_eq!(proc.wait().unwrap(), Exit::SUCCESS);
+        let mut errors = String::new();
+        stderr.read_to_string(&mut errors).unwrap();
+        assert_eq!(errors, "");
+    }
+}
@@ -0,0 +1,186 @@
+use super::map_items;
+use crate::streams::WriteStream;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::DataPath;
+use std::io::Write;
+
+register_builtin!(
+    "flatten",
+    Flatten,
+    "pass on the elements of arrays as items"
+);
+
+/// `flatten` passes on the elements of each array in its input as items of their own, and
+/// anything else unchanged. With `-f PATH`, it flattens the array at PATH in each item instead,
+/// and with `--with-parent`, each element is passed on as the rest of the item it came from,
+/// with the element under `item`. `--depth N` flattens arrays inside arrays too, N levels deep.
+pub struct Flatten;
+
+/// How `flatten` was asked to flatten its input.
+#[derive(Debug)]
+struct FlattenArgs {
+    field: Option<DataPath>,
+    with_parent: bool,
+    depth: usize,
+}
+
+impl Flatten {
+    /// Parse the arguments of a `flatten` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<FlattenArgs, String> {
+        let mut parsed = FlattenArgs {
+            field: None,
+            with_parent: false,
+            depth: 1,
+        };
+
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            match arg {
+                "--with-parent" => parsed.with_parent = true,
+                "-f" | "--field" => {
+                    let path = args.next().ok_or("`--field` needs a path")?;
+                    let path = DataPath::parse_strict(path).map_err(|e| e.to_string())?;
+                    parsed.field = Some(path);
+                }
+                "--depth" => {
+                    let depth = args.next().ok_or("`--depth` needs a number")?;
+                    parsed.depth = depth
+                        .parse()
+                        .map_err(|_| format!("`{}` isn't a depth", depth))?;
+                }
+                other => return Err(format!("unexpected argument `{}`", other)),
+            }
+        }
+
+        if parsed.with_parent && parsed.field.is_none() {
+            return Err("`--with-parent` needs a field to flatten, given with `-f`".to_string());
+        }
+
+        Ok(parsed)
+    }
+
+    /// Add the elements of a value to `out`, flattening arrays inside it until `depth` runs out.
+    fn flatten_into(value: Value, depth: usize, out: &mut Vec<Value>) {
+        match value {
+            Value::Array(items) if depth > 0 => {
+                for item in items {
+                    Flatten::flatten_into(item, depth - 1, out);
+                }
+            }
+            other => out.push(other),
+        }
+    }
+
+    /// Flatten one item of the input.
+    fn flatten(args: &FlattenArgs, mut item: Value) -> Vec<Value> {
+        let path = match &args.field {
+            Some(path) if matches!(path.get_ref(&item), Some(Value::Array(_))) => path,
+
+            // Without a field, it's the item itself that's flattened.
+            None => {
+                let mut out = vec![];
+                Flatten::flatten_into(item, args.depth, &mut out);
+                return out;
+            }
+
+            // Items without an array at their path aren't changed.
+            Some(_) => return vec![item],
+        };
+
+        let mut elements = vec![];
+        let array = path.take_from(&mut item).unwrap_or(Value::Null);
+        Flatten::flatten_into(array, args.depth, &mut elements);
+
+        if !args.with_parent {
+            return elements;
+        }
+
+        let field = DataPath::parse("item");
+        elements
+            .into_iter()
+            .map(|element| {
+                let mut parent = item.clone();
+                field.set_into(&mut parent, element);
+                parent
+            })
+            .collect()
+    }
+}
+
+impl exe::Execute for Flatten {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match Flatten::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: flatten: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        // Any header describes the items before they're flattened, so it isn't passed on.
+        let flatten = move |_, item, _: &mut WriteStream| Ok(Flatten::flatten(&args, item));
+        Ok(map_items("flatten", ios, |_| None, flatten))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_with_input, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn flatten_arrays() {
+        let dir = scratch_dir("flatten_arrays");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut flatten = |args: &str, input: &str| {
+            let line = format!("from json | flatten {} | to json", args);
+            run_with_input(&mut int, &line, input.as_bytes())
+        };
+
+        // Arrays are split up, and anything else passes through. Empty arrays disappear. The
+        // input can't start with an array, or `from json` would split that up itself.
+        let nested = r#""text" [1, [2, [3]]] [] [4]"#;
+        assert_eq!(
+            flatten("", nested),
+            ("\"text\"\n1\n[2,[3]]\n4\n".into(), Exit::SUCCESS)
+        );
+        assert_eq!(flatten("--depth 2", nested).0, "\"text\"\n1\n2\n[3]\n4\n");
+        assert_eq!(flatten("--depth 5", nested).0, "\"text\"\n1\n2\n3\n4\n");
+        assert_eq!(
+            flatten("--depth 0", nested).0,
+            "\"text\"\n[1,[2,[3]]]\n[]\n[4]\n"
+        );
+
+        // Arrays inside items can be split up, keeping the rest of the item alongside.
+        let items = r#"{"dir": "a", "files": ["x", "y"]} {"dir": "b", "files": []} {"dir": "c"}"#;
+        assert_eq!(
+            flatten("-f .files", items).0,
+            "\"x\"\n\"y\"\n{\"dir\":\"c\"}\n"
+        );
+        assert_eq!(
+            flatten("-f .files --with-parent", items).0,
+            "{\"dir\":\"a\",\"item\":\"x\"}\n{\"dir\":\"a\",\"item\":\"y\"}\n{\"dir\":\"c\"}\n"
+        );
+
+        assert_eq!(flatten("--with-parent", "").1, Exit::BAD_SYNTAX);
+        assert_eq!(flatten("--depth deep", "").1, Exit::BAD_SYNTAX);
+        assert_eq!(flatten("-f", "").1, Exit::BAD_SYNTAX);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,51 @@
+use crate::types::Ty;
+use std::str::FromStr;
+
+/// A format that `to` converts CBOR into, or that `from` converts into CBOR.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum DataFormat {
+    Tty,
+    Cbor,
+    Text,
+    Json,
+    Csv,
+}
+
+impl DataFormat {
+    /// The type of data written in this format.
+    pub(super) fn ty(self) -> Ty {
+        match self {
+            DataFormat::Tty => Ty::Tty,
+            DataFormat::Cbor => Ty::Cbor,
+            DataFormat::Text | DataFormat::Json | DataFormat::Csv => Ty::Text,
+        }
+    }
+}
+
+impl std::fmt::Display for DataFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let name = match self {
+            DataFormat::Tty => "tty",
+            DataFormat::Cbor => "cbor",
+            DataFormat::Text => "text",
+            DataFormat::Json => "json",
+            DataFormat::Csv => "csv",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DataFormat {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<DataFormat, Self::Err> {
+        match name.trim().to_lowercase().as_str() {
+            "tty" => Ok(DataFormat::Tty),
+            "cbor" => Ok(DataFormat::Cbor),
+            "text" => Ok(DataFormat::Text),
+            "json" => Ok(DataFormat::Json),
+            "csv" => Ok(DataFormat::Csv),
+            _ => Err(()),
+        }
+    }
+}
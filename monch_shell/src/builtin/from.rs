@@ -0,0 +1,352 @@
+use super::format::DataFormat;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::convert::json_to_cbor;
+use std::io::{self, Write};
+use std::thread;
+
+register_builtin!("from", FromBuiltin, "convert data from another format");
+
+/// `from FORMAT` converts data in another format into CBOR items. `from json` reads JSON values
+/// one after another, like JSON Lines, or a single array whose elements are each an item.
+/// `from csv` reads a map for each row, keyed by the header row, or an array for each row with
+/// `from csv --no-header`.
+pub struct FromBuiltin;
+
+impl FromBuiltin {
+    /// Parse the arguments of a `from` invocation, returning the format we're converting from,
+    /// and whether CSV has a header row, or an error message.
+    fn parse_args(args: &[String]) -> Result<(DataFormat, bool), String> {
+        match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["json"] => Ok((DataFormat::Json, false)),
+            ["csv"] => Ok((DataFormat::Csv, true)),
+            ["csv", "--no-header"] => Ok((DataFormat::Csv, false)),
+            [name] => Err(format!("from: can't convert from '{}'", name)),
+            _ => Err("from: expected a format name only, or `csv --no-header`".to_string()),
+        }
+    }
+
+    /// Convert CSV rows from `input` into CBOR items, as they're read. Every field is text.
+    fn from_csv(input: impl io::Read, out: &mut impl Write, header: bool) -> Result<(), String> {
+        let mut reader = csv::ReaderBuilder::new()
+            .has_headers(header)
+            .flexible(!header)
+            .from_reader(input);
+        let keys: Option<Vec<Value>> = if header {
+            let keys = reader.headers().map_err(|e| e.to_string())?;
+            Some(
+                keys.iter()
+                    .map(|key| Value::Text(key.to_string()))
+                    .collect(),
+            )
+        } else {
+            None
+        };
+
+        for record in reader.records() {
+            let record = record.map_err(|e| e.to_string())?;
+            let fields = record.iter().map(|field| Value::Text(field.to_string()));
+            let item = match &keys {
+                Some(keys) => Value::Map(keys.iter().cloned().zip(fields).collect()),
+                None => Value::Array(fields.collect()),
+            };
+
+            match ciborium::ser::into_writer(&item, &mut *out) {
+                Ok(()) => {}
+
+                // Nothing wants the rest.
+                Err(ciborium::ser::Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => break,
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Convert JSON values from `input` into CBOR items, as they're read.
+    fn from_json(input: impl io::Read, out: &mut impl Write) -> Result<(), serde_json::Error> {
+        use serde::de::{Deserializer as _, Error as _};
+
+        // Look at the first value to tell whether this is a single array. The whitespace
+        // before it is kept, so that the parser still knows which line it's on.
+        let mut input = io::BufReader::new(input);
+        let mut skipped = vec![];
+        let is_array = loop {
+            let buf = io::BufRead::fill_buf(&mut input).map_err(serde_json::Error::io)?;
+            match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+                Some(i) => {
+                    let is_array = buf[i] == b'[';
+                    skipped.extend_from_slice(&buf[..i]);
+                    io::BufRead::consume(&mut input, i);
+                    break is_array;
+                }
+                None if buf.is_empty() => return Ok(()),
+                None => {
+                    let n = buf.len();
+                    skipped.extend_from_slice(buf);
+                    io::BufRead::consume(&mut input, n);
+                }
+            }
+        };
+        let input = io::Read::chain(io::Cursor::new(skipped), input);
+
+        // Set if nothing wants the rest of the items, so we stop parsing without it being an error.
+        let mut closed = false;
+        let mut write_item = |json: serde_json::Value| {
+            let written = ciborium::ser::into_writer(&json_to_cbor(json), &mut *out);
+            match written {
+                Ok(()) => Ok(()),
+                Err(ciborium::ser::Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {
+                    closed = true;
+                    Err(serde_json::Error::custom("output closed"))
+                }
+                Err(e) => Err(serde_json::Error::custom(e.to_string())),
+            }
+        };
+
+        let converted = if is_array {
+            let mut de = serde_json::Deserializer::from_reader(input);
+            (&mut de)
+                .deserialize_seq(JsonElements(&mut write_item))
+                .and_then(|()| de.end())
+        } else {
+            serde_json::Deserializer::from_reader(input)
+                .into_iter()
+                .try_for_each(|json| write_item(json?))
+        };
+
+        match converted {
+            Err(_) if closed => Ok(()),
+            converted => converted,
+        }
+    }
+}
+
+/// Visits a JSON array, passing each element to a callback as soon as it's parsed.
+struct JsonElements<F>(F);
+
+impl<'de, F> serde::de::Visitor<'de> for JsonElements<F>
+where
+    F: FnMut(serde_json::Value) -> Result<(), serde_json::Error>,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "an array")
+    }
+
+    fn visit_seq<A: serde::de::SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        use serde::de::Error as _;
+
+        while let Some(element) = seq.next_element()? {
+            (self.0)(element).map_err(A::Error::custom)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl exe::Execute for FromBuiltin {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let (format, header) = match FromBuiltin::parse_args(args) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "{}", e);
+                exit!(Exit::FAILURE)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let converted = match format {
+                DataFormat::Csv => FromBuiltin::from_csv(ios.stdin, &mut ios.stdout, header),
+                _ => FromBuiltin::from_json(ios.stdin, &mut ios.stdout).map_err(|e| e.to_string()),
+            };
+
+            match converted {
+                Ok(()) => Exit::SUCCESS,
+                Err(e) => {
+                    let _ = writeln!(ios.stderr, "from: {}: {}", format, e);
+                    Exit::FAILURE
+                }
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Any
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        FromBuiltin::parse_args(args).map_or(Ty::Nothing, |_| Ty::Cbor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::to::To;
+    use crate::exe::Execute;
+    use crate::streams::stream_pipe;
+    use crate::testing::{run, scratch_dir};
+    use monch_syntax::Parser;
+    use std::fs;
+    use std::io::Read;
+
+    #[test]
+    fn json_conversions() {
+        let dir = scratch_dir("json_conversions");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let args = |a: &[&str]| a.iter().map(|a| a.to_string()).collect::<exe::Args>();
+
+        // JSON Lines, with values spread over lines, and integers too big for an i64.
+        fs::write(
+            dir.join("lines.json"),
+            "{\"a\": [1, 2.5, null]}\n\"text\"\n\n18446744073709551615\n{\n  \"b\": true\n}\n",
+        )
+        .unwrap();
+        let (out, exit) = run(&mut int, &["from json <lines.json | to json"]);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(
+            out,
+            "{\"a\":[1,2.5,null]}\n\"text\"\n18446744073709551615\n{\"b\":true}\n"
+        );
+
+        // A single array is split into its elements.
+        fs::write(
+            dir.join("array.json"),
+            "\n[\n  {\"n\": 1},\n  {\"n\": 2}\n]\n",
+        )
+        .unwrap();
+        let (out, _) = run(&mut int, &["from json <array.json | to json"]);
+        assert_eq!(out, "{\"n\":1}\n{\"n\":2}\n");
+
+        // Errors say which line they're on.
+        let mut out = vec![];
+        let error = FromBuiltin::from_json(&b"[\n  1,\n  2,\n  ]\n"[..], &mut out).unwrap_err();
+        assert_eq!(error.line(), 4);
+        let error = FromBuiltin::from_json(&b"\n{}\n{\"b\": }\n"[..], &mut out).unwrap_err();
+        assert_eq!(error.line(), 3);
+        fs::write(dir.join("bad.json"), "{\"a\" 1}").unwrap();
+        assert_eq!(run(&mut int, &["from json <bad.json"]).1, Exit::FAILURE);
+
+        // Binary data can't be written as JSON.
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&Value::Bytes(vec![1, 2]), &mut bytes).unwrap();
+        fs::write(dir.join("bytes.cbor"), &bytes).unwrap();
+        assert_eq!(run(&mut int, &["to json <bytes.cbor"]).1, Exit::FAILURE);
+
+        assert_eq!(To.output_type(&args(&["json"])), Ty::Text);
+        assert_eq!(FromBuiltin.input_type(&args(&["json"])), Ty::Any);
+        assert_eq!(FromBuiltin.output_type(&args(&["json"])), Ty::Cbor);
+        assert_eq!(FromBuiltin.output_type(&args(&["yaml"])), Ty::Nothing);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stops_when_output_closes() {
+        let dir = scratch_dir("from_stops_when_output_closes");
+        let (mut stderr, stderr_w) = stream_pipe().unwrap();
+        let ios = Streams {
+            stderr: stderr_w,
+            ..Streams::null()
+        };
+        let mut int = Interpreter::new(ios, &dir);
+        int.set_pipefail(true);
+
+        // Much more than fits in a pipe, as JSON Lines, one JSON array, and CSV.
+        let lines: Vec<String> = (0..100_000).map(|n| format!("{{\"n\": {}}}", n)).collect();
+        fs::write(dir.join("lines.json"), lines.join("\n")).unwrap();
+        fs::write(dir.join("array.json"), format!("[{}]", lines.join(",\n"))).unwrap();
+        let rows: Vec<String> = (0..100_000).map(|n| n.to_string()).collect();
+        fs::write(dir.join("rows.csv"), format!("n\n{}", rows.join("\n"))).unwrap();
+
+        for (line, first) in [
+            ("from json <lines.json", "{\"n\":0}\n"),
+            ("from json <array.json", "{\"n\":0}\n"),
+            ("from csv <rows.csv", "{\"n\":\"0\"}\n"),
+        ] {
+            let line = format!("{} | first 1 | to json", line);
+            assert_eq!(run(&mut int, &[&line]), (first.into(), Exit::SUCCESS));
+            assert_eq!(
+                int.last_pipeline_status()[0],
+                ("from".to_string(), Exit::SUCCESS)
+            );
+        }
+
+        drop(int);
+        let mut errors = String::new();
+        stderr.read_to_string(&mut errors).unwrap();
+        assert_eq!(errors, "");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn csv_conversions() {
+        let dir = scratch_dir("csv_conversions");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let args = |a: &[&str]| a.iter().map(|a| a.to_string()).collect::<exe::Args>();
+
+        // Awkward fields are quoted, and nested values are written as JSON. The columns are the
+        // keys of the first row, in order.
+        fs::write(
+            dir.join("rows.json"),
+            concat!(
+                "{\"n\": 1, \"name\": \"plain\", \"note\": \"a, b\"}\n",
+                "{\"note\": \"say \\\"hi\\\"\\nbye\", \"name\": \"quoted\", \"extra\": 2}\n",
+                "{\"name\": \"\", \"note\": null, \"n\": [1, {\"x\": true}]}\n",
+            ),
+        )
+        .unwrap();
+        let (out, exit) = run(&mut int, &["from json <rows.json | to csv"]);
+        assert_eq!(exit, Exit::SUCCESS);
+        let table = concat!(
+            "n,name,note\n",
+            "1,plain,\"a, b\"\n",
+            ",quoted,\"say \"\"hi\"\"\nbye\"\n",
+            "\"[1,{\"\"x\"\":true}]\",,\n",
+        );
+        assert_eq!(out, table);
+
+        let (out, _) = run(
+            &mut int,
+            &["from json <rows.json | to csv --columns 'n,name'"],
+        );
+        assert_eq!(out, "n,name\n1,plain\n,quoted\n\"[1,{\"\"x\"\":true}]\",\n");
+
+        // Reading the table back gets the same text, and empty fields stay empty.
+        fs::write(dir.join("table.csv"), table).unwrap();
+        let (out, exit) = run(&mut int, &["from csv <table.csv | to json"]);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(
+            out,
+            concat!(
+                "{\"n\":\"1\",\"name\":\"plain\",\"note\":\"a, b\"}\n",
+                "{\"n\":\"\",\"name\":\"quoted\",\"note\":\"say \\\"hi\\\"\\nbye\"}\n",
+                "{\"n\":\"[1,{\\\"x\\\":true}]\",\"name\":\"\",\"note\":\"\"}\n",
+            )
+        );
+        let (out, _) = run(&mut int, &["from csv --no-header <table.csv | to json"]);
+        assert!(out.starts_with("[\"n\",\"name\",\"note\"]\n[\"1\",\"plain\",\"a, b\"]\n"));
+
+        // Rows have to match the header, and only maps make rows.
+        fs::write(dir.join("ragged.csv"), "a,b\n1,2\n3\n").unwrap();
+        let cmd = Parser::new().parse_command("from csv <ragged.csv").unwrap();
+        assert_eq!(int.eval_command_capture(&cmd).unwrap().1, Exit::FAILURE);
+        assert_eq!(run(&mut int, &["echo a | to csv"]).1, Exit::FAILURE);
+
+        assert_eq!(To.output_type(&args(&["csv"])), Ty::Text);
+        assert_eq!(To.output_type(&args(&["csv", "--columns", "a"])), Ty::Text);
+        assert_eq!(
+            FromBuiltin.output_type(&args(&["csv", "--no-header"])),
+            Ty::Cbor
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,283 @@
+use super::number::Number;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::{canonicalize, DataPath};
+use std::collections::HashMap;
+use std::io::Write;
+use std::thread;
+
+register_builtin!(
+    "group-by",
+    GroupBy,
+    "count and total up items by the value at a path"
+);
+
+/// `group-by PATH` reads its whole input, and writes a `{key, count}` map for each distinct value
+/// at PATH, in the order they first appear. Keys can be any value, and maps are the same key
+/// whatever order their keys are in. Items without a value at PATH are grouped under `null`.
+///
+/// `--sum PATH` and `--avg PATH` add the `sum` or `avg` of the numbers at PATH in each group,
+/// along with a count of the items `skipped` because they didn't have a number there. Only these
+/// totals are kept, unless `--items` asks for every item to be included in its group's `items`.
+pub struct GroupBy;
+
+/// How `group-by` was asked to group and aggregate items.
+#[derive(Debug)]
+struct GroupByArgs {
+    key: DataPath,
+    sum: Option<DataPath>,
+    avg: Option<DataPath>,
+    items: bool,
+}
+
+/// One group of items in `group-by`, and what's been totalled up for it so far.
+#[derive(Debug)]
+struct Group {
+    key: Value,
+    count: u64,
+    sum: Number,
+    avg: (Number, u64),
+    skipped: u64,
+    items: Vec<Value>,
+}
+
+impl Group {
+    fn new(key: Value) -> Group {
+        Group {
+            key,
+            count: 0,
+            sum: Number::Integer(0),
+            avg: (Number::Integer(0), 0),
+            skipped: 0,
+            items: vec![],
+        }
+    }
+
+    /// Add an item to the group.
+    fn add(&mut self, args: &GroupByArgs, item: Value) {
+        self.count += 1;
+
+        let number = |path: &DataPath| path.get_ref(&item).and_then(Number::of);
+        let sum = args.sum.as_ref().map(number);
+        let avg = args.avg.as_ref().map(number);
+
+        if let Some(Some(n)) = sum {
+            self.sum = self.sum.add(n);
+        }
+        if let Some(Some(n)) = avg {
+            self.avg = (self.avg.0.add(n), self.avg.1 + 1);
+        }
+        if sum == Some(None) || avg == Some(None) {
+            self.skipped += 1;
+        }
+
+        if args.items {
+            self.items.push(item);
+        }
+    }
+
+    /// The map describing the group.
+    fn into_value(self, args: &GroupByArgs) -> Value {
+        let field = |name: &str, value| (Value::Text(name.to_string()), value);
+        let mut fields = vec![
+            field("key", self.key),
+            field("count", Value::Integer(self.count.into())),
+        ];
+
+        if args.sum.is_some() {
+            fields.push(field("sum", self.sum.to_value()));
+        }
+        if args.avg.is_some() {
+            let (total, n) = self.avg;
+            let avg = match n {
+                0 => Value::Null,
+                n => Value::Float(total.as_f64() / n as f64),
+            };
+            fields.push(field("avg", avg));
+        }
+        if args.sum.is_some() || args.avg.is_some() {
+            fields.push(field("skipped", Value::Integer(self.skipped.into())));
+        }
+        if args.items {
+            fields.push(field("items", Value::Array(self.items)));
+        }
+
+        Value::Map(fields)
+    }
+}
+
+impl GroupBy {
+    /// Parse the arguments of a `group-by` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<GroupByArgs, String> {
+        let path = |arg: Option<&str>, flag: &str| {
+            let arg = arg.ok_or_else(|| format!("`{}` needs a path", flag))?;
+            DataPath::parse_strict(arg).map_err(|e| format!("`{}` isn't a path: {}", arg, e))
+        };
+
+        let mut key = None;
+        let mut parsed = GroupByArgs {
+            key: DataPath::parse(""),
+            sum: None,
+            avg: None,
+            items: false,
+        };
+
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            match arg {
+                "--items" => parsed.items = true,
+                "--sum" => parsed.sum = Some(path(args.next(), arg)?),
+                "--avg" => parsed.avg = Some(path(args.next(), arg)?),
+                _ if key.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+                _ => key = Some(path(Some(arg), arg)?),
+            }
+        }
+
+        parsed.key = key.ok_or("expected a path to group by, like `group-by .kind`")?;
+        Ok(parsed)
+    }
+}
+
+impl exe::Execute for GroupBy {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match GroupBy::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: group-by: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let parser = monch_io::InputParser::<Value, _>::new(ios.stdin)
+                .with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+
+            // Each group, in the order they were found, and where to find each key's group.
+            let mut groups: Vec<Group> = vec![];
+            let mut group_of = HashMap::new();
+
+            for item in parser {
+                let item = match item {
+                    Ok(item) => item,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: group-by: {}", e);
+                        return Exit::FAILURE;
+                    }
+                };
+
+                let key = canonicalize(args.key.get_ref(&item).cloned().unwrap_or(Value::Null));
+                let mut encoded = vec![];
+                if let Err(e) = ciborium::ser::into_writer(&key, &mut encoded) {
+                    let _ = writeln!(ios.stderr, "monch: group-by: {}", e);
+                    return Exit::FAILURE;
+                }
+
+                let i = *group_of.entry(encoded).or_insert_with(|| {
+                    groups.push(Group::new(key));
+                    groups.len() - 1
+                });
+                groups[i].add(&args, item);
+            }
+
+            let written = groups.into_iter().try_for_each(|group| {
+                ciborium::ser::into_writer(&group.into_value(&args), &mut ios.stdout)
+            });
+            match written {
+                Ok(()) => Exit::SUCCESS,
+                Err(_) => Exit::FAILURE,
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{encode, run_with_input, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn group_by_key() {
+        let dir = scratch_dir("group_by_key");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut group = |args: &str, items: &[Value]| {
+            let line = format!("group-by {} | to json", args);
+            run_with_input(&mut int, &line, &encode(items))
+        };
+
+        let text = |s: &str| Value::Text(s.to_string());
+        let item = |fields: Vec<(&str, Value)>| {
+            Value::Map(fields.into_iter().map(|(k, v)| (text(k), v)).collect())
+        };
+        let file = |kind: &str, size: Value| item(vec![("kind", text(kind)), ("size", size)]);
+        let number = |n: i64| Value::Integer(n.into());
+
+        // Numbers are totalled up, skipping anything else.
+        let files = [
+            file("file", number(10)),
+            file("dir", number(1)),
+            file("file", number(5)),
+            file("file", Value::Float(0.5)),
+            file("dir", text("big")),
+            item(vec![("kind", text("dir"))]),
+        ];
+        assert_eq!(
+            group(".kind", &files),
+            (
+                "{\"key\":\"file\",\"count\":3}\n{\"key\":\"dir\",\"count\":3}\n".into(),
+                Exit::SUCCESS
+            )
+        );
+        assert_eq!(
+            group(".kind --sum .size --avg .size", &files).0,
+            "{\"key\":\"file\",\"count\":3,\"sum\":15.5,\"avg\":5.166666666666667,\"skipped\":0}\n\
+             {\"key\":\"dir\",\"count\":3,\"sum\":1,\"avg\":1.0,\"skipped\":2}\n"
+        );
+        assert_eq!(
+            group(".kind --items", &files[..2]).0,
+            "{\"key\":\"file\",\"count\":1,\"items\":[{\"kind\":\"file\",\"size\":10}]}\n\
+             {\"key\":\"dir\",\"count\":1,\"items\":[{\"kind\":\"dir\",\"size\":1}]}\n"
+        );
+
+        // Keys can be anything, and maps are the same whatever order their keys are in.
+        let keyed = |key| item(vec![("k", key)]);
+        let ab = Value::Map(vec![(text("a"), number(1)), (text("b"), number(2))]);
+        let ba = Value::Map(vec![(text("b"), number(2)), (text("a"), number(1))]);
+        let keys = [
+            keyed(number(1)),
+            keyed(text("1")),
+            keyed(Value::Null),
+            item(vec![]),
+            keyed(Value::Array(vec![number(1)])),
+            keyed(ab),
+            keyed(ba),
+            keyed(number(1)),
+        ];
+        assert_eq!(
+            group(".k", &keys).0,
+            "{\"key\":1,\"count\":2}\n{\"key\":\"1\",\"count\":1}\n{\"key\":null,\"count\":2}\n\
+             {\"key\":[1],\"count\":1}\n{\"key\":{\"a\":1,\"b\":2},\"count\":2}\n"
+        );
+
+        assert_eq!(group("", &[]).1, Exit::BAD_SYNTAX);
+        assert_eq!(group(".k --sum", &[]).1, Exit::BAD_SYNTAX);
+        assert_eq!(group(".k .j", &[]).1, Exit::BAD_SYNTAX);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::io::Write;
+
+register_builtin!(
+    "hash",
+    Hash,
+    "list or forget the programs the shell has found"
+);
+
+/// `hash` lists the programs the shell has found on MONCH_PATH and PATH, as a stream of maps like
+/// `{name, kind, path}`. `hash -r` forgets them, so that it looks for them afresh.
+pub struct Hash;
+
+impl exe::Execute for Hash {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => {
+                let text = |s: &str| Value::Text(s.to_string());
+                for (name, kind, path) in int.hashed_programs() {
+                    let program = Value::Map(vec![
+                        (text("name"), text(&name)),
+                        (text("kind"), text(&kind.to_string())),
+                        (text("path"), text(&path.to_string_lossy())),
+                    ]);
+
+                    if let Err(e) = ciborium::ser::into_writer(&program, &mut ios.stdout) {
+                        let _ = writeln!(ios.stderr, "monch: hash: {}", e);
+                        exit!(Exit::FAILURE)
+                    }
+                }
+
+                exit!(Exit::SUCCESS)
+            }
+            ["-r"] => {
+                int.forget_commands();
+                exit!(Exit::SUCCESS)
+            }
+            _ => {
+                let _ = writeln!(ios.stderr, "monch: hash: expected no arguments, or -r");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        }
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        match args.len() {
+            0 => Ty::Cbor,
+            _ => Ty::Nothing,
+        }
+    }
+
+    fn is_synchronous(&self, args: &exe::Args) -> bool {
+        !args.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run, run_builtin, scratch_dir};
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    #[test]
+    fn hash_lists_found_programs() {
+        let dir = scratch_dir("hash_lists_found_programs");
+        fs::copy("/bin/true", dir.join("b")).unwrap();
+        fs::copy("/bin/true", dir.join("a")).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_var("MONCH_PATH", "");
+        int.set_var("PATH", dir.to_string_lossy());
+        run(&mut int, &["b", "a", "cd ."]);
+
+        let hashed = |int: &mut Interpreter| {
+            let (out, _, exit) = run_builtin(&Hash, int, &[], &[]);
+            assert_eq!(exit, Exit::SUCCESS);
+
+            let mut programs = vec![];
+            let mut reader = &out[..];
+            while !reader.is_empty() {
+                let p: BTreeMap<String, String> = ciborium::de::from_reader(&mut reader).unwrap();
+                programs.push(p);
+            }
+            programs
+        };
+
+        // Programs are listed by name, and builtins aren't listed at all.
+        let programs = hashed(&mut int);
+        assert_eq!(programs.len(), 2);
+        assert_eq!(programs[0]["name"], "a");
+        assert_eq!(programs[0]["kind"], "external");
+        assert_eq!(programs[0]["path"], dir.join("a").to_string_lossy());
+        assert_eq!(programs[1]["name"], "b");
+
+        assert_eq!(run_builtin(&Hash, &mut int, &["-r"], &[]).2, Exit::SUCCESS);
+        assert!(hashed(&mut int).is_empty());
+
+        assert_eq!(
+            run_builtin(&Hash, &mut int, &["-x"], &[]).2,
+            Exit::BAD_SYNTAX
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
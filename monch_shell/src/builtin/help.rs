@@ -0,0 +1,171 @@
+use super::BUILTINS;
+use crate::{exe, suggest, types::Ty, Error, Exit, Interpreter, Streams};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+
+register_builtin!("help", Help, "list the builtins and monch utilities");
+
+/// `help` describes every builtin, and every monch utility on MONCH_PATH, as a map like
+/// `{name, kind, summary, path}` for each. `help NAME` describes just one of them.
+pub struct Help;
+
+/// A command described by `help`. Builtins have a summary, and utilities have a path.
+#[derive(Serialize)]
+struct HelpEntry {
+    name: String,
+    kind: exe::ExeKind,
+    summary: Option<&'static str>,
+    path: Option<PathBuf>,
+}
+
+impl Help {
+    /// Every builtin, then every monch utility which isn't hidden by a builtin or an earlier
+    /// directory on MONCH_PATH.
+    fn entries(int: &Interpreter) -> Vec<HelpEntry> {
+        let mut entries: Vec<HelpEntry> = BUILTINS
+            .iter()
+            .map(|(name, builtin)| HelpEntry {
+                name: name.to_string(),
+                kind: exe::ExeKind::Builtin,
+                summary: Some(builtin.summary),
+                path: None,
+            })
+            .collect();
+
+        let mut utilities = BTreeMap::new();
+        for dir in int.monch_path() {
+            for name in suggest::executables_on(dir.as_os_str()) {
+                if !BUILTINS.contains_key(name.as_str()) && !utilities.contains_key(&name) {
+                    let path = dir.join(&name);
+                    utilities.insert(name, path);
+                }
+            }
+        }
+
+        entries.extend(utilities.into_iter().map(|(name, path)| HelpEntry {
+            name,
+            kind: exe::ExeKind::Monch,
+            summary: None,
+            path: Some(path),
+        }));
+        entries
+    }
+}
+
+impl exe::Execute for Help {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let mut entries = Help::entries(int);
+
+        match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => {}
+            [name] => {
+                entries.retain(|entry| entry.name == name);
+                if entries.is_empty() {
+                    let _ = writeln!(
+                        ios.stderr,
+                        "monch: help: no builtin or monch utility called '{}'",
+                        name
+                    );
+                    exit!(Exit::FAILURE)
+                }
+            }
+            _ => {
+                let _ = writeln!(ios.stderr, "monch: help: expected at most one command name");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        }
+
+        for entry in &entries {
+            if let Err(e) = ciborium::ser::into_writer(entry, &mut ios.stdout) {
+                let _ = writeln!(ios.stderr, "monch: help: {}", e);
+                exit!(Exit::FAILURE)
+            }
+        }
+
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_builtin, scratch_dir};
+    use ciborium::value::Value;
+    use std::fs;
+
+    #[test]
+    #[cfg(unix)]
+    fn help_lists_commands() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("help_lists_commands");
+        for (sub, name) in [
+            ("one", "stub"),
+            ("one", "cd"),
+            ("two", "stub"),
+            ("two", "other"),
+        ] {
+            fs::create_dir_all(dir.join(sub)).unwrap();
+            let stub = dir.join(sub).join(name);
+            fs::write(&stub, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_monch_path(&[dir.join("one"), dir.join("two")])
+            .unwrap();
+        let entries = |int: &mut Interpreter, args: &[&str]| {
+            let (out, _, exit) = run_builtin(&Help, int, args, &[]);
+            let mut out = &out[..];
+            let mut entries = vec![];
+            while !out.is_empty() {
+                let entry: BTreeMap<String, Value> = ciborium::de::from_reader(&mut out).unwrap();
+                entries.push(entry);
+            }
+            (entries, exit)
+        };
+        let text = |s: &str| Value::Text(s.to_string());
+
+        // Every builtin has a summary, and utilities have the path they'd be run from.
+        let (all, exit) = entries(&mut int, &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+        let names: Vec<&Value> = all.iter().map(|e| &e["name"]).collect();
+        assert_eq!(names.len(), BUILTINS.len() + 2);
+        assert!(names.contains(&&text("help")));
+        assert!(all
+            .iter()
+            .all(|e| e["summary"] != Value::Null || e["path"] != Value::Null));
+
+        let (one, _) = entries(&mut int, &["stub"]);
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0]["kind"], text("monch"));
+        assert_eq!(one[0]["path"], text(dir.join("one/stub").to_str().unwrap()));
+
+        // Builtins hide utilities with the same name.
+        let (one, _) = entries(&mut int, &["cd"]);
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0]["kind"], text("builtin"));
+        assert_eq!(one[0]["summary"], text("change the current directory"));
+
+        let (none, exit) = entries(&mut int, &["nonexistent"]);
+        assert!(none.is_empty());
+        assert_eq!(exit, Exit::FAILURE);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
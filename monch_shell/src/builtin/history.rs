@@ -0,0 +1,110 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::io::Write;
+
+register_builtin!(
+    "history",
+    HistoryBuiltin,
+    "list or clear the lines typed at the prompt"
+);
+
+/// `history` outputs each command line typed at the prompt, oldest first, as a map like
+/// `{index, command}`. `history clear` forgets them all.
+pub struct HistoryBuiltin;
+
+impl exe::Execute for HistoryBuiltin {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => {
+                let text = |s: &str| Value::Text(s.to_string());
+                for (index, command) in (1..).zip(int.history().entries()) {
+                    let entry = Value::Map(vec![
+                        (text("index"), Value::Integer(index.into())),
+                        (text("command"), text(command)),
+                    ]);
+
+                    if let Err(e) = ciborium::ser::into_writer(&entry, &mut ios.stdout) {
+                        let _ = writeln!(ios.stderr, "monch: history: {}", e);
+                        exit!(Exit::FAILURE)
+                    }
+                }
+
+                exit!(Exit::SUCCESS)
+            }
+            ["clear"] => match int.history_mut().clear() {
+                Ok(()) => exit!(Exit::SUCCESS),
+                Err(e) => {
+                    let _ = writeln!(ios.stderr, "monch: history: {}", e);
+                    exit!(Exit::FAILURE)
+                }
+            },
+            _ => {
+                let _ = writeln!(
+                    ios.stderr,
+                    "monch: history: expected no arguments, or clear"
+                );
+                exit!(Exit::BAD_SYNTAX)
+            }
+        }
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        match args.len() {
+            0 => Ty::Cbor,
+            _ => Ty::Nothing,
+        }
+    }
+
+    fn is_synchronous(&self, args: &exe::Args) -> bool {
+        !args.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::run_builtin;
+    use std::collections::BTreeMap;
+    use std::env;
+
+    #[test]
+    fn history_as_data() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.history_mut().add("echo one").unwrap();
+        int.history_mut().add("ls | get name").unwrap();
+
+        let (out, _, exit) = run_builtin(&HistoryBuiltin, &mut int, &[], &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+
+        let mut entries = vec![];
+        let mut reader = &out[..];
+        while !reader.is_empty() {
+            let entry: BTreeMap<String, Value> = ciborium::de::from_reader(&mut reader).unwrap();
+            entries.push(entry);
+        }
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["index"], Value::Integer(1.into()));
+        assert_eq!(entries[0]["command"], Value::Text("echo one".into()));
+        assert_eq!(entries[1]["index"], Value::Integer(2.into()));
+        assert_eq!(entries[1]["command"], Value::Text("ls | get name".into()));
+
+        assert_eq!(
+            run_builtin(&HistoryBuiltin, &mut int, &["clear"], &[]).2,
+            Exit::SUCCESS
+        );
+        assert!(int.history().entries().is_empty());
+        assert_eq!(
+            run_builtin(&HistoryBuiltin, &mut int, &["x"], &[]).2,
+            Exit::BAD_SYNTAX
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::thread;
+
+register_builtin! {
+    ("last", Last, "pass on only the last few items"),
+    ("tail", Last, "pass on only the last few items, like `last`"),
+}
+
+/// `last [N]` passes on the last N items of its input, 10 by default, once it's all been read.
+/// It's also called `tail`, and takes the count like `last -n N` too.
+///
+/// It holds on to N items at a time, so `--bytes-limit BYTES` can be used to refuse items larger
+/// than BYTES, bounding how much memory it needs. With `-n +K`, it instead passes on every item
+/// from the Kth onwards, without holding on to any of them.
+pub struct Last;
+
+/// Which items `last` passes on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LastItems {
+    /// The final N items.
+    Last(usize),
+
+    /// Every item from the Kth, counting from 1.
+    From(usize),
+}
+
+impl Last {
+    /// Parse the arguments of a `last` invocation, returning which items to pass on and the
+    /// largest item allowed, or `None` if they don't make sense.
+    fn parse_args(args: &[String]) -> Option<(LastItems, usize)> {
+        let mut items = LastItems::Last(10);
+        let mut bytes_limit = monch_io::DEFAULT_MAX_ITEM_SIZE;
+
+        let mut args = args.iter().map(String::as_str);
+        let mut count_given = false;
+        while let Some(arg) = args.next() {
+            let count = match arg {
+                "--bytes-limit" => {
+                    bytes_limit = args.next()?.parse().ok()?;
+                    continue;
+                }
+                "-n" => args.next()?,
+                count => count,
+            };
+
+            if count_given {
+                return None;
+            }
+            count_given = true;
+            items = match count.strip_prefix('+') {
+                Some(first) => LastItems::From(first.parse().ok()?),
+                None => LastItems::Last(count.parse().ok()?),
+            };
+        }
+
+        Some((items, bytes_limit))
+    }
+}
+
+impl exe::Execute for Last {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let (items, bytes_limit) = match Last::parse_args(args) {
+            Some(parsed) => parsed,
+            None => {
+                let _ = writeln!(
+                    ios.stderr,
+                    "monch: last: expected a number of items, like `last [--bytes-limit BYTES] [-n] [+]N`"
+                );
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let mut parser =
+                monch_io::InputParser::<Value, _>::new(ios.stdin).with_max_item_size(bytes_limit);
+
+            // Fails with the exit to stop with, which is a success if nothing wants the rest.
+            let mut write = |value: &Value| {
+                let written = ciborium::ser::into_writer(value, &mut ios.stdout);
+                match written {
+                    Ok(()) => Ok(()),
+                    Err(ciborium::ser::Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {
+                        Err(Exit::SUCCESS)
+                    }
+                    Err(_) => Err(Exit::FAILURE),
+                }
+            };
+
+            let mut kept = VecDeque::new();
+
+            let mut read = 0;
+            while let Some(item) = parser.next() {
+                let item = match item {
+                    Ok(item) => item,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: last: {}", e);
+                        return Exit::FAILURE;
+                    }
+                };
+                read += 1;
+
+                // The header's read along with the first item, so pass it on before anything.
+                let header = parser.header().filter(|_| read == 1);
+                if let Err(exit) = header.map_or(Ok(()), |h| write(&h.to_value())) {
+                    return exit;
+                }
+
+                match items {
+                    LastItems::Last(0) => {}
+                    LastItems::Last(count) => {
+                        if kept.len() == count {
+                            kept.pop_front();
+                        }
+                        kept.push_back(item);
+                    }
+                    LastItems::From(first) if read >= first => {
+                        if let Err(exit) = write(&item) {
+                            return exit;
+                        }
+                    }
+                    LastItems::From(_) => {}
+                }
+            }
+
+            match kept.iter().try_for_each(write) {
+                Ok(()) => Exit::SUCCESS,
+                Err(exit) => exit,
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streams::stream_pipe;
+    use crate::testing::{run, scratch_dir};
+    use monch_syntax::Parser;
+    use std::fs;
+    use std::io::Read;
+
+    #[test]
+    fn last_items() {
+        let dir = scratch_dir("last_items");
+        let numbers = |range: std::ops::Range<u32>| {
+            let mut items = vec![];
+            for i in range {
+                ciborium::ser::into_writer(&Value::Integer(i.into()), &mut items).unwrap();
+            }
+            items
+        };
+        fs::write(dir.join("numbers.cbor"), numbers(0..100)).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let mut last = |args: &str| {
+            let line = format!("last {} <numbers.cbor", args);
+            let cmd = Parser::new().parse_command(&line).unwrap();
+            int.eval_command_capture(&cmd).unwrap()
+        };
+
+        assert_eq!(last("5"), (numbers(95..100), Exit::SUCCESS));
+        assert_eq!(last("").0, numbers(90..100));
+        assert_eq!(last("-n 1").0, numbers(99..100));
+        assert_eq!(last("0").0, numbers(0..0));
+
+        // Asking for more than there are gives all of them.
+        assert_eq!(last("1000").0, numbers(0..100));
+
+        // Counting from the start skips the ones before.
+        assert_eq!(last("-n +98").0, numbers(97..100));
+        assert_eq!(last("-n +1").0, numbers(0..100));
+        assert_eq!(last("-n +1000").0, numbers(0..0));
+
+        // Items can be limited in size, to bound how much is held at once.
+        fs::write(dir.join("numbers.cbor"), numbers(1000..1010)).unwrap();
+        assert_eq!(last("--bytes-limit 3 -n 2").0, numbers(1008..1010));
+        assert_eq!(last("--bytes-limit 2 -n 2").1, Exit::FAILURE);
+
+        assert_eq!(last("-n").1, Exit::BAD_SYNTAX);
+        assert_eq!(last("1 2").1, Exit::BAD_SYNTAX);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stops_when_output_closes() {
+        let dir = scratch_dir("last_stops_when_output_closes");
+        let (mut stderr, stderr_w) = stream_pipe().unwrap();
+        let ios = Streams {
+            stderr: stderr_w,
+            ..Streams::null()
+        };
+        let mut int = Interpreter::new(ios, &dir);
+        int.set_pipefail(true);
+
+        // Much more than fits in a pipe, whether it's kept until the end or passed on as it's read.
+        let mut items = vec![];
+        for i in 0..100_000u32 {
+            ciborium::ser::into_writer(&Value::Integer(i.into()), &mut items).unwrap();
+        }
+        fs::write(dir.join("numbers.cbor"), items).unwrap();
+
+        for (args, first) in [("100000", "0\n"), ("-n +2", "1\n")] {
+            let line = format!("last {} <numbers.cbor | first 1 | to json", args);
+            assert_eq!(run(&mut int, &[&line]), (first.into(), Exit::SUCCESS));
+            assert_eq!(
+                int.last_pipeline_status()[0],
+                ("last".to_string(), Exit::SUCCESS)
+            );
+        }
+
+        drop(int);
+        let mut errors = String::new();
+        stderr.read_to_string(&mut errors).unwrap();
+        assert_eq!(errors, "");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
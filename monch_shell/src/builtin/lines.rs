@@ -0,0 +1,218 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::io::{self, BufRead, Write};
+use std::thread;
+
+register_builtin!("lines", Lines, "split text into an item for each line");
+
+/// `lines` reads text, like the output of most programs, and writes each line as a text item,
+/// without its line ending, which can be `\n` or `\r\n`. The last line doesn't need one.
+/// `--trim` trims whitespace from each line, and `--skip-empty` leaves out lines which are empty,
+/// after trimming.
+///
+/// Lines which aren't valid UTF-8 have the invalid parts replaced, with a warning the first time,
+/// unless `--binary` asks for them to be written as bytes instead.
+pub struct Lines;
+
+/// How `lines` was asked to treat each line.
+#[derive(Debug, Default)]
+struct LinesArgs {
+    trim: bool,
+    skip_empty: bool,
+    binary: bool,
+}
+
+impl Lines {
+    /// Parse the arguments of a `lines` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<LinesArgs, String> {
+        let mut parsed = LinesArgs::default();
+        for arg in args {
+            match arg.as_str() {
+                "--trim" => parsed.trim = true,
+                "--skip-empty" => parsed.skip_empty = true,
+                "--binary" => parsed.binary = true,
+                other => return Err(format!("unexpected argument `{}`", other)),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+/// Remove the ASCII whitespace from either end of a line.
+fn trim(line: &[u8]) -> &[u8] {
+    let start = line.iter().position(|b| !b.is_ascii_whitespace());
+    let end = line.iter().rposition(|b| !b.is_ascii_whitespace());
+    match (start, end) {
+        (Some(start), Some(end)) => &line[start..=end],
+        _ => &[],
+    }
+}
+
+impl exe::Execute for Lines {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match Lines::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: lines: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let mut input = io::BufReader::new(ios.stdin);
+            let (mut line, mut item) = (vec![], vec![]);
+            let mut warned = false;
+
+            for number in 1.. {
+                line.clear();
+                match input.read_until(b'\n', &mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: lines: {}", e);
+                        return Exit::FAILURE;
+                    }
+                }
+
+                let mut text = line.strip_suffix(b"\n").unwrap_or(&line);
+                text = text.strip_suffix(b"\r").unwrap_or(text);
+                if args.trim {
+                    text = trim(text);
+                }
+                if args.skip_empty && text.is_empty() {
+                    continue;
+                }
+
+                let value = match std::str::from_utf8(text) {
+                    Ok(text) => Value::Text(text.to_string()),
+                    Err(_) if args.binary => Value::Bytes(text.to_vec()),
+                    Err(_) => {
+                        if !warned {
+                            let _ = writeln!(
+                                ios.stderr,
+                                "monch: lines: line {} isn't valid UTF-8, so invalid parts were \
+                                 replaced",
+                                number
+                            );
+                            warned = true;
+                        }
+                        Value::Text(String::from_utf8_lossy(text).into_owned())
+                    }
+                };
+
+                item.clear();
+                ciborium::ser::into_writer(&value, &mut item).expect("values can be encoded");
+                match ios.stdout.write_all(&item) {
+                    Ok(()) => {}
+
+                    // Nothing wants the rest.
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: lines: {}", e);
+                        return Exit::FAILURE;
+                    }
+                }
+            }
+
+            Exit::SUCCESS
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Any
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_builtin, run_with_input, scratch_dir};
+    use std::{env, fs};
+
+    #[test]
+    fn lines_of_text() {
+        let dir = scratch_dir("lines_of_text");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut lines = |args: &str, input: &[u8]| {
+            run_with_input(&mut int, &format!("lines {} | to json", args), input)
+        };
+        let ok = |s: &str| (s.to_string(), Exit::SUCCESS);
+
+        // The last line doesn't need a line ending, and either kind is removed.
+        assert_eq!(lines("", b"one\ntwo\n"), ok("\"one\"\n\"two\"\n"));
+        assert_eq!(lines("", b"one\ntwo"), ok("\"one\"\n\"two\"\n"));
+        assert_eq!(lines("", b"one\r\ntwo\r\n"), ok("\"one\"\n\"two\"\n"));
+        assert_eq!(lines("", b""), ok(""));
+        assert_eq!(lines("", b"\n"), ok("\"\"\n"));
+
+        let spaced = b" one \n\n  \ntwo\r\n";
+        assert_eq!(lines("", spaced), ok("\" one \"\n\"\"\n\"  \"\n\"two\"\n"));
+        assert_eq!(
+            lines("--trim", spaced),
+            ok("\"one\"\n\"\"\n\"\"\n\"two\"\n")
+        );
+        assert_eq!(
+            lines("--skip-empty", spaced),
+            ok("\" one \"\n\"  \"\n\"two\"\n")
+        );
+        assert_eq!(
+            lines("--trim --skip-empty", spaced),
+            ok("\"one\"\n\"two\"\n")
+        );
+
+        assert_eq!(lines("--sort", b"").1, Exit::BAD_SYNTAX);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn lines_of_invalid_utf8() {
+        let lines = |args: &[&str]| {
+            let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+            let input = b"ok\nbad \xff\nworse \xfe\xfe\n";
+            let (out, err, exit) = run_builtin(&Lines, &mut int, args, input);
+            assert_eq!(exit, Exit::SUCCESS);
+
+            let items: Vec<Value> = monch_io::InputParser::new(&out[..])
+                .map(Result::unwrap)
+                .collect();
+            (items, err)
+        };
+
+        // Invalid parts are replaced, with one warning.
+        let text = |s: &str| Value::Text(s.to_string());
+        assert_eq!(
+            lines(&[]),
+            (
+                vec![
+                    text("ok"),
+                    text("bad \u{fffd}"),
+                    text("worse \u{fffd}\u{fffd}")
+                ],
+                "monch: lines: line 2 isn't valid UTF-8, so invalid parts were replaced\n".into()
+            )
+        );
+
+        // Or they're kept as bytes, without one.
+        let bytes = |b: &[u8]| Value::Bytes(b.to_vec());
+        assert_eq!(
+            lines(&["--binary"]),
+            (
+                vec![text("ok"), bytes(b"bad \xff"), bytes(b"worse \xfe\xfe")],
+                String::new()
+            )
+        );
+    }
+}
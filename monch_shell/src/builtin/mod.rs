@@ -0,0 +1,330 @@
+//! The builtin commands, which run inside the shell instead of as programs of their own. Each
+//! one is in a module of its own, which registers it with [`register_builtin!`].
+
+use crate::streams::WriteStream;
+use crate::{exe, Exit, Streams};
+use ciborium::value::Value;
+use lazy_static::lazy_static;
+use monch_io::StreamHeader;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::thread;
+
+/// A builtin command, with a short description of what it does.
+#[derive(Clone, Copy)]
+pub struct Builtin {
+    pub exe: &'static (dyn exe::Execute + Sync),
+    pub summary: &'static str,
+}
+
+/// Register the builtins a module provides, each with its name and a short summary of what it
+/// does, like `register_builtin!("cd", Cd, "change the current directory")`. A module with more
+/// than one, like aliases, lists them as tuples instead.
+///
+/// The builtins are `'static` instances, so they're never allocated. Every module's builtins are
+/// collected into [`BUILTINS`] by [`builtin_modules!`].
+macro_rules! register_builtin {
+    ($name:literal, $exe:expr, $summary:literal) => {
+        register_builtin! { ($name, $exe, $summary) }
+    };
+
+    ($(($name:literal, $exe:expr, $summary:literal)),+ $(,)?) => {
+        pub(super) const REGISTERED: &[(&str, super::Builtin)] = &[$((
+            $name,
+            super::Builtin {
+                exe: &$exe,
+                summary: $summary,
+            },
+        )),+];
+    };
+}
+
+/// Convenience macro to return an immediate exit code from the [`Execute`] impl of a builtin.
+macro_rules! exit {
+    ($code:literal) => {
+        exit!($crate::exe::Exit::Code(literal))
+    };
+
+    ($exit:expr) => {
+        return {
+            let improc = $crate::exe::ImmediateProc($exit);
+            Ok(Box::new(improc))
+        }
+    };
+}
+
+/// Declare the modules holding builtins, and collect the builtins they register.
+macro_rules! builtin_modules {
+    ($($module:ident),+ $(,)?) => {
+        $(mod $module;)+
+
+        /// The builtins registered by each module.
+        const REGISTERED: &[&[(&str, Builtin)]] = &[$($module::REGISTERED),+];
+    };
+}
+
+builtin_modules! {
+    cd,
+    count,
+    dirs,
+    echo,
+    enumerate,
+    exec,
+    exit,
+    explain,
+    export,
+    r#false,
+    first,
+    flatten,
+    from,
+    group_by,
+    hash,
+    help,
+    history,
+    last,
+    lines,
+    monch_path,
+    popd,
+    pushd,
+    pwd,
+    read,
+    reduce,
+    select,
+    seq,
+    set,
+    sleep,
+    sort_by,
+    status,
+    tee,
+    throttle,
+    timeout,
+    to,
+    r#true,
+    r#type,
+    uniq,
+    unset,
+    r#where,
+    which,
+}
+
+mod format;
+mod number;
+
+pub(crate) use to::To;
+
+lazy_static! {
+    /// Every builtin, by name.
+    pub static ref BUILTINS: BTreeMap<&'static str, Builtin> =
+        REGISTERED.iter().flat_map(|builtins| builtins.iter().copied()).collect();
+}
+
+/// Returns whether `name` can be used as a variable name: letters, digits, and underscores, not
+/// starting with a digit.
+fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Run a builtin which transforms each item of its input on its own, like `where` or `select`,
+/// in a worker thread. `map` is called with the index of each item and the item itself, and
+/// every item it returns is written out. It can warn about things on the stderr it's passed, but
+/// if it returns an error, that's reported and the builtin fails.
+///
+/// The stream header, if there is one, is passed through `map_header` first, which can describe
+/// the items as they are now, or leave the header out.
+fn map_items<I, F>(
+    name: &'static str,
+    mut ios: Streams,
+    map_header: impl FnOnce(&StreamHeader) -> Option<StreamHeader> + Send + 'static,
+    mut map: F,
+) -> Box<dyn exe::Wait>
+where
+    I: IntoIterator<Item = Value>,
+    F: FnMut(u64, Value, &mut WriteStream) -> Result<I, String> + Send + 'static,
+{
+    let worker = thread::spawn(move || {
+        let mut parser = monch_io::InputParser::<Value, _>::new(ios.stdin)
+            .with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+
+        let mut map_header = Some(map_header);
+        let mut index = 0;
+        while let Some(item) = parser.next() {
+            let item = match item {
+                Ok(item) => item,
+                Err(e) => {
+                    let _ = writeln!(ios.stderr, "monch: {}: {}", name, e);
+                    return Exit::FAILURE;
+                }
+            };
+
+            // The header's read along with the first item, so write it before anything else.
+            let header = match (parser.header(), map_header.take()) {
+                (Some(header), Some(map_header)) => map_header(header),
+                _ => None,
+            };
+
+            let mapped = match map(index, item, &mut ios.stderr) {
+                Ok(mapped) => mapped,
+                Err(e) => {
+                    let _ = writeln!(ios.stderr, "monch: {}: {}", name, e);
+                    return Exit::FAILURE;
+                }
+            };
+            index += 1;
+
+            let written = header
+                .map(|h| h.to_value())
+                .into_iter()
+                .chain(mapped)
+                .try_for_each(|value| ciborium::ser::into_writer(&value, &mut ios.stdout));
+            match written {
+                Ok(()) => {}
+
+                // Nothing wants the rest.
+                Err(ciborium::ser::Error::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => break,
+                Err(_) => return Exit::FAILURE,
+            }
+        }
+
+        Exit::SUCCESS
+    });
+
+    Box::new(worker)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exe::Execute;
+    use crate::streams::stream_pipe;
+    use crate::testing::{encode, run_streams, scratch_dir};
+    use crate::Interpreter;
+    use std::collections::BTreeSet;
+    use std::fs;
+    use std::io::Read;
+
+    #[test]
+    fn names_are_unique() {
+        // Collecting into the map would quietly keep only the last of two with the same name.
+        let mut names = BTreeSet::new();
+        for (name, _) in REGISTERED.iter().copied().flatten() {
+            assert!(names.insert(name), "`{}` is registered twice", name);
+        }
+
+        assert_eq!(names.len(), BUILTINS.len());
+    }
+
+    #[test]
+    fn every_builtin() {
+        let dir = scratch_dir("every_builtin");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_var("HOME", dir.to_string_lossy());
+        let no_args = exe::Args::new();
+
+        for (name, Builtin { exe: builtin, .. }) in BUILTINS.iter() {
+            // Types only depend on the arguments.
+            let types = (builtin.input_type(&no_args), builtin.output_type(&no_args));
+            assert_eq!(
+                types,
+                (builtin.input_type(&no_args), builtin.output_type(&no_args)),
+                "{}",
+                name
+            );
+
+            // Running it without arguments either works, or fails cleanly.
+            let proc = builtin.execute(&mut int, Streams::null(), &no_args);
+            if let Ok(proc) = proc {
+                proc.wait().unwrap();
+            }
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn map_items_per_item() {
+        /// Run `map_items` over some items, returning what it writes to stdout and stderr, and
+        /// its exit.
+        fn run<I, F>(
+            items: &[Value],
+            header: Option<StreamHeader>,
+            map_header: impl FnOnce(&StreamHeader) -> Option<StreamHeader> + Send + 'static,
+            map: F,
+        ) -> (Vec<u8>, String, Exit)
+        where
+            I: IntoIterator<Item = Value>,
+            F: FnMut(u64, Value, &mut WriteStream) -> Result<I, String> + Send + 'static,
+        {
+            let mut input = vec![];
+            if let Some(header) = header {
+                monch_io::write_header_to(&mut input, &header).unwrap();
+            }
+            input.extend(encode(items));
+
+            run_streams(&input, |ios| map_items("test", ios, map_header, map))
+        }
+
+        let number = |n: u64| Value::Integer(n.into());
+        let numbers = [number(1), number(2), number(3)];
+
+        // Each item can turn into any number of items, and knows where it was.
+        let repeat = |index, item, _: &mut WriteStream| Ok(vec![item; index as usize]);
+        assert_eq!(
+            run(&numbers, None, |_| None, repeat),
+            (
+                encode(&[number(2), number(3), number(3)]),
+                String::new(),
+                Exit::SUCCESS
+            )
+        );
+
+        // The header's changed before the first item, even if that's dropped.
+        let header = StreamHeader::new("thing", &["a"]);
+        let rename = |h: &StreamHeader| Some(StreamHeader::new(h.type_name.clone(), &["b"]));
+        let drop_first = |index, item, _: &mut WriteStream| Ok(Some(item).filter(|_| index > 0));
+        let mut expected = vec![];
+        monch_io::write_header_to(&mut expected, &StreamHeader::new("thing", &["b"])).unwrap();
+        expected.extend(encode(&numbers[1..]));
+        assert_eq!(run(&numbers, Some(header), rename, drop_first).0, expected);
+
+        // Errors stop everything, but warnings don't.
+        let fail = |index, item, stderr: &mut WriteStream| {
+            writeln!(stderr, "warning {}", index).unwrap();
+            match index {
+                1 => Err("broken".to_string()),
+                _ => Ok(Some(item)),
+            }
+        };
+        assert_eq!(
+            run(&numbers, None, |_| None, fail),
+            (
+                encode(&numbers[..1]),
+                "warning 0\nwarning 1\nmonch: test: broken\n".into(),
+                Exit::FAILURE
+            )
+        );
+
+        // If nothing's reading the output, it stops without that being a failure.
+        let (stdin, mut stdin_w) = stream_pipe().unwrap();
+        let (stdout, stdout_w) = stream_pipe().unwrap();
+        let (mut stderr, stderr_w) = stream_pipe().unwrap();
+        stdin_w.write_all(&encode(&numbers)).unwrap();
+        drop((stdin_w, stdout));
+        let ios = Streams {
+            stdin,
+            stdout: stdout_w,
+            stderr: stderr_w,
+        };
+        let pass = |_, item, _: &mut WriteStream| Ok(Some(item));
+        let exit = map_items("test", ios, |_| None, pass).wait().unwrap();
+        assert_eq!(exit, Exit::SUCCESS);
+        let mut errors = String::new();
+        stderr.read_to_string(&mut errors).unwrap();
+        assert_eq!(errors, "");
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+use std::path::Path;
+
+register_builtin!(
+    "monch-path",
+    MonchPath,
+    "list or change where monch utilities are found"
+);
+
+/// `monch-path list` outputs the directories searched for monch utilities, as an array of
+/// strings. `monch-path add DIR` searches another directory after those, and
+/// `monch-path remove DIR` stops searching one.
+pub struct MonchPath;
+
+impl exe::Execute for MonchPath {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let mut dirs = int.monch_path();
+
+        match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["list"] => {
+                let dirs: Vec<String> = dirs
+                    .iter()
+                    .map(|dir| dir.to_string_lossy().to_string())
+                    .collect();
+
+                if let Err(e) = ciborium::ser::into_writer(&dirs, &mut ios.stdout) {
+                    let _ = writeln!(ios.stderr, "monch: monch-path: {}", e);
+                    exit!(Exit::FAILURE)
+                }
+
+                exit!(Exit::SUCCESS)
+            }
+            ["add", dir] => {
+                let dir = int.current_dir().join(dir);
+                if !dir.is_dir() {
+                    let _ = writeln!(
+                        ios.stderr,
+                        "monch: monch-path: '{}': not a directory",
+                        dir.display()
+                    );
+                    exit!(Exit::FAILURE)
+                }
+
+                if !dirs.contains(&dir) {
+                    dirs.push(dir);
+                }
+            }
+            ["remove", dir] => {
+                // Match it as it was given, or relative to the current directory.
+                let relative = int.current_dir().join(dir);
+                let before = dirs.len();
+                dirs.retain(|d| d != Path::new(dir) && d != &relative);
+
+                if dirs.len() == before {
+                    let _ = writeln!(
+                        ios.stderr,
+                        "monch: monch-path: '{}': not on MONCH_PATH",
+                        dir
+                    );
+                    exit!(Exit::FAILURE)
+                }
+            }
+            _ => {
+                let _ = writeln!(
+                    ios.stderr,
+                    "monch: monch-path: expected `list`, `add DIR`, or `remove DIR`"
+                );
+                exit!(Exit::BAD_SYNTAX)
+            }
+        }
+
+        if let Err(e) = int.set_monch_path(&dirs) {
+            let _ = writeln!(ios.stderr, "monch: monch-path: {}", e);
+            exit!(Exit::FAILURE)
+        }
+
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        match args.first().map(String::as_str) {
+            Some("list") => Ty::Cbor,
+            _ => Ty::Nothing,
+        }
+    }
+
+    fn is_synchronous(&self, args: &exe::Args) -> bool {
+        !matches!(args.first().map(String::as_str), Some("list"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run, run_builtin, scratch_dir};
+    use std::fs;
+
+    #[test]
+    #[cfg(unix)]
+    fn monch_path_changes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("monch_path_changes");
+        for name in ["one", "two"] {
+            fs::create_dir(dir.join(name)).unwrap();
+            let stub = dir.join(name).join("stub");
+            fs::write(&stub, format!("#!/bin/sh\necho {}\n", name)).unwrap();
+            fs::set_permissions(&stub, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.unset_var("MONCH_PATH");
+        let listed = |int: &mut Interpreter| {
+            let (out, _, exit) = run_builtin(&MonchPath, int, &["list"], &[]);
+            assert_eq!(exit, Exit::SUCCESS);
+            ciborium::de::from_reader::<Vec<String>, _>(&out[..]).unwrap()
+        };
+        assert!(listed(&mut int).is_empty());
+
+        // Directories are searched in the order they were added, and children see them too.
+        let (out, _) = run(
+            &mut int,
+            &["monch-path add one", "monch-path add two", "stub"],
+        );
+        assert_eq!(out, "one\n");
+        let both = [dir.join("one"), dir.join("two")].map(|d| d.to_string_lossy().to_string());
+        assert_eq!(listed(&mut int), both);
+        let (out, _) = run(&mut int, &["env"]);
+        assert!(out.contains(&format!("MONCH_PATH={}:{}", both[0], both[1])));
+
+        // Removing one takes effect straight away, even though the stub was found there before.
+        let (out, _) = run(&mut int, &["monch-path remove one", "stub"]);
+        assert_eq!(out, "two\n");
+        assert_eq!(listed(&mut int), [both[1].clone()]);
+
+        let fails =
+            |int: &mut Interpreter, args: &[&str]| run_builtin(&MonchPath, int, args, &[]).2;
+        assert_eq!(fails(&mut int, &["remove", "one"]), Exit::FAILURE);
+        assert_eq!(fails(&mut int, &["add", "nowhere"]), Exit::FAILURE);
+        assert_eq!(fails(&mut int, &["frobnicate"]), Exit::BAD_SYNTAX);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
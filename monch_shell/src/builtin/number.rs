@@ -0,0 +1,95 @@
+use ciborium::value::Value;
+use std::cmp::Ordering;
+
+/// A number from an item, which compares with any other number. Integers are kept exact, as
+/// long as they fit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Number {
+    Integer(i128),
+    Float(f64),
+}
+
+impl Number {
+    /// The number in a value, if it's an integer or a float.
+    pub(super) fn of(value: &Value) -> Option<Number> {
+        match value {
+            Value::Integer(i) => Some(Number::Integer(i128::from(*i))),
+            Value::Float(f) => Some(Number::Float(*f)),
+            _ => None,
+        }
+    }
+
+    /// Parse text that looks like a number, ignoring any whitespace around it.
+    pub(super) fn parse(text: &str) -> Option<Number> {
+        let text = text.trim();
+        text.parse()
+            .map(Number::Integer)
+            .or_else(|_| text.parse().map(Number::Float))
+            .ok()
+    }
+
+    pub(super) fn as_f64(self) -> f64 {
+        match self {
+            Number::Integer(i) => i as f64,
+            Number::Float(f) => f,
+        }
+    }
+
+    /// Add two numbers, which stay integers unless they're too big.
+    pub(super) fn add(self, other: Number) -> Number {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => match a.checked_add(b) {
+                Some(sum) => Number::Integer(sum),
+                None => Number::Float(a as f64 + b as f64),
+            },
+            _ => Number::Float(self.as_f64() + other.as_f64()),
+        }
+    }
+
+    /// Convert the number back into a value, as a float if it's an integer too big for CBOR.
+    pub(super) fn to_value(self) -> Value {
+        match self {
+            Number::Integer(i) => {
+                ciborium::value::Integer::try_from(i).map_or(Value::Float(i as f64), Value::Integer)
+            }
+            Number::Float(f) => Value::Float(f),
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            // Compare integers exactly, since they might not fit in a float.
+            (Number::Integer(a), Number::Integer(b)) => a.partial_cmp(b),
+
+            // NaN sorts after every other number.
+            _ => {
+                let (a, b) = (self.as_f64(), other.as_f64());
+                Some(
+                    a.partial_cmp(&b)
+                        .unwrap_or_else(|| a.is_nan().cmp(&b.is_nan())),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_promotes_on_overflow() {
+        let sum = Number::Integer(i128::MAX).add(Number::Integer(1));
+        assert_eq!(sum, Number::Float(i128::MAX as f64 + 1.0));
+        assert_eq!(
+            Number::Integer(2).add(Number::Float(0.5)),
+            Number::Float(2.5)
+        );
+        assert_eq!(
+            Number::Integer(2).add(Number::Integer(-3)),
+            Number::Integer(-1)
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+
+register_builtin!("popd", Popd, "go back to the directory on top of the stack");
+
+/// `popd` changes back to the directory on top of the directory stack, and removes it.
+pub struct Popd;
+
+impl exe::Execute for Popd {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        if !args.is_empty() {
+            let _ = writeln!(ios.stderr, "monch: popd: too many arguments");
+            exit!(Exit::FAILURE)
+        }
+
+        match int.pop_dir() {
+            Ok(Some(_)) => exit!(Exit::SUCCESS),
+            Ok(None) => {
+                let _ = writeln!(ios.stderr, "monch: popd: directory stack empty");
+                exit!(Exit::FAILURE)
+            }
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: popd: {}", e);
+                exit!(Exit::FAILURE)
+            }
+        }
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn is_synchronous(&self, _: &exe::Args) -> bool {
+        true
+    }
+}
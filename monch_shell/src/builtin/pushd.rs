@@ -0,0 +1,47 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+
+register_builtin!(
+    "pushd",
+    Pushd,
+    "change directory, keeping the old one on the stack"
+);
+
+/// `pushd DIR` saves the current directory on the directory stack, and changes to `DIR`.
+pub struct Pushd;
+
+impl exe::Execute for Pushd {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let dir = if let [ref dir] = args[..] {
+            dir
+        } else {
+            let _ = writeln!(ios.stderr, "monch: pushd: expected one directory");
+            exit!(Exit::FAILURE)
+        };
+
+        match int.push_dir(dir) {
+            Ok(()) => exit!(Exit::SUCCESS),
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: pushd: {}: {}", dir, e);
+                exit!(Exit::FAILURE)
+            }
+        }
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn is_synchronous(&self, _: &exe::Args) -> bool {
+        true
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+
+register_builtin!("pwd", Pwd, "write the current directory");
+
+/// `pwd` writes the current directory as a CBOR text item, or as a line of plain text with
+/// `--raw`.
+pub struct Pwd;
+
+impl exe::Execute for Pwd {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        // This is the shell's directory, which isn't necessarily the process's.
+        let dir = int.current_dir().to_string_lossy().to_string();
+
+        let written = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [] => ciborium::ser::into_writer(&dir, &mut ios.stdout).map_err(|e| e.to_string()),
+            ["--raw"] => writeln!(ios.stdout, "{}", dir).map_err(|e| e.to_string()),
+            _ => {
+                let _ = writeln!(ios.stderr, "monch: pwd: expected no arguments, or --raw");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        if let Err(e) = written {
+            let _ = writeln!(ios.stderr, "monch: pwd: {}", e);
+            exit!(Exit::FAILURE)
+        }
+
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["--raw"] => Ty::Text,
+            _ => Ty::Cbor,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_builtin, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn pwd_is_the_shells() {
+        let dir = scratch_dir("pwd_is_the_shells");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let path = dir.to_str().unwrap();
+
+        // A text item: major type 3, with the length in the following bytes.
+        let (out, _, exit) = run_builtin(&Pwd, &mut int, &[], &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+        let mut expected = vec![0x78, path.len() as u8];
+        expected.extend_from_slice(path.as_bytes());
+        assert_eq!(out, expected);
+
+        let (out, _, _) = run_builtin(&Pwd, &mut int, &["--raw"], &[]);
+        assert_eq!(out, format!("{}\n", path).as_bytes());
+
+        let (_, err, exit) = run_builtin(&Pwd, &mut int, &["-x"], &[]);
+        assert_eq!(exit, Exit::BAD_SYNTAX);
+        assert!(err.contains("expected no arguments"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,185 @@
+use super::is_var_name;
+use crate::cbor_display::format_cbor_opts;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::io::{self, Write};
+
+register_builtin!(
+    "read",
+    Read,
+    "read a line or an item from input into a variable"
+);
+
+/// `read NAME` reads a line from stdin into a variable. `read --cbor NAME` reads one CBOR item
+/// instead, and stores it as text, the way `to tty` would show it.
+pub struct Read;
+
+impl Read {
+    /// Read a line, without reading past it, so that whatever's next is left for someone else.
+    /// Returns `None` if there's nothing left to read.
+    fn read_line(stdin: &mut impl io::Read) -> io::Result<Option<String>> {
+        let mut line = vec![];
+        let mut byte = [0];
+        loop {
+            match stdin.read(&mut byte) {
+                Ok(0) if line.is_empty() => return Ok(None),
+                Ok(0) => break,
+                Ok(_) if byte[0] == b'\n' => break,
+                Ok(_) => line.push(byte[0]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(Some(String::from_utf8_lossy(&line).to_string()))
+    }
+
+    /// Read one CBOR item, skipping any stream header before it, and describe it as plain text.
+    /// This reads no further than the item, like [`Read::read_line`].
+    fn read_item(stdin: &mut impl io::Read) -> Result<String, monch_io::Error> {
+        let item: Value = monch_io::read_one_from(stdin)?;
+
+        let mut text = vec![];
+        format_cbor_opts(&mut text, &item, false)?;
+        Ok(String::from_utf8_lossy(&text).to_string())
+    }
+}
+
+impl exe::Execute for Read {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let (cbor, name) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            ["--cbor", name] => (true, name),
+            [name] => (false, name),
+            _ => {
+                let _ = writeln!(ios.stderr, "monch: read: expected [--cbor] NAME");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        if !is_var_name(name) {
+            let _ = writeln!(ios.stderr, "monch: read: '{}': not a valid name", name);
+            exit!(Exit::FAILURE)
+        }
+
+        let value = match cbor {
+            true => Read::read_item(&mut ios.stdin).map_err(|e| e.to_string()),
+            false => match Read::read_line(&mut ios.stdin) {
+                Ok(Some(line)) => Ok(line),
+
+                // Like in Bash, running out of input is a failure, which ends `while read` loops.
+                Ok(None) => exit!(Exit::FAILURE),
+                Err(e) => Err(e.to_string()),
+            },
+        };
+
+        match value {
+            Ok(value) => {
+                int.set_var(name, value);
+                exit!(Exit::SUCCESS)
+            }
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: read: {}", e);
+                exit!(Exit::FAILURE)
+            }
+        }
+    }
+
+    fn input_type(&self, args: &exe::Args) -> Ty {
+        match args.first().map(String::as_str) {
+            Some("--cbor") => Ty::Cbor,
+            _ => Ty::Any,
+        }
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn is_synchronous(&self, _: &exe::Args) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::streams::{stream_pipe, ReadStream, WriteStream};
+    use crate::testing::run;
+    use monch_syntax::Parser;
+    use std::env;
+
+    #[test]
+    fn read_into_variables() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+
+        let stdin = |write: &dyn Fn(&mut WriteStream)| {
+            let (stdin, mut stdin_w) = stream_pipe().unwrap();
+            write(&mut stdin_w);
+            stdin
+        };
+        let read = |int: &mut Interpreter, stdin: &ReadStream, args: &[&str]| {
+            let ios = Streams {
+                stdin: stdin.try_clone().unwrap(),
+                stdout: WriteStream::Null,
+                stderr: WriteStream::Null,
+            };
+            let args: exe::Args = args.iter().map(|a| a.to_string()).collect();
+            exe::Execute::execute(&Read, int, ios, &args)
+                .unwrap()
+                .wait()
+                .unwrap()
+        };
+
+        // Each read takes what it needs from the same stream, and leaves the rest.
+        let lines = stdin(&|w| {
+            writeln!(w, "first line").unwrap();
+            ciborium::ser::into_writer(&("name", 3), &mut *w).unwrap();
+            write!(w, "last").unwrap();
+        });
+        assert_eq!(read(&mut int, &lines, &["FIRST"]), Exit::SUCCESS);
+        assert_eq!(read(&mut int, &lines, &["--cbor", "ITEM"]), Exit::SUCCESS);
+        assert_eq!(read(&mut int, &lines, &["LAST"]), Exit::SUCCESS);
+        assert_eq!(read(&mut int, &lines, &["AFTER"]), Exit::FAILURE);
+        assert_eq!(int.get_var("FIRST"), Some("first line"));
+        assert_eq!(int.get_var("ITEM"), Some("[name, 3]"));
+        assert_eq!(int.get_var("LAST"), Some("last"));
+        assert_eq!(int.get_var("AFTER"), None);
+
+        // A stream header, like `ls` writes, isn't an item, and the one after it is left alone.
+        let headered = stdin(&|w| {
+            let header = monch_io::StreamHeader::new("entry", &["name"]);
+            monch_io::write_header_to(&mut *w, &header).unwrap();
+            monch_io::write_all_to(&mut *w, ["first", "second"]).unwrap();
+        });
+        assert_eq!(read(&mut int, &headered, &["--cbor", "ONE"]), Exit::SUCCESS);
+        assert_eq!(read(&mut int, &headered, &["--cbor", "TWO"]), Exit::SUCCESS);
+        assert_eq!(int.get_var("ONE"), Some("first"));
+        assert_eq!(int.get_var("TWO"), Some("second"));
+
+        let text = stdin(&|w| write!(w, "not cbor").unwrap());
+        assert_eq!(read(&mut int, &text, &["--cbor", "BAD"]), Exit::FAILURE);
+        assert_eq!(int.get_var("BAD"), None);
+        assert_eq!(read(&mut int, &text, &["not-a-name"]), Exit::FAILURE);
+        assert_eq!(read(&mut int, &text, &[]), Exit::BAD_SYNTAX);
+
+        // At the end of a pipeline, it reads what comes before.
+        let (_, exit) = run(&mut int, &["set | read --cbor OPTIONS"]);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert!(int.get_var("OPTIONS").unwrap().contains("errexit: false"));
+
+        // Anywhere else, what it read would be lost.
+        let cmd = Parser::new()
+            .parse_command("read --cbor X | to tty")
+            .unwrap();
+        let err = int.eval_command(&cmd).unwrap_err();
+        assert_eq!(err.as_exit(), Exit::BAD_SYNTAX);
+        assert!(err
+            .to_string()
+            .starts_with("read: can't be used in a pipeline"));
+    }
+}
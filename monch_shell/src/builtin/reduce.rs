@@ -0,0 +1,253 @@
+use super::number::Number;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::DataPath;
+use std::io::Write;
+use std::thread;
+
+register_builtin! {
+    ("avg", Reduce(Reduction::Avg), "average the numbers in the input"),
+    ("max", Reduce(Reduction::Max), "find the largest number in the input"),
+    ("min", Reduce(Reduction::Min), "find the smallest number in the input"),
+    ("sum", Reduce(Reduction::Sum), "add up the numbers in the input"),
+}
+
+/// `sum`, `min`, `max`, and `avg` read their whole input, and write a single number: the total,
+/// the smallest, the largest, or the average of the numbers in it. Integers are added up exactly,
+/// and only become floats if a float is added to them, or if they get too big.
+///
+/// With `-f PATH`, the numbers are found at PATH in each item. Items without a number are skipped,
+/// and how many were skipped is noted on stderr. Without any numbers at all, `sum` writes 0, and
+/// the others write `null`, or fail if they're given `--strict`.
+pub struct Reduce(Reduction);
+
+/// What a [`Reduce`] works out from the numbers in its input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reduction {
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl Reduction {
+    /// The name of the builtin which does this.
+    fn name(self) -> &'static str {
+        match self {
+            Reduction::Sum => "sum",
+            Reduction::Min => "min",
+            Reduction::Max => "max",
+            Reduction::Avg => "avg",
+        }
+    }
+
+    /// Combine the result so far with the next number. For `avg`, that's the total.
+    fn combine(self, acc: Number, n: Number) -> Number {
+        match self {
+            Reduction::Sum | Reduction::Avg => acc.add(n),
+            Reduction::Min if n < acc => n,
+            Reduction::Max if n > acc => n,
+            Reduction::Min | Reduction::Max => acc,
+        }
+    }
+}
+
+/// Where a [`Reduce`] finds its numbers, and what it does without any.
+#[derive(Debug, Default)]
+struct ReduceArgs {
+    field: Option<DataPath>,
+    strict: bool,
+}
+
+impl Reduce {
+    /// Parse the arguments of a `sum`, `min`, `max`, or `avg` invocation, or describe what's wrong
+    /// with them.
+    fn parse_args(args: &[String]) -> Result<ReduceArgs, String> {
+        let mut parsed = ReduceArgs::default();
+
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            match arg {
+                "--strict" => parsed.strict = true,
+                "-f" | "--field" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| format!("`{}` needs a path", arg))?;
+                    let path = DataPath::parse_strict(path)
+                        .map_err(|e| format!("`{}` isn't a path: {}", path, e))?;
+                    parsed.field = Some(path);
+                }
+                other => return Err(format!("unexpected argument `{}`", other)),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl exe::Execute for Reduce {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let reduction = self.0;
+        let name = reduction.name();
+        let args = match Reduce::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: {}: {}", name, e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let parser = monch_io::InputParser::<Value, _>::new(ios.stdin)
+                .with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+
+            let mut result: Option<Number> = None;
+            let (mut count, mut skipped) = (0u64, 0u64);
+
+            for item in parser {
+                let item = match item {
+                    Ok(item) => item,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: {}: {}", name, e);
+                        return Exit::FAILURE;
+                    }
+                };
+
+                let number = match &args.field {
+                    None => Number::of(&item),
+                    Some(path) => path.get_ref(&item).and_then(Number::of),
+                };
+                let number = match number {
+                    Some(number) => number,
+                    None => {
+                        skipped += 1;
+                        continue;
+                    }
+                };
+
+                count += 1;
+                result = Some(match result {
+                    None => number,
+                    Some(acc) => reduction.combine(acc, number),
+                });
+            }
+
+            if skipped > 0 {
+                let _ = writeln!(
+                    ios.stderr,
+                    "monch: {}: skipped {} of {} items, which weren't numbers",
+                    name,
+                    skipped,
+                    count + skipped
+                );
+            }
+
+            let value = match (reduction, result) {
+                (Reduction::Sum, None) => Value::Integer(0.into()),
+                (Reduction::Avg, Some(total)) => Value::Float(total.as_f64() / count as f64),
+                (_, Some(n)) => n.to_value(),
+                (_, None) if args.strict => {
+                    let _ = writeln!(ios.stderr, "monch: {}: there weren't any numbers", name);
+                    return Exit::FAILURE;
+                }
+                (_, None) => Value::Null,
+            };
+
+            match ciborium::ser::into_writer(&value, &mut ios.stdout) {
+                Ok(()) => Exit::SUCCESS,
+                Err(_) => Exit::FAILURE,
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{encode, run_builtin, run_with_input, scratch_dir};
+    use std::{env, fs};
+
+    #[test]
+    fn reduce_numbers() {
+        let dir = scratch_dir("reduce_numbers");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut reduce = |line: &str, items: &[Value]| {
+            run_with_input(&mut int, &format!("{} | to json", line), &encode(items))
+        };
+
+        let number = |n: i64| Value::Integer(n.into());
+        let ok = |s: &str| (format!("{}\n", s), Exit::SUCCESS);
+        let numbers = [number(3), number(-4), number(10)];
+        assert_eq!(reduce("sum", &numbers), ok("9"));
+        assert_eq!(reduce("min", &numbers), ok("-4"));
+        assert_eq!(reduce("max", &numbers), ok("10"));
+        assert_eq!(reduce("avg", &numbers), ok("3.0"));
+
+        // Integers too big for CBOR become a float, rather than wrapping around.
+        let big = Value::Integer(u64::MAX.into());
+        assert_eq!(
+            reduce("sum", &[big.clone(), number(1)]),
+            ok("1.8446744073709552e19")
+        );
+        assert_eq!(reduce("max", &[big, number(1)]), ok("18446744073709551615"));
+
+        // Floats make the total a float, and compare with integers.
+        let mixed = [number(1), Value::Float(0.5), number(2)];
+        assert_eq!(reduce("sum", &mixed), ok("3.5"));
+        assert_eq!(reduce("min", &mixed), ok("0.5"));
+        assert_eq!(reduce("max", &mixed), ok("2"));
+
+        // Without any numbers, only `sum` has an answer.
+        assert_eq!(reduce("sum", &[]), ok("0"));
+        assert_eq!(reduce("min", &[]), ok("null"));
+        assert_eq!(reduce("avg", &[]), ok("null"));
+        assert_eq!(reduce("sum --strict", &[]), ok("0"));
+        assert_eq!(reduce("max --strict", &[]).1, Exit::FAILURE);
+
+        // Numbers can be found in maps, and anything else is skipped.
+        let text = |s: &str| Value::Text(s.to_string());
+        let file = |size| Value::Map(vec![(text("size"), size)]);
+        let files = [
+            file(number(10)),
+            file(text("big")),
+            Value::Null,
+            file(number(5)),
+        ];
+        assert_eq!(reduce("sum -f .size", &files), ok("15"));
+        assert_eq!(reduce("avg --field .size", &files), ok("7.5"));
+        assert_eq!(reduce("sum", &files), ok("0"));
+
+        assert_eq!(reduce("sum -f", &[]).1, Exit::BAD_SYNTAX);
+        assert_eq!(reduce("sum 1", &[]).1, Exit::BAD_SYNTAX);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reduce_notes_skipped() {
+        let input = encode(&[Value::Integer(1.into()), Value::Text("one".into())]);
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let (out, err, exit) = run_builtin(&Reduce(Reduction::Sum), &mut int, &[], &input);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(out, [0x01]);
+        assert_eq!(
+            err,
+            "monch: sum: skipped 1 of 2 items, which weren't numbers\n"
+        );
+    }
+}
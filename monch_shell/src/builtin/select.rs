@@ -0,0 +1,230 @@
+use super::map_items;
+use crate::streams::WriteStream;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::{DataPath, StreamHeader};
+use std::io::Write;
+
+register_builtin!("select", Select, "pick some fields out of each item");
+
+/// `select PATH...` turns each item into a map of just the values at the PATHs, keyed by the
+/// last key of each path, like `ls | select .name .kind`. A field can be given a different name
+/// with `NAME=PATH`, and fields missing from an item are `null`, unless `--skip-missing` leaves
+/// them out. With `--keep-structure`, nested values are kept at their paths, rather than all
+/// being brought up to the top.
+///
+/// Items which aren't maps are passed on unchanged, with a warning the first time, unless
+/// `--strict` makes them an error.
+pub struct Select;
+
+/// One field that `select` picks out of each item: where it's found, and where it's put.
+#[derive(Debug)]
+struct SelectField {
+    from: DataPath,
+    to: DataPath,
+}
+
+/// How `select` was asked to pick fields.
+#[derive(Debug, Default)]
+struct SelectArgs {
+    fields: Vec<SelectField>,
+    skip_missing: bool,
+    strict: bool,
+}
+
+impl Select {
+    /// Parse the arguments of a `select` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<SelectArgs, String> {
+        let mut parsed = SelectArgs::default();
+        let mut keep_structure = false;
+        let mut fields = vec![];
+
+        for arg in args {
+            match arg.as_str() {
+                "--keep-structure" => keep_structure = true,
+                "--skip-missing" => parsed.skip_missing = true,
+                "--strict" => parsed.strict = true,
+                field => fields.push(field),
+            }
+        }
+
+        let root = DataPath::parse("");
+        for field in fields {
+            let (name, path) = match field.split_once('=') {
+                Some((name, path)) => (Some(name), path),
+                None => (None, field),
+            };
+
+            let from = DataPath::parse_strict(path)
+                .map_err(|e| format!("`{}` isn't a path: {}", path, e))?;
+            let to = match (name, from.last()) {
+                (Some(name), _) => root.child(Value::Text(name.to_string())),
+                (None, _) if keep_structure => from.clone(),
+                (None, Some(last)) => root.child(last.clone()),
+                (None, None) => {
+                    return Err(format!(
+                        "`{}` is the whole item, so it needs a name, like `item={}`",
+                        path, path
+                    ))
+                }
+            };
+
+            parsed.fields.push(SelectField { from, to });
+        }
+
+        if parsed.fields.is_empty() {
+            return Err("expected the paths to select, like `select .name .kind`".to_string());
+        }
+
+        Ok(parsed)
+    }
+
+    /// The top-level keys of the maps `select` makes, for their stream header.
+    fn field_names(fields: &[SelectField]) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for field in fields {
+            let name = match field.to.split_head() {
+                Some((Value::Text(name), _)) => name,
+                Some((Value::Integer(i), _)) => i128::from(i).to_string(),
+                _ => continue,
+            };
+
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        names
+    }
+}
+
+impl exe::Execute for Select {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match Select::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: select: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        // The items change shape, so the header has to say what they look like now.
+        let names = Select::field_names(&args.fields);
+        let map_header = move |header: &StreamHeader| {
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            Some(StreamHeader::new(header.type_name.clone(), &names))
+        };
+
+        let root = DataPath::parse("");
+        let mut warned = false;
+        let select = move |index, item, stderr: &mut WriteStream| {
+            // Paths pass through tags, so tagged maps count as maps too.
+            if !matches!(root.get_ref(&item), Some(Value::Map(_))) {
+                if args.strict {
+                    return Err(format!("item {} isn't a map", index));
+                }
+
+                if !warned {
+                    let _ = writeln!(
+                        stderr,
+                        "monch: select: item {} isn't a map, so it's passed on unchanged",
+                        index
+                    );
+                    warned = true;
+                }
+                return Ok(Some(item));
+            }
+
+            let mut selected = Value::Map(vec![]);
+            for field in &args.fields {
+                match field.from.get_ref(&item) {
+                    Some(value) => field.to.set_into(&mut selected, value.clone()),
+                    None if args.skip_missing => {}
+                    None => field.to.set_into(&mut selected, Value::Null),
+                }
+            }
+            Ok(Some(selected))
+        };
+
+        Ok(map_items("select", ios, map_header, select))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_with_input, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn select_fields() {
+        let dir = scratch_dir("select_fields");
+        let files = r#"{"name": "a", "kind": "File", "meta": {"size": 10, "mode": 420}}
+                       {"name": "b", "meta": {"size": 20}}"#;
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut select = |args: &str, input: &str| {
+            let line = format!("from json | select {} | to json", args);
+            run_with_input(&mut int, &line, input.as_bytes())
+        };
+
+        // Fields are named by the last key of their path, or the name they're given.
+        assert_eq!(
+            select(".name .meta.size", files),
+            (
+                "{\"name\":\"a\",\"size\":10}\n{\"name\":\"b\",\"size\":20}\n".into(),
+                Exit::SUCCESS
+            )
+        );
+        assert_eq!(
+            select("n=.name bytes=.meta.size", files).0,
+            "{\"n\":\"a\",\"bytes\":10}\n{\"n\":\"b\",\"bytes\":20}\n"
+        );
+
+        // Missing fields are null, unless they're skipped.
+        assert_eq!(
+            select(".name .kind", files).0,
+            "{\"name\":\"a\",\"kind\":\"File\"}\n{\"name\":\"b\",\"kind\":null}\n"
+        );
+        assert_eq!(
+            select("--skip-missing .kind .meta.mode", files).0,
+            "{\"kind\":\"File\",\"mode\":420}\n{}\n"
+        );
+
+        // Nested fields can stay where they were.
+        assert_eq!(
+            select("--keep-structure .name .meta.size", files).0,
+            "{\"name\":\"a\",\"meta\":{\"size\":10}}\n{\"name\":\"b\",\"meta\":{\"size\":20}}\n"
+        );
+
+        // Anything else is passed through, unless that's an error.
+        let mixed = r#"{"name": "a"} "not a map""#;
+        assert_eq!(
+            select(".name", mixed).0,
+            "{\"name\":\"a\"}\n\"not a map\"\n"
+        );
+        assert_eq!(select("--strict .name", mixed).1, Exit::FAILURE);
+
+        assert_eq!(select("", mixed).1, Exit::BAD_SYNTAX);
+        assert_eq!(select(".", mixed).1, Exit::BAD_SYNTAX);
+        assert_eq!(select("..name", mixed).1, Exit::BAD_SYNTAX);
+        assert_eq!(
+            select("all=.", mixed).0,
+            "{\"all\":{\"name\":\"a\"}}\n\"not a map\"\n"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::io::{self, Write};
+use std::thread;
+
+register_builtin!("seq", Seq, "count from one number to another");
+
+/// `seq [FIRST [STEP]] LAST` writes the integers from FIRST to LAST, counting by STEP, which can be
+/// negative to count down. FIRST and STEP are both 1 if they're left out. With `--pad`, it writes
+/// text instead, padded with zeroes to the same width, like `08`, `09`, `10`.
+///
+/// It writes each number as it goes, and stops once nothing's reading them.
+pub struct Seq;
+
+/// Which numbers `seq` was asked for.
+#[derive(Debug, PartialEq, Eq)]
+struct SeqArgs {
+    first: i64,
+    step: i64,
+    last: i64,
+    pad: bool,
+}
+
+impl Seq {
+    /// Parse the arguments of a `seq` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<SeqArgs, String> {
+        let mut numbers = vec![];
+        let mut pad = false;
+
+        // Negative numbers look like flags, so anything which is a number is taken as one.
+        for arg in args {
+            match (arg.parse::<i64>(), arg.as_str()) {
+                (Ok(n), _) => numbers.push(n),
+                (_, "--pad") => pad = true,
+                (_, arg) if arg.starts_with('-') => {
+                    return Err(format!("unexpected argument `{}`", arg))
+                }
+                (_, arg) => return Err(format!("`{}` isn't an integer", arg)),
+            }
+        }
+
+        let (first, step, last) = match numbers[..] {
+            [last] => (1, 1, last),
+            [first, last] => (first, 1, last),
+            [first, step, last] => (first, step, last),
+            _ => return Err("expected `seq [FIRST [STEP]] LAST`".to_string()),
+        };
+        if step == 0 {
+            return Err("the step can't be 0".to_string());
+        }
+
+        Ok(SeqArgs {
+            first,
+            step,
+            last,
+            pad,
+        })
+    }
+}
+
+impl exe::Execute for Seq {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match Seq::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: seq: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let width = args
+                .first
+                .to_string()
+                .len()
+                .max(args.last.to_string().len());
+            let mut item = vec![];
+
+            let in_range = |i: i64| {
+                if args.step > 0 {
+                    i <= args.last
+                } else {
+                    i >= args.last
+                }
+            };
+
+            let mut n = Some(args.first);
+            while let Some(i) = n.filter(|&i| in_range(i)) {
+                let value = if args.pad {
+                    Value::Text(format!("{:0width$}", i, width = width))
+                } else {
+                    Value::Integer(i.into())
+                };
+
+                item.clear();
+                ciborium::ser::into_writer(&value, &mut item).expect("values can be encoded");
+                match ios.stdout.write_all(&item) {
+                    Ok(()) => {}
+
+                    // Nothing wants the rest.
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => break,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: seq: {}", e);
+                        return Exit::FAILURE;
+                    }
+                }
+
+                n = i.checked_add(args.step);
+            }
+
+            Exit::SUCCESS
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::run;
+    use std::env;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn seq_counts() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_pipefail(true);
+        let mut seq = |args: &str| run(&mut int, &[&format!("seq {} | to json", args)]);
+        let ok = |lines: &[&str]| {
+            let out: String = lines.iter().map(|l| format!("{}\n", l)).collect();
+            (out, Exit::SUCCESS)
+        };
+
+        assert_eq!(seq("3"), ok(&["1", "2", "3"]));
+        assert_eq!(seq("-1 1"), ok(&["-1", "0", "1"]));
+        assert_eq!(seq("0 2 5"), ok(&["0", "2", "4"]));
+        assert_eq!(seq("3 -2 -3"), ok(&["3", "1", "-1", "-3"]));
+        assert_eq!(seq("3 1"), ok(&[]));
+        assert_eq!(seq("0"), ok(&[]));
+        assert_eq!(seq("8 10 --pad"), ok(&["\"08\"", "\"09\"", "\"10\""]));
+        assert_eq!(seq("--pad -1 1"), ok(&["\"-1\"", "\"00\"", "\"01\""]));
+
+        // It stops at the end of the integers, rather than wrapping around.
+        let max = i64::MAX.to_string();
+        assert_eq!(seq(&format!("{} {}", max, max)), ok(&[&max]));
+
+        for bad in ["", "1 0 5", "1 2 3 4", "one", "--pod 1"] {
+            assert_eq!(seq(bad).1, Exit::BAD_SYNTAX, "{}", bad);
+        }
+    }
+
+    #[test]
+    fn seq_stops_early() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_pipefail(true);
+
+        let start = Instant::now();
+        let out = run(&mut int, &["seq 1000000000000 | first 2 | to json"]);
+        assert_eq!(out, ("1\n2\n".to_string(), Exit::SUCCESS));
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(
+            int.last_pipeline_status()[0],
+            ("seq".to_string(), Exit::SUCCESS)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use super::is_var_name;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::collections::BTreeMap;
+use std::io::Write;
+
+register_builtin!("set", Set, "list or set shell variables and options");
+
+/// `set NAME value` sets a variable, without passing it on to child processes.
+///
+/// `set` also changes shell options:
+///
+/// - `set -e` and `set +e` turn errexit on and off, as do `set -o errexit` and `set +o errexit`.
+/// - `set -o pipefail` and `set +o pipefail` turn pipefail on and off.
+/// - `set -x` and `set +x` turn tracing on and off, as do `set -o xtrace` and `set +o xtrace`.
+/// - `set -o pipekill` and `set +o pipekill` choose whether a stage of a pipeline finishing stops
+///   the stages before it.
+/// - `set autoconvert on|off` chooses whether stages of a pipeline which don't fit together, like
+///   a program writing text into a builtin reading CBOR, get a builtin like `lines` between them.
+/// - `set autoformat on|off` chooses whether CBOR output at the end of a pipeline gets formatted
+///   with `to tty`.
+/// - `set stderr-prefix on|off` chooses whether builtins write whole lines to stderr, starting
+///   with their name, so that their messages don't get mixed up.
+///
+/// With no arguments, `set` writes a map of the current options.
+pub struct Set;
+
+impl Set {
+    /// Turn the named option on or off. Returns false if there's no such option.
+    fn set_option(int: &mut Interpreter, name: &str, enabled: bool) -> bool {
+        match name {
+            "autoconvert" => int.set_auto_convert(enabled),
+            "autoformat" => int.set_auto_format(enabled),
+            "errexit" => int.set_errexit(enabled),
+            "pipefail" => int.set_pipefail(enabled),
+            "pipekill" => int.set_pipekill(enabled),
+            "stderr-prefix" => int.set_stderr_prefix(enabled),
+            "xtrace" => int.set_trace(enabled),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl exe::Execute for Set {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let (name, value) = match args[..] {
+            [] => {
+                let options = BTreeMap::from([
+                    ("autoconvert", int.auto_convert()),
+                    ("autoformat", int.auto_format()),
+                    ("errexit", int.errexit()),
+                    ("pipefail", int.pipefail()),
+                    ("pipekill", int.pipekill()),
+                    ("stderr-prefix", int.stderr_prefix()),
+                    ("xtrace", int.trace()),
+                ]);
+
+                if let Err(e) = ciborium::ser::into_writer(&options, &mut ios.stdout) {
+                    let _ = writeln!(ios.stderr, "monch: set: {}", e);
+                    exit!(Exit::FAILURE)
+                }
+                exit!(Exit::SUCCESS)
+            }
+
+            [flag @ ("-e" | "+e")] => {
+                int.set_errexit(flag == "-e");
+                exit!(Exit::SUCCESS)
+            }
+
+            [flag @ ("-x" | "+x")] => {
+                int.set_trace(flag == "-x");
+                exit!(Exit::SUCCESS)
+            }
+
+            [flag @ ("-o" | "+o"), option] => {
+                if !Set::set_option(int, option, flag == "-o") {
+                    let _ = writeln!(ios.stderr, "monch: set: '{}': no such option", option);
+                    exit!(Exit::FAILURE)
+                }
+                exit!(Exit::SUCCESS)
+            }
+
+            [name, value] => (name, value),
+
+            _ => {
+                let _ = writeln!(ios.stderr, "monch: set: expected NAME value");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        if let "autoconvert" | "autoformat" | "stderr-prefix" = name {
+            let enabled = match value {
+                "on" => true,
+                "off" => false,
+                _ => {
+                    let _ = writeln!(ios.stderr, "monch: set: {}: expected on or off", name);
+                    exit!(Exit::FAILURE)
+                }
+            };
+            Set::set_option(int, name, enabled);
+            exit!(Exit::SUCCESS)
+        }
+
+        if !is_var_name(name) {
+            let _ = writeln!(ios.stderr, "monch: set: '{}': not a valid name", name);
+            exit!(Exit::FAILURE)
+        }
+
+        int.set_var(name, value);
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        if args.is_empty() {
+            Ty::Cbor
+        } else {
+            Ty::Nothing
+        }
+    }
+
+    fn is_synchronous(&self, args: &exe::Args) -> bool {
+        !args.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run, run_builtin};
+    use std::env;
+
+    #[test]
+    fn set_autoformat() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+
+        assert_eq!(run(&mut int, &["set autoformat on"]).1, Exit::SUCCESS);
+        assert!(int.auto_format());
+        assert_eq!(run(&mut int, &["set autoformat off"]).1, Exit::SUCCESS);
+        assert!(!int.auto_format());
+
+        assert_eq!(run(&mut int, &["set autoformat maybe"]).1, Exit::FAILURE);
+        assert_eq!(int.get_var("autoformat"), None);
+    }
+
+    #[test]
+    fn set_options() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let options = |int: &mut Interpreter| {
+            let (out, _, exit) = run_builtin(&Set, int, &[], &[]);
+            assert_eq!(exit, Exit::SUCCESS);
+            ciborium::de::from_reader::<BTreeMap<String, bool>, _>(&out[..]).unwrap()
+        };
+
+        let all_off = BTreeMap::from([
+            ("autoconvert".to_string(), false),
+            ("autoformat".to_string(), false),
+            ("errexit".to_string(), false),
+            ("pipefail".to_string(), false),
+            ("pipekill".to_string(), false),
+            ("stderr-prefix".to_string(), false),
+            ("xtrace".to_string(), false),
+        ]);
+
+        // Everything's off to begin with, except converting between stages.
+        let mut defaults = all_off.clone();
+        defaults.insert("autoconvert".to_string(), true);
+        assert_eq!(options(&mut int), defaults);
+
+        run(
+            &mut int,
+            &[
+                "set -e",
+                "set -o pipefail",
+                "set -o pipekill",
+                "set autoformat on",
+                "set stderr-prefix on",
+                "set -x",
+            ],
+        );
+        assert!(options(&mut int).values().all(|&on| on));
+
+        run(
+            &mut int,
+            &[
+                "set +e",
+                "set +o pipefail",
+                "set +o pipekill",
+                "set +o autoformat",
+                "set autoconvert off",
+                "set stderr-prefix off",
+                "set +o xtrace",
+            ],
+        );
+        assert_eq!(options(&mut int), all_off);
+
+        assert_eq!(run(&mut int, &["set -o nothing"]).1, Exit::FAILURE);
+    }
+}
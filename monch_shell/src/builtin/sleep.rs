@@ -0,0 +1,130 @@
+use crate::{exe, foreground, types::Ty, Error, Exit, Interpreter, Streams};
+use monch_io::units::parse_duration;
+use std::io::Write;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+register_builtin!("sleep", Sleep, "wait for a while");
+
+/// How often a `sleep` checks whether it's been interrupted.
+const INTERRUPT_POLL: Duration = Duration::from_millis(20);
+
+/// `sleep DURATION` waits for DURATION, which is a number of seconds like `1.5`, or has units like
+/// `200ms`, `2m30s` or `1d`. Like an external program, it stops early when it's interrupted, or when
+/// pipekill stops it.
+pub struct Sleep;
+
+/// Whether a `sleep` has been stopped, and a way to wake it up when it is.
+type Alarm = Arc<(Mutex<bool>, Condvar)>;
+
+/// A `sleep` in progress, which can be stopped early, like an external program.
+struct Sleeping {
+    worker: thread::JoinHandle<Exit>,
+    stopped: Alarm,
+}
+
+impl exe::Wait for Sleeping {
+    fn wait(self: Box<Self>) -> Result<Exit, Error> {
+        Box::new(self.worker).wait()
+    }
+
+    fn terminator(&self) -> Option<exe::Terminator> {
+        let stopped = self.stopped.clone();
+        Some(Box::new(move || {
+            let (lock, wake) = &*stopped;
+            *lock.lock().unwrap() = true;
+            wake.notify_all();
+        }))
+    }
+}
+
+impl exe::Execute for Sleep {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let duration = match &args[..] {
+            [duration] => parse_duration(duration).map_err(|e| e.to_string()),
+            _ => Err("expected a duration, like `sleep 1.5` or `sleep 200ms`".to_string()),
+        };
+        let duration = match duration {
+            Ok(duration) => duration,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: sleep: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let stopped = Alarm::default();
+        let alarm = stopped.clone();
+        let interrupts = foreground::interrupts();
+        let deadline = Instant::now() + duration;
+
+        // Wake up every so often to check whether the shell's been interrupted.
+        let worker = thread::spawn(move || {
+            let (lock, wake) = &*alarm;
+            let mut stopped = lock.lock().unwrap();
+            loop {
+                // Stopped early, like a program would be with SIGTERM.
+                if *stopped {
+                    return Exit::TERMINATED;
+                }
+                if foreground::interrupts() != interrupts {
+                    return Exit::INTERRUPTED;
+                }
+
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Exit::SUCCESS;
+                }
+
+                stopped = wake
+                    .wait_timeout(stopped, remaining.min(INTERRUPT_POLL))
+                    .unwrap()
+                    .0;
+            }
+        });
+
+        Ok(Box::new(Sleeping { worker, stopped }))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::run_builtin;
+    use std::env;
+
+    #[test]
+    fn sleep_waits() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+
+        let start = Instant::now();
+        let (out, err, exit) = run_builtin(&Sleep, &mut int, &["50ms"], &[]);
+        assert_eq!((out, err.as_str(), exit), (vec![], "", Exit::SUCCESS));
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        let (_, err, exit) = run_builtin(&Sleep, &mut int, &["soon"], &[]);
+        assert_eq!(exit, Exit::BAD_SYNTAX);
+        assert_eq!(
+            err,
+            "monch: sleep: 'soon' isn't a duration, like `30s`, `2d` or `1h30m`\n"
+        );
+        assert_eq!(run_builtin(&Sleep, &mut int, &[], &[]).2, Exit::BAD_SYNTAX);
+        assert_eq!(
+            run_builtin(&Sleep, &mut int, &["1", "2"], &[]).2,
+            Exit::BAD_SYNTAX
+        );
+    }
+}
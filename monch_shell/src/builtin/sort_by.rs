@@ -0,0 +1,278 @@
+use super::number::Number;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::DataPath;
+use std::cmp::Ordering;
+use std::io::Write;
+use std::thread;
+
+register_builtin!("sort-by", SortBy, "sort items by the value at a path");
+
+/// `sort-by PATH` reads its whole input, and passes the items on sorted by the value at PATH in
+/// each of them. Items which sort the same keep the order they came in.
+///
+/// Values sort first by their kind: booleans, then numbers, then text, bytes, arrays, and maps,
+/// with items where the value is missing or `null` at the end. Within each kind, numbers compare
+/// numerically (integers against floats too), text compares lexically, and arrays and maps all
+/// sort the same. With `--ignore-case`, text is compared ignoring case, and with `--numeric`,
+/// text that looks like a number sorts as one. `--reverse` reverses the order, but still leaves
+/// the missing values at the end.
+///
+/// Since it holds on to all of its input, it fails once it's read more than `--bytes-limit`, or
+/// 1GiB by default, rather than running out of memory.
+pub struct SortBy;
+
+/// The most input `sort-by` will hold on to, unless it's told otherwise.
+const SORT_BYTES_LIMIT: usize = 1024 * 1024 * 1024;
+
+/// How `sort-by` was asked to sort.
+#[derive(Debug)]
+struct SortByArgs {
+    path: DataPath,
+    reverse: bool,
+    numeric: bool,
+    ignore_case: bool,
+    bytes_limit: usize,
+}
+
+/// What an item is sorted by, taken from the value at its path. The variants are in the order
+/// the kinds of value sort in.
+#[derive(Debug, PartialEq, PartialOrd)]
+enum SortKey {
+    Bool(bool),
+    Number(Number),
+    Text(String),
+    Bytes(Vec<u8>),
+    Array,
+    Map,
+    Missing,
+}
+
+impl SortKey {
+    /// Find the sort key of an item.
+    fn new(args: &SortByArgs, item: &Value) -> SortKey {
+        match args.path.get_ref(item) {
+            Some(Value::Bool(b)) => SortKey::Bool(*b),
+            Some(Value::Integer(i)) => SortKey::Number(Number::Integer(i128::from(*i))),
+            Some(Value::Float(f)) => SortKey::Number(Number::Float(*f)),
+            Some(Value::Text(text)) => match Number::parse(text) {
+                Some(number) if args.numeric => SortKey::Number(number),
+                _ if args.ignore_case => SortKey::Text(text.to_lowercase()),
+                _ => SortKey::Text(text.clone()),
+            },
+            Some(Value::Bytes(bytes)) => SortKey::Bytes(bytes.clone()),
+            Some(Value::Array(_)) => SortKey::Array,
+            Some(Value::Map(_)) => SortKey::Map,
+            _ => SortKey::Missing,
+        }
+    }
+}
+
+impl SortBy {
+    /// Parse the arguments of a `sort-by` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<SortByArgs, String> {
+        let mut path = None;
+        let mut parsed = SortByArgs {
+            path: DataPath::parse(""),
+            reverse: false,
+            numeric: false,
+            ignore_case: false,
+            bytes_limit: SORT_BYTES_LIMIT,
+        };
+
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            match arg {
+                "-r" | "--reverse" => parsed.reverse = true,
+                "-n" | "--numeric" => parsed.numeric = true,
+                "-i" | "--ignore-case" => parsed.ignore_case = true,
+                "--bytes-limit" => {
+                    let limit = args
+                        .next()
+                        .ok_or("`--bytes-limit` needs a number of bytes")?;
+                    parsed.bytes_limit = limit
+                        .parse()
+                        .map_err(|_| format!("`{}` isn't a number of bytes", limit))?;
+                }
+                _ if path.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+                _ => path = Some(DataPath::parse_strict(arg).map_err(|e| e.to_string())?),
+            }
+        }
+
+        parsed.path = path.ok_or("expected a path to sort by, like `sort-by .name`")?;
+        Ok(parsed)
+    }
+}
+
+impl exe::Execute for SortBy {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match SortBy::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: sort-by: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let mut parser = monch_io::InputParser::<Value, _>::new(ios.stdin)
+                .with_max_item_size(args.bytes_limit.min(monch_io::DEFAULT_MAX_ITEM_SIZE));
+
+            let mut items = vec![];
+            while let Some(item) = parser.next() {
+                let item = match item {
+                    Ok(item) => item,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: sort-by: {}", e);
+                        return Exit::FAILURE;
+                    }
+                };
+
+                if parser.byte_offset() > args.bytes_limit as u64 {
+                    let _ = writeln!(
+                        ios.stderr,
+                        "monch: sort-by: input is larger than the limit of {} bytes, which can \
+                         be raised with `--bytes-limit`",
+                        args.bytes_limit
+                    );
+                    return Exit::FAILURE;
+                }
+
+                items.push((SortKey::new(&args, &item), item));
+            }
+
+            items.sort_by(|(a, _), (b, _)| {
+                let order = a.partial_cmp(b).unwrap_or(Ordering::Equal);
+                match (a, b) {
+                    (SortKey::Missing, _) | (_, SortKey::Missing) => order,
+                    _ if args.reverse => order.reverse(),
+                    _ => order,
+                }
+            });
+
+            let header = parser.header().map(|h| h.to_value());
+            let written = header
+                .iter()
+                .chain(items.iter().map(|(_, item)| item))
+                .try_for_each(|value| ciborium::ser::into_writer(value, &mut ios.stdout));
+            match written {
+                Ok(()) => Exit::SUCCESS,
+                Err(_) => Exit::FAILURE,
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{encode, run_with_input, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn sort_by_field() {
+        let dir = scratch_dir("sort_by_field");
+        let text = |s: &str| Value::Text(s.to_string());
+        let number = |n: i64| Value::Integer(n.into());
+
+        // Sort maps with the given values at `.k`, or without one if it's `None`, returning the
+        // `.id` of each, which is where it was in the input.
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut sort = |args: &str, keys: Vec<Option<Value>>| {
+            let mut items = vec![];
+            for (id, key) in keys.into_iter().enumerate() {
+                let mut item = vec![(text("id"), number(id as i64))];
+                item.extend(key.map(|k| (text("k"), k)));
+                items.push(Value::Map(item));
+            }
+
+            let line = format!("sort-by {} | select .id | to json", args);
+            let (out, exit) = run_with_input(&mut int, &line, &encode(&items));
+            let ids: Vec<i64> = out
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["id"].as_i64())
+                .map(Option::unwrap)
+                .collect();
+            (ids, exit)
+        };
+
+        // Numbers compare numerically, whether they're integers or floats.
+        let numbers = || {
+            vec![
+                Some(number(10)),
+                Some(Value::Float(2.5)),
+                Some(number(-3)),
+                Some(number(3)),
+            ]
+        };
+        assert_eq!(sort(".k", numbers()), (vec![2, 1, 3, 0], Exit::SUCCESS));
+        assert_eq!(sort("--reverse .k", numbers()).0, [0, 3, 1, 2]);
+
+        // Text compares lexically, optionally ignoring case.
+        let words = || vec![Some(text("b")), Some(text("C")), Some(text("a"))];
+        assert_eq!(sort(".k", words()).0, [1, 2, 0]);
+        assert_eq!(sort(".k -i", words()).0, [2, 0, 1]);
+
+        // Numbers in text only compare as numbers if asked to.
+        let counts = || {
+            vec![
+                Some(text("10")),
+                Some(text("9")),
+                Some(number(5)),
+                Some(text("x")),
+            ]
+        };
+        assert_eq!(sort(".k", counts()).0, [2, 0, 1, 3]);
+        assert_eq!(sort(".k --numeric", counts()).0, [2, 1, 0, 3]);
+
+        // Missing and null values go last, even when reversed, and equal items stay in order.
+        let sparse = || {
+            vec![
+                None,
+                Some(number(1)),
+                Some(Value::Null),
+                Some(number(2)),
+                Some(number(1)),
+            ]
+        };
+        assert_eq!(sort(".k", sparse()).0, [1, 4, 3, 0, 2]);
+        assert_eq!(sort(".k -r", sparse()).0, [3, 1, 4, 0, 2]);
+
+        // Different kinds of value are grouped together.
+        let mixed = vec![
+            Some(Value::Map(vec![])),
+            Some(text("a")),
+            Some(Value::Array(vec![])),
+            Some(number(1)),
+            Some(Value::Bytes(vec![])),
+            Some(Value::Bool(true)),
+            Some(text("1")),
+        ];
+        assert_eq!(sort(".k", mixed).0, [5, 3, 6, 1, 4, 2, 0]);
+
+        // It won't hold on to more than it's allowed to.
+        assert_eq!(sort(".k --bytes-limit 20", numbers()).1, Exit::FAILURE);
+        assert_eq!(sort(".k --bytes-limit 1000", numbers()).1, Exit::SUCCESS);
+
+        assert_eq!(sort("", vec![]).1, Exit::BAD_SYNTAX);
+        assert_eq!(sort(".k .j", vec![]).1, Exit::BAD_SYNTAX);
+        assert_eq!(sort(".k --bytes-limit lots", vec![]).1, Exit::BAD_SYNTAX);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::io::Write;
+
+register_builtin!(
+    "status",
+    Status,
+    "describe how each stage of the last pipeline exited"
+);
+
+/// `status` writes how each stage of the last pipeline exited, as an array of maps like
+/// `{command, code, signal}`. Only one of `code` and `signal` is set.
+pub struct Status;
+
+impl exe::Execute for Status {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        if !args.is_empty() {
+            let _ = writeln!(ios.stderr, "monch: status: expected no arguments");
+            exit!(Exit::BAD_SYNTAX)
+        }
+
+        let text = |s: &str| Value::Text(s.to_string());
+        let number = |n: Option<u32>| n.map_or(Value::Null, |n| Value::Integer(n.into()));
+        let stages = int
+            .last_pipeline_status()
+            .iter()
+            .map(|(command, exit)| {
+                Value::Map(vec![
+                    (text("command"), text(command)),
+                    (text("code"), number(exit.code())),
+                    (text("signal"), number(exit.signal())),
+                ])
+            })
+            .collect();
+
+        if let Err(e) = ciborium::ser::into_writer(&Value::Array(stages), &mut ios.stdout) {
+            let _ = writeln!(ios.stderr, "monch: status: {}", e);
+            exit!(Exit::FAILURE)
+        }
+
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run, run_builtin};
+    use std::collections::BTreeMap;
+    use std::env;
+
+    #[test]
+    fn status_of_last_pipeline() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        run(&mut int, &["sh -c 'exit 3' | sh -c 'kill -9 $$' | true"]);
+
+        let (out, _, exit) = run_builtin(&Status, &mut int, &[], &[]);
+        assert_eq!(exit, Exit::SUCCESS);
+
+        let stages: Vec<BTreeMap<String, Option<Value>>> =
+            ciborium::de::from_reader(&out[..]).unwrap();
+        let field = |i: usize, key: &str| stages[i][key].clone();
+        assert_eq!(stages.len(), 3);
+        assert_eq!(field(0, "command"), Some(Value::Text("sh".into())));
+        assert_eq!(field(0, "code"), Some(Value::Integer(3.into())));
+        assert_eq!(field(0, "signal"), None);
+        assert_eq!(field(1, "code"), None);
+        assert_eq!(field(1, "signal"), Some(Value::Integer(9.into())));
+        assert_eq!(field(2, "command"), Some(Value::Text("true".into())));
+    }
+}
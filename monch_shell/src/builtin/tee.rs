@@ -0,0 +1,135 @@
+use crate::plan::WriteTarget;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::{self, Write};
+use std::thread;
+
+register_builtin!("tee", Tee, "pass input through, saving a copy to a file");
+
+/// `tee FILE` passes its input through unchanged, while also writing a copy of it to FILE. With
+/// `--append`, it adds to the end of the file instead of replacing it.
+pub struct Tee;
+
+impl exe::Execute for Tee {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let target = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [file] => WriteTarget::Truncate(int.current_dir().join(file)),
+            ["--append", file] => WriteTarget::Append(int.current_dir().join(file)),
+            _ => {
+                let _ = writeln!(
+                    ios.stderr,
+                    "monch: tee: expected a file, like `tee [--append] FILE`"
+                );
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let mut file = match target.open() {
+            Ok(file) => file,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: tee: {}", e);
+                exit!(Exit::FAILURE)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let mut buf = [0; 8192];
+            loop {
+                let n = match io::Read::read(&mut ios.stdin, &mut buf) {
+                    Ok(0) => return Exit::SUCCESS,
+                    Ok(n) => n,
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: tee: {}", e);
+                        return Exit::FAILURE;
+                    }
+                };
+
+                if let Err(e) = file.write_all(&buf[..n]) {
+                    let _ = writeln!(ios.stderr, "monch: tee: {}", e);
+                    return Exit::FAILURE;
+                }
+
+                // If nothing's reading any more, there's no use carrying on.
+                match ios.stdout.write_all(&buf[..n]) {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Exit::FAILURE,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: tee: {}", e);
+                        return Exit::FAILURE;
+                    }
+                }
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_builtin, scratch_dir};
+    use ciborium::value::Value;
+    use monch_syntax::Parser;
+    use std::fs;
+
+    #[test]
+    fn tee_copies_input() {
+        let dir = scratch_dir("tee_copies_input");
+        let mut input = vec![];
+        for i in 0..1000 {
+            ciborium::ser::into_writer(&Value::Integer(i.into()), &mut input).unwrap();
+        }
+        fs::write(dir.join("input.cbor"), &input).unwrap();
+
+        // What comes out is exactly what went in, and so is what's saved.
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let cmd = Parser::new()
+            .parse_command("tee saved.cbor <input.cbor")
+            .unwrap();
+        let (out, exit) = int.eval_command_capture(&cmd).unwrap();
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(out, input);
+        assert_eq!(fs::read(dir.join("saved.cbor")).unwrap(), input);
+
+        // Appending keeps what was there.
+        let cmd = Parser::new()
+            .parse_command("tee --append saved.cbor <input.cbor")
+            .unwrap();
+        assert_eq!(int.eval_command_capture(&cmd).unwrap().0, input);
+        assert_eq!(
+            fs::read(dir.join("saved.cbor")).unwrap(),
+            [&input[..], &input[..]].concat()
+        );
+
+        // When it's last, its output is still formatted for a person.
+        int.set_auto_format(true);
+        let plan = int.explain_command(
+            &Parser::new()
+                .parse_command("dirs | tee saved.cbor")
+                .unwrap(),
+        );
+        let last = plan.unwrap().stages.pop().unwrap();
+        assert_eq!((last.command.as_str(), last.inserted), ("to", true));
+
+        assert_eq!(run_builtin(&Tee, &mut int, &[], &[]).2, Exit::BAD_SYNTAX);
+        assert_eq!(
+            run_builtin(&Tee, &mut int, &["nowhere/saved.cbor"], &[]).2,
+            Exit::FAILURE
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
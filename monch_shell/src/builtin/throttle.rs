@@ -0,0 +1,245 @@
+use super::map_items;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use monch_io::units::parse_duration;
+use std::io::Write;
+use std::thread;
+use std::time::{Duration, Instant};
+
+register_builtin! {
+    ("limit-rate", Throttle, "pass items on no faster than a rate, like `throttle`"),
+    ("throttle", Throttle, "pass items on no faster than a rate"),
+}
+
+/// `throttle N/PERIOD` passes on its input, but no more than N items every PERIOD, like `10/s` or
+/// `30/m`. With `--burst N`, up to N items can be passed on at once after a pause, as long as the
+/// rate is kept to overall. `throttle --delay DURATION` waits for DURATION between every item
+/// instead. It's also called `limit-rate`.
+///
+/// Items are only read as they're passed on, so whatever's writing them has to wait too.
+pub struct Throttle;
+
+/// How fast `throttle` passes items on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Pace {
+    /// At most `rate` items a second, with up to `burst` at once.
+    Rate { rate: f64, burst: f64 },
+
+    /// With a fixed delay between items.
+    Delay(Duration),
+}
+
+/// A token bucket, which fills up at a fixed rate, and holds a token for each item that can be
+/// passed on without waiting.
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    filled_at: Instant,
+}
+
+impl TokenBucket {
+    /// Make a full bucket.
+    fn new(rate: f64, capacity: f64) -> TokenBucket {
+        TokenBucket {
+            rate,
+            capacity,
+            tokens: capacity,
+            filled_at: Instant::now(),
+        }
+    }
+
+    /// Wait until there's a token, and take it.
+    fn take(&mut self) {
+        loop {
+            let now = Instant::now();
+            let elapsed = now.duration_since(self.filled_at).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+            self.filled_at = now;
+
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+                return;
+            }
+
+            // Cap the wait at over a century, as good as forever, so it fits in a Duration.
+            let wait = ((1.0 - self.tokens) / self.rate).min(f64::from(u32::MAX));
+            thread::sleep(Duration::from_secs_f64(wait));
+        }
+    }
+}
+
+impl Throttle {
+    /// Parse the arguments of a `throttle` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<Pace, String> {
+        let (mut rate, mut burst, mut delay) = (None, None, None);
+
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            match arg {
+                "--burst" => {
+                    let n = args.next().ok_or("`--burst` needs a number of items")?;
+                    match n.parse::<u32>() {
+                        Ok(n) if n > 0 => burst = Some(f64::from(n)),
+                        _ => return Err(format!("`{}` isn't a number of items", n)),
+                    }
+                }
+                "--delay" => {
+                    let d = args.next().ok_or("`--delay` needs a duration")?;
+                    delay = Some(parse_duration(d).map_err(|e| e.to_string())?);
+                }
+                _ if rate.is_some() => return Err(format!("unexpected argument `{}`", arg)),
+                _ => rate = Some(Throttle::parse_rate(arg)?),
+            }
+        }
+
+        match (rate, delay) {
+            (Some(rate), None) => Ok(Pace::Rate {
+                rate,
+                burst: burst.unwrap_or(1.0),
+            }),
+            (None, Some(_)) if burst.is_some() => Err("`--burst` needs a rate".to_string()),
+            (None, Some(delay)) => Ok(Pace::Delay(delay)),
+            (Some(_), Some(_)) => Err("expected a rate or `--delay`, but not both".to_string()),
+            (None, None) => Err("expected a rate, like `throttle 10/s`".to_string()),
+        }
+    }
+
+    /// Parse a rate like `10/s`, `2/500ms`, or just `10`, as a number of items a second.
+    fn parse_rate(text: &str) -> Result<f64, String> {
+        let (count, period) = text.split_once('/').unwrap_or((text, "s"));
+
+        // A bare unit is one of it, like `/s` for a second.
+        let bare_unit = period.starts_with(|c: char| c.is_ascii_alphabetic());
+        let period = match parse_duration(period) {
+            Err(e) if bare_unit => parse_duration(&format!("1{}", period)).map_err(|_| e),
+            parsed => parsed,
+        };
+        let period = period.map_err(|e| e.to_string())?;
+
+        match count.parse::<f64>() {
+            Ok(count) if count > 0.0 && count.is_finite() && period > Duration::ZERO => {
+                Ok(count / period.as_secs_f64())
+            }
+            _ => Err(format!("`{}` isn't a rate, like `10/s`", text)),
+        }
+    }
+}
+
+impl exe::Execute for Throttle {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let pace = match Throttle::parse_args(args) {
+            Ok(pace) => pace,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: throttle: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        // Waiting before passing on each item means the next isn't read until it's time.
+        let mut bucket = match pace {
+            Pace::Rate { rate, burst } => Some(TokenBucket::new(rate, burst)),
+            Pace::Delay(_) => None,
+        };
+        let throttle = move |index, item, _: &mut _| {
+            match (&mut bucket, pace) {
+                (Some(bucket), _) => bucket.take(),
+                (None, Pace::Delay(delay)) if index > 0 => thread::sleep(delay),
+                (None, _) => {}
+            }
+
+            Ok(Some(item))
+        };
+
+        Ok(map_items(
+            "throttle",
+            ios,
+            |header| Some(header.clone()),
+            throttle,
+        ))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{encode, run_with_input, scratch_dir};
+    use ciborium::value::Value;
+    use std::fs;
+
+    #[test]
+    fn throttle_items() {
+        let dir = scratch_dir("throttle_items");
+        let items: Vec<Value> = (0..10).map(|i| Value::Integer(i.into())).collect();
+        let output: String = (0..10).map(|i| format!("{}\n", i)).collect();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut throttle = |args: &str| {
+            let line = format!("throttle {} | to json", args);
+            let start = Instant::now();
+            let (out, exit) = run_with_input(&mut int, &line, &encode(&items));
+            (out, exit, start.elapsed())
+        };
+
+        // The first item goes straight away, and the other nine wait 20ms each.
+        let (out, exit, elapsed) = throttle("50/s");
+        assert_eq!((out, exit), (output.clone(), Exit::SUCCESS));
+        assert!(elapsed >= Duration::from_millis(170), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+
+        // A burst can go at once.
+        let (out, _, elapsed) = throttle("1/s --burst 10");
+        assert_eq!(out, output);
+        assert!(elapsed < Duration::from_millis(900), "{:?}", elapsed);
+
+        let (out, _, elapsed) = throttle("--delay 20ms");
+        assert_eq!(out, output);
+        assert!(elapsed >= Duration::from_millis(170), "{:?}", elapsed);
+
+        for bad in [
+            "",
+            "0/s",
+            "ten/s",
+            "10/fortnight",
+            "10/s 20/s",
+            "10/s --delay 1s",
+            "--delay 1s --burst 2",
+        ] {
+            assert_eq!(throttle(bad).1, Exit::BAD_SYNTAX, "{}", bad);
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rates() {
+        assert_eq!(Throttle::parse_rate("10"), Ok(10.0));
+        assert_eq!(Throttle::parse_rate("10/s"), Ok(10.0));
+        assert_eq!(Throttle::parse_rate("30/m"), Ok(0.5));
+        assert_eq!(Throttle::parse_rate("1/500ms"), Ok(2.0));
+        assert_eq!(Throttle::parse_rate("864/d"), Ok(0.01));
+        assert!(Throttle::parse_rate("1/0s").is_err());
+        assert_eq!(
+            Throttle::parse_rate("10/fortnight"),
+            Err("'fortnight' isn't a duration, like `30s`, `2d` or `1h30m`".to_string())
+        );
+
+        let args = |a: &[&str]| a.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            Throttle::parse_args(&args(&["--delay", "1d"])),
+            Ok(Pace::Delay(Duration::from_secs(24 * 60 * 60)))
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use monch_io::units::parse_duration;
+use std::io::Write;
+use std::time::Instant;
+
+register_builtin!(
+    "timeout",
+    Timeout,
+    "run a command, giving up on it after a while"
+);
+
+/// `timeout DURATION COMMAND ARGS...` runs a command, but stops waiting for it after DURATION,
+/// like `10` seconds or `1m30s`, and exits with 124. External programs are killed, but builtins
+/// can only be left to finish in the background.
+pub struct Timeout;
+
+/// A command being run by `timeout`.
+struct TimedWait {
+    inner: Box<dyn exe::Wait>,
+    deadline: Instant,
+}
+
+impl exe::Wait for TimedWait {
+    fn wait(self: Box<Self>) -> Result<Exit, Error> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        self.inner.wait_timeout(remaining)
+    }
+}
+
+impl exe::Execute for Timeout {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let (seconds, command, args) = match &args[..] {
+            [seconds, command, args @ ..] => (seconds, command, args.to_vec()),
+            _ => {
+                let _ = writeln!(ios.stderr, "monch: timeout: expected DURATION COMMAND...");
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let timeout = match parse_duration(seconds) {
+            Ok(timeout) => timeout,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: timeout: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let exe = match int.lookup_exe(command) {
+            Ok(resolved) => resolved.exe,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: timeout: {}", e);
+                exit!(e.as_exit())
+            }
+        };
+
+        let deadline = Instant::now() + timeout;
+        let inner = exe.execute(int, ios, &args)?;
+        Ok(Box::new(TimedWait { inner, deadline }))
+    }
+
+    // We can't know what the command will read and write until it's run.
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Any
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Unknown
+    }
+}
@@ -0,0 +1,247 @@
+use super::format::DataFormat;
+use crate::cbor_display::format_cbor_opts;
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::convert::{cbor_to_json, cbor_to_text, ConvertError};
+use monch_io::table::CsvWriter;
+use owo_colors::OwoColorize;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::thread;
+
+register_builtin!("to", To, "convert data into another format");
+
+/// `to FORMAT` converts CBOR items into another format: `tty` to show them to a person, `text`
+/// for a line of plain text each, `json` for JSON Lines, `csv` for a table with a row for each
+/// map, or `cbor` to leave them as they are.
+///
+/// `to csv` takes its columns from the keys of the first map, unless they're given like
+/// `to csv --columns 'a,b,c'`.
+pub struct To;
+
+impl To {
+    /// Parse the arguments of a `to` invocation, returning the format we're converting to and
+    /// the columns given for CSV, or an error message.
+    fn parse_args(args: &[String]) -> Result<(DataFormat, Option<Vec<String>>), String> {
+        let (format_name, columns) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+            [format_name] => (format_name, None),
+            ["csv", "--columns", columns] => {
+                ("csv", Some(columns.split(',').map(String::from).collect()))
+            }
+            _ => return Err("to: expected a format name only, or `csv --columns A,B,C`".into()),
+        };
+
+        let format = DataFormat::from_str(format_name)
+            .map_err(|_| format!("to: '{}' is not a valid format name", format_name))?;
+        Ok((format, columns))
+    }
+
+    /// Render an item as a line of compact JSON for `to json`.
+    fn json_line(value: Value) -> Result<String, ConvertError> {
+        Ok(cbor_to_json(value)?.to_string())
+    }
+
+    /// Write a stream of maps from `input` as CSV, with a header row naming the columns. See
+    /// [`CsvWriter`] for how maps become rows.
+    fn write_csv(
+        input: impl io::Read,
+        out: impl Write,
+        columns: Option<Vec<String>>,
+    ) -> Result<(), String> {
+        let mut writer = match columns {
+            Some(columns) => CsvWriter::with_columns(out, columns).map_err(|e| e.to_string())?,
+            None => CsvWriter::new(out),
+        };
+
+        let parser =
+            monch_io::InputParser::new(input).with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+        for item in parser {
+            let item = item.map_err(|e| e.to_string())?;
+            writer.write_map(item).map_err(|e| e.to_string())?;
+        }
+
+        writer.finish().map(drop).map_err(|e| e.to_string())
+    }
+}
+
+impl exe::Execute for To {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let (target, columns) = match To::parse_args(args) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                let _ = writeln!(ios.stderr, "{}", err);
+                exit!(Exit::FAILURE)
+            }
+        };
+
+        // Color depends on where the text is actually going, not where it'd usually go.
+        let use_color = int.use_color(&ios.stdout);
+
+        let worker = thread::spawn(move || {
+            match target {
+                // Format CBOR as text
+                DataFormat::Tty => {
+                    let mut parser = monch_io::InputParser::new(ios.stdin)
+                        .with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+                    let mut header_shown = false;
+
+                    // Loop over input data
+                    loop {
+                        let item = parser.next();
+
+                        // The header is read along with the first item, so show it before that.
+                        if let (Some(header), false) = (parser.header(), header_shown) {
+                            let summary = format!("# {}", header);
+                            let _ = if use_color {
+                                writeln!(ios.stdout, "{}", summary.dimmed())
+                            } else {
+                                writeln!(ios.stdout, "{}", summary)
+                            };
+                            header_shown = true;
+                        }
+
+                        let data = match item {
+                            None => break,
+                            Some(Err(e)) => {
+                                let _ = writeln!(ios.stderr, "to: tty: {}", e);
+                                return Exit::FAILURE;
+                            }
+                            Some(Ok(d)) => d,
+                        };
+
+                        // Output the item
+                        if let Err(e) = format_cbor_opts(&mut ios.stdout, &data, use_color) {
+                            let _ = writeln!(ios.stderr, "to: {}", e);
+                            return Exit::FAILURE;
+                        }
+
+                        let _ = writeln!(ios.stderr);
+                    }
+
+                    Exit::SUCCESS
+                }
+
+                // Write each item on a line of its own
+                DataFormat::Text | DataFormat::Json => {
+                    let render: fn(Value) -> _ = match target {
+                        DataFormat::Json => To::json_line,
+                        _ => cbor_to_text,
+                    };
+                    let parser = monch_io::InputParser::new(ios.stdin)
+                        .with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+
+                    for item in parser {
+                        let line = match item {
+                            Ok(value) => render(value).map_err(|e| e.to_string()),
+                            Err(e) => Err(e.to_string()),
+                        };
+                        let line = match line {
+                            Ok(line) => line,
+                            Err(e) => {
+                                let _ = writeln!(ios.stderr, "to: {}: {}", target, e);
+                                return Exit::FAILURE;
+                            }
+                        };
+
+                        if let Err(e) = writeln!(ios.stdout, "{}", line) {
+                            let _ = writeln!(ios.stderr, "to: {}", e);
+                            return Exit::FAILURE;
+                        }
+                    }
+
+                    Exit::SUCCESS
+                }
+
+                DataFormat::Csv => match To::write_csv(ios.stdin, &mut ios.stdout, columns) {
+                    Ok(()) => Exit::SUCCESS,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "to: csv: {}", e);
+                        Exit::FAILURE
+                    }
+                },
+
+                // Pass through CBOR unchanged
+                DataFormat::Cbor => io::copy(&mut ios.stdin, &mut ios.stdout)
+                    .map(|_| Exit::SUCCESS)
+                    .unwrap_or(Exit::FAILURE),
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, args: &exe::Args) -> Ty {
+        To::parse_args(args).map_or(Ty::Nothing, |(format, _)| format.ty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exe::Execute;
+    use crate::testing::{run, scratch_dir};
+    use std::{env, fs};
+
+    #[test]
+    fn to_text_lines() {
+        let dir = scratch_dir("to_text_lines");
+        let mut items = vec![];
+        let values = [
+            Value::Text("plain text".into()),
+            Value::Integer((-3).into()),
+            Value::Float(1.5),
+            Value::Bool(true),
+            Value::Null,
+            Value::Array(vec![Value::Integer(1.into()), Value::Text("a".into())]),
+            Value::Map(vec![(Value::Text("k".into()), Value::Bool(false))]),
+            Value::Tag(1, Box::new(Value::Integer(1000.into()))),
+        ];
+        for value in &values {
+            ciborium::ser::into_writer(value, &mut items).unwrap();
+        }
+        fs::write(dir.join("items.cbor"), &items).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let (out, exit) = run(&mut int, &["to text <items.cbor"]);
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(
+            out,
+            "plain text\n-3\n1.5\ntrue\nnull\n[1,\"a\"]\n{\"k\":false}\n1000\n"
+        );
+
+        // Binary data has no plain text form.
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&Value::Bytes(vec![1, 2]), &mut bytes).unwrap();
+        fs::write(dir.join("bytes.cbor"), &bytes).unwrap();
+        assert_eq!(run(&mut int, &["to text <bytes.cbor"]).1, Exit::FAILURE);
+
+        assert_eq!(To.output_type(&vec!["text".to_string()]), Ty::Text);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn to_tty_colors() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let escapes = |out: &str| out.contains('\x1b');
+
+        // Output that isn't going to a terminal is plain.
+        let (out, _) = run(&mut int, &["unset NO_COLOR CLICOLOR_FORCE", "set | to tty"]);
+        assert!(out.contains("errexit"));
+        assert!(!escapes(&out), "{:?}", out);
+
+        // CLICOLOR_FORCE colors it anyway, unless NO_COLOR says otherwise.
+        let (out, _) = run(&mut int, &["set CLICOLOR_FORCE 1", "set | to tty"]);
+        assert!(escapes(&out), "{:?}", out);
+        let (out, _) = run(&mut int, &["set NO_COLOR 1", "set | to tty"]);
+        assert!(!escapes(&out), "{:?}", out);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+
+register_builtin!("true", True, "do nothing, successfully");
+
+/// `true` does nothing, successfully.
+pub struct True;
+
+impl exe::Execute for True {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        _ios: Streams,
+        _args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::r#false::False;
+    use crate::testing::{run, run_builtin};
+    use std::env;
+
+    #[test]
+    fn true_and_false() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        assert_eq!(
+            run_builtin(&True, &mut int, &[], &[]),
+            (vec![], String::new(), Exit::SUCCESS)
+        );
+        assert_eq!(
+            run_builtin(&False, &mut int, &[], &[]),
+            (vec![], String::new(), Exit::FAILURE)
+        );
+
+        // They're found before any programs of the same name.
+        let (out, _) = run(&mut int, &["which true false"]);
+        assert!(!out.contains('/'), "{}", out);
+    }
+}
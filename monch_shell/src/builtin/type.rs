@@ -0,0 +1,140 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use std::io::Write;
+
+register_builtin!(
+    "type",
+    Type,
+    "describe how a command name would be interpreted"
+);
+
+/// `type NAME...` describes what each command name resolves to, with a map like
+/// `{name, kind, path, input_type, output_type}`. It uses the same lookup as running the command,
+/// so the answer is always the truth.
+pub struct Type;
+
+impl Type {
+    /// Describe what `name` resolved to, or that it wasn't found.
+    fn describe(name: &str, resolved: Option<exe::Resolved>) -> Value {
+        let text = |s: &str| Value::Text(s.to_string());
+
+        let (kind, path, input_type, output_type) = match resolved {
+            Some(resolved) => {
+                let no_args = exe::Args::new();
+                (
+                    text(&resolved.kind.to_string()),
+                    match resolved.path {
+                        Some(path) => text(&path.to_string_lossy()),
+                        None => Value::Null,
+                    },
+                    text(&resolved.exe.input_type(&no_args).to_string()),
+                    text(&resolved.exe.output_type(&no_args).to_string()),
+                )
+            }
+            None => (text("not found"), Value::Null, Value::Null, Value::Null),
+        };
+
+        Value::Map(vec![
+            (text("name"), text(name)),
+            (text("kind"), kind),
+            (text("path"), path),
+            (text("input_type"), input_type),
+            (text("output_type"), output_type),
+        ])
+    }
+}
+
+impl exe::Execute for Type {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let mut exit = Exit::SUCCESS;
+        for name in args {
+            let resolved = match int.lookup_exe(name) {
+                Ok(resolved) => Some(resolved),
+
+                // Commands which don't exist are described, but still count as a failure.
+                Err(Error::ResolveBinary {
+                    source: which::Error::CannotFindBinaryPath,
+                    ..
+                }) => {
+                    exit = Exit::FAILURE;
+                    None
+                }
+
+                Err(e) => {
+                    let _ = writeln!(ios.stderr, "monch: type: {}", e);
+                    exit = Exit::FAILURE;
+                    continue;
+                }
+            };
+
+            let description = Type::describe(name, resolved);
+            if let Err(e) = ciborium::ser::into_writer(&description, &mut ios.stdout) {
+                let _ = writeln!(ios.stderr, "monch: type: {}", e);
+                exit!(Exit::FAILURE)
+            }
+        }
+
+        exit!(exit)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_builtin, scratch_dir};
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    #[test]
+    fn type_describes_resolution() {
+        let dir = scratch_dir("type_describes_resolution");
+        let monch_bin = dir.join("monch_bin");
+        fs::create_dir(&monch_bin).unwrap();
+        fs::copy("/bin/true", monch_bin.join("mtrue")).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_var("MONCH_PATH", monch_bin.to_string_lossy());
+
+        let (out, _, exit) =
+            run_builtin(&Type, &mut int, &["cd", "mtrue", "sh", "nonexistent"], &[]);
+        assert_eq!(exit, Exit::FAILURE);
+
+        let mut descriptions = vec![];
+        let mut reader = &out[..];
+        while !reader.is_empty() {
+            let d: BTreeMap<String, Option<String>> =
+                ciborium::de::from_reader(&mut reader).unwrap();
+            descriptions.push(d);
+        }
+
+        let field = |i: usize, key: &str| descriptions[i][key].clone();
+        assert_eq!(field(0, "kind").unwrap(), "builtin");
+        assert_eq!(field(0, "path"), None);
+
+        assert_eq!(field(1, "kind").unwrap(), "monch");
+        assert_eq!(
+            field(1, "path").unwrap(),
+            monch_bin.join("mtrue").to_string_lossy()
+        );
+        assert_eq!(field(1, "output_type").unwrap(), "cbor");
+
+        assert_eq!(field(2, "kind").unwrap(), "external");
+        assert_eq!(field(3, "name").unwrap(), "nonexistent");
+        assert_eq!(field(3, "kind").unwrap(), "not found");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
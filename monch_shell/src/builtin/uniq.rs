@@ -0,0 +1,247 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::{canonicalize, DataPath, StreamHeader};
+use std::collections::HashMap;
+use std::io::Write;
+use std::thread;
+
+register_builtin!("uniq", Uniq, "pass on items without repeats");
+
+/// `uniq` passes its input on without repeats: each run of equal items is passed on as just the
+/// first one. With `--all`, only the first of all the equal items is passed on, wherever they
+/// are, and with `--count`, each is passed on as a `{value, count}` map saying how many there
+/// were. With `-f PATH`, items are compared by just the value at PATH, but still passed on whole.
+///
+/// Items are compared by their canonical encoding, so maps are equal whatever order their keys
+/// are in. `--all` remembers the encoding of every distinct item, so it can use a lot of memory.
+pub struct Uniq;
+
+/// How `uniq` was asked to compare and pass on items.
+#[derive(Debug, Default)]
+struct UniqArgs {
+    all: bool,
+    count: bool,
+    field: Option<DataPath>,
+}
+
+impl Uniq {
+    /// Parse the arguments of a `uniq` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<UniqArgs, String> {
+        let mut parsed = UniqArgs::default();
+
+        let mut args = args.iter().map(String::as_str);
+        while let Some(arg) = args.next() {
+            match arg {
+                "--all" => parsed.all = true,
+                "--count" => parsed.count = true,
+                "-f" | "--field" => {
+                    let path = args.next().ok_or("`--field` needs a path")?;
+                    let path = DataPath::parse_strict(path).map_err(|e| e.to_string())?;
+                    parsed.field = Some(path);
+                }
+                other => return Err(format!("unexpected argument `{}`", other)),
+            }
+        }
+
+        Ok(parsed)
+    }
+}
+
+impl exe::Execute for Uniq {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let args = match Uniq::parse_args(args) {
+            Ok(args) => args,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: uniq: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let worker = thread::spawn(move || {
+            let mut parser = monch_io::InputParser::<Value, _>::new(ios.stdin)
+                .with_max_item_size(monch_io::DEFAULT_MAX_ITEM_SIZE);
+            let mut write = |value: &Value| ciborium::ser::into_writer(value, &mut ios.stdout);
+            let counted = |(value, count): (Value, u64)| {
+                Value::Map(vec![
+                    (Value::Text("value".into()), value),
+                    (Value::Text("count".into()), Value::Integer(count.into())),
+                ])
+            };
+
+            // Each group of equal items is numbered in the order it started in. With `--all`,
+            // every key's group is remembered, but otherwise only the one before.
+            let mut groups = 0;
+            let mut group_of = HashMap::new();
+            let mut previous = None;
+
+            // With `--count`, the first item of each group that might still grow, and how many
+            // items are in it so far.
+            let mut counts: Vec<(Value, u64)> = vec![];
+
+            let mut read = 0;
+            while let Some(item) = parser.next() {
+                let item = match item {
+                    Ok(item) => item,
+                    Err(e) => {
+                        let _ = writeln!(ios.stderr, "monch: uniq: {}", e);
+                        return Exit::FAILURE;
+                    }
+                };
+                read += 1;
+
+                // The header's read along with the first item, so pass it on before anything.
+                // Counted items are maps of the item and its count, so they get a new header.
+                let header = parser.header().filter(|_| read == 1).map(|header| {
+                    if args.count {
+                        StreamHeader::new(header.type_name.clone(), &["value", "count"])
+                    } else {
+                        header.clone()
+                    }
+                });
+                if header.map_or(Ok(()), |h| write(&h.to_value())).is_err() {
+                    return Exit::FAILURE;
+                }
+
+                let compared = match &args.field {
+                    Some(path) => path.get_ref(&item).cloned().unwrap_or(Value::Null),
+                    None => item.clone(),
+                };
+                let mut key = vec![];
+                if let Err(e) = ciborium::ser::into_writer(&canonicalize(compared), &mut key) {
+                    let _ = writeln!(ios.stderr, "monch: uniq: {}", e);
+                    return Exit::FAILURE;
+                }
+
+                let group = if args.all {
+                    *group_of.entry(key).or_insert(groups)
+                } else if previous.as_ref() == Some(&key) {
+                    groups - 1
+                } else {
+                    previous = Some(key);
+                    groups
+                };
+
+                let new = group == groups;
+                if new {
+                    groups += 1;
+                }
+
+                if !args.count {
+                    if new && write(&item).is_err() {
+                        return Exit::FAILURE;
+                    }
+                } else if new {
+                    // A run of adjacent items can't grow once another one starts.
+                    if !args.all {
+                        if let Some(run) = counts.pop() {
+                            if write(&counted(run)).is_err() {
+                                return Exit::FAILURE;
+                            }
+                        }
+                    }
+                    counts.push((item, 1));
+                } else {
+                    let i = if args.all { group } else { 0 };
+                    counts[i].1 += 1;
+                }
+            }
+
+            match counts.into_iter().map(counted).try_for_each(|c| write(&c)) {
+                Ok(()) => Exit::SUCCESS,
+                Err(_) => Exit::FAILURE,
+            }
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{encode, run_builtin, run_with_input, scratch_dir};
+    use std::{env, fs};
+
+    #[test]
+    fn uniq_items() {
+        let dir = scratch_dir("uniq_items");
+        let text = |s: &str| Value::Text(s.to_string());
+        let number = |n: u64| Value::Integer(n.into());
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut uniq = |args: &str, items: &[Value]| {
+            let line = format!("uniq {} | to json", args);
+            run_with_input(&mut int, &line, &encode(items))
+        };
+
+        // Only adjacent repeats are dropped, unless it's asked to drop all of them.
+        let numbers = [1, 1, 2, 1, 1, 1].map(number);
+        assert_eq!(uniq("", &numbers), ("1\n2\n1\n".into(), Exit::SUCCESS));
+        assert_eq!(uniq("--all", &numbers).0, "1\n2\n");
+
+        assert_eq!(
+            uniq("--count", &numbers).0,
+            "{\"value\":1,\"count\":2}\n{\"value\":2,\"count\":1}\n{\"value\":1,\"count\":3}\n"
+        );
+        assert_eq!(
+            uniq("--all --count", &numbers).0,
+            "{\"value\":1,\"count\":5}\n{\"value\":2,\"count\":1}\n"
+        );
+
+        // Maps are equal whatever order their keys are in.
+        let ab = Value::Map(vec![(text("a"), number(1)), (text("b"), number(2))]);
+        let ba = Value::Map(vec![(text("b"), number(2)), (text("a"), number(1))]);
+        let ac = Value::Map(vec![(text("a"), number(1)), (text("c"), number(3))]);
+        assert_eq!(
+            uniq("", &[ab.clone(), ba, ac.clone()]).0,
+            "{\"a\":1,\"b\":2}\n{\"a\":1,\"c\":3}\n"
+        );
+
+        // Comparing by a field still passes on the whole item.
+        assert_eq!(uniq("-f .a", &[ab, ac]).0, "{\"a\":1,\"b\":2}\n");
+
+        assert_eq!(uniq("", &[]).0, "");
+        assert_eq!(uniq("-f", &[]).1, Exit::BAD_SYNTAX);
+        assert_eq!(uniq("things", &[]).1, Exit::BAD_SYNTAX);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn uniq_count_header() {
+        let header = StreamHeader::new("entry", &["name"]);
+        let mut input = vec![];
+        monch_io::write_header_to(&mut input, &header).unwrap();
+        input.extend(encode(&[Value::Text("a".into()), Value::Text("a".into())]));
+
+        // Counted items aren't entries any more, so the header says what they are instead.
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let (out, _, exit) = run_builtin(&Uniq, &mut int, &["--count"], &input);
+        assert_eq!(exit, Exit::SUCCESS);
+        let mut parser = monch_io::InputParser::<Value, _>::new(&out[..]);
+        assert!(parser.next().is_some());
+        assert_eq!(
+            parser.header(),
+            Some(&StreamHeader::new("entry", &["value", "count"]))
+        );
+
+        // Otherwise, it's passed on as it was.
+        let (out, _, _) = run_builtin(&Uniq, &mut int, &[], &input);
+        let mut parser = monch_io::InputParser::<Value, _>::new(&out[..]);
+        assert!(parser.next().is_some());
+        assert_eq!(parser.header(), Some(&header));
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+
+register_builtin!("unset", Unset, "remove variables");
+
+/// `unset NAME...` removes variables.
+pub struct Unset;
+
+impl exe::Execute for Unset {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        _ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        for name in args {
+            int.unset_var(name);
+        }
+
+        exit!(Exit::SUCCESS)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn is_synchronous(&self, _: &exe::Args) -> bool {
+        true
+    }
+}
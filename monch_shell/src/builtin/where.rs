@@ -0,0 +1,285 @@
+use super::map_items;
+use super::number::Number;
+use crate::streams::WriteStream;
+use crate::{exe, suggest, types::Ty, Error, Exit, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_io::{DataPath, StreamHeader};
+use std::cmp::Ordering;
+use std::io::Write;
+
+register_builtin!(
+    "where",
+    Where,
+    "pass on only the items matching a predicate"
+);
+
+/// `where PATH OP VALUE` passes on only the items where the value at PATH compares to VALUE as
+/// OP says, which is one of `eq`, `ne`, `gt`, `lt`, `ge`, `le`, or `contains`. There's also
+/// `where PATH exists`, which takes no VALUE, and any OP can be negated, like `where PATH not eq
+/// VALUE`. More predicates can follow, and items have to match all of them.
+///
+/// Values compare as numbers if both sides look like one, and as text otherwise. `contains`
+/// looks for VALUE in text, among the items of an array, or among the keys of a map. A missing or
+/// `null` value fails every predicate except `not exists`.
+pub struct Where;
+
+/// An operator of a `where` predicate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WhereOp {
+    Eq,
+    Ne,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Contains,
+    Exists,
+}
+
+/// The names of the `where` operators, and what each one is.
+const WHERE_OPS: [(&str, WhereOp); 8] = [
+    ("eq", WhereOp::Eq),
+    ("ne", WhereOp::Ne),
+    ("gt", WhereOp::Gt),
+    ("lt", WhereOp::Lt),
+    ("ge", WhereOp::Ge),
+    ("le", WhereOp::Le),
+    ("contains", WhereOp::Contains),
+    ("exists", WhereOp::Exists),
+];
+
+/// One predicate that items have to match to get through `where`.
+#[derive(Debug)]
+struct Predicate {
+    path: DataPath,
+    negated: bool,
+    op: WhereOp,
+    operand: String,
+}
+
+impl Predicate {
+    /// Whether an item matches this predicate.
+    fn matches(&self, item: &Value) -> bool {
+        let value = match self.path.get_ref(item) {
+            None | Some(Value::Null) => return self.negated && self.op == WhereOp::Exists,
+            Some(value) => value,
+        };
+
+        let order = compare_operand(value, &self.operand);
+        let matched = match self.op {
+            WhereOp::Eq => order == Some(Ordering::Equal),
+            WhereOp::Ne => order != Some(Ordering::Equal),
+            WhereOp::Gt => order == Some(Ordering::Greater),
+            WhereOp::Lt => order == Some(Ordering::Less),
+            WhereOp::Ge => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+            WhereOp::Le => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+            WhereOp::Contains => match value {
+                Value::Text(text) => text.contains(&self.operand),
+                Value::Array(items) => items
+                    .iter()
+                    .any(|item| compare_operand(item, &self.operand) == Some(Ordering::Equal)),
+                Value::Map(pairs) => pairs
+                    .iter()
+                    .any(|(key, _)| compare_operand(key, &self.operand) == Some(Ordering::Equal)),
+                _ => false,
+            },
+            WhereOp::Exists => true,
+        };
+
+        matched != self.negated
+    }
+}
+
+/// Compare a value to the text of an operand: as numbers if they both look like one, and
+/// otherwise as text. Values that have no text form, like maps, don't compare to anything.
+fn compare_operand(value: &Value, operand: &str) -> Option<Ordering> {
+    let number = match value {
+        Value::Text(text) => Number::parse(text),
+        other => Number::of(other),
+    };
+
+    if let (Some(a), Some(b)) = (number, Number::parse(operand)) {
+        return a.partial_cmp(&b);
+    }
+
+    let text = match value {
+        Value::Text(text) => text.clone(),
+        Value::Integer(i) => i128::from(*i).to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Bool(b) => b.to_string(),
+        _ => return None,
+    };
+    Some(text.as_str().cmp(operand))
+}
+
+impl Where {
+    /// Parse the predicates of a `where` invocation, or describe what's wrong with them.
+    fn parse_args(args: &[String]) -> Result<Vec<Predicate>, String> {
+        let mut predicates = vec![];
+
+        let mut args = args.iter().map(String::as_str).peekable();
+        while let Some(path) = args.next() {
+            let path = DataPath::parse_strict(path)
+                .map_err(|e| format!("`{}` isn't a path: {}", path, e))?;
+
+            let negated = args.next_if_eq(&"not").is_some();
+            let op_name = args.next().ok_or("expected an operator after the path")?;
+            let op = match WHERE_OPS.iter().find(|(name, _)| *name == op_name) {
+                Some((_, op)) => *op,
+                None => {
+                    // The operators are all short, so only suggest the ones that are one typo away.
+                    let names: Vec<_> = WHERE_OPS.iter().map(|(name, _)| *name).collect();
+                    let mut suggestions = suggest::suggest(op_name, names.iter().copied());
+                    suggestions.retain(|name| suggest::levenshtein(op_name, name) == 1);
+
+                    let mut message = format!("unknown operator `{}`", op_name);
+                    if suggestions.is_empty() {
+                        message += ".";
+                    } else {
+                        message += &format!(", {}", suggest::did_you_mean(&suggestions));
+                    }
+                    message += &format!(" The operators are {}.", names.join(", "));
+                    return Err(message);
+                }
+            };
+
+            let operand = match op {
+                WhereOp::Exists => String::new(),
+                _ => args
+                    .next()
+                    .ok_or_else(|| format!("`{}` needs a value to compare with", op_name))?
+                    .to_string(),
+            };
+
+            predicates.push(Predicate {
+                path,
+                negated,
+                op,
+                operand,
+            });
+        }
+
+        if predicates.is_empty() {
+            return Err("expected a predicate, like `where .kind eq Dir`".to_string());
+        }
+
+        Ok(predicates)
+    }
+}
+
+impl exe::Execute for Where {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let predicates = match Where::parse_args(args) {
+            Ok(predicates) => predicates,
+            Err(e) => {
+                let _ = writeln!(ios.stderr, "monch: where: {}", e);
+                exit!(Exit::BAD_SYNTAX)
+            }
+        };
+
+        let map_header = |header: &StreamHeader| Some(header.clone());
+        let filter = move |_, item, _: &mut WriteStream| {
+            let matched = predicates.iter().all(|p| p.matches(&item));
+            Ok(Some(item).filter(|_| matched))
+        };
+
+        Ok(map_items("where", ios, map_header, filter))
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Cbor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{run_with_input, scratch_dir};
+    use std::fs;
+
+    #[test]
+    fn where_predicates() {
+        let dir = scratch_dir("where_predicates");
+        let input = r#"{"id": 0, "n": 5, "s": "apple", "tags": ["a", 1]}
+                       {"id": 1, "n": 10.5, "s": "banana", "tags": []}
+                       {"id": 2, "n": "7", "s": "cherry", "m": {"k": 1}}
+                       {"id": 3, "n": null, "s": "Apple"}
+                       {"id": 4, "s": "10"}"#;
+
+        // Run `where` over the items, and return the ids of the ones which got through.
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_pipefail(true);
+        let mut ids = |predicates: &str| {
+            let line = format!("from json | where {} | to text", predicates);
+            let (out, exit) = run_with_input(&mut int, &line, input.as_bytes());
+            let ids = out
+                .lines()
+                .map(|line| {
+                    serde_json::from_str::<serde_json::Value>(line).unwrap()["id"].to_string()
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            (ids, exit)
+        };
+
+        // Numbers compare as numbers, even when they're text, and other things compare as text.
+        assert_eq!(ids(".n eq 5"), ("0".into(), Exit::SUCCESS));
+        assert_eq!(ids(".n eq 5.0").0, "0");
+        assert_eq!(ids(".n ne 5").0, "1 2");
+        assert_eq!(ids(".n gt 6").0, "1 2");
+        assert_eq!(ids(".n lt 7").0, "0");
+        assert_eq!(ids(".n ge 7").0, "1 2");
+        assert_eq!(ids(".n le 7").0, "0 2");
+        assert_eq!(ids(".s eq apple").0, "0");
+        assert_eq!(ids(".s gt b").0, "1 2");
+        assert_eq!(ids(".s lt 11").0, "4");
+
+        // Text contains substrings, arrays contain items, and maps contain keys.
+        assert_eq!(ids(".s contains an").0, "1");
+        assert_eq!(ids(".tags contains a").0, "0");
+        assert_eq!(ids(".tags contains 1").0, "0");
+        assert_eq!(ids(".m contains k").0, "2");
+        assert_eq!(ids(".n contains 5").0, "");
+
+        // Missing and null values fail everything but `not exists`.
+        assert_eq!(ids(".n exists").0, "0 1 2");
+        assert_eq!(ids(".n not exists").0, "3 4");
+        assert_eq!(ids(".n not eq 5").0, "1 2");
+        assert_eq!(ids(".tags not contains a").0, "1");
+
+        // Every predicate has to match.
+        assert_eq!(ids(".n gt 1 .s contains an").0, "1");
+        assert_eq!(ids(".n gt 1 .s lt a").0, "");
+
+        assert_eq!(ids("").1, Exit::BAD_SYNTAX);
+        assert_eq!(ids(".n").1, Exit::BAD_SYNTAX);
+        assert_eq!(ids(".n eq").1, Exit::BAD_SYNTAX);
+        assert_eq!(ids(".n eq 1 .s").1, Exit::BAD_SYNTAX);
+        assert_eq!(ids("..n exists").1, Exit::BAD_SYNTAX);
+
+        // Unknown operators are explained, with a fix if there's an obvious one.
+        let error = |args: &[&str]| {
+            let args: Vec<_> = args.iter().map(|a| a.to_string()).collect();
+            Where::parse_args(&args).unwrap_err()
+        };
+        assert_eq!(
+            error(&[".n", "eqq", "1"]),
+            "unknown operator `eqq`, did you mean `eq`? \
+             The operators are eq, ne, gt, lt, ge, le, contains, exists."
+        );
+        assert_eq!(
+            error(&[".n", "is", "1"]),
+            "unknown operator `is`. The operators are eq, ne, gt, lt, ge, le, contains, exists."
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{exe, types::Ty, Error, Exit, Interpreter, Streams};
+use std::io::Write;
+
+register_builtin!("which", Which, "show where commands are found");
+
+/// `which NAME...` prints the path of each command, or says it's built in.
+pub struct Which;
+
+impl exe::Execute for Which {
+    fn execute(
+        &self,
+        int: &mut Interpreter,
+        mut ios: Streams,
+        args: &exe::Args,
+    ) -> Result<Box<dyn exe::Wait>, Error> {
+        let mut exit = Exit::SUCCESS;
+        for name in args {
+            match int.lookup_exe(name) {
+                Ok(exe::Resolved {
+                    path: Some(path), ..
+                }) => {
+                    let _ = writeln!(ios.stdout, "{}", path.display());
+                }
+                Ok(resolved) => {
+                    let _ = writeln!(ios.stdout, "{}: {}", name, resolved.kind);
+                }
+                Err(e) => {
+                    let _ = writeln!(ios.stderr, "monch: which: {}", e);
+                    exit = Exit::FAILURE;
+                }
+            }
+        }
+
+        exit!(exit)
+    }
+
+    fn input_type(&self, _: &exe::Args) -> Ty {
+        Ty::Nothing
+    }
+
+    fn output_type(&self, _: &exe::Args) -> Ty {
+        Ty::Text
+    }
+}
@@ -1,20 +1,28 @@
 use ciborium::value::Value;
-use owo_colors::OwoColorize;
+use owo_colors::{OwoColorize, Style};
 use std::io;
 
-/// Write a human-readable inline description of the CBOR object to the output writer
-pub fn format_cbor<'w>(out: &mut impl io::Write, val: &Value) -> io::Result<()> {
+/// Write a human-readable inline description of the CBOR object to the output writer, colored
+/// if `use_color` is set.
+pub fn format_cbor_opts(out: &mut impl io::Write, val: &Value, use_color: bool) -> io::Result<()> {
+    // A plain style writes no escape codes at all.
+    let paint = |style: Style| if use_color { style } else { Style::new() };
+    let green = paint(Style::new().green());
+    let purple = paint(Style::new().purple());
+    let italic = paint(Style::new().italic());
+    let dimmed = paint(Style::new().dimmed());
+
     use Value::*;
     match val {
-        Float(f) => write!(out, "{:0.3}", f.green()),
-        Integer(i) => write!(out, "{}", i128::from(*i).green()),
+        Float(f) => write!(out, "{:0.3}", f.style(green)),
+        Integer(i) => write!(out, "{}", i128::from(*i).style(green)),
         Text(s) => write!(out, "{}", s),
-        Bool(b) => write!(out, "{}", b.purple()),
-        Bytes(_) => write!(out, "{}", "(binary data)".italic()),
-        Null => write!(out, "{}", "(null)".italic()),
+        Bool(b) => write!(out, "{}", b.style(purple)),
+        Bytes(_) => write!(out, "{}", "(binary data)".style(italic)),
+        Null => write!(out, "{}", "(null)".style(italic)),
         Tag(t, inner) => {
-            write!(out, "{}", format!("(tag {}) ", t).italic())?;
-            format_cbor(out, inner)
+            write!(out, "{}", format!("(tag {}) ", t).style(italic))?;
+            format_cbor_opts(out, inner, use_color)
         }
 
         Array(arr) => {
@@ -24,29 +32,29 @@ pub fn format_cbor<'w>(out: &mut impl io::Write, val: &Value) -> io::Result<()>
                     write!(out, ", ")?;
                 }
 
-                format_cbor(out, item)?;
+                format_cbor_opts(out, item, use_color)?;
             }
             write!(out, "]")
         }
 
         Map(pairs) => {
-            write!(out, "{}", "{".dimmed())?;
+            write!(out, "{}", "{".style(dimmed))?;
             for (i, (k, v)) in pairs.iter().enumerate() {
                 if i != 0 {
-                    write!(out, "{}", ", ".dimmed())?;
+                    write!(out, "{}", ", ".style(dimmed))?;
                 }
 
                 match k {
-                    Value::Text(s) => write!(out, "{}", format!("{}: ", s).dimmed())?,
+                    Value::Text(s) => write!(out, "{}", format!("{}: ", s).style(dimmed))?,
                     _ => {
                         write!(out, ": ")?;
-                        format_cbor(out, k)?;
+                        format_cbor_opts(out, k, use_color)?;
                     }
                 }
 
-                format_cbor(out, v)?;
+                format_cbor_opts(out, v, use_color)?;
             }
-            write!(out, "{}", "}".dimmed())
+            write!(out, "{}", "}".style(dimmed))
         }
 
         _ => write!(out, "[cannot display]"),
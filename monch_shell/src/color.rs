@@ -0,0 +1,36 @@
+//! Whether to color output, following the `NO_COLOR` and `CLICOLOR_FORCE` conventions.
+
+/// Decide whether to color output going to a stream, given the values of `NO_COLOR` and
+/// `CLICOLOR_FORCE`, and whether the stream is a terminal.
+///
+/// `NO_COLOR` turns color off, and `CLICOLOR_FORCE` turns it on even when the stream isn't a
+/// terminal. Either is ignored if it's empty, and `CLICOLOR_FORCE` is ignored if it's `0`.
+pub fn use_color(no_color: Option<&str>, clicolor_force: Option<&str>, is_terminal: bool) -> bool {
+    match (no_color, clicolor_force) {
+        (Some(no), _) if !no.is_empty() => false,
+        (_, Some(force)) if !force.is_empty() && force != "0" => true,
+        _ => is_terminal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy() {
+        // Without either variable, only terminals get color.
+        assert!(use_color(None, None, true));
+        assert!(!use_color(None, None, false));
+
+        // NO_COLOR wins over everything, unless it's empty.
+        assert!(!use_color(Some("1"), None, true));
+        assert!(!use_color(Some("1"), Some("1"), true));
+        assert!(use_color(Some(""), None, true));
+
+        // CLICOLOR_FORCE colors anything, unless it's empty or 0.
+        assert!(use_color(None, Some("1"), false));
+        assert!(!use_color(None, Some("0"), false));
+        assert!(!use_color(None, Some(""), false));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::suggest::did_you_mean;
 use crate::{exe::Exit, types::Ty};
 use std::io;
 use thiserror::Error;
@@ -7,11 +8,17 @@ pub enum Error {
     #[error("{0}")]
     Io(#[from] io::Error),
 
-    #[error("execution of child process failed: {0}")]
-    ExecutionFailed(io::Error),
+    #[error("{cmd}: couldn't execute: {source}")]
+    ExecutionFailed { cmd: String, source: io::Error },
 
-    #[error("{cmd}: command not found: {source}")]
-    ResolveBinary { cmd: String, source: which::Error },
+    #[error("{cmd}: command not found: {source}{}", hint(.suggestions))]
+    ResolveBinary {
+        cmd: String,
+        source: which::Error,
+
+        /// Commands with similar names, closest first.
+        suggestions: Vec<String>,
+    },
 
     #[error("invalid working directory '{0}'")]
     BadWorkingDirectory(String),
@@ -25,6 +32,17 @@ pub enum Error {
         r_cmd: String,
         r_ty: Ty,
     },
+
+    #[error("{cmd}: can't be used in a pipeline, since it changes the shell itself")]
+    NotPipeable { cmd: String },
+}
+
+/// Tack suggestions onto the end of an error message, if there are any.
+fn hint(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        _ => format!("; {}", did_you_mean(suggestions)),
+    }
 }
 
 impl Error {
@@ -32,7 +50,8 @@ impl Error {
         match self {
             Error::Io(_) => Exit::FAILURE,
             Error::TypeMismatch { .. } => Exit::BAD_SYNTAX,
-            Error::ExecutionFailed(_) => Exit::COULD_NOT_EXECUTE,
+            Error::NotPipeable { .. } => Exit::BAD_SYNTAX,
+            Error::ExecutionFailed { .. } => Exit::COULD_NOT_EXECUTE,
             Error::ResolveBinary { .. } => Exit::COMMAND_NOT_FOUND,
             Error::BadWorkingDirectory(_) => Exit::FAILURE,
         }
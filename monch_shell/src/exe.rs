@@ -1,6 +1,14 @@
-use crate::{interpreter::Interpreter, streams::Streams, types::Ty, Error};
+use crate::{foreground, interpreter::Interpreter, streams::Streams, types::Ty, Error};
+use serde::{Serialize, Serializer};
+use std::ops::Add;
+#[cfg(unix)]
+use std::os::unix::process::ExitStatusExt;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 use std::{fmt, process, thread};
+#[cfg(unix)]
+use std::{io, mem};
 
 pub type Args = Vec<String>;
 
@@ -22,6 +30,12 @@ pub trait Execute {
 
     /// The type of data we're going to output on stdout.
     fn output_type(&self, _args: &Args) -> Ty;
+
+    /// Whether this changes the shell itself, like `cd`, and so has to run on its own rather
+    /// than as part of a pipeline. If it reads input, it can also come at the end of one.
+    fn is_synchronous(&self, _args: &Args) -> bool {
+        false
+    }
 }
 
 /// Implement [`Execute`] for references to [`Execute`]
@@ -42,6 +56,70 @@ impl<T: Execute + ?Sized> Execute for &T {
     fn output_type(&self, args: &Args) -> Ty {
         (*self).output_type(args)
     }
+
+    fn is_synchronous(&self, args: &Args) -> bool {
+        (*self).is_synchronous(args)
+    }
+}
+
+/// Something which finds executables by name, consulted by the [`Interpreter`] before it looks for
+/// builtins or binaries. Used to run commands without spawning real processes.
+pub trait ExeResolver {
+    /// Find the executable for the command `name`, or `None` to fall back to the usual lookup.
+    fn resolve(&self, name: &str) -> Option<Box<dyn Execute>>;
+}
+
+/// Where a command name was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExeKind {
+    /// Served by the interpreter's [`ExeResolver`].
+    Resolver,
+
+    /// A builtin command.
+    Builtin,
+
+    /// A program on the MONCH_PATH, which reads and writes CBOR.
+    Monch,
+
+    /// A program on the system PATH.
+    External,
+}
+
+impl ExeKind {
+    /// Whether commands found this way run as processes of their own, rather than in ours.
+    pub fn is_program(&self) -> bool {
+        matches!(self, ExeKind::Monch | ExeKind::External)
+    }
+}
+
+impl fmt::Display for ExeKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ExeKind::Resolver => "resolver",
+            ExeKind::Builtin => "builtin",
+            ExeKind::Monch => "monch",
+            ExeKind::External => "external",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Serialize for ExeKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A command name, resolved into something we can run.
+pub struct Resolved {
+    /// Where the command was found.
+    pub kind: ExeKind,
+
+    /// The program's path, unless it's built into the shell.
+    pub path: Option<PathBuf>,
+
+    /// The executable itself.
+    pub exe: Box<dyn Execute>,
 }
 
 /// An implementation of [`Execute`] that will search for an external binary and execute it as a
@@ -49,8 +127,28 @@ impl<T: Execute + ?Sized> Execute for &T {
 pub struct ExternalExecutable {
     binary: PathBuf,
 
+    /// Extra environment variables to set for the child process.
+    env: Vec<(String, String)>,
+
     input_type: Ty,
     output_type: Ty,
+
+    /// A flag which makes the program write plain text instead, if it has one.
+    text_flag: Option<String>,
+}
+
+/// Monch utilities which don't write objects to stdout, by name, and what they write instead.
+const MONCH_OUTPUT_TYPES: &[(&str, Ty)] = &[
+    // `save` writes to a file, so there's nothing to show once it's done.
+    ("save", Ty::Nothing),
+];
+
+/// The type a Monch utility writes to stdout, going by its name. Most of them write CBOR.
+pub fn monch_output_type(name: &str) -> Ty {
+    MONCH_OUTPUT_TYPES
+        .iter()
+        .find(|(utility, _)| *utility == name)
+        .map_or(Ty::Cbor, |&(_, ty)| ty)
 }
 
 impl ExternalExecutable {
@@ -59,6 +157,8 @@ impl ExternalExecutable {
         ExternalExecutable {
             binary: path.as_ref().to_path_buf(),
 
+            env: vec![],
+
             // Default input to Any because that's the status quo: leave it to the program to
             // handle bad input
             input_type: Ty::Any,
@@ -66,6 +166,8 @@ impl ExternalExecutable {
             // Default output to Unknown so you can't pipe it into anything that can't handle an
             // Any
             output_type: Ty::Unknown,
+
+            text_flag: None,
         }
     }
 
@@ -78,6 +180,16 @@ impl ExternalExecutable {
     pub fn set_input_type(&mut self, ty: Ty) {
         self.input_type = ty;
     }
+
+    /// Set a flag which makes this executable write plain text, rather than its usual output type
+    pub fn set_text_flag(&mut self, flag: impl Into<String>) {
+        self.text_flag = Some(flag.into());
+    }
+
+    /// Set an environment variable for the child process
+    pub fn set_env(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.env.push((key.into(), value.into()));
+    }
 }
 
 impl Execute for ExternalExecutable {
@@ -91,6 +203,13 @@ impl Execute for ExternalExecutable {
         let mut cmd = process::Command::new(&self.binary);
         cmd.args(args);
 
+        // Pass on exported variables, and nothing else.
+        cmd.env_clear();
+        cmd.envs(int.exported_vars());
+        cmd.env("PWD", int.current_dir());
+        cmd.env("MONCH_SHELL", "1");
+        cmd.envs(self.env.iter().map(|(k, v)| (k, v)));
+
         // Set the working directory to that of the interpreter
         cmd.current_dir(int.current_dir());
 
@@ -99,24 +218,102 @@ impl Execute for ExternalExecutable {
         cmd.stdout(ios.stdout);
         cmd.stderr(ios.stderr);
 
+        // Run it in the pipeline's process group, so that Ctrl-C reaches it and not us.
+        foreground::set_process_group(&mut cmd, int.process_group());
+
         // Start the child, and return its join handle.
-        let wait_handle = Box::new(cmd.spawn()?);
-        Ok(wait_handle)
+        let child = cmd.spawn()?;
+        let group = foreground::enter_foreground(child.id(), int.process_group());
+        int.set_process_group(group);
+
+        Ok(Box::new(child))
     }
 
     fn input_type(&self, _args: &Args) -> Ty {
         self.input_type
     }
 
-    fn output_type(&self, _args: &Args) -> Ty {
-        self.output_type
+    fn output_type(&self, args: &Args) -> Ty {
+        // Only look at flags, so that an argument after `--` can be anything.
+        let mut flags = args.iter().take_while(|arg| *arg != "--");
+        match &self.text_flag {
+            Some(text_flag) if flags.any(|arg| arg == text_flag) => Ty::Text,
+            _ => self.output_type,
+        }
     }
 }
 
 /// An in-flight process, either an external process, or a thread in the interpreter.
-pub trait Wait {
+///
+/// These can be sent to another thread, so that they can be waited on with a timeout.
+pub trait Wait: Send + 'static {
     /// Block until the process has completed, returning its exit code, or an internal error.
     fn wait(self: Box<Self>) -> Result<Exit, Error>;
+
+    /// Like [`wait`](Wait::wait), but also return the CPU time the process used. By default,
+    /// that's none at all.
+    fn wait_usage(self: Box<Self>) -> Result<(Exit, Usage), Error> {
+        Ok((self.wait()?, Usage::default()))
+    }
+
+    /// Like [`wait`](Wait::wait), but give up once `timeout` has passed, returning
+    /// [`Exit::TIMED_OUT`].
+    ///
+    /// By default, this waits on another thread, which is left to finish on its own if it takes
+    /// too long: threads can't be killed.
+    fn wait_timeout(self: Box<Self>, timeout: Duration) -> Result<Exit, Error> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || sender.send(self.wait()));
+
+        receiver
+            .recv_timeout(timeout)
+            .unwrap_or(Ok(Exit::TIMED_OUT))
+    }
+
+    /// Something which asks the process to stop early, because nothing needs what it's doing any
+    /// more. It's used from other threads while this is being waited on. By default, there isn't
+    /// one: threads can't be stopped.
+    fn terminator(&self) -> Option<Terminator> {
+        None
+    }
+}
+
+/// Stops a process early. See [`Wait::terminator`].
+pub type Terminator = Box<dyn Fn() + Send>;
+
+/// The CPU time used by a process.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// Time spent running the process's own code.
+    pub user: Duration,
+
+    /// Time spent in the kernel, on the process's behalf.
+    pub sys: Duration,
+}
+
+#[cfg(unix)]
+impl Usage {
+    fn from_rusage(rusage: &libc::rusage) -> Usage {
+        let duration = |tv: libc::timeval| {
+            Duration::from_secs(tv.tv_sec as u64) + Duration::from_micros(tv.tv_usec as u64)
+        };
+
+        Usage {
+            user: duration(rusage.ru_utime),
+            sys: duration(rusage.ru_stime),
+        }
+    }
+}
+
+impl Add for Usage {
+    type Output = Usage;
+
+    fn add(self, other: Usage) -> Usage {
+        Usage {
+            user: self.user + other.user,
+            sys: self.sys + other.sys,
+        }
+    }
 }
 
 /// A simple [`Wait`] implementation, which immediately returns an exit code.
@@ -133,6 +330,7 @@ impl Wait for ImmediateProc {
 impl<E> Wait for thread::JoinHandle<E>
 where
     Exit: From<E>,
+    E: Send + 'static,
 {
     fn wait(self: Box<Self>) -> Result<Exit, Error> {
         let thread_result = self
@@ -145,9 +343,81 @@ where
 
 /// Wait for a child process to exit.
 impl Wait for process::Child {
+    #[cfg(unix)]
+    fn wait(self: Box<Self>) -> Result<Exit, Error> {
+        let (exit, _) = self.wait_usage()?;
+        Ok(exit)
+    }
+
+    /// Without `wait4`, there's no resource usage to report, so `wait_usage` reports none.
+    #[cfg(not(unix))]
     fn wait(mut self: Box<Self>) -> Result<Exit, Error> {
-        let status = process::Child::wait(&mut self).map_err(Error::ExecutionFailed)?;
-        Ok(Exit::from(status))
+        Ok(Exit::from(process::Child::wait(&mut self)?))
+    }
+
+    /// Wait with `wait4`, which gives us the child's resource usage as well.
+    #[cfg(unix)]
+    fn wait_usage(self: Box<Self>) -> Result<(Exit, Usage), Error> {
+        let pid = self.id() as libc::pid_t;
+        let mut status = 0;
+
+        // SAFETY: rusage is plain old data, so all zeroes is a valid value.
+        let mut rusage: libc::rusage = unsafe { mem::zeroed() };
+
+        loop {
+            // SAFETY: both pointers are to locals which outlive the call.
+            let waited = unsafe { libc::wait4(pid, &mut status, libc::WUNTRACED, &mut rusage) };
+
+            // Until there's job control to resume it later, a job stopped with Ctrl-Z is started
+            // again straight away, rather than leaving us waiting on it forever.
+            if waited >= 0 && libc::WIFSTOPPED(status) {
+                // SAFETY: kill has no memory-safety requirements.
+                unsafe { libc::kill(pid, libc::SIGCONT) };
+                continue;
+            }
+
+            if waited >= 0 {
+                break;
+            }
+
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err.into());
+            }
+        }
+
+        let exit = Exit::from(process::ExitStatus::from_raw(status));
+        Ok((exit, Usage::from_rusage(&rusage)))
+    }
+
+    /// Poll the child until it exits, killing it if it takes too long.
+    fn wait_timeout(mut self: Box<Self>, timeout: Duration) -> Result<Exit, Error> {
+        let deadline = Instant::now() + timeout;
+
+        while Instant::now() < deadline {
+            if let Some(status) = self.try_wait()? {
+                return Ok(Exit::from(status));
+            }
+
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        // Reap the child once it's dead, so it doesn't linger as a zombie.
+        self.kill()?;
+        process::Child::wait(&mut self)?;
+        Ok(Exit::TIMED_OUT)
+    }
+
+    /// Send the child SIGTERM, so it can clean up after itself. Elsewhere, there's no way to ask
+    /// nicely, so the child is left to finish.
+    #[cfg(unix)]
+    fn terminator(&self) -> Option<Terminator> {
+        let pid = self.id() as libc::pid_t;
+
+        // SAFETY: kill has no memory-safety requirements.
+        Some(Box::new(move || unsafe {
+            libc::kill(pid, libc::SIGTERM);
+        }))
     }
 }
 
@@ -185,6 +455,24 @@ impl Exit {
     /// The exit given when a command binary could not be found.
     pub const COMMAND_NOT_FOUND: Exit = Exit::Code(127);
 
+    /// The exit given when a command took too long, and was stopped. It's the same as GNU
+    /// `timeout`'s.
+    pub const TIMED_OUT: Exit = Exit::Code(124);
+
+    /// The exit of a process stopped early by SIGTERM. Without signals, it's the code a shell
+    /// would give for one.
+    #[cfg(unix)]
+    pub const TERMINATED: Exit = Exit::Signal(libc::SIGTERM as u32);
+    #[cfg(not(unix))]
+    pub const TERMINATED: Exit = Exit::Code(143);
+
+    /// The exit of a process interrupted by SIGINT, like with Ctrl-C. Without signals, it's the
+    /// code a shell would give for one.
+    #[cfg(unix)]
+    pub const INTERRUPTED: Exit = Exit::Signal(libc::SIGINT as u32);
+    #[cfg(not(unix))]
+    pub const INTERRUPTED: Exit = Exit::Code(130);
+
     /// Get the exit code from the process, if there is one.
     pub fn code(&self) -> Option<u32> {
         match self {
@@ -202,6 +490,15 @@ impl Exit {
         }
     }
 
+    /// The status a shell reports for this exit, like in `$?`: the exit code, or 128 plus the
+    /// signal number if the process was killed by a signal.
+    pub fn as_status(&self) -> u32 {
+        match self {
+            Exit::Code(c) => *c,
+            Exit::Signal(s) => 128 + s,
+        }
+    }
+
     /// Returns whether this signal represents a successful exit: a zero status code.
     pub fn success(&self) -> bool {
         *self == Self::SUCCESS
@@ -217,6 +514,12 @@ impl Exit {
     }
 }
 
+impl Default for Exit {
+    fn default() -> Exit {
+        Exit::SUCCESS
+    }
+}
+
 impl fmt::Display for Exit {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -243,7 +546,6 @@ impl From<process::ExitStatus> for Exit {
 
         #[cfg(target_family = "unix")]
         {
-            use std::os::unix::process::ExitStatusExt;
             let signal_number = status.signal()
                 .unwrap_or_else(|| unreachable!("POSIX process exited with neither an exit code nor a signal. This is impossible."));
 
@@ -0,0 +1,158 @@
+//! The foreground pipeline: the process group holding the external commands we're waiting on.
+//!
+//! Each pipeline's external commands run in a process group of their own, so a Ctrl-C or Ctrl-Z
+//! meant for them doesn't reach the shell. While they run, that group gets the terminal, and any
+//! SIGINT the shell receives is passed on to it.
+
+use std::io;
+use std::os::unix::process::CommandExt;
+use std::process;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+
+/// The process group of the pipeline we're waiting on, or 0 if there isn't one.
+static FOREGROUND_GROUP: AtomicI32 = AtomicI32::new(0);
+
+/// Whether we hand the terminal to the foreground pipeline.
+static CONTROLS_TERMINAL: AtomicBool = AtomicBool::new(false);
+
+/// How many times the shell has been interrupted, for builtins to notice while they run.
+static INTERRUPTS: AtomicUsize = AtomicUsize::new(0);
+
+/// Install a SIGINT handler which passes the signal on to the foreground pipeline, and counts it
+/// for any builtins running, rather than letting it kill the shell.
+pub fn install_interrupt_handler() -> io::Result<()> {
+    let handler = forward_interrupt as extern "C" fn(libc::c_int);
+
+    // SAFETY: the handler only does async-signal-safe things.
+    let previous = unsafe { libc::signal(libc::SIGINT, handler as libc::sighandler_t) };
+    if previous == libc::SIG_ERR {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+extern "C" fn forward_interrupt(_signo: libc::c_int) {
+    INTERRUPTS.fetch_add(1, Ordering::SeqCst);
+
+    let group = FOREGROUND_GROUP.load(Ordering::SeqCst);
+    if group > 0 {
+        // SAFETY: kill is async-signal-safe.
+        unsafe { libc::kill(-group, libc::SIGINT) };
+    }
+}
+
+/// How many times the shell has been interrupted since the handler was installed. A builtin which
+/// runs for a while can stop once this changes, like a program would on SIGINT.
+pub(crate) fn interrupts() -> usize {
+    INTERRUPTS.load(Ordering::SeqCst)
+}
+
+/// Give the terminal to each foreground pipeline while it runs, so that programs reading from it
+/// aren't stopped, and Ctrl-C goes straight to them. Does nothing unless stdin is a terminal, and
+/// we're in its foreground.
+pub fn claim_terminal() {
+    // SAFETY: these only look at stdin, and the process group we're in.
+    let in_foreground = unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1
+            && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+    };
+    if !in_foreground {
+        return;
+    }
+
+    // Taking the terminal back from a pipeline sends us SIGTTOU, which would stop us. Ctrl-Z is
+    // meant for the pipeline too, not for us.
+    // SAFETY: ignoring a signal is always safe.
+    unsafe {
+        libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+        libc::signal(libc::SIGTSTP, libc::SIG_IGN);
+    }
+    CONTROLS_TERMINAL.store(true, Ordering::SeqCst);
+}
+
+/// Make `cmd` start in the process group `group`, or in a new group of its own if that's `None`.
+pub(crate) fn set_process_group(cmd: &mut process::Command, group: Option<u32>) {
+    let group = group.unwrap_or(0) as libc::pid_t;
+    restore_signals(cmd);
+
+    // SAFETY: setpgid is async-signal-safe, so it can run between fork and exec.
+    unsafe {
+        cmd.pre_exec(move || match libc::setpgid(0, group) {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        });
+    }
+}
+
+/// Undo what `claim_terminal` did, just before `cmd` execs. Ignored signals stay ignored across
+/// exec, unlike handled ones.
+pub(crate) fn restore_signals(cmd: &mut process::Command) {
+    // SAFETY: signal is async-signal-safe, so it can run between fork and exec.
+    unsafe {
+        cmd.pre_exec(|| {
+            libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+            libc::signal(libc::SIGTSTP, libc::SIG_DFL);
+            Ok(())
+        });
+    }
+}
+
+/// How the shell handles the signals which running a program resets, to put back if the shell
+/// tries to replace itself with a program and fails. Besides those `restore_signals` resets, the
+/// standard library resets SIGPIPE, which we ignore.
+pub(crate) struct SavedSignals(Vec<(libc::c_int, libc::sigaction)>);
+
+/// Remember how the shell handles the signals which running a program resets.
+pub(crate) fn save_signals() -> SavedSignals {
+    let signals = [libc::SIGPIPE, libc::SIGTTOU, libc::SIGTSTP];
+    let saved = signals
+        .iter()
+        .map(|&signal| {
+            // SAFETY: sigaction only writes the current action, and a zeroed one is valid.
+            let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+            unsafe { libc::sigaction(signal, std::ptr::null(), &mut action) };
+            (signal, action)
+        })
+        .collect();
+
+    SavedSignals(saved)
+}
+
+impl SavedSignals {
+    /// Handle each signal the way the shell did when these were saved.
+    pub(crate) fn restore(self) {
+        for (signal, action) in self.0 {
+            // SAFETY: the action is one the shell was using before.
+            unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) };
+        }
+    }
+}
+
+/// Move a newly-spawned child into the pipeline's process group (or a new one, if `group` is
+/// `None`), and make that group the foreground. Returns the group.
+pub(crate) fn enter_foreground(child: u32, group: Option<u32>) -> u32 {
+    let group = group.unwrap_or(child);
+
+    // The child does this itself before it execs, but we don't know whether it's got that far.
+    // Doing it from both sides means the group exists before anything else tries to join it.
+    // SAFETY: setpgid has no memory-safety requirements.
+    unsafe { libc::setpgid(child as libc::pid_t, group as libc::pid_t) };
+
+    FOREGROUND_GROUP.store(group as i32, Ordering::SeqCst);
+    if CONTROLS_TERMINAL.load(Ordering::SeqCst) {
+        // SAFETY: as above.
+        unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, group as libc::pid_t) };
+    }
+
+    group
+}
+
+/// Take the foreground back from the pipeline, once it's finished.
+pub(crate) fn leave_foreground() {
+    FOREGROUND_GROUP.store(0, Ordering::SeqCst);
+    if CONTROLS_TERMINAL.load(Ordering::SeqCst) {
+        // SAFETY: as above.
+        unsafe { libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp()) };
+    }
+}
@@ -0,0 +1,31 @@
+//! The foreground pipeline, on platforms without process groups or terminal control. Pipelines
+//! share the shell's terminal and signals, and nothing here does anything.
+
+use std::{io, process};
+
+/// Does nothing: interrupts reach pipelines and the shell alike.
+pub fn install_interrupt_handler() -> io::Result<()> {
+    Ok(())
+}
+
+/// Always zero, since there's no handler to count interrupts.
+pub(crate) fn interrupts() -> usize {
+    0
+}
+
+/// Does nothing: the terminal can't be handed to pipelines.
+pub fn claim_terminal() {}
+
+/// Does nothing: there are no process groups to start `cmd` in.
+pub(crate) fn set_process_group(_cmd: &mut process::Command, _group: Option<u32>) {}
+
+/// Does nothing: there are no signals to restore.
+pub(crate) fn restore_signals(_cmd: &mut process::Command) {}
+
+/// Returns the group a pipeline would have been in, which is never used for anything else.
+pub(crate) fn enter_foreground(child: u32, group: Option<u32>) -> u32 {
+    group.unwrap_or(child)
+}
+
+/// Does nothing, since nothing took the foreground.
+pub(crate) fn leave_foreground() {}
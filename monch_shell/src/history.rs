@@ -0,0 +1,93 @@
+//! The command lines typed at the prompt, kept in a file so they outlast the shell.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Every command line typed at the prompt, oldest first. If it has a file, every line added is
+/// written to it straight away, one per line, so that nothing's lost if the shell dies.
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Make an empty history, which isn't kept anywhere.
+    pub fn new() -> History {
+        History::default()
+    }
+
+    /// Read the history kept in the file at `path`, and keep adding to it. If the file doesn't
+    /// exist yet, the history starts empty, and it's created when the first line is added.
+    pub fn load(path: impl Into<PathBuf>) -> io::Result<History> {
+        let path = path.into();
+        let entries = match fs::read_to_string(&path) {
+            Ok(contents) => contents.lines().map(String::from).collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e),
+        };
+
+        Ok(History {
+            entries,
+            path: Some(path),
+        })
+    }
+
+    /// Add a command line to the end of the history.
+    pub fn add(&mut self, line: &str) -> io::Result<()> {
+        // Each entry's a line of the file, so it can't be more than one.
+        let line = line.replace('\n', " ");
+
+        if let Some(path) = &self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", line)?;
+        }
+
+        self.entries.push(line);
+        Ok(())
+    }
+
+    /// Forget every command line, including those in the file.
+    pub fn clear(&mut self) -> io::Result<()> {
+        if let Some(path) = &self.path {
+            fs::write(path, "")?;
+        }
+
+        self.entries.clear();
+        Ok(())
+    }
+
+    /// Every command line, oldest first.
+    pub fn entries(&self) -> &[String] {
+        &self.entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::scratch_dir;
+
+    #[test]
+    fn persistence() {
+        let dir = scratch_dir("history_persistence");
+        let path = dir.join("history");
+
+        // It starts empty, without a file.
+        let mut history = History::load(&path).unwrap();
+        assert!(history.entries().is_empty());
+        history.add("echo one").unwrap();
+        history.add("ls | get name").unwrap();
+
+        // Another shell sees what this one added.
+        let mut other = History::load(&path).unwrap();
+        assert_eq!(other.entries(), ["echo one", "ls | get name"]);
+
+        other.clear().unwrap();
+        assert!(other.entries().is_empty());
+        assert!(History::load(&path).unwrap().entries().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
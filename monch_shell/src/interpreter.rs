@@ -1,15 +1,25 @@
 use crate::builtin::{self, BUILTINS};
-use crate::exe::{Execute, Exit, ExternalExecutable, Wait};
-use crate::streams::{stream_pipe, ReadStream, Streams, WriteStream};
-use crate::types::{can_connect, Ty};
-use crate::Error;
-use itertools::{izip, Itertools};
+use crate::cbor_display::format_cbor_opts;
+use crate::exe::{
+    monch_output_type, ExeKind, ExeResolver, Execute, Exit, ExternalExecutable, Resolved,
+    Terminator, Usage, Wait,
+};
+use crate::plan::{PipelinePlan, StagePlan, WriteTarget};
+use crate::streams::{stream_pipe, ReadStream, SharedLines, Streams, WriteStream};
+use crate::types::{can_connect, Connect, Ty};
+use crate::{color, foreground, suggest, Error, History};
+use ciborium::value::Value;
+use itertools::izip;
 use monch_syntax::ast;
-use std::fs;
+use std::cell::{RefCell, RefMut};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ffi::OsStr;
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{env, fs, process, thread};
 
-// TODO: settings, like 'set -e', pipefail, and the like
-// TODO: perhaps some kind of mock execution for testing
 #[derive(Default)]
 pub struct Interpreter {
     /// IO Streams
@@ -17,36 +27,653 @@ pub struct Interpreter {
 
     /// Current working directory
     current_dir: PathBuf,
+
+    /// The working directory before the last change, for `cd -`.
+    previous_dir: Option<PathBuf>,
+
+    /// Directories saved by `pushd`, with the most recent last.
+    dir_stack: Vec<PathBuf>,
+
+    /// Looks up commands before builtins and binaries, if set.
+    resolver: Option<Box<dyn ExeResolver>>,
+
+    /// Shell variables.
+    vars: HashMap<String, String>,
+
+    /// The names of variables which are passed on to child processes.
+    exported: HashSet<String>,
+
+    /// Whether to format CBOR at the end of a pipeline for a person to read. If unset, only
+    /// format it when our stdout is a terminal.
+    auto_format: Option<bool>,
+
+    /// Whether to convert between stages of a pipeline which don't fit together, where there's a
+    /// builtin which can.
+    auto_convert: bool,
+
+    /// Stop running a script at the first command which fails, like `set -e`.
+    errexit: bool,
+
+    /// Make a pipeline fail if any of its stages fail, like `set -o pipefail`.
+    pipefail: bool,
+
+    /// Describe each stage of a pipeline on stderr before running it, like `set -x`.
+    trace: bool,
+
+    /// When a stage of a pipeline finishes, stop the stages before it, rather than waiting for
+    /// them to notice that nothing's reading their output.
+    pipekill: bool,
+
+    /// Have stages running in our process write whole lines to stderr, prefixed with their name.
+    stderr_prefix: bool,
+
+    /// The process group of the running pipeline's external commands, once one has started.
+    process_group: Option<u32>,
+
+    /// The exit of the last command, for `$?`.
+    last_exit: Exit,
+
+    /// The name and exit of each stage of the last pipeline, for `status`.
+    last_pipeline_status: Vec<(String, Exit)>,
+
+    /// The name of the script we're running, for `$0`.
+    script_name: Option<String>,
+
+    /// The arguments the script was run with, for `$1` onwards.
+    script_args: Vec<String>,
+
+    /// What we've found on MONCH_PATH and PATH, so we don't have to look again.
+    path_cache: RefCell<PathCache>,
+
+    /// Something a builtin asked of whoever's running the shell, like `exit`.
+    pending_action: Option<ShellAction>,
+
+    /// The command lines typed at the prompt, for `history`.
+    history: History,
+}
+
+/// Something a builtin asks of whoever's running the shell, which it can't do itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShellAction {
+    /// Stop running commands, and exit the shell with this status.
+    Exit(Exit),
+
+    /// Replace the shell with this program, using [`Interpreter::exec`].
+    Exec { program: PathBuf, args: Vec<String> },
+}
+
+/// What we've found on MONCH_PATH and PATH. It's kept until either of them changes, or until
+/// `hash -r`.
+#[derive(Default)]
+struct PathCache {
+    /// The values of MONCH_PATH and PATH when we looked.
+    paths: [Option<String>; 2],
+
+    /// Where we found each program we've looked up, and which path it was on.
+    programs: HashMap<String, (ExeKind, PathBuf)>,
+
+    /// The name of every command we could run, for suggesting alternatives to typos. They're
+    /// only listed when first needed.
+    names: Option<BTreeSet<String>>,
+}
+
+/// Make an executable for a program found on MONCH_PATH or PATH.
+fn program(kind: ExeKind, path: PathBuf) -> Resolved {
+    let mut exe = ExternalExecutable::new(&path);
+
+    if kind == ExeKind::Monch {
+        // Because we found this program on MONCH_PATH, we're expecting CBOR
+        exe.set_input_type(Ty::Cbor);
+        let name = path.file_stem().and_then(OsStr::to_str).unwrap_or_default();
+        exe.set_output_type(monch_output_type(name));
+
+        // Make sure it writes CBOR, even if the shell was started with a JSON format.
+        exe.set_env(monch_io::FORMAT_VAR, "cbor");
+
+        // Monch utilities write plain lines of text with `--raw`, like `get --raw .name`.
+        exe.set_text_flag("--raw");
+    }
+    // Otherwise, input and output types are set by default in new()
+
+    Resolved {
+        kind,
+        path: Some(path),
+        exe: Box::new(exe),
+    }
+}
+
+/// A stage in the pipeline, before execution.
+struct Stage {
+    /// The name of the command being invoked.
+    command: String,
+
+    /// This stage's executable.
+    exe: Box<dyn Execute>,
+
+    /// Where the executable was found, and its path if it has one.
+    kind: ExeKind,
+    path: Option<PathBuf>,
+
+    /// The evaluated arguments for this stage's executable.
+    args: Vec<String>,
+
+    /// Whether we added this stage ourselves, rather than it being in the command.
+    inserted: bool,
+}
+
+/// A pipeline which has been worked out and type-checked, but not started.
+struct Planned {
+    /// The stages, from left to right.
+    stages: Vec<Stage>,
+
+    /// The file the first stage reads, if its input is redirected.
+    stdin_redirect: Option<PathBuf>,
+
+    /// The file the last stage writes, if its output is redirected.
+    stdout_redirect: Option<WriteTarget>,
+}
+
+impl Planned {
+    /// Describe the plan, without the executables themselves.
+    fn describe(&self) -> PipelinePlan {
+        let last = self.stages.len() - 1;
+        let stages = self.stages.iter().enumerate().map(|(i, stage)| StagePlan {
+            command: stage.command.clone(),
+            args: stage.args.clone(),
+            kind: stage.kind,
+            path: stage.path.clone(),
+            input_type: stage.exe.input_type(&stage.args),
+            output_type: stage.exe.output_type(&stage.args),
+            stdin_redirect: self.stdin_redirect.clone().filter(|_| i == 0),
+            stdout_redirect: self.stdout_redirect.clone().filter(|_| i == last),
+            inserted: stage.inserted,
+        });
+
+        PipelinePlan {
+            stages: stages.collect(),
+        }
+    }
 }
 
 impl Interpreter {
-    /// Create a new Interpreter using the given streams for I/O
+    /// Create a new Interpreter using the given streams for I/O. It starts with all of this
+    /// process's environment variables, exported.
     pub fn new(ios: Streams, current_dir: &Path) -> Interpreter {
+        // Skip variables which aren't Unicode: we couldn't expand them anyway.
+        let vars: HashMap<String, String> = env::vars_os()
+            .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+            .collect();
+        let exported = vars.keys().cloned().collect();
+
         Interpreter {
             ios,
             current_dir: current_dir.to_path_buf(),
+            previous_dir: None,
+            dir_stack: vec![],
+            resolver: None,
+            vars,
+            exported,
+            auto_format: None,
+            auto_convert: true,
+            errexit: false,
+            pipefail: false,
+            trace: false,
+            pipekill: false,
+            stderr_prefix: false,
+            process_group: None,
+            last_exit: Exit::SUCCESS,
+            last_pipeline_status: vec![],
+            script_name: None,
+            script_args: vec![],
+            path_cache: RefCell::default(),
+            pending_action: None,
+            history: History::new(),
         }
     }
 
+    /// Look up commands with `resolver` first, before falling back to builtins and binaries.
+    pub fn set_resolver(&mut self, resolver: impl ExeResolver + 'static) {
+        self.resolver = Some(Box::new(resolver));
+    }
+
+    /// Choose whether CBOR output at the end of a pipeline gets formatted with `to tty`. By
+    /// default, it's only formatted if our stdout is a terminal.
+    pub fn set_auto_format(&mut self, enabled: bool) {
+        self.auto_format = Some(enabled);
+    }
+
+    /// Whether CBOR output at the end of a pipeline gets formatted with `to tty`.
+    pub fn auto_format(&self) -> bool {
+        self.auto_format
+            .unwrap_or_else(|| self.ios.stdout.is_terminal())
+    }
+
+    /// Choose whether stages of a pipeline which don't fit together get a builtin between them to
+    /// convert one's output into the other's input, like `lines`, rather than being an error.
+    pub fn set_auto_convert(&mut self, enabled: bool) {
+        self.auto_convert = enabled;
+    }
+
+    /// Whether stages which don't fit together get a builtin between them to convert.
+    pub fn auto_convert(&self) -> bool {
+        self.auto_convert
+    }
+
+    /// Whether output to our stdout should be colored. That's up to `NO_COLOR` and
+    /// `CLICOLOR_FORCE`, or otherwise whether it's a terminal.
+    pub fn stdout_color(&self) -> bool {
+        self.use_color(&self.ios.stdout)
+    }
+
+    /// Whether output to our stderr should be colored, like [`Interpreter::stdout_color`].
+    pub fn stderr_color(&self) -> bool {
+        self.use_color(&self.ios.stderr)
+    }
+
+    /// Whether output to `stream` should be colored.
+    pub(crate) fn use_color(&self, stream: &WriteStream) -> bool {
+        color::use_color(
+            self.get_var("NO_COLOR"),
+            self.get_var("CLICOLOR_FORCE"),
+            stream.is_terminal(),
+        )
+    }
+
+    /// Choose whether scripts stop at the first command which fails.
+    pub fn set_errexit(&mut self, enabled: bool) {
+        self.errexit = enabled;
+    }
+
+    /// Whether scripts stop at the first command which fails.
+    pub fn errexit(&self) -> bool {
+        self.errexit
+    }
+
+    /// Choose whether each stage of a pipeline is described on stderr before it runs.
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = enabled;
+    }
+
+    /// Whether each stage of a pipeline is described on stderr before it runs.
+    pub fn trace(&self) -> bool {
+        self.trace
+    }
+
+    /// Choose whether a stage of a pipeline finishing stops the stages before it. Only external
+    /// programs can be stopped, which is done with SIGTERM.
+    pub fn set_pipekill(&mut self, enabled: bool) {
+        self.pipekill = enabled;
+    }
+
+    /// Whether a stage of a pipeline finishing stops the stages before it.
+    pub fn pipekill(&self) -> bool {
+        self.pipekill
+    }
+
+    /// Choose whether builtins write whole lines to stderr, each starting with the builtin's
+    /// name, so that stages writing at the same time don't garble each other's messages.
+    pub fn set_stderr_prefix(&mut self, enabled: bool) {
+        self.stderr_prefix = enabled;
+    }
+
+    /// Whether builtins write whole lines to stderr, each starting with the builtin's name.
+    pub fn stderr_prefix(&self) -> bool {
+        self.stderr_prefix
+    }
+
+    /// Choose whether a pipeline fails if any of its stages fail, rather than just the last.
+    pub fn set_pipefail(&mut self, enabled: bool) {
+        self.pipefail = enabled;
+    }
+
+    /// Whether a pipeline fails if any of its stages fail, rather than just the last.
+    pub fn pipefail(&self) -> bool {
+        self.pipefail
+    }
+
+    /// Evaluate each command in a script, returning the exit code of the last one. Errors are
+    /// reported on stderr, along with `name` and the line they happened on, and count as failed
+    /// commands.
+    ///
+    /// With errexit set, this stops at the first command which fails, and returns its exit code.
+    /// It also stops when a builtin asks for an action, like `exit`, which is left to be taken.
+    pub fn eval_script(&mut self, name: &str, script: &ast::Script) -> Exit {
+        let mut exit = Exit::SUCCESS;
+        for cmd in &script.commands {
+            exit = match self.eval_command(cmd) {
+                Ok(exit) => exit,
+                Err(e) => {
+                    let _ = writeln!(self.ios.stderr, "monch: {}: line {}: {}", name, cmd.line, e);
+                    e.as_exit()
+                }
+            };
+
+            if (self.errexit && !exit.success()) || self.pending_action.is_some() {
+                break;
+            }
+        }
+
+        exit
+    }
+
+    /// Set the name of the script we're running, and the arguments it was given, for `$0` and
+    /// `$1` onwards.
+    pub fn set_script(&mut self, name: impl Into<String>, args: Vec<String>) {
+        self.script_name = Some(name.into());
+        self.script_args = args;
+    }
+
+    /// The exit of the last command we ran. Errors count as the exit they'd be reported with.
+    pub fn last_exit(&self) -> Exit {
+        self.last_exit
+    }
+
+    /// Take the action a builtin asked for, if there is one. Whoever runs the shell should check
+    /// this after each command.
+    pub fn take_action(&mut self) -> Option<ShellAction> {
+        self.pending_action.take()
+    }
+
+    /// Ask whoever's running the shell to do something once this command's finished.
+    pub(crate) fn request_action(&mut self, action: ShellAction) {
+        self.pending_action = Some(action);
+    }
+
+    /// Use `history` for the command lines typed at the prompt.
+    pub fn set_history(&mut self, history: History) {
+        self.history = history;
+    }
+
+    /// The command lines typed at the prompt.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    /// The command lines typed at the prompt, to add to or clear.
+    pub fn history_mut(&mut self) -> &mut History {
+        &mut self.history
+    }
+
+    /// The name and exit of each stage of the last pipeline, in order. Stages we added ourselves,
+    /// like formatters, are left out. It's empty if the pipeline couldn't be run.
+    pub fn last_pipeline_status(&self) -> &[(String, Exit)] {
+        &self.last_pipeline_status
+    }
+
     /// Evaluate the given command, returning its exit code.
     pub fn eval_command(&mut self, cmd: &ast::Command) -> Result<Exit, Error> {
+        let result = self.eval_pipeline(cmd, None);
+        self.record_exit(&result);
+        result
+    }
+
+    /// Evaluate the given command, collecting everything it writes to stdout instead of passing
+    /// it through to ours. Returns the output, and the exit code.
+    ///
+    /// CBOR output is returned as-is, rather than being formatted for a terminal. If the command
+    /// redirects its output to a file, nothing is captured.
+    pub fn eval_command_capture(&mut self, cmd: &ast::Command) -> Result<(Vec<u8>, Exit), Error> {
+        let (mut read, write) = stream_pipe()?;
+
+        // Drain the pipe as we go, so the pipeline never blocks on a full pipe.
+        let collector = thread::spawn(move || {
+            let mut output = vec![];
+            read.read_to_end(&mut output).map(|_| output)
+        });
+
+        // Once the pipeline is done, every copy of the write end is closed, and the collector
+        // sees EOF.
+        let exit = self.eval_pipeline(cmd, Some(write));
+        self.record_exit(&exit);
+        let output = collector
+            .join()
+            .expect("Panic while collecting command output")?;
+
+        Ok((output, exit?))
+    }
+
+    /// Remember how the last command exited, for `$?`.
+    fn record_exit(&mut self, result: &Result<Exit, Error>) {
+        self.last_exit = match result {
+            Ok(exit) => *exit,
+            Err(e) => {
+                self.last_pipeline_status.clear();
+                e.as_exit()
+            }
+        };
+    }
+
+    /// Evaluate a command, writing its output to `captured_stdout` if given, and to our own
+    /// stdout otherwise.
+    fn eval_pipeline(
+        &mut self,
+        cmd: &ast::Command,
+        captured_stdout: Option<WriteStream>,
+    ) -> Result<Exit, Error> {
         // Empty pipelines are successful no-ops.
         if cmd.pipeline.len() < 1 {
             return Ok(Exit::SUCCESS);
         }
 
-        /// A stage in the pipeline, before execution.
-        struct Stage {
-            /// The name of the command being invoked.
-            command: String,
+        let to_person = cmd.stdout_redirect.is_none() && captured_stdout.is_none();
+        let planned = self.plan_pipeline(cmd, to_person)?;
+        if self.trace {
+            // Like reports from `time`, tracing is best-effort.
+            let use_color = self.use_color(&self.ios.stderr);
+            let _ = planned
+                .describe()
+                .write_lines(&mut self.ios.stderr, "+ ", use_color);
+        }
 
-            /// This stage's executable.
-            exe: Box<dyn Execute>,
+        let Planned {
+            stages,
+            stdin_redirect,
+            stdout_redirect,
+        } = planned;
 
-            /// The evaluated arguments for this stage's executable.
-            args: Vec<String>,
+        // Create all the plumbing we're going to need to connect processes in the pipeline
+        // together. Do this by opening the redirects on either end of the pipeline if they
+        // exist, and otherwise connecting the pipeline ends to the parent streams.
+        let pipeline_ends = Streams {
+            stdin: match stdin_redirect {
+                Some(path) => ReadStream::File(fs::File::open(path)?), // Read from a file
+                None => self.ios.stdin.try_clone()?, // If not redirected, inherit from the parent.
+            },
+            stdout: match (stdout_redirect, captured_stdout) {
+                (Some(target), _) => WriteStream::File(target.open()?), // Write into a file
+                (None, Some(captured)) => captured, // Write to whoever's capturing the output
+                (None, None) => self.ios.stdout.try_clone()?, // Otherwise, inherit from the parent.
+            },
+            stderr: self.ios.stderr.try_clone()?, // always passed through to parent
+        };
+
+        // If the times are going to stdout, hang onto it until the pipeline's done.
+        let time_stdout = match &cmd.time {
+            Some(ast::Time { put: true }) => Some(pipeline_ends.stdout.try_clone()?),
+            _ => None,
+        };
+
+        let io_streams: Vec<Streams> = self.make_stream_chain(pipeline_ends, &stages)?;
+        let synchronous = match &stages[..] {
+            [only] => only.exe.is_synchronous(&only.args),
+            _ => false,
+        };
+
+        // Start all the processes, then wait for them all to finish.
+        let start = Instant::now();
+        let waited = if synchronous {
+            // It's alone, so there's nothing to wait on alongside it: finish it right here.
+            let stage = &stages[0];
+            let ios = io_streams.into_iter().next().expect("one stage");
+            stage
+                .exe
+                .execute(self, ios, &stage.args)
+                .and_then(|child| Ok(vec![child.wait_usage()?]))
+        } else {
+            self.start_pipeline(&stages, io_streams)
+                .and_then(|children| self.wait_pipeline(children))
+        };
+        let real = start.elapsed();
+
+        // Take back the foreground from any external commands, even if something went wrong.
+        if self.process_group.take().is_some() {
+            foreground::leave_foreground();
+        }
+        let (exit_codes, usages): (Vec<Exit>, Vec<Usage>) = waited?.into_iter().unzip();
+
+        // Remember how each stage went. This is only done once they're all finished, so that a
+        // `status` in this pipeline can still see the last one.
+        self.last_pipeline_status = izip!(&stages, &exit_codes)
+            .filter(|(stage, _)| !stage.inserted)
+            .map(|(stage, exit)| (stage.command.clone(), *exit))
+            .collect();
+
+        if let Some(time) = &cmd.time {
+            let usage = usages.into_iter().fold(Usage::default(), |a, b| a + b);
+            self.report_time(time, real, usage, time_stdout, to_person);
+        }
+
+        // Come up with an exit status that represents the entire pipeline. Like Bash, that's the
+        // exit of the last command, or with pipefail, the last one which failed.
+        let exit = if self.pipefail {
+            exit_codes
+                .into_iter()
+                .rev()
+                .find(|exit| !exit.success())
+                .unwrap_or(Exit::SUCCESS)
+        } else {
+            // Look past any formatter we added after it, unless that failed itself.
+            let last = stages.iter().rposition(|stage| !stage.inserted);
+            exit_codes
+                .into_iter()
+                .skip(last.unwrap_or(0))
+                .reduce(Exit::reduce_worst)
+                .unwrap_or(Exit::SUCCESS)
+        };
+
+        Ok(exit)
+    }
+
+    /// Start every stage of a pipeline, from left to right. If one can't be started, the ones
+    /// before it are stopped, rather than being left to run on their own.
+    fn start_pipeline(
+        &mut self,
+        stages: &[Stage],
+        io_streams: Vec<Streams>,
+    ) -> Result<Vec<Box<dyn Wait>>, Error> {
+        let mut children: Vec<Box<dyn Wait>> = vec![];
+        for (stage, ios) in izip!(stages, io_streams) {
+            match stage.exe.execute(self, ios, &stage.args) {
+                Ok(child) => children.push(child),
+                Err(e) => {
+                    // Threads can't be stopped, but they finish once the pipes around them close.
+                    for child in children {
+                        if let Some(terminate) = child.terminator() {
+                            terminate();
+                            let _ = child.wait();
+                        }
+                    }
+
+                    // All a program does here is spawn, so any IO error means it couldn't be.
+                    return Err(match e {
+                        Error::Io(source) if stage.kind.is_program() => Error::ExecutionFailed {
+                            cmd: stage.command.clone(),
+                            source,
+                        },
+                        e => e,
+                    });
+                }
+            }
+        }
+
+        Ok(children)
+    }
+
+    /// Wait for every stage of a pipeline, in whatever order they finish. With pipekill set, each
+    /// stage which finishes stops the ones before it.
+    fn wait_pipeline(&self, children: Vec<Box<dyn Wait>>) -> Result<Vec<(Exit, Usage)>, Error> {
+        let terminators: Vec<Option<Terminator>> =
+            children.iter().map(|c| c.terminator()).collect();
+
+        // Wait on each stage in a thread of its own, so we hear about them as they finish.
+        let (sender, receiver) = mpsc::channel();
+        for (i, child) in children.into_iter().enumerate() {
+            let sender = sender.clone();
+            thread::spawn(move || sender.send((i, child.wait_usage())));
+        }
+        drop(sender);
+
+        let mut results: Vec<Option<Result<(Exit, Usage), Error>>> =
+            terminators.iter().map(|_| None).collect();
+        for (i, result) in receiver {
+            results[i] = Some(result);
+
+            // Stop whatever's still running before this stage, since nothing will read its output.
+            if self.pipekill {
+                for (terminator, result) in izip!(&terminators, &results).take(i) {
+                    if let (Some(terminate), None) = (terminator, result) {
+                        terminate();
+                    }
+                }
+            }
+        }
+
+        results
+            .into_iter()
+            .map(|r| r.expect("every stage has been waited on"))
+            .collect()
+    }
+
+    /// Report how long a timed command took: to stdout as data if it asked for `--put`, and to
+    /// stderr as text otherwise. These are best-effort, so errors writing them are ignored.
+    fn report_time(
+        &mut self,
+        time: &ast::Time,
+        real: Duration,
+        usage: Usage,
+        stdout: Option<WriteStream>,
+        to_person: bool,
+    ) {
+        let seconds = |d: Duration| Value::Float(d.as_secs_f64());
+        let times = Value::Map(vec![
+            (Value::Text("real".into()), seconds(real)),
+            (Value::Text("user".into()), seconds(usage.user)),
+            (Value::Text("sys".into()), seconds(usage.sys)),
+        ]);
+
+        match stdout {
+            // Write data, unless it's going straight to a person.
+            Some(mut stdout) if time.put && !(to_person && self.auto_format()) => {
+                let _ = ciborium::ser::into_writer(&times, &mut stdout);
+            }
+            Some(mut stdout) if time.put => {
+                let use_color = self.use_color(&stdout);
+                let _ = format_cbor_opts(&mut stdout, &times, use_color);
+                let _ = writeln!(stdout);
+            }
+            _ => {
+                let use_color = self.use_color(&self.ios.stderr);
+                let _ = format_cbor_opts(&mut self.ios.stderr, &times, use_color);
+                let _ = writeln!(self.ios.stderr);
+            }
         }
+    }
 
+    /// Work out what a command would do, without running anything: evaluate its arguments and
+    /// redirects, find its executables, add a formatter if one's needed, and check that the
+    /// stages fit together. It's planned as if its output were going to our stdout.
+    pub fn explain_command(&self, cmd: &ast::Command) -> Result<PipelinePlan, Error> {
+        if cmd.pipeline.is_empty() {
+            return Ok(PipelinePlan { stages: vec![] });
+        }
+
+        let to_person = cmd.stdout_redirect.is_none();
+        Ok(self.plan_pipeline(cmd, to_person)?.describe())
+    }
+
+    /// Work out the stages of a non-empty pipeline, and check that they fit together. If
+    /// `to_person` is set, the output is going to our stdout rather than a file or a capture.
+    fn plan_pipeline(&self, cmd: &ast::Command, to_person: bool) -> Result<Planned, Error> {
         // Calculate all the stages of the pipeline
         let mut stages: Vec<Stage> = vec![];
         for inv in &cmd.pipeline {
@@ -54,180 +681,308 @@ impl Interpreter {
             let command = self.eval_term(&inv.executable)?;
 
             // Evaluate the arguments
-            let args: Vec<String> = inv
-                .arguments
-                .iter()
-                .map(|t| self.eval_term(t))
-                .collect::<Result<_, _>>()?;
+            let args = self.eval_args(&inv.arguments)?;
 
             // Resolve the executable to an actual thing we can run
-            let exe = self.resolve_exe(&command)?;
+            let Resolved { kind, path, exe } = self.lookup_exe(&command)?;
 
             // Add the stage
-            stages.push(Stage { exe, command, args });
+            stages.push(Stage {
+                command,
+                exe,
+                kind,
+                path,
+                args,
+                inserted: false,
+            });
         }
 
-        // If the last stage is giving CBOR output, sneakily insert a formatter.
+        // Builtins which change the shell itself run in it directly, so they can't share it with
+        // other stages, unless they come last and read what's before them, like `read`. Bash
+        // would run them in a subshell instead, where they'd do nothing.
+        if stages.len() > 1 {
+            let last = stages.len() - 1;
+            for (i, stage) in stages.iter().enumerate() {
+                let reads_pipeline = i == last && stage.exe.input_type(&stage.args) != Ty::Nothing;
+                if stage.exe.is_synchronous(&stage.args) && !reads_pipeline {
+                    return Err(Error::NotPipeable {
+                        cmd: stage.command.clone(),
+                    });
+                }
+            }
+        }
+
+        // If the last stage is giving CBOR output to a person, sneakily insert a formatter.
         let final_stage = stages.last().expect("non-empty pipeline");
         let final_type = final_stage.exe.output_type(&final_stage.args);
-        if final_type == Ty::Cbor && !cmd.stdout_redirect.is_some() {
+        if final_type == Ty::Cbor && to_person && self.auto_format() {
             stages.push(Stage {
                 command: "to".to_string(),
                 exe: Box::new(builtin::To),
+                kind: ExeKind::Builtin,
+                path: None,
                 args: vec!["tty".to_string()],
+                inserted: true,
             });
         }
 
-        // Type-check the pipeline
-        for (l, r) in stages.iter().tuple_windows() {
+        // Type-check the pipeline, converting between stages which don't fit if we can.
+        let mut i = 1;
+        while i < stages.len() {
+            let (l, r) = (&stages[i - 1], &stages[i]);
             let l_output = l.exe.output_type(&l.args);
             let r_input = r.exe.input_type(&r.args);
 
-            if !can_connect(l_output, r_input) {
-                return Err(Error::TypeMismatch {
-                    l_cmd: l.command.clone(),
-                    l_ty: l_output,
-                    r_cmd: r.command.clone(),
-                    r_ty: r_input,
-                });
+            match can_connect(l_output, r_input) {
+                Connect::Direct => {}
+
+                // An adapter's output has to fit as it is, so they can't pile up.
+                Connect::Via(adapter) if self.auto_convert() && !l.inserted => {
+                    let mut words = adapter.split(' ').map(String::from);
+                    let command = words.next().expect("adapters aren't empty");
+                    let builtin = BUILTINS
+                        .get(command.as_str())
+                        .expect("adapters are builtins");
+                    stages.insert(
+                        i,
+                        Stage {
+                            command,
+                            exe: Box::new(builtin.exe),
+                            kind: ExeKind::Builtin,
+                            path: None,
+                            args: words.collect(),
+                            inserted: true,
+                        },
+                    );
+
+                    // Check that the adapter fits what comes after it, too.
+                    continue;
+                }
+                _ => {
+                    return Err(Error::TypeMismatch {
+                        l_cmd: l.command.clone(),
+                        l_ty: l_output,
+                        r_cmd: r.command.clone(),
+                        r_ty: r_input,
+                    });
+                }
             }
+
+            i += 1;
         }
 
-        // Create all the plumbing we're going to need to connect processes in the pipeline
-        // together. Do this by evaluating the redirects on either end of the pipeline if they
-        // exist, and otherwise connecting the pipeline ends to the parent streams.
-        let pipeline_ends = Streams {
-            stdin: match &cmd.stdin_redirect {
-                Some(redir) => self.eval_read_redirect(redir)?, // Read from a file
-                None => self.ios.stdin.try_clone()?, // If not redirected, inherit from the parent.
+        Ok(Planned {
+            stages,
+            stdin_redirect: match &cmd.stdin_redirect {
+                Some(redir) => Some(self.eval_read_redirect(redir)?),
+                None => None,
             },
-            stdout: match &cmd.stdout_redirect {
-                Some(redir) => self.eval_write_redirect(redir)?, // Write into a file
-                None => self.ios.stdout.try_clone()?, // If not redirected, inherit from the parent.
+            stdout_redirect: match &cmd.stdout_redirect {
+                Some(redir) => Some(self.eval_write_redirect(redir)?),
+                None => None,
             },
-            stderr: self.ios.stderr.try_clone()?, // always passed through to parent
-        };
-        let io_streams: Vec<Streams> = self.make_stream_chain(pipeline_ends, stages.len())?;
-
-        // Start all the processes
-        let children: Vec<Box<dyn Wait>> = izip!(&stages, io_streams)
-            .map(|(stage, ios)| stage.exe.execute(self, ios, &stage.args))
-            .collect::<Result<_, _>>()?;
-
-        // Wait for all the child processes to finish
-        let exit_codes: Vec<Exit> = children
-            .into_iter()
-            .map(|c| c.wait())
-            .collect::<Result<_, Error>>()?;
-
-        // Come up with an exit status that represents the entire pipeline.
-        // We use Bash's `&&` logic here by default.
-        let exit = exit_codes
-            .into_iter()
-            .reduce(Exit::reduce_worst) // Pick the worst of their exit codes
-            .unwrap_or(Exit::SUCCESS); // If we don't have any children, return success.
-
-        Ok(exit)
+        })
     }
 
-    /// Resolve the name of a command into an Execute impl.
-    fn resolve_exe(&self, bin_name: &str) -> Result<Box<dyn Execute>, Error> {
-        use std::env;
+    /// Resolve the name of a command, describing where it was found as well.
+    pub(crate) fn lookup_exe(&self, bin_name: &str) -> Result<Resolved, Error> {
+        // Let the resolver have the first say, if we have one.
+        if let Some(exe) = self.resolver.as_ref().and_then(|r| r.resolve(bin_name)) {
+            return Ok(Resolved {
+                kind: ExeKind::Resolver,
+                path: None,
+                exe,
+            });
+        }
 
         // Try to look up a builtin with that name
         if let Some(builtin) = BUILTINS.get(bin_name) {
-            return Ok(Box::new(builtin));
+            return Ok(Resolved {
+                kind: ExeKind::Builtin,
+                path: None,
+                exe: Box::new(builtin.exe),
+            });
+        }
+
+        // Check whether we've found this program before. Names with a slash in them are relative
+        // to the current directory, so they could be different next time.
+        let cacheable = !bin_name.contains('/');
+        if let Some((kind, path)) = self.path_cache().programs.get(bin_name) {
+            return Ok(program(*kind, path.clone()));
         }
 
-        // Try to look up a program on the monch PATH
-        match which::which_in(bin_name, env::var_os("MONCH_PATH"), self.current_dir()) {
-            Err(e) => match e {
-                which::Error::CannotFindBinaryPath => {} // fall through to the other lookups
+        // Try to look up a program on the monch PATH, and then on the system PATH.
+        let searches = [
+            (ExeKind::Monch, self.get_var("MONCH_PATH")),
+            (ExeKind::External, self.get_var("PATH")),
+        ];
+        for (kind, paths) in searches {
+            match which::which_in(bin_name, paths, self.current_dir()) {
+                // Fall through to the next path.
+                Err(which::Error::CannotFindBinaryPath) => {}
 
                 // If `which` has some other nasty error, return it.
-                _ => {
+                Err(e) => {
                     return Err(Error::ResolveBinary {
                         cmd: bin_name.to_string(),
                         source: e,
+                        suggestions: vec![],
                     });
                 }
-            },
-
-            // We found a binary on the MONCH_PATH.
-            Ok(monch_bin) => {
-                let mut exe = ExternalExecutable::new(monch_bin);
 
-                // Because we found this program on MONCH_PATH, we're expecting CBOR
-                exe.set_input_type(Ty::Cbor);
-                exe.set_output_type(Ty::Cbor);
+                Ok(path) => {
+                    if cacheable {
+                        let programs = &mut self.path_cache().programs;
+                        programs.insert(bin_name.to_string(), (kind, path.clone()));
+                    }
 
-                return Ok(Box::new(exe));
+                    return Ok(program(kind, path));
+                }
             }
-        };
+        }
+
+        Err(Error::ResolveBinary {
+            cmd: bin_name.to_string(),
+            source: which::Error::CannotFindBinaryPath,
+            suggestions: self.suggest_commands(bin_name),
+        })
+    }
+
+    /// Borrow what we've found on MONCH_PATH and PATH, forgetting it first if they've changed.
+    fn path_cache(&self) -> RefMut<'_, PathCache> {
+        let paths = [self.get_var("MONCH_PATH"), self.get_var("PATH")].map(|p| p.map(String::from));
+
+        let mut cache = self.path_cache.borrow_mut();
+        if cache.paths != paths {
+            *cache = PathCache {
+                paths,
+                ..Default::default()
+            };
+        }
+
+        cache
+    }
+
+    /// Every program we've found on MONCH_PATH or PATH, and where, sorted by name.
+    pub(crate) fn hashed_programs(&self) -> Vec<(String, ExeKind, PathBuf)> {
+        let mut programs: Vec<_> = self
+            .path_cache()
+            .programs
+            .iter()
+            .map(|(name, (kind, path))| (name.clone(), *kind, path.clone()))
+            .collect();
 
-        // Try to look up a program on the system PATH
-        match which::which_in(bin_name, env::var_os("PATH"), self.current_dir()) {
-            Ok(other_bin) => {
-                let exe = ExternalExecutable::new(other_bin);
-                // input and output types set by default in new()
+        programs.sort_by(|a, b| a.0.cmp(&b.0));
+        programs
+    }
+
+    /// Suggest commands with names close to `name`, which couldn't be found.
+    fn suggest_commands(&self, name: &str) -> Vec<String> {
+        let mut cache = self.path_cache();
 
-                Ok(Box::new(exe))
+        // List every command, if we haven't since the paths last changed.
+        if cache.names.is_none() {
+            let mut names: BTreeSet<String> = BUILTINS.keys().map(|k| k.to_string()).collect();
+            for path in cache.paths.iter().flatten() {
+                names.extend(suggest::executables_on(OsStr::new(path)));
             }
 
-            Err(e) => Err(Error::ResolveBinary {
-                cmd: bin_name.to_string(),
-                source: e,
-            }),
+            cache.names = Some(names);
         }
+
+        let names = cache
+            .names
+            .as_ref()
+            .expect("command names were just listed");
+        suggest::suggest(name, names.iter().map(String::as_str))
+    }
+
+    /// Forget the commands we've found, so that we look for them again next time they're needed.
+    pub fn forget_commands(&mut self) {
+        *self.path_cache.get_mut() = PathCache::default();
+    }
+
+    /// The process group of the running pipeline's external commands, if any have started.
+    pub(crate) fn process_group(&self) -> Option<u32> {
+        self.process_group
+    }
+
+    /// Record the process group of the running pipeline's external commands.
+    pub(crate) fn set_process_group(&mut self, group: u32) {
+        self.process_group = Some(group);
     }
 
-    /// Open a file for input redirection, returning the right ReadStream
-    fn eval_read_redirect(&self, redir: &ast::ReadRedirect) -> Result<ReadStream, Error> {
-        // Here, we have our input redirected. Open the file and connect that.
+    /// Work out which file input is redirected from.
+    fn eval_read_redirect(&self, redir: &ast::ReadRedirect) -> Result<PathBuf, Error> {
         let name_term = match redir {
             ast::ReadRedirect::File { file } => file,
         };
         let name = self.eval_term(name_term)?;
-        let path: PathBuf = self.current_dir.join(name);
-        let file = fs::File::open(&path)?;
-        Ok(ReadStream::File(file))
+        Ok(self.current_dir.join(name))
     }
 
-    /// Open a file for output redirection, returning the right WriteStream
-    fn eval_write_redirect(&self, redir: &ast::WriteRedirect) -> Result<WriteStream, Error> {
-        // Options to open any file for writing
-        let mut opts = fs::OpenOptions::new();
-        opts.write(true);
-        opts.create(true);
-
-        // Figure out the filename, and if we're appending or not.
-        let name_term = match redir {
+    /// Work out which file output is redirected to, and whether it's appended to.
+    fn eval_write_redirect(&self, redir: &ast::WriteRedirect) -> Result<WriteTarget, Error> {
+        Ok(match redir {
             ast::WriteRedirect::TruncateFile { file } => {
-                opts.truncate(true);
-                file
+                WriteTarget::Truncate(self.current_dir.join(self.eval_term(file)?))
             }
             ast::WriteRedirect::AppendFile { file } => {
-                opts.append(true);
-                file
+                WriteTarget::Append(self.current_dir.join(self.eval_term(file)?))
             }
-        };
-
-        // Open the file.
-        let name = self.eval_term(name_term)?;
-        let path: PathBuf = self.current_dir.join(name);
-        let file = opts.open(&path)?;
-        Ok(WriteStream::File(file))
+        })
     }
 
     /// Evaluate an [`ast::Term`] to a [`String`] value
     pub fn eval_term(&self, term: &ast::Term) -> Result<String, Error> {
         match term {
             ast::Term::Literal { value } => Ok(value.clone()),
+            ast::Term::Variable { name } => Ok(self.expand_var(name)),
+            ast::Term::Concat { parts } => parts.iter().map(|t| self.eval_term(t)).collect(),
+        }
+    }
+
+    /// Evaluate a command's arguments. A whole argument of `$@` becomes one argument for each of
+    /// the script's arguments; anywhere else, they're joined with spaces.
+    fn eval_args(&self, terms: &[ast::Term]) -> Result<Vec<String>, Error> {
+        let mut args = vec![];
+        for term in terms {
+            match term {
+                ast::Term::Variable { name } if name == "@" => {
+                    args.extend(self.script_args.iter().cloned())
+                }
+                _ => args.push(self.eval_term(term)?),
+            }
         }
+
+        Ok(args)
     }
 
-    /// Create a series of `length` [`Streams`] instances in a (stdout -> stdin) chain.
-    fn make_stream_chain(&self, ends: Streams, length: usize) -> Result<Vec<Streams>, Error> {
+    /// Get the value of a variable for expansion, including the special variables like `$?`
+    /// which are worked out on the spot. Unset variables expand to an empty string.
+    fn expand_var(&self, name: &str) -> String {
+        match name {
+            "?" => self.last_exit.as_status().to_string(),
+            "#" => self.script_args.len().to_string(),
+            "@" => self.script_args.join(" "),
+            "0" => self.script_name.as_deref().unwrap_or("monch").to_string(),
+            "PWD" => self.current_dir.to_string_lossy().to_string(),
+
+            // Positional parameters, starting from `$1`.
+            _ if name.bytes().all(|b| b.is_ascii_digit()) => name
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| self.script_args.get(n.checked_sub(1)?))
+                .cloned()
+                .unwrap_or_default(),
+
+            _ => self.get_var(name).unwrap_or_default().to_string(),
+        }
+    }
+
+    /// Create a [`Streams`] for each stage, in a (stdout -> stdin) chain.
+    fn make_stream_chain(&self, ends: Streams, stages: &[Stage]) -> Result<Vec<Streams>, Error> {
+        let length = stages.len();
         assert!(length > 0, "cannot make stream chain with length <= 1");
 
         // Start with a bunch of null streams, one for each item in the pipeline.
@@ -250,7 +1005,23 @@ impl Interpreter {
         for stream in ios.iter_mut().skip(1) {
             stream.stderr = ends.stderr.try_clone()?; // dup() the stream
         }
-        ios[0].stderr = ends.stderr; // move the stream, avoiding extra dup()
+
+        // With stderr-prefix, stages running in our process only write whole lines to stderr,
+        // starting with their name. Programs still write to it directly.
+        if self.stderr_prefix {
+            let shared = Arc::new(Mutex::new(ends.stderr.try_clone()?));
+            for (stream, stage) in ios.iter_mut().zip(stages) {
+                if !stage.kind.is_program() {
+                    let prefix = format!("{}: ", stage.command);
+                    stream.stderr = WriteStream::Shared(SharedLines::new(shared.clone(), prefix));
+                }
+            }
+        }
+
+        // Move the stream, avoiding extra dup(), unless the first stage has a shared one.
+        if matches!(ios[0].stderr, WriteStream::Null) {
+            ios[0].stderr = ends.stderr;
+        }
 
         // Connect stdin to the first element
         ios[0].stdin = ends.stdin;
@@ -262,6 +1033,95 @@ impl Interpreter {
         Ok(ios)
     }
 
+    /// Get the value of a shell variable.
+    pub fn get_var(&self, name: &str) -> Option<&str> {
+        self.vars.get(name).map(String::as_str)
+    }
+
+    /// Set a shell variable. It's only passed on to child processes if it's been exported.
+    pub fn set_var(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.vars.insert(name.into(), value.into());
+    }
+
+    /// Mark a variable to be passed on to child processes. If it isn't set yet, it's set to an
+    /// empty string.
+    pub fn export_var(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.vars.entry(name.clone()).or_default();
+        self.exported.insert(name);
+    }
+
+    /// Remove a shell variable, so it's neither set nor exported.
+    pub fn unset_var(&mut self, name: &str) {
+        self.vars.remove(name);
+        self.exported.remove(name);
+    }
+
+    /// Every exported variable, and its value.
+    pub fn exported_vars(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.exported
+            .iter()
+            .filter_map(move |name| Some((name.as_str(), self.get_var(name)?)))
+    }
+
+    /// Replace this process with `program`, run with `args` in our working directory, with our
+    /// exported variables. This only returns if it couldn't be run.
+    ///
+    /// Where a process can't be replaced, the program is run as a child, and we exit with its
+    /// exit once it's finished.
+    pub fn exec(&self, program: &Path, args: &[String]) -> Error {
+        let mut cmd = process::Command::new(program);
+        cmd.args(args);
+        cmd.env_clear();
+        cmd.envs(self.exported_vars());
+        cmd.env("PWD", self.current_dir());
+        cmd.current_dir(self.current_dir());
+        foreground::restore_signals(&mut cmd);
+
+        let failed = |source| Error::ExecutionFailed {
+            cmd: program.display().to_string(),
+            source,
+        };
+
+        // If exec works, it never returns. If not, it's already reset our signals for the
+        // program, so they need putting back.
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            let saved = foreground::save_signals();
+            let error = cmd.exec();
+            saved.restore();
+            failed(error)
+        }
+
+        #[cfg(not(unix))]
+        match cmd.status() {
+            Ok(status) => process::exit(Exit::from(status).as_status() as i32),
+            Err(e) => failed(e),
+        }
+    }
+
+    /// The directories searched for monch utilities, in order, from MONCH_PATH.
+    pub fn monch_path(&self) -> Vec<PathBuf> {
+        match self.get_var("MONCH_PATH") {
+            Some(path) => env::split_paths(path)
+                .filter(|dir| !dir.as_os_str().is_empty())
+                .collect(),
+            None => vec![],
+        }
+    }
+
+    /// Search `dirs` for monch utilities, in order. MONCH_PATH is set to match, and exported, so
+    /// that child processes search them too.
+    pub fn set_monch_path(&mut self, dirs: &[PathBuf]) -> Result<(), Error> {
+        let path = env::join_paths(dirs)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e.to_string()))?;
+
+        self.set_var("MONCH_PATH", path.to_string_lossy());
+        self.export_var("MONCH_PATH");
+        Ok(())
+    }
+
     /// Get the current working directory of the Interpreter
     pub fn current_dir(&self) -> &Path {
         &self.current_dir
@@ -281,9 +1141,956 @@ impl Interpreter {
             ));
         }
 
-        // Update the cwd
-        self.current_dir = new_cwd;
+        // Update the cwd, remembering where we were.
+        let old_cwd = std::mem::replace(&mut self.current_dir, new_cwd);
+        self.set_var("OLDPWD", old_cwd.to_string_lossy());
+        self.previous_dir = Some(old_cwd);
+
+        Ok(())
+    }
 
+    /// Save the current directory on the directory stack, and change to `dir`, relative to the
+    /// current directory. If we can't change directory, nothing is saved.
+    pub fn push_dir(&mut self, dir: impl AsRef<Path>) -> Result<(), Error> {
+        let here = self.current_dir.clone();
+        self.set_current_dir(here.join(dir))?;
+        self.dir_stack.push(here);
         Ok(())
     }
+
+    /// Change back to the directory on top of the directory stack, removing it. Returns `None`
+    /// if the stack is empty.
+    pub fn pop_dir(&mut self) -> Result<Option<PathBuf>, Error> {
+        let dir = match self.dir_stack.last() {
+            Some(dir) => dir.clone(),
+            None => return Ok(None),
+        };
+
+        self.set_current_dir(&dir)?;
+        self.dir_stack.pop();
+        Ok(Some(dir))
+    }
+
+    /// The directory stack, with the most recently pushed directory last.
+    pub fn dir_stack(&self) -> &[PathBuf] {
+        &self.dir_stack
+    }
+
+    /// Get the working directory from before the last time it changed, if it has.
+    pub fn previous_dir(&self) -> Option<&Path> {
+        self.previous_dir.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{scratch_dir, Call, MockResolver, RecordingExe};
+    use monch_syntax::Parser;
+
+    /// Run a command line against mock executables, returning its result and everything it wrote
+    /// to stdout.
+    fn run(dir: &Path, resolver: MockResolver, line: &str) -> (Result<Exit, Error>, Vec<u8>) {
+        run_with(dir, resolver, line, |_| {})
+    }
+
+    /// Like [`run`], but lets `setup` configure the interpreter first.
+    fn run_with(
+        dir: &Path,
+        resolver: MockResolver,
+        line: &str,
+        setup: impl FnOnce(&mut Interpreter),
+    ) -> (Result<Exit, Error>, Vec<u8>) {
+        let (mut stdout, stdout_w) = stream_pipe().unwrap();
+        let ios = Streams {
+            stdin: ReadStream::Null,
+            stdout: stdout_w,
+            stderr: WriteStream::Null,
+        };
+
+        let mut int = Interpreter::new(ios, dir);
+        int.set_resolver(resolver);
+        setup(&mut int);
+
+        let cmd = Parser::new().parse_command(line).unwrap();
+        let result = int.eval_command(&cmd);
+
+        // Close the interpreter's end of the pipe, so that we see EOF.
+        drop(int);
+        let mut out = vec![];
+        stdout.read_to_end(&mut out).unwrap();
+
+        (result, out)
+    }
+
+    fn call(args: &[&str], stdin: &[u8]) -> Call {
+        Call {
+            args: args.iter().map(|a| a.to_string()).collect(),
+            stdin: stdin.to_vec(),
+        }
+    }
+
+    #[test]
+    fn pipeline_connects_stages() {
+        let mut resolver = MockResolver::new();
+        let first = resolver.add("first", RecordingExe::new().with_stdout("hello"));
+        let second = resolver.add("second", RecordingExe::new().with_stdout("world"));
+
+        let (result, out) = run(&env::temp_dir(), resolver, "first a b | second 'c d'");
+
+        assert_eq!(result.unwrap(), Exit::SUCCESS);
+        assert_eq!(out, b"world");
+        assert_eq!(first.calls(), vec![call(&["a", "b"], b"")]);
+        assert_eq!(second.calls(), vec![call(&["c d"], b"hello")]);
+    }
+
+    fn failing_resolver() -> MockResolver {
+        let mut resolver = MockResolver::new();
+        resolver.add("fail2", RecordingExe::new().with_exit(Exit::Code(2)));
+        resolver.add("fail3", RecordingExe::new().with_exit(Exit::Code(3)));
+        resolver.add("ok", RecordingExe::new());
+        resolver
+    }
+
+    #[test]
+    fn pipeline_exit_is_the_last() {
+        let exit = |line| run(&env::temp_dir(), failing_resolver(), line).0.unwrap();
+
+        assert_eq!(exit("fail3 | ok"), Exit::SUCCESS);
+        assert_eq!(exit("ok | fail3"), Exit::Code(3));
+        assert_eq!(exit("fail2 | fail3"), Exit::Code(3));
+    }
+
+    #[test]
+    fn pipefail() {
+        let exit = |line| {
+            run_with(&env::temp_dir(), failing_resolver(), line, |int| {
+                int.set_pipefail(true)
+            })
+            .0
+            .unwrap()
+        };
+
+        assert_eq!(exit("ok | ok"), Exit::SUCCESS);
+        assert_eq!(exit("fail3 | ok"), Exit::Code(3));
+        assert_eq!(exit("fail3 | fail2 | ok"), Exit::Code(2));
+    }
+
+    #[test]
+    fn pipeline_status() {
+        let mut resolver = failing_resolver();
+        resolver.add(
+            "data",
+            RecordingExe::new()
+                .with_types(Ty::Any, Ty::Cbor)
+                .with_exit(Exit::Signal(9)),
+        );
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(resolver);
+        int.set_auto_format(true);
+
+        let mut run = |line| {
+            let cmd = Parser::new().parse_command(line).unwrap();
+            let _ = int.eval_command(&cmd);
+            int.last_pipeline_status().to_vec()
+        };
+        let status = |stages: &[(&str, Exit)]| -> Vec<(String, Exit)> {
+            stages.iter().map(|(c, e)| (c.to_string(), *e)).collect()
+        };
+
+        assert_eq!(
+            run("ok | fail3 | fail2 | ok"),
+            status(&[
+                ("ok", Exit::SUCCESS),
+                ("fail3", Exit::Code(3)),
+                ("fail2", Exit::Code(2)),
+                ("ok", Exit::SUCCESS),
+            ])
+        );
+
+        // The formatter isn't one of the stages the user asked for.
+        assert_eq!(
+            run("ok | data"),
+            status(&[("ok", Exit::SUCCESS), ("data", Exit::Signal(9))])
+        );
+
+        // Pipelines which couldn't start don't have stages.
+        assert_eq!(run("ok | nonexistent-command"), []);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn stage_fails_to_start() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("stage_fails_to_start");
+        let script = |name, source| {
+            let path = dir.join(name);
+            fs::write(&path, source).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+        script("slow", "#!/bin/sh\nsleep 0.5\ntouch started\n");
+        script("broken", "#!/nonexistent/interpreter\n");
+
+        let (result, _) = run(&dir, MockResolver::new(), "./slow | ./broken");
+
+        // The error says which stage couldn't be started.
+        let err = result.unwrap_err();
+        assert_eq!(err.as_exit(), Exit::COULD_NOT_EXECUTE);
+        assert!(
+            err.to_string().starts_with("./broken: couldn't execute: "),
+            "{}",
+            err
+        );
+
+        // The stage before it was stopped, rather than left running.
+        thread::sleep(Duration::from_secs(1));
+        assert!(!dir.join("started").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn pipeline_ends_early() {
+        let mut resolver = MockResolver::new();
+        let first = resolver.add("first", RecordingExe::new().with_read_limit(2));
+        resolver.add("none", RecordingExe::new().with_read_limit(0));
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(resolver);
+        let run = |int: &mut Interpreter, line| {
+            let start = Instant::now();
+            let cmd = Parser::new().parse_command(line).unwrap();
+            let exit = int.eval_command(&cmd).unwrap();
+            (exit, start.elapsed())
+        };
+
+        // An endless generator stops once nothing's reading it.
+        let (exit, elapsed) = run(&mut int, "yes | first");
+        assert_eq!(exit, Exit::SUCCESS);
+        assert!(elapsed < Duration::from_millis(500));
+        assert_eq!(first.calls()[0].stdin, b"y\n");
+
+        // Something which never writes anything won't notice, unless it's stopped.
+        int.set_pipekill(true);
+        let (exit, elapsed) = run(&mut int, "sleep 5 | none");
+        assert_eq!(exit, Exit::SUCCESS);
+        assert!(elapsed < Duration::from_millis(500));
+        assert_eq!(
+            int.last_pipeline_status()[0],
+            ("sleep".to_string(), Exit::TERMINATED)
+        );
+    }
+
+    #[test]
+    fn stderr_prefix() {
+        let lines = |line: &str| line.repeat(50);
+        let mut resolver = MockResolver::new();
+        resolver.add(
+            "one",
+            RecordingExe::new().with_stderr(lines("first stage\n")),
+        );
+        resolver.add(
+            "two",
+            RecordingExe::new().with_stderr(lines("second stage\n")),
+        );
+        resolver.add("three", RecordingExe::new().with_stderr("unfinished"));
+
+        let (mut stderr, stderr_w) = stream_pipe().unwrap();
+        let ios = Streams {
+            stdin: ReadStream::Null,
+            stdout: WriteStream::Null,
+            stderr: stderr_w,
+        };
+
+        let mut int = Interpreter::new(ios, &env::temp_dir());
+        int.set_resolver(resolver);
+        int.set_stderr_prefix(true);
+
+        let cmd = Parser::new().parse_command("one | two | three").unwrap();
+        assert_eq!(int.eval_command(&cmd).unwrap(), Exit::SUCCESS);
+
+        drop(int);
+        let mut err = String::new();
+        stderr.read_to_string(&mut err).unwrap();
+
+        // Every line is whole, and says where it came from.
+        let count = |line| err.lines().filter(|&l| l == line).count();
+        assert_eq!(count("one: first stage"), 50);
+        assert_eq!(count("two: second stage"), 50);
+        assert_eq!(count("three: unfinished"), 1);
+        assert_eq!(err.lines().count(), 101);
+    }
+
+    #[test]
+    fn formatter_exit_is_ignored() {
+        let mut resolver = MockResolver::new();
+        resolver.add(
+            "data",
+            RecordingExe::new()
+                .with_types(Ty::Cbor, Ty::Cbor)
+                .with_exit(Exit::Code(3)),
+        );
+
+        // The last stage is really the formatter, but it's the command's exit we care about.
+        let (result, _) = run_with(&env::temp_dir(), resolver, "data", |int| {
+            int.set_auto_format(true)
+        });
+        assert_eq!(result.unwrap(), Exit::Code(3));
+    }
+
+    #[test]
+    fn trace() {
+        let mut resolver = MockResolver::new();
+        resolver.add(
+            "data",
+            RecordingExe::new().with_types(Ty::Nothing, Ty::Cbor),
+        );
+        resolver.add("filter", RecordingExe::new().with_types(Ty::Cbor, Ty::Cbor));
+
+        let (mut stderr, stderr_w) = stream_pipe().unwrap();
+        let ios = Streams {
+            stdin: ReadStream::Null,
+            stdout: WriteStream::Null,
+            stderr: stderr_w,
+        };
+
+        let mut int = Interpreter::new(ios, &env::temp_dir());
+        int.set_resolver(resolver);
+        int.set_auto_format(true);
+        int.set_trace(true);
+
+        let cmd = Parser::new()
+            .parse_command("data one 'two' | filter")
+            .unwrap();
+        assert_eq!(int.eval_command(&cmd).unwrap(), Exit::SUCCESS);
+
+        drop(int);
+        let mut err = String::new();
+        stderr.read_to_string(&mut err).unwrap();
+
+        // Leave out the colours, which get in the way of comparing.
+        let mut plain = String::new();
+        let mut chars = err.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\x1b' => {
+                    // Skip to the end of the escape sequence.
+                    for c in chars.by_ref() {
+                        if c == 'm' {
+                            break;
+                        }
+                    }
+                }
+                c => plain.push(c),
+            }
+        }
+
+        let common = "kind: resolver, path: (null), ";
+        let unredirected = "stdin_redirect: (null), stdout_redirect: (null)";
+        assert_eq!(
+            plain.lines().collect::<Vec<_>>(),
+            [
+                format!(
+                    "+ {{command: data, args: [one, two], {}input_type: [nothing], output_type: cbor, {}, inserted: false}}",
+                    common, unredirected
+                ),
+                format!(
+                    "+ {{command: filter, args: [], {}input_type: cbor, output_type: cbor, {}, inserted: false}}",
+                    common, unredirected
+                ),
+                // The formatter is shown too, so it's clear where it came from.
+                format!(
+                    "+ {{command: to, args: [tty], kind: builtin, path: (null), input_type: cbor, output_type: tty, {}, inserted: true}}",
+                    unredirected
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn explain_without_running() {
+        let dir = scratch_dir("explain_without_running");
+        let mut resolver = MockResolver::new();
+        let data = resolver.add("data", RecordingExe::new().with_types(Ty::Any, Ty::Cbor));
+        let text = resolver.add("text", RecordingExe::new().with_types(Ty::Any, Ty::Text));
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_resolver(resolver);
+        int.set_auto_format(true);
+        int.set_var("NAME", "out");
+
+        let explain = |int: &Interpreter, line| {
+            let cmd = Parser::new().parse_command(line).unwrap();
+            int.explain_command(&cmd)
+        };
+
+        let stage = |command: &str, args: &[&str], kind, input_type, output_type| StagePlan {
+            command: command.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+            kind,
+            path: None,
+            input_type,
+            output_type,
+            stdin_redirect: None,
+            stdout_redirect: None,
+            inserted: false,
+        };
+
+        // Redirects are worked out, but nothing's opened.
+        let plan = explain(&int, "data $NAME <in.txt | to cbor >>$NAME.json").unwrap();
+        let mut first = stage("data", &["out"], ExeKind::Resolver, Ty::Any, Ty::Cbor);
+        first.stdin_redirect = Some(dir.join("in.txt"));
+        let mut last = stage("to", &["cbor"], ExeKind::Builtin, Ty::Cbor, Ty::Cbor);
+        last.stdout_redirect = Some(WriteTarget::Append(dir.join("out.json")));
+        assert_eq!(plan.stages, [first, last]);
+        assert!(!dir.join("out.json").exists());
+
+        // The formatter is planned too.
+        let plan = explain(&int, "data").unwrap();
+        let mut formatter = stage("to", &["tty"], ExeKind::Builtin, Ty::Cbor, Ty::Tty);
+        formatter.inserted = true;
+        assert_eq!(plan.stages[1], formatter);
+
+        // Commands which can't run are caught, without running the rest.
+        assert!(matches!(
+            explain(&int, "data | text | to tty"),
+            Err(Error::TypeMismatch { .. })
+        ));
+        assert!(data.calls().is_empty() && text.calls().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn errexit() {
+        let script = "ok\nfail3\nfail2\n";
+        let script = Parser::new().parse_script(script).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(failing_resolver());
+        assert_eq!(int.eval_script("script", &script), Exit::Code(2));
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(failing_resolver());
+        int.set_errexit(true);
+        assert_eq!(int.eval_script("script", &script), Exit::Code(3));
+    }
+
+    #[test]
+    fn exit_action() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(failing_resolver());
+        let mut eval = |line| {
+            let cmd = Parser::new().parse_command(line).unwrap();
+            let exit = int.eval_command(&cmd).unwrap();
+            (exit, int.take_action())
+        };
+
+        // Without a code, it's the last command's exit.
+        assert_eq!(
+            eval("exit 4"),
+            (Exit::Code(4), Some(ShellAction::Exit(Exit::Code(4))))
+        );
+        eval("fail3");
+        assert_eq!(
+            eval("exit"),
+            (Exit::Code(3), Some(ShellAction::Exit(Exit::Code(3))))
+        );
+
+        // Codes which aren't numbers are refused, and don't exit.
+        assert_eq!(eval("exit four"), (Exit::BAD_SYNTAX, None));
+        assert_eq!(eval("exit 256"), (Exit::BAD_SYNTAX, None));
+        assert_eq!(eval("exit 1 2"), (Exit::BAD_SYNTAX, None));
+
+        // Scripts stop as soon as it runs, leaving the action to be taken.
+        let script = Parser::new().parse_script("ok\nexit 5\nfail2\n").unwrap();
+        assert_eq!(int.eval_script("script", &script), Exit::Code(5));
+        assert_eq!(int.take_action(), Some(ShellAction::Exit(Exit::Code(5))));
+    }
+
+    #[test]
+    fn script_errors_have_lines() {
+        let script = "ok
+
+# Nothing here
+nonexistent-command
+ok
+";
+        let script = Parser::new().parse_script(script).unwrap();
+
+        let (mut stderr, stderr_w) = stream_pipe().unwrap();
+        let ios = Streams {
+            stdin: ReadStream::Null,
+            stdout: WriteStream::Null,
+            stderr: stderr_w,
+        };
+
+        let mut int = Interpreter::new(ios, &env::temp_dir());
+        int.set_resolver(failing_resolver());
+        assert_eq!(int.eval_script("script.monch", &script), Exit::SUCCESS);
+
+        drop(int);
+        let mut err = String::new();
+        stderr.read_to_string(&mut err).unwrap();
+        assert!(err.starts_with("monch: script.monch: line 4: nonexistent-command: "));
+    }
+
+    #[test]
+    fn synchronous_builtins() {
+        let dir = scratch_dir("synchronous_builtins");
+        fs::create_dir_all(dir.join("sub")).unwrap();
+
+        // On their own, they change the shell.
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_resolver(failing_resolver());
+        let cmd = Parser::new().parse_command("cd sub").unwrap();
+        assert_eq!(int.eval_command(&cmd).unwrap(), Exit::SUCCESS);
+        assert_eq!(int.current_dir(), dir.join("sub"));
+
+        // In a pipeline, they're refused before anything runs.
+        let script = Parser::new()
+            .parse_script("ok | cd ..\nexport A=b | ok\n")
+            .unwrap();
+        let (mut stderr, stderr_w) = stream_pipe().unwrap();
+        let ios = Streams {
+            stdin: ReadStream::Null,
+            stdout: WriteStream::Null,
+            stderr: stderr_w,
+        };
+        let mut int = Interpreter::new(ios, &dir.join("sub"));
+        int.set_resolver(failing_resolver());
+        assert_eq!(int.eval_script("script", &script), Exit::BAD_SYNTAX);
+        assert_eq!(int.current_dir(), dir.join("sub"));
+        assert_eq!(int.get_var("A"), None);
+
+        drop(int);
+        let mut err = String::new();
+        stderr.read_to_string(&mut err).unwrap();
+        assert_eq!(
+            err,
+            "monch: script: line 1: cd: can't be used in a pipeline, since it changes the shell itself\n\
+             monch: script: line 2: export: can't be used in a pipeline, since it changes the shell itself\n"
+        );
+
+        // Listing doesn't change anything, so that can still be piped.
+        let (result, out) = run(&dir, failing_resolver(), "set | to cbor");
+        assert_eq!(result.unwrap(), Exit::SUCCESS);
+        assert!(!out.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn resolver_comes_before_builtins() {
+        let mut resolver = MockResolver::new();
+        let cd = resolver.add("cd", RecordingExe::new());
+
+        let (result, _) = run(&env::temp_dir(), resolver, "cd nowhere");
+        assert_eq!(result.unwrap(), Exit::SUCCESS);
+        assert_eq!(cd.calls(), vec![call(&["nowhere"], b"")]);
+    }
+
+    #[test]
+    fn redirects() {
+        let dir = scratch_dir("redirects");
+        fs::write(dir.join("in.txt"), "from a file").unwrap();
+
+        let mut resolver = MockResolver::new();
+        let exe = resolver.add("exe", RecordingExe::new().with_stdout("to a file"));
+
+        let (result, out) = run(&dir, resolver, "exe <in.txt >out.txt");
+
+        assert_eq!(result.unwrap(), Exit::SUCCESS);
+        assert!(out.is_empty());
+        assert_eq!(exe.calls(), vec![call(&[], b"from a file")]);
+        assert_eq!(fs::read(dir.join("out.txt")).unwrap(), b"to a file");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cbor_output_is_formatted() {
+        let mut cbor = vec![];
+        ciborium::ser::into_writer(&42, &mut cbor).unwrap();
+
+        let cbor_exe = || {
+            RecordingExe::new()
+                .with_types(Ty::Cbor, Ty::Cbor)
+                .with_stdout(cbor.clone())
+        };
+
+        // At the end of a pipeline, CBOR is formatted for humans.
+        let mut resolver = MockResolver::new();
+        resolver.add("data", cbor_exe());
+        let (result, out) = run_with(&env::temp_dir(), resolver, "data", |int| {
+            int.set_auto_format(true)
+        });
+
+        assert_eq!(result.unwrap(), Exit::SUCCESS);
+        assert_ne!(out, cbor);
+        assert!(String::from_utf8(out).unwrap().contains("42"));
+
+        // But not when it's redirected into a file.
+        let dir = scratch_dir("cbor_output_is_formatted");
+        let mut resolver = MockResolver::new();
+        resolver.add("data", cbor_exe());
+        let (result, out) = run(&dir, resolver, "data >out.cbor");
+
+        assert_eq!(result.unwrap(), Exit::SUCCESS);
+        assert!(out.is_empty());
+        assert_eq!(fs::read(dir.join("out.cbor")).unwrap(), cbor);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn auto_format_setting() {
+        let mut resolver = MockResolver::new();
+        resolver.add("data", RecordingExe::new().with_types(Ty::Cbor, Ty::Cbor));
+        resolver.add("text", RecordingExe::new().with_types(Ty::Any, Ty::Text));
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(resolver);
+
+        let stage_names = |int: &Interpreter, line: &str, to_person: bool| -> Vec<String> {
+            let cmd = Parser::new().parse_command(line).unwrap();
+            let planned = int.plan_pipeline(&cmd, to_person).unwrap();
+            planned.stages.into_iter().map(|s| s.command).collect()
+        };
+
+        // Our stdout isn't a terminal, so nothing is formatted by default.
+        assert!(!int.auto_format());
+        assert_eq!(stage_names(&int, "data", true), ["data"]);
+
+        // Turned on, CBOR going to a person gets formatted.
+        int.set_auto_format(true);
+        assert_eq!(stage_names(&int, "data", true), ["data", "to"]);
+        assert_eq!(stage_names(&int, "data", false), ["data"]);
+        assert_eq!(stage_names(&int, "text", true), ["text"]);
+
+        // And turned off again, it doesn't.
+        int.set_auto_format(false);
+        assert_eq!(stage_names(&int, "data", true), ["data"]);
+    }
+
+    #[test]
+    fn auto_convert_setting() {
+        let mut resolver = MockResolver::new();
+        resolver.add("program", RecordingExe::new().with_stdout("one\ntwo\n"));
+        resolver.add("data", RecordingExe::new().with_types(Ty::Cbor, Ty::Cbor));
+        resolver.add(
+            "raw",
+            RecordingExe::new().with_types(Ty::Unknown, Ty::Unknown),
+        );
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(resolver);
+        int.set_pipefail(true);
+
+        let stages = |int: &Interpreter, line: &str| {
+            let cmd = Parser::new().parse_command(line).unwrap();
+            let plan = int.explain_command(&cmd)?;
+            let stages = plan.stages.into_iter();
+            Ok(stages
+                .map(|s| (s.command, s.args.join(" "), s.inserted))
+                .collect::<Vec<_>>())
+        };
+        let stage =
+            |command: &str, args: &str, inserted| (command.to_string(), args.to_string(), inserted);
+
+        // A program's output is split into lines for a builtin reading CBOR, and CBOR is written
+        // as text for something reading unknown data.
+        assert_eq!(
+            stages(&int, "program | data").unwrap(),
+            [
+                stage("program", "", false),
+                stage("lines", "", true),
+                stage("data", "", false),
+            ]
+        );
+        assert_eq!(
+            stages(&int, "data | raw").unwrap(),
+            [
+                stage("data", "", false),
+                stage("to", "text", true),
+                stage("raw", "", false),
+            ]
+        );
+
+        let cmd = Parser::new().parse_command("program | to json").unwrap();
+        let (out, exit) = int.eval_command_capture(&cmd).unwrap();
+        assert_eq!((out, exit), (b"\"one\"\n\"two\"\n".to_vec(), Exit::SUCCESS));
+
+        // Turned off, they don't fit together.
+        int.set_auto_convert(false);
+        for line in ["program | data", "data | raw"] {
+            let result = stages(&int, line);
+            assert!(
+                matches!(result, Err(Error::TypeMismatch { .. })),
+                "{}",
+                line
+            );
+        }
+    }
+
+    #[test]
+    fn adapter_exits() {
+        let mut resolver = MockResolver::new();
+        resolver.add("program", RecordingExe::new().with_stdout("one\n"));
+        resolver.add(
+            "mid",
+            RecordingExe::new()
+                .with_types(Ty::Cbor, Ty::Cbor)
+                .with_exit(Exit::FAILURE),
+        );
+        resolver.add("last", RecordingExe::new());
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(resolver);
+        let mut exit = |line: &str| {
+            let cmd = Parser::new().parse_command(line).unwrap();
+            int.eval_command_capture(&cmd).unwrap().1
+        };
+
+        // Without pipefail, only the last stage counts, wherever adapters were added.
+        assert_eq!(exit("mid | last"), Exit::SUCCESS);
+        assert_eq!(exit("program | mid | last"), Exit::SUCCESS);
+        assert_eq!(exit("program | mid"), Exit::FAILURE);
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let mut resolver = MockResolver::new();
+        resolver.add("text", RecordingExe::new().with_types(Ty::Any, Ty::Text));
+        resolver.add("cbor", RecordingExe::new().with_types(Ty::Cbor, Ty::Cbor));
+
+        let (result, _) = run(&env::temp_dir(), resolver, "text | cbor");
+        assert!(matches!(result, Err(Error::TypeMismatch { .. })));
+    }
+
+    #[test]
+    fn capture_raw_cbor() {
+        let mut cbor = vec![];
+        ciborium::ser::into_writer(&42, &mut cbor).unwrap();
+
+        let mut resolver = MockResolver::new();
+        let data = RecordingExe::new()
+            .with_types(Ty::Cbor, Ty::Cbor)
+            .with_stdout(cbor.clone());
+        resolver.add("data", data);
+
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        int.set_resolver(resolver);
+
+        // Captured CBOR isn't formatted for a terminal.
+        let cmd = Parser::new().parse_command("data").unwrap();
+        let (out, exit) = int.eval_command_capture(&cmd).unwrap();
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(out, cbor);
+    }
+
+    #[test]
+    fn capture_builtin_output() {
+        let dir = scratch_dir("capture_builtin_output");
+        let mut cbor = vec![];
+        ciborium::ser::into_writer(&"item", &mut cbor).unwrap();
+        fs::write(dir.join("in.cbor"), &cbor).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        let cmd = Parser::new().parse_command("to cbor <in.cbor").unwrap();
+        let (out, exit) = int.eval_command_capture(&cmd).unwrap();
+
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(out, cbor);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn capture_external_output() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+        let cmd = Parser::new().parse_command("sh -c 'echo hello'").unwrap();
+        let (out, exit) = int.eval_command_capture(&cmd).unwrap();
+
+        assert_eq!(exit, Exit::SUCCESS);
+        assert_eq!(out, b"hello\n");
+    }
+
+    #[test]
+    fn variable_expansion() {
+        let dir = scratch_dir("variable_expansion");
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_script("test.monch", vec!["a".to_string(), "b c".to_string()]);
+        int.set_var("GREETING", "hello");
+
+        let mut output = |line: &str| {
+            let cmd = Parser::new().parse_command(line).unwrap();
+            let (out, _) = int.eval_command_capture(&cmd).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        assert_eq!(
+            output("echo --raw $GREETING ${GREETING}s \"$GREETING, world\""),
+            "hello hellos hello, world\n"
+        );
+        assert_eq!(output("echo --raw $NOT_A_REAL_VARIABLE"), "\n");
+        assert_eq!(output("echo --raw $PWD"), format!("{}\n", dir.display()));
+
+        // Special variables, from the script and the last command.
+        assert_eq!(output("echo --raw $0 $# $1 ${2}"), "test.monch 2 a b c\n");
+        output("sh -c 'exit 3'");
+        assert_eq!(output("echo --raw $?"), "3\n");
+        assert_eq!(output("echo --raw $?"), "0\n");
+
+        // `$@` is split into arguments, unless it's part of a bigger term.
+        assert_eq!(output("sh -c 'echo $#' sh $@"), "2\n");
+        assert_eq!(output("sh -c 'echo $#' sh \"[$@]\""), "1\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn time_command() {
+        let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+
+        // With --put, the times are written as data.
+        let cmd = Parser::new().parse_command("time --put sleep 0.2").unwrap();
+        let (out, exit) = int.eval_command_capture(&cmd).unwrap();
+        assert_eq!(exit, Exit::SUCCESS);
+
+        let times: HashMap<String, f64> = ciborium::de::from_reader(&out[..]).unwrap();
+        assert!(times["real"] >= 0.2, "real time too short: {:?}", times);
+        assert!(times.contains_key("user") && times.contains_key("sys"));
+
+        // Otherwise, they go to stderr.
+        let cmd = Parser::new().parse_command("time sleep 0").unwrap();
+        let (out, exit) = int.eval_command_capture(&cmd).unwrap();
+        assert_eq!(exit, Exit::SUCCESS);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn not_found_suggestions() {
+        let dir = scratch_dir("not_found_suggestions");
+        fs::copy("/bin/true", dir.join("grep")).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_var("MONCH_PATH", "");
+        int.set_var("PATH", dir.to_string_lossy());
+
+        let error = |int: &mut Interpreter, line: &str| {
+            let cmd = Parser::new().parse_command(line).unwrap();
+            int.eval_command(&cmd).unwrap_err().to_string()
+        };
+
+        // Builtins and programs on the path are both suggested.
+        assert!(error(&mut int, "gerp").ends_with("did you mean `grep` or `help`?"));
+        assert!(error(&mut int, "dirz").ends_with("did you mean `dirs`?"));
+
+        // New programs aren't noticed until we forget the old list.
+        fs::copy("/bin/true", dir.join("gerp2")).unwrap();
+        assert!(error(&mut int, "gerp").ends_with("did you mean `grep` or `help`?"));
+        int.forget_commands();
+        assert!(error(&mut int, "gerp").ends_with("did you mean `gerp2`, `grep`, or `help`?"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn raw_monch_programs() {
+        let dir = scratch_dir("raw_monch_programs");
+        fs::copy("/bin/true", dir.join("get")).unwrap();
+        fs::copy("/bin/true", dir.join("other")).unwrap();
+        fs::copy("/bin/true", dir.join("save")).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_var("MONCH_PATH", dir.to_string_lossy());
+        int.set_var("PATH", "");
+
+        let output_type = |name, args: &[&str]| {
+            let exe = int.lookup_exe(name).unwrap().exe;
+            exe.output_type(&args.iter().map(|a| a.to_string()).collect())
+        };
+        assert_eq!(output_type("get", &[".name"]), Ty::Cbor);
+        assert_eq!(output_type("get", &["--raw", ".name"]), Ty::Text);
+        assert_eq!(output_type("other", &["--raw"]), Ty::Text);
+        assert_eq!(output_type("get", &["--", "--raw"]), Ty::Cbor);
+
+        // Saving to a file leaves nothing to show.
+        assert_eq!(output_type("save", &["listing.csv"]), Ty::Nothing);
+        let save = int.lookup_exe("save").unwrap().exe;
+        assert_eq!(save.input_type(&vec!["listing.csv".to_string()]), Ty::Cbor);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn path_cache() {
+        let dir = scratch_dir("path_cache");
+        let program = dir.join("cached");
+        fs::copy("/bin/true", &program).unwrap();
+
+        let mut resolver = MockResolver::new();
+        resolver.add("mocked", RecordingExe::new());
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        int.set_resolver(resolver);
+        int.set_var("MONCH_PATH", "");
+        int.set_var("PATH", dir.to_string_lossy());
+
+        let path = |int: &Interpreter, name| int.lookup_exe(name).ok().and_then(|r| r.path);
+        assert_eq!(path(&int, "cached"), Some(program.clone()));
+        assert_eq!(path(&int, "mocked"), None);
+
+        // Once found, the program isn't looked for again, so deleting it goes unnoticed.
+        fs::remove_file(&program).unwrap();
+        assert_eq!(path(&int, "cached"), Some(program.clone()));
+
+        // Only programs from the paths are remembered, not builtins or the resolver's.
+        assert!(int.lookup_exe("cd").is_ok());
+        let hashed = int.hashed_programs();
+        assert_eq!(
+            hashed,
+            [("cached".to_string(), ExeKind::External, program.clone())]
+        );
+
+        // Changing PATH forgets everything, even once it's changed back.
+        int.set_var("PATH", "/nonexistent");
+        assert_eq!(path(&int, "cached"), None);
+        int.set_var("PATH", dir.to_string_lossy());
+        assert_eq!(path(&int, "cached"), None);
+
+        // So does `hash -r`.
+        fs::copy("/bin/true", &program).unwrap();
+        assert_eq!(path(&int, "cached"), Some(program.clone()));
+        fs::remove_file(&program).unwrap();
+        int.forget_commands();
+        assert_eq!(path(&int, "cached"), None);
+        assert!(int.hashed_programs().is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn set_current_dir_remembers_previous() {
+        let dir = scratch_dir("set_current_dir_remembers_previous");
+        fs::create_dir(dir.join("sub")).unwrap();
+
+        let mut int = Interpreter::new(Streams::null(), &dir);
+        assert_eq!(int.previous_dir(), None);
+
+        int.set_current_dir(dir.join("sub")).unwrap();
+        assert_eq!(int.current_dir(), dir.join("sub"));
+        assert_eq!(int.previous_dir(), Some(dir.as_path()));
+        assert_eq!(int.get_var("OLDPWD"), dir.to_str());
+
+        // Failing to change directory doesn't forget where we were.
+        assert!(int.set_current_dir(dir.join("missing")).is_err());
+        assert_eq!(int.current_dir(), dir.join("sub"));
+        assert_eq!(int.previous_dir(), Some(dir.as_path()));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -1,12 +1,27 @@
 pub(crate) mod builtin;
+pub(crate) mod cbor_display;
+pub(crate) mod color;
 pub(crate) mod exe;
+#[cfg(unix)]
+pub(crate) mod foreground;
+#[cfg(not(unix))]
+#[path = "foreground_fallback.rs"]
+pub(crate) mod foreground;
+pub(crate) mod history;
 pub(crate) mod interpreter;
+pub(crate) mod plan;
 pub(crate) mod streams;
+pub(crate) mod suggest;
 pub(crate) mod types;
-pub(crate) mod cbor_display;
+
+#[cfg(test)]
+pub(crate) mod testing;
 
 mod error;
 pub use error::Error;
 pub use exe::Exit;
-pub use interpreter::Interpreter;
+pub use foreground::{claim_terminal, install_interrupt_handler};
+pub use history::History;
+pub use interpreter::{Interpreter, ShellAction};
+pub use plan::{PipelinePlan, StagePlan, WriteTarget};
 pub use streams::Streams;
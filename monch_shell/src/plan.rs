@@ -0,0 +1,103 @@
+//! Plans for pipelines: what a command will run, and how its stages connect, worked out before
+//! anything is started.
+
+use crate::cbor_display::format_cbor_opts;
+use crate::exe::ExeKind;
+use crate::types::Ty;
+use ciborium::value::Value;
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// What a command would do if it ran, from [`Interpreter::explain_command`].
+///
+/// [`Interpreter::explain_command`]: crate::Interpreter::explain_command
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PipelinePlan {
+    /// The stages of the pipeline, from left to right.
+    pub stages: Vec<StagePlan>,
+}
+
+/// One stage of a [`PipelinePlan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StagePlan {
+    /// The name of the command being invoked.
+    pub command: String,
+
+    /// The evaluated arguments.
+    pub args: Vec<String>,
+
+    /// Where the command was found, and its path if it has one.
+    pub kind: ExeKind,
+    pub path: Option<PathBuf>,
+
+    /// What the command reads and writes, given these arguments.
+    pub input_type: Ty,
+    pub output_type: Ty,
+
+    /// The file this stage reads, instead of the pipeline's stdin. Only the first stage can
+    /// have one.
+    pub stdin_redirect: Option<PathBuf>,
+
+    /// The file this stage writes, instead of the pipeline's stdout. Only the last stage can
+    /// have one.
+    pub stdout_redirect: Option<WriteTarget>,
+
+    /// Whether the shell added this stage itself, like the `to tty` formatter.
+    pub inserted: bool,
+}
+
+/// A file that output is redirected into.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteTarget {
+    /// Replace the file's contents, like `>`.
+    Truncate(PathBuf),
+
+    /// Add to the end of the file, like `>>`.
+    Append(PathBuf),
+}
+
+impl WriteTarget {
+    /// Open the file for writing, creating it if it doesn't exist.
+    pub(crate) fn open(&self) -> io::Result<fs::File> {
+        let mut opts = fs::OpenOptions::new();
+        opts.write(true).create(true);
+
+        let path = match self {
+            WriteTarget::Truncate(path) => {
+                opts.truncate(true);
+                path
+            }
+            WriteTarget::Append(path) => {
+                opts.append(true);
+                path
+            }
+        };
+
+        opts.open(path)
+    }
+}
+
+impl PipelinePlan {
+    /// Describe each stage on a line of its own, starting with `prefix`, and colored if
+    /// `use_color` is set.
+    pub fn write_lines(
+        &self,
+        out: &mut impl Write,
+        prefix: &str,
+        use_color: bool,
+    ) -> io::Result<()> {
+        for stage in &self.stages {
+            let stage = Value::serialized(stage)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+
+            write!(out, "{}", prefix)?;
+            format_cbor_opts(out, &stage, use_color)?;
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+}
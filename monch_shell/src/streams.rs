@@ -1,4 +1,8 @@
 use os_pipe::{dup_stderr, dup_stdin, dup_stdout, PipeReader, PipeWriter};
+use std::io::Write;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+use std::sync::{Arc, Mutex, MutexGuard};
 use std::{fs, io, process};
 
 /// Represents a readable stream.
@@ -28,6 +32,15 @@ impl ReadStream {
             ReadStream::Null => Ok(ReadStream::Null),
         }
     }
+
+    /// Check whether this stream reads from an interactive terminal.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            ReadStream::Pipe(p) => is_terminal(p),
+            ReadStream::File(f) => is_terminal(f),
+            ReadStream::Null => false,
+        }
+    }
 }
 
 impl io::Read for ReadStream {
@@ -62,6 +75,9 @@ pub enum WriteStream {
     /// Write into an open file
     File(fs::File),
 
+    /// Write whole lines into a stream shared with other writers, so they don't get mixed up.
+    Shared(SharedLines),
+
     /// Discard all data written
     Null,
 }
@@ -82,9 +98,33 @@ impl WriteStream {
         match self {
             WriteStream::Pipe(p) => Ok(WriteStream::Pipe(p.try_clone()?)),
             WriteStream::File(f) => Ok(WriteStream::File(f.try_clone()?)),
+            WriteStream::Shared(s) => Ok(WriteStream::Shared(s.clone())),
             WriteStream::Null => Ok(WriteStream::Null),
         }
     }
+
+    /// Check whether this stream writes to an interactive terminal.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            WriteStream::Pipe(p) => is_terminal(p),
+            WriteStream::File(f) => is_terminal(f),
+            WriteStream::Shared(s) => s.lock().is_terminal(),
+            WriteStream::Null => false,
+        }
+    }
+}
+
+/// Check whether a file or pipe is an interactive terminal.
+#[cfg(unix)]
+fn is_terminal(file: &impl AsRawFd) -> bool {
+    // SAFETY: isatty only looks at the descriptor, which stays open for as long as we borrow it.
+    unsafe { libc::isatty(file.as_raw_fd()) == 1 }
+}
+
+/// Terminals are only detected on unix, so elsewhere everything is treated as non-interactive.
+#[cfg(not(unix))]
+fn is_terminal<T>(_file: &T) -> bool {
+    false
 }
 
 impl io::Write for WriteStream {
@@ -96,6 +136,7 @@ impl io::Write for WriteStream {
             // Pass writes through
             WriteStream::Pipe(w) => w.write(buf),
             WriteStream::File(w) => w.write(buf),
+            WriteStream::Shared(w) => w.write(buf),
         }
     }
 
@@ -107,6 +148,7 @@ impl io::Write for WriteStream {
             // Flush the underlying stream
             WriteStream::Pipe(w) => w.flush(),
             WriteStream::File(w) => w.flush(),
+            WriteStream::Shared(w) => w.flush(),
         }
     }
 }
@@ -116,11 +158,95 @@ impl From<WriteStream> for process::Stdio {
         match rs {
             WriteStream::Pipe(p) => p.into(),
             WriteStream::File(f) => f.into(),
+
+            // A process can't share our lock, so it writes straight to the shared stream.
+            WriteStream::Shared(s) => match s.lock().try_clone() {
+                Ok(shared) => shared.into(),
+                Err(_) => process::Stdio::null(),
+            },
+
             WriteStream::Null => process::Stdio::null(),
         }
     }
 }
 
+/// A writer which only writes whole lines to a stream shared with other writers, each starting
+/// with a prefix, like `grep: `. Anything left over without a newline is written when it's
+/// dropped.
+#[derive(Debug)]
+pub struct SharedLines {
+    /// The stream shared between writers.
+    shared: Arc<Mutex<WriteStream>>,
+
+    /// Written before each line.
+    prefix: String,
+
+    /// The start of a line which hasn't been finished yet.
+    partial: Vec<u8>,
+}
+
+impl SharedLines {
+    /// Write lines to `shared`, starting each one with `prefix`.
+    pub fn new(shared: Arc<Mutex<WriteStream>>, prefix: impl Into<String>) -> SharedLines {
+        SharedLines {
+            shared,
+            prefix: prefix.into(),
+            partial: vec![],
+        }
+    }
+
+    /// Lock the shared stream. If another writer panicked while holding it, carry on anyway: the
+    /// worst that happened is a missing line.
+    fn lock(&self) -> MutexGuard<'_, WriteStream> {
+        self.shared
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Write a line, which should include its newline, in one go.
+    fn write_line(&self, line: &[u8]) -> io::Result<()> {
+        let mut prefixed = self.prefix.as_bytes().to_vec();
+        prefixed.extend_from_slice(line);
+        self.lock().write_all(&prefixed)
+    }
+}
+
+impl Clone for SharedLines {
+    /// Make another writer to the same stream, with the same prefix, but its own partial line.
+    fn clone(&self) -> SharedLines {
+        SharedLines::new(self.shared.clone(), self.prefix.clone())
+    }
+}
+
+impl io::Write for SharedLines {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+
+        // Write out every line we've finished.
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            self.write_line(&line)?;
+        }
+
+        Ok(buf.len())
+    }
+
+    /// Flushes the shared stream. Partial lines are kept until they're finished.
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+}
+
+impl Drop for SharedLines {
+    fn drop(&mut self) {
+        if !self.partial.is_empty() {
+            let mut line = std::mem::take(&mut self.partial);
+            line.push(b'\n');
+            let _ = self.write_line(&line);
+        }
+    }
+}
+
 /// Data streams for stdin, stdout, and stderr.
 #[derive(Debug)]
 pub struct Streams {
@@ -0,0 +1,142 @@
+//! Suggestions for commands which weren't found, by edit distance from the commands which exist.
+
+use std::collections::BTreeSet;
+use std::ffi::OsStr;
+use std::fs;
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+
+/// The most suggestions to make at once.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Find up to three candidates close to `name`, closest first. Ties are broken alphabetically.
+pub fn suggest<'c>(name: &str, candidates: impl IntoIterator<Item = &'c str>) -> Vec<String> {
+    // Allow more typos in longer names, but always allow a swapped pair of letters.
+    let max_distance = (name.chars().count() / 3).max(2);
+
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|c| (levenshtein(name, c), c))
+        .filter(|&(d, c)| d <= max_distance && c != name)
+        .collect();
+
+    close.sort_unstable();
+    close.dedup();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.to_string())
+        .collect()
+}
+
+/// The number of single-character insertions, deletions, and substitutions needed to turn `a`
+/// into `b`.
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+
+    // The distances from the prefix of `a` so far, to each prefix of `b`.
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = diagonal + if ca == cb { 0 } else { 1 };
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// The names of the executables in each directory of a `PATH`-style list.
+pub fn executables_on(path: &OsStr) -> BTreeSet<String> {
+    let mut names = BTreeSet::new();
+
+    for dir in std::env::split_paths(path) {
+        // Skip directories which don't exist, or which we can't read.
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let executable = entry.metadata().map(|m| is_executable(&m)).unwrap_or(false);
+
+            if let (true, Ok(name)) = (executable, entry.file_name().into_string()) {
+                names.insert(name);
+            }
+        }
+    }
+
+    names
+}
+
+/// Describe some suggestions, like "did you mean `a`, `b`, or `c`?"
+pub fn did_you_mean(suggestions: &[String]) -> String {
+    let quoted: Vec<String> = suggestions.iter().map(|s| format!("`{}`", s)).collect();
+    match quoted.as_slice() {
+        [] => String::new(),
+        [one] => format!("did you mean {}?", one),
+        [a, b] => format!("did you mean {} or {}?", a, b),
+        [rest @ .., last] => format!("did you mean {}, or {}?", rest.join(", "), last),
+    }
+}
+
+/// Whether a file can be run, going by its permission bits.
+#[cfg(unix)]
+fn is_executable(meta: &fs::Metadata) -> bool {
+    meta.is_file() && meta.permissions().mode() & 0o111 != 0
+}
+
+/// Without permission bits to go by, any file might be something we can run.
+#[cfg(not(unix))]
+fn is_executable(meta: &fs::Metadata) -> bool {
+    meta.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("grep", "grep"), 0);
+        assert_eq!(levenshtein("", "grep"), 4);
+        assert_eq!(levenshtein("gre", "grep"), 1);
+        assert_eq!(levenshtein("grpe", "grep"), 2);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn ranking() {
+        let candidates = ["grep", "egrep", "get", "gre", "sed", "ls", "greps"];
+
+        // Closest first, then alphabetical, and no more than three.
+        assert_eq!(suggest("grep", candidates), ["egrep", "gre", "greps"]);
+        assert_eq!(suggest("gerp", candidates), ["get", "gre", "grep"]);
+        assert_eq!(suggest("sd", candidates), ["sed", "ls"]);
+
+        // Nothing is suggested if nothing's close.
+        assert!(suggest("python", candidates).is_empty());
+    }
+
+    #[test]
+    fn descriptions() {
+        let names = |n: &[&str]| n.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(did_you_mean(&names(&[])), "");
+        assert_eq!(did_you_mean(&names(&["grep"])), "did you mean `grep`?");
+        assert_eq!(
+            did_you_mean(&names(&["get", "grep"])),
+            "did you mean `get` or `grep`?"
+        );
+        assert_eq!(
+            did_you_mean(&names(&["get", "grep", "sed"])),
+            "did you mean `get`, `grep`, or `sed`?"
+        );
+    }
+}
@@ -0,0 +1,244 @@
+//! Mock executables, for testing the interpreter without spawning real processes.
+
+use crate::exe::{Args, ExeResolver, Execute, Exit, Wait};
+use crate::streams::{stream_pipe, ReadStream};
+use crate::{types::Ty, Error, Interpreter, Streams};
+use ciborium::value::Value;
+use monch_syntax::ast::{ReadRedirect, Term};
+use monch_syntax::Parser;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::{env, fs, process, thread};
+
+/// Make an empty scratch directory for a test.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("monch-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir.canonicalize().unwrap()
+}
+
+/// Encode items as a stream of CBOR, like a command's input.
+pub fn encode(items: &[Value]) -> Vec<u8> {
+    let mut bytes = vec![];
+    for item in items {
+        ciborium::ser::into_writer(item, &mut bytes).unwrap();
+    }
+    bytes
+}
+
+/// Start something with `stdin` as its input, and wait for it, returning what it wrote to stdout
+/// and stderr, and its exit. Without any input, stdin is null.
+pub fn run_streams(
+    stdin: &[u8],
+    start: impl FnOnce(Streams) -> Box<dyn Wait>,
+) -> (Vec<u8>, String, Exit) {
+    let (mut stdout, stdout_w) = stream_pipe().unwrap();
+    let (mut stderr, stderr_w) = stream_pipe().unwrap();
+    let stdin = match stdin {
+        [] => ReadStream::Null,
+        input => {
+            let (stdin, mut stdin_w) = stream_pipe().unwrap();
+            let input = input.to_vec();
+            thread::spawn(move || stdin_w.write_all(&input));
+            stdin
+        }
+    };
+    let ios = Streams {
+        stdin,
+        stdout: stdout_w,
+        stderr: stderr_w,
+    };
+
+    let exit = start(ios).wait().unwrap();
+
+    let (mut out, mut err) = (vec![], String::new());
+    stdout.read_to_end(&mut out).unwrap();
+    stderr.read_to_string(&mut err).unwrap();
+    (out, err, exit)
+}
+
+/// Run a builtin directly with `stdin` as its input, returning what it wrote to stdout and
+/// stderr, and its exit.
+pub fn run_builtin(
+    exe: &dyn Execute,
+    int: &mut Interpreter,
+    args: &[&str],
+    stdin: &[u8],
+) -> (Vec<u8>, String, Exit) {
+    let args: Args = args.iter().map(|a| a.to_string()).collect();
+    run_streams(stdin, |ios| exe.execute(int, ios, &args).unwrap())
+}
+
+/// Run each command line in turn, returning the output of the last one.
+pub fn run(int: &mut Interpreter, lines: &[&str]) -> (String, Exit) {
+    let mut result = (vec![], Exit::SUCCESS);
+    for line in lines {
+        let cmd = Parser::new().parse_command(line).unwrap();
+        result = int.eval_command_capture(&cmd).unwrap();
+    }
+
+    (String::from_utf8(result.0).unwrap(), result.1)
+}
+
+/// Run a command line with its input read from a file holding `input`, in the interpreter's
+/// current directory. Returns its output, and its exit.
+pub fn run_with_input(int: &mut Interpreter, line: &str, input: &[u8]) -> (String, Exit) {
+    let file = int.current_dir().join("input");
+    fs::write(&file, input).unwrap();
+
+    let mut cmd = Parser::new().parse_command(line).unwrap();
+    cmd.stdin_redirect = Some(ReadRedirect::File {
+        file: Term::Literal {
+            value: file.to_string_lossy().into_owned(),
+        },
+    });
+    let (out, exit) = int.eval_command_capture(&cmd).unwrap();
+
+    fs::remove_file(file).unwrap();
+    (String::from_utf8(out).unwrap(), exit)
+}
+
+/// One run of a [`RecordingExe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    /// The arguments it was run with.
+    pub args: Args,
+
+    /// Everything written to its stdin.
+    pub stdin: Vec<u8>,
+}
+
+/// An executable which records how it was run, and writes scripted output.
+///
+/// Clones share their record of calls, so a test can keep one clone and hand the other to the
+/// interpreter.
+#[derive(Clone)]
+pub struct RecordingExe {
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+    read_limit: Option<u64>,
+    exit: Exit,
+    input_type: Ty,
+    output_type: Ty,
+    calls: Arc<Mutex<Vec<Call>>>,
+}
+
+impl RecordingExe {
+    /// Create a RecordingExe which succeeds without any output, and has the same types as an
+    /// external program from the system PATH.
+    pub fn new() -> RecordingExe {
+        RecordingExe {
+            stdout: vec![],
+            stderr: vec![],
+            read_limit: None,
+            exit: Exit::SUCCESS,
+            input_type: Ty::Any,
+            output_type: Ty::Unknown,
+            calls: Default::default(),
+        }
+    }
+
+    /// Write these bytes to stdout, once all of stdin has been read.
+    pub fn with_stdout(mut self, stdout: impl Into<Vec<u8>>) -> RecordingExe {
+        self.stdout = stdout.into();
+        self
+    }
+
+    /// Write these bytes to stderr as soon as it starts, a byte at a time, so that anything else
+    /// writing to stderr at once gets mixed in.
+    pub fn with_stderr(mut self, stderr: impl Into<Vec<u8>>) -> RecordingExe {
+        self.stderr = stderr.into();
+        self
+    }
+
+    /// Stop reading stdin after this many bytes, rather than reading all of it.
+    pub fn with_read_limit(mut self, bytes: u64) -> RecordingExe {
+        self.read_limit = Some(bytes);
+        self
+    }
+
+    /// Exit with this status.
+    pub fn with_exit(mut self, exit: Exit) -> RecordingExe {
+        self.exit = exit;
+        self
+    }
+
+    /// Claim to read and write these types.
+    pub fn with_types(mut self, input_type: Ty, output_type: Ty) -> RecordingExe {
+        self.input_type = input_type;
+        self.output_type = output_type;
+        self
+    }
+
+    /// Every call made so far, in order.
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+impl Execute for RecordingExe {
+    fn execute(
+        &self,
+        _int: &mut Interpreter,
+        mut ios: Streams,
+        args: &Args,
+    ) -> Result<Box<dyn Wait>, Error> {
+        let this = self.clone();
+        let args = args.clone();
+
+        let worker = thread::spawn(move || {
+            for byte in &this.stderr {
+                let _ = ios.stderr.write_all(&[*byte]);
+                thread::yield_now();
+            }
+
+            let mut stdin = vec![];
+            let _ = match this.read_limit {
+                Some(limit) => (&mut ios.stdin).take(limit).read_to_end(&mut stdin),
+                None => ios.stdin.read_to_end(&mut stdin),
+            };
+            let _ = ios.stdout.write_all(&this.stdout);
+
+            this.calls.lock().unwrap().push(Call { args, stdin });
+            this.exit
+        });
+
+        Ok(Box::new(worker))
+    }
+
+    fn input_type(&self, _args: &Args) -> Ty {
+        self.input_type
+    }
+
+    fn output_type(&self, _args: &Args) -> Ty {
+        self.output_type
+    }
+}
+
+/// An [`ExeResolver`] serving [`RecordingExe`]s by name.
+#[derive(Default)]
+pub struct MockResolver {
+    exes: HashMap<String, RecordingExe>,
+}
+
+impl MockResolver {
+    pub fn new() -> MockResolver {
+        Default::default()
+    }
+
+    /// Serve `exe` under `name`, returning a clone to inspect its calls later.
+    pub fn add(&mut self, name: &str, exe: RecordingExe) -> RecordingExe {
+        self.exes.insert(name.to_string(), exe.clone());
+        exe
+    }
+}
+
+impl ExeResolver for MockResolver {
+    fn resolve(&self, name: &str) -> Option<Box<dyn Execute>> {
+        let exe = self.exes.get(name)?.clone();
+        Some(Box::new(exe))
+    }
+}
@@ -1,3 +1,4 @@
+use serde::{Serialize, Serializer};
 use std::fmt;
 use std::str::FromStr;
 
@@ -37,6 +38,12 @@ impl fmt::Display for Ty {
     }
 }
 
+impl Serialize for Ty {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
 impl FromStr for Ty {
     type Err = ();
 
@@ -53,20 +60,48 @@ impl FromStr for Ty {
     }
 }
 
-/// If we can connect output of type [`from`] to an input stream of type [`to`]
-pub fn can_connect(from: Ty, to: Ty) -> bool {
+/// Whether output of one type can be connected to input of another, and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connect {
+    /// The output can be passed straight on.
+    Direct,
+
+    /// The output has to be converted first, by the builtin command line given, like `lines`.
+    Via(&'static str),
+
+    /// They can't be connected.
+    No,
+}
+
+/// Builtins which convert one type of output into another type of input, so that stages which
+/// don't fit together can still be connected.
+const ADAPTERS: &[(Ty, Ty, &str)] = &[
+    // Programs mostly write lines of text.
+    (Ty::Unknown, Ty::Cbor, "lines"),
+    (Ty::Cbor, Ty::Unknown, "to text"),
+];
+
+/// If we can connect output of type [`from`] to an input stream of type [`to`], and whether it
+/// needs converting on the way.
+pub fn can_connect(from: Ty, to: Ty) -> Connect {
     use Ty::*;
     match (from, to) {
         // If we're receiving [`Any`] data, we can always connect.
-        (_, Any) => true,
+        (_, Any) => Connect::Direct,
 
         // We can always connect data to itself.
-        (x, y) if x == y => true,
+        (x, y) if x == y => Connect::Direct,
 
         // It's fine to pipe something into nothing.
-        (_, Nothing) => true,
+        (_, Nothing) => Connect::Direct,
+
+        // Text is what something reading data of an unknown type most likely wants.
+        (Text, Unknown) => Connect::Direct,
 
-        // Otherwise, we can't connect.
-        _ => false,
+        // Otherwise, we can only connect through an adapter, if there is one.
+        _ => ADAPTERS
+            .iter()
+            .find(|&&(f, t, _)| (f, t) == (from, to))
+            .map_or(Connect::No, |&(_, _, adapter)| Connect::Via(adapter)),
     }
 }
@@ -0,0 +1,396 @@
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
+
+/// Run the monch binary with these arguments.
+fn monch(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_monch"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// Run the monch binary, piping it `stdin`.
+fn monch_piped(stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monch"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// The monch binary, running interactively on a pseudo-terminal of its own.
+struct Session {
+    child: Child,
+    terminal: File,
+    output: Arc<Mutex<String>>,
+    collector: JoinHandle<()>,
+
+    /// A home directory of its own, removed once it's finished.
+    home: PathBuf,
+}
+
+impl Session {
+    /// Start monch with these arguments and extra environment variables. It's the leader of a
+    /// new session, with the pseudo-terminal as its controlling terminal, like a login shell.
+    fn start(args: &[&str], envs: &[(&str, &str)]) -> Session {
+        // SAFETY: these only make a new pseudo-terminal, and ask for its name.
+        let (master, slave_name) = unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0, "couldn't open a pseudo-terminal");
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+
+            let name = CStr::from_ptr(libc::ptsname(master));
+            (
+                File::from_raw_fd(master),
+                name.to_str().unwrap().to_string(),
+            )
+        };
+
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(slave_name)
+            .unwrap();
+        // Keep the shell's history out of the real home directory, and apart from other tests'.
+        static SESSIONS: AtomicUsize = AtomicUsize::new(0);
+        let session = SESSIONS.fetch_add(1, Ordering::Relaxed);
+        let home = env::temp_dir().join(format!("monch-home-{}-{}", process::id(), session));
+        fs::create_dir_all(&home).unwrap();
+
+        let mut command = Command::new(env!("CARGO_BIN_EXE_monch"));
+        command
+            .args(args)
+            .env("HOME", &home)
+            .envs(envs.iter().copied())
+            .stdin(slave.try_clone().unwrap())
+            .stdout(slave.try_clone().unwrap())
+            .stderr(slave);
+
+        // SAFETY: setsid and ioctl are async-signal-safe, so they can run between fork and exec.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY, 0) < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        let child = command.spawn().unwrap();
+
+        // Collect the output as it comes, until the shell exits and the terminal closes.
+        let output = Arc::new(Mutex::new(String::new()));
+        let mut reader = master.try_clone().unwrap();
+        let collected = output.clone();
+        let collector = thread::spawn(move || {
+            let mut buf = [0; 1024];
+            loop {
+                match reader.read(&mut buf) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => collected
+                        .lock()
+                        .unwrap()
+                        .push_str(&String::from_utf8_lossy(&buf[..n])),
+                }
+            }
+        });
+
+        Session {
+            child,
+            terminal: master,
+            output,
+            collector,
+            home,
+        }
+    }
+
+    /// Wait until the shell has shown `prompts` prompts in all.
+    fn wait_for_prompt(&self, prompts: usize) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while self.output().matches("$ ").count() < prompts {
+            assert!(Instant::now() < deadline, "no prompt: {:?}", self.output());
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Type some keys at the terminal.
+    fn type_keys(&mut self, keys: &str) {
+        write!(self.terminal, "{}", keys).unwrap();
+    }
+
+    /// Everything the shell has written to the terminal so far.
+    fn output(&self) -> String {
+        self.output.lock().unwrap().clone()
+    }
+
+    /// Wait for the shell to exit, returning everything it wrote to the terminal.
+    fn finish(mut self) -> String {
+        self.child.wait().unwrap();
+        self.collector.join().unwrap();
+        fs::remove_dir_all(&self.home).unwrap();
+        let output = self.output.lock().unwrap().clone();
+        output
+    }
+}
+
+/// Run the monch binary interactively, on a pseudo-terminal. Each of `lines` is typed at a prompt
+/// of its own, and then the shell's told to exit. Returns everything it wrote to the terminal.
+fn monch_interactive(args: &[&str], envs: &[(&str, &str)], lines: &[&str]) -> String {
+    let mut session = Session::start(args, envs);
+
+    // Wait for each prompt before typing, so nothing's typed before the shell's ready for it.
+    for (prompts, line) in (1..).zip(lines.iter().chain(&["\x04"])) {
+        session.wait_for_prompt(prompts);
+        session.type_keys(&format!("{}\r", line));
+    }
+
+    session.finish()
+}
+
+/// Write a script into the temp directory, returning its path.
+fn script(name: &str, source: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!("monch-{}-{}.monch", name, process::id()));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn run_command() {
+    let out = monch(&["-c", "echo --raw hello | wc -c"]);
+    assert!(out.status.success());
+    assert_eq!(String::from_utf8_lossy(&out.stdout).trim(), "6");
+
+    // Syntax errors are reported, and exit with 2.
+    let out = monch(&["-c", "echo |"]);
+    assert_eq!(out.status.code(), Some(2));
+    assert!(!out.stderr.is_empty());
+}
+
+#[test]
+fn explain_command() {
+    let out = monch(&["--explain", "echo hello | wc -c"]);
+    assert!(out.status.success());
+
+    // Both stages are described, but neither is run.
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(stdout.lines().count(), 2);
+    assert!(stdout.contains("echo") && stdout.contains("wc"));
+    assert!(!stdout.contains("hello\n"));
+
+    let out = monch(&["--explain", "nonexistent-command"]);
+    assert_eq!(out.status.code(), Some(127));
+}
+
+#[test]
+fn no_color_when_captured() {
+    let no_escapes = |out: Output| {
+        let (stdout, stderr) = (out.stdout, out.stderr);
+        assert!(
+            !stdout.contains(&0x1b),
+            "{}",
+            String::from_utf8_lossy(&stdout)
+        );
+        assert!(
+            !stderr.contains(&0x1b),
+            "{}",
+            String::from_utf8_lossy(&stderr)
+        );
+    };
+
+    no_escapes(monch(&["-c", "set | to tty"]));
+    no_escapes(monch(&["-c", "time set | to tty"]));
+    no_escapes(monch(&["--explain", "set | to tty"]));
+}
+
+#[test]
+fn run_script() {
+    let path = script(
+        "run_script",
+        "# A comment\necho --raw one\nfalse\necho --raw two\n",
+    );
+    let out = monch(&[path.to_str().unwrap()]);
+
+    // Without errexit, every command runs, and the last one's exit is the script's.
+    assert!(out.status.success());
+    assert_eq!(out.stdout, b"one\ntwo\n");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn script_arguments() {
+    let path = script("script_arguments", "echo --raw $# $1 $2\n");
+    let out = monch(&[path.to_str().unwrap(), "one", "two"]);
+
+    assert!(out.status.success());
+    assert_eq!(out.stdout, b"2 one two\n");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn errexit_stops_script() {
+    let path = script(
+        "errexit_stops_script",
+        "set -e\necho --raw one\nfalse\necho --raw two\n",
+    );
+    let out = monch(&[path.to_str().unwrap()]);
+
+    assert_eq!(out.status.code(), Some(1));
+    assert_eq!(out.stdout, b"one\n");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn exit_stops_script() {
+    let path = script(
+        "exit_stops_script",
+        "echo --raw one\nexit 7\necho --raw two\n",
+    );
+    let out = monch(&[path.to_str().unwrap()]);
+
+    assert_eq!(out.status.code(), Some(7));
+    assert_eq!(out.stdout, b"one\n");
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn exec_replaces_shell() {
+    let out = monch(&["-c", "exec sh -c 'echo $PPID; exit 3'"]);
+    assert_eq!(out.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        process::id().to_string()
+    );
+
+    let out = monch(&["-c", "exec nonexistent-command"]);
+    assert_eq!(out.status.code(), Some(126));
+}
+
+#[test]
+fn signal_exit_status() {
+    let out = monch(&["-c", "sh -c 'kill -9 $$'"]);
+    assert_eq!(out.status.code(), Some(128 + 9));
+}
+
+#[test]
+fn piped_script() {
+    let out = monch_piped("echo --raw one\necho --raw two | wc -l\nsh -c 'exit 3'\n");
+    assert_eq!(out.status.code(), Some(3));
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).replace(' ', ""),
+        "one\n1\n"
+    );
+
+    // Nothing is printed besides the commands' own output: no prompts.
+    assert!(out.stderr.is_empty());
+}
+
+#[test]
+fn startup_file() {
+    let rc = script(
+        "startup_file",
+        "# Errors don't stop the rest from running.\nnonexistent-command\nexport MONCH_RC_TEST=loaded\n",
+    );
+    let rc_env = [("MONCH_RC", rc.to_str().unwrap())];
+
+    // The startup file runs in the shell itself, so what it exports is seen by later commands.
+    let show = ["env --match MONCH_RC_TEST"];
+    let out = monch_interactive(&[], &rc_env, &show);
+    assert!(out.contains("loaded"), "{}", out);
+    assert!(out.contains("line 2: nonexistent-command"), "{}", out);
+
+    // --norc skips it.
+    let out = monch_interactive(&["--norc"], &rc_env, &show);
+    assert!(!out.contains("loaded"), "{}", out);
+
+    // --rcfile takes precedence over MONCH_RC.
+    let other = script("startup_file_other", "export MONCH_RC_TEST=other\n");
+    let out = monch_interactive(&["--rcfile", other.to_str().unwrap()], &rc_env, &show);
+    assert!(out.contains("other"), "{}", out);
+
+    fs::remove_file(rc).unwrap();
+    fs::remove_file(other).unwrap();
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn foreground_jobs() {
+    let mut session = Session::start(&["--norc"], &[]);
+    session.wait_for_prompt(1);
+
+    // A job runs in a process group of its own, which has the terminal while it runs.
+    session.type_keys("sh -c 'cat /proc/$$/stat'\r");
+    session.wait_for_prompt(2);
+    let output = session.output();
+    let stat = output.lines().find(|l| l.contains(" (sh) ")).unwrap();
+    let fields: Vec<&str> = stat.split_whitespace().collect();
+    let (pid, group, terminal_group) = (fields[0], fields[4], fields[7]);
+    assert_eq!(group, pid, "{}", stat);
+    assert_eq!(terminal_group, group, "{}", stat);
+
+    // Ctrl-C goes to the job, and not to the shell.
+    let start = Instant::now();
+    session.type_keys("sleep 5\r");
+    thread::sleep(Duration::from_millis(300));
+    session.type_keys("\x03");
+    session.wait_for_prompt(3);
+    assert!(start.elapsed() < Duration::from_secs(4));
+
+    // Ctrl-Z doesn't stop the shell, or leave it waiting on a stopped job.
+    session.type_keys("sleep 1\r");
+    thread::sleep(Duration::from_millis(300));
+    session.type_keys("\x1a");
+    session.wait_for_prompt(4);
+
+    session.type_keys("echo --raw still here\r");
+    session.wait_for_prompt(5);
+    session.type_keys("\x04");
+    let output = session.finish();
+    assert!(output.contains("still here\r\n"), "{}", output);
+}
+
+#[test]
+fn history_persists() {
+    let home = env::temp_dir().join(format!("monch-history_persists-{}", process::id()));
+    let _ = fs::remove_dir_all(&home);
+    fs::create_dir_all(&home).unwrap();
+    let home_env = [("HOME", home.to_str().unwrap())];
+
+    monch_interactive(&["--norc"], &home_env, &["echo first", "echo second"]);
+
+    // The next shell can see them, as data.
+    let out = monch_interactive(&["--norc"], &home_env, &["history | to cbor | to tty"]);
+    assert!(out.contains("echo first"), "{}", out);
+    assert!(out.contains("echo second"), "{}", out);
+    assert!(home.join(".monch_history").exists());
+
+    monch_interactive(&["--norc"], &home_env, &["history clear"]);
+    let history = fs::read_to_string(home.join(".monch_history")).unwrap();
+    assert!(history.is_empty(), "{}", history);
+
+    fs::remove_dir_all(home).unwrap();
+}
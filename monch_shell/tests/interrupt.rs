@@ -0,0 +1,33 @@
+#![cfg(unix)]
+
+use monch_shell::{Exit, Interpreter, Streams};
+use monch_syntax::Parser;
+use std::time::{Duration, Instant};
+use std::{env, process, thread};
+
+#[test]
+fn interrupt_sleep() {
+    monch_shell::install_interrupt_handler().unwrap();
+
+    let mut int = Interpreter::new(Streams::null(), &env::temp_dir());
+    let cmd = Parser::new().parse_command("sleep 5").unwrap();
+
+    // Once the sleep has had time to start, interrupt ourselves like a Ctrl-C would.
+    let interrupter = thread::spawn(|| {
+        thread::sleep(Duration::from_millis(200));
+        unsafe { libc::kill(process::id() as libc::pid_t, libc::SIGINT) };
+    });
+
+    let start = Instant::now();
+    let exit = int.eval_command(&cmd).unwrap();
+    interrupter.join().unwrap();
+
+    // The sleep was interrupted, and we survived it.
+    assert_eq!(exit, Exit::INTERRUPTED);
+    assert!(start.elapsed() < Duration::from_secs(4));
+
+    // With nothing running, an interrupt doesn't do anything.
+    unsafe { libc::kill(process::id() as libc::pid_t, libc::SIGINT) };
+    let cmd = Parser::new().parse_command("true").unwrap();
+    assert_eq!(int.eval_command(&cmd).unwrap(), Exit::SUCCESS);
+}
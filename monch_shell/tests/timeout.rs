@@ -0,0 +1,43 @@
+#![cfg(unix)]
+
+use monch_shell::{Exit, Interpreter, Streams};
+use monch_syntax::Parser;
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
+
+#[test]
+fn timeout_kills_sleep() {
+    let dir = env::temp_dir();
+    let pid_file = dir.join(format!("monch-timeout-{}.pid", process::id()));
+
+    let mut int = Interpreter::new(Streams::null(), &dir);
+    let line = format!(
+        "timeout 0.2 sh -c 'echo $$ >{}; exec sleep 5'",
+        pid_file.display()
+    );
+    let cmd = Parser::new().parse_command(&line).unwrap();
+
+    let start = Instant::now();
+    let exit = int.eval_command(&cmd).unwrap();
+
+    // It gave up promptly, and said so.
+    assert_eq!(exit, Exit::TIMED_OUT);
+    assert!(start.elapsed() < Duration::from_secs(2));
+
+    // The sleep is gone: there's no process with its pid any more.
+    let pid: libc::pid_t = fs::read_to_string(&pid_file)
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    assert!(!alive);
+
+    // Commands which finish in time exit as usual.
+    let cmd = Parser::new()
+        .parse_command("timeout 5 sh -c 'exit 3'")
+        .unwrap();
+    assert_eq!(int.eval_command(&cmd).unwrap(), Exit::Code(3));
+
+    fs::remove_file(pid_file).unwrap();
+}
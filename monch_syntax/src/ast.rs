@@ -22,6 +22,19 @@ pub struct Command {
 
     /// Optionally, an output redirection (like `echo thing >out.txt`)
     pub stdout_redirect: Option<WriteRedirect>,
+
+    /// Whether to time the command, like `time sleep 1`.
+    pub time: Option<Time>,
+
+    /// The line the command starts on, counting from 1, for reporting errors in scripts.
+    pub line: usize,
+}
+
+/// How to report the time a command took.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Time {
+    /// Write the times to stdout as data, rather than to stderr as text.
+    pub put: bool,
 }
 
 /// A script: for now, just a list of commands
@@ -53,6 +66,11 @@ pub enum WriteRedirect {
 pub enum Term {
     /// A literal term
     Literal { value: String },
-    // TODO: variable identifiers
-    // TODO: format strings (i.e: concatenation of several terms)
+
+    /// The value of a variable, like `$HOME`. Special variables like `$?` are named without the
+    /// `$`, so that's `?`.
+    Variable { name: String },
+
+    /// Several terms concatenated together, like `"$HOME/src"`.
+    Concat { parts: Vec<Term> },
 }
@@ -63,8 +63,13 @@ impl Parser {
     }
 
     fn p_command(&self, input: Pair<Rule>) -> Result<ast::Command> {
+        let (line, _) = input.as_span().start_pos().line_col();
         let mut ctx = Context::unpack(input, Rule::Command);
 
+        let time = ctx
+            .match_optional_rule(Rule::TimePrefix)
+            .map(|prefix| self.p_time_prefix(prefix));
+
         // Get each inner invocation rule
         let inv_rules = ctx.inner().collect::<Vec<Pair<Rule>>>();
         let inv_rules_len = inv_rules.len();
@@ -103,9 +108,19 @@ impl Parser {
             pipeline: invocations,
             stdin_redirect: read_redirect,
             stdout_redirect: write_redirect,
+            time,
+            line,
         })
     }
 
+    fn p_time_prefix(&self, input: Pair<Rule>) -> ast::Time {
+        let mut ctx = Context::unpack(input, Rule::TimePrefix);
+        let put = ctx.match_optional_rule(Rule::TimePut).is_some();
+        ctx.done();
+
+        ast::Time { put }
+    }
+
     fn p_invocation(
         &self,
         input: Pair<Rule>,
@@ -232,17 +247,25 @@ impl Parser {
 
     fn p_term(&self, input: Pair<Rule>) -> Result<ast::Term> {
         let mut ctx = Context::unpack(input, Rule::Term);
-        let term = ctx.match_any();
-        ctx.done();
 
-        let value = match term.as_rule() {
-            Rule::BareTerm => self.p_bare_term(term)?,
-            Rule::SingleQuotedStringLiteral => self.p_single_quoted_string_literal(term)?,
-            Rule::DoubleQuotedStringLiteral => self.p_double_quoted_string_literal(term)?,
-            _ => unreachable!("unexpected flavor of Term"),
-        };
+        let mut parts = vec![];
+        for part in ctx.inner() {
+            match part.as_rule() {
+                Rule::BareTerm => parts.push(literal(self.p_bare_term(part)?)),
+                Rule::SingleQuotedStringLiteral => {
+                    parts.push(literal(self.p_single_quoted_string_literal(part)?))
+                }
+                Rule::DoubleQuotedStringLiteral => {
+                    parts.extend(self.p_double_quoted_string_literal(part)?)
+                }
+                Rule::Variable => parts.push(self.p_variable(part)?),
+                _ => unreachable!("unexpected flavor of Term"),
+            }
+        }
+
+        ctx.done();
 
-        Ok(ast::Term::Literal { value })
+        Ok(concat(parts))
     }
 
     fn p_bare_term(&self, input: Pair<Rule>) -> Result<String> {
@@ -255,10 +278,55 @@ impl Parser {
         Ok(raw[1..raw.len() - 1].to_string())
     }
 
-    fn p_double_quoted_string_literal(&self, input: Pair<Rule>) -> Result<String> {
-        // Chop off the double-quotes
-        let raw = input.as_str();
-        Ok(raw[1..raw.len() - 1].to_string())
+    fn p_double_quoted_string_literal(&self, input: Pair<Rule>) -> Result<Vec<ast::Term>> {
+        let mut ctx = Context::unpack(input, Rule::DoubleQuotedStringLiteral);
+
+        // The quotes themselves aren't pairs, so this is just the text and variables inside.
+        let parts = ctx
+            .inner()
+            .map(|part| match part.as_rule() {
+                Rule::DoubleQuotedText => Ok(literal(part.as_str().to_string())),
+                Rule::Variable => self.p_variable(part),
+                _ => unreachable!("unexpected rule inside DoubleQuotedStringLiteral"),
+            })
+            .collect();
+
+        ctx.done();
+        parts
+    }
+
+    fn p_variable(&self, input: Pair<Rule>) -> Result<ast::Term> {
+        let mut ctx = Context::unpack(input, Rule::Variable);
+        let name = ctx.match_any();
+        ctx.done();
+
+        Ok(ast::Term::Variable {
+            name: name.as_str().to_string(),
+        })
+    }
+}
+
+/// Make a literal term.
+fn literal(value: String) -> ast::Term {
+    ast::Term::Literal { value }
+}
+
+/// Join the parts of a term together, merging literals which are next to each other.
+fn concat(parts: Vec<ast::Term>) -> ast::Term {
+    let mut merged: Vec<ast::Term> = vec![];
+    for part in parts {
+        match (merged.last_mut(), part) {
+            (Some(ast::Term::Literal { value }), ast::Term::Literal { value: next }) => {
+                value.push_str(&next)
+            }
+            (_, part) => merged.push(part),
+        }
+    }
+
+    match merged.len() {
+        0 => literal(String::new()),
+        1 => merged.pop().expect("one part"),
+        _ => ast::Term::Concat { parts: merged },
     }
 }
 
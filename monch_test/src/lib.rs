@@ -0,0 +1,80 @@
+//! Helpers for the utilities' integration tests: running a utility, reading the items it wrote
+//! and their fields, and giving each test a scratch directory.
+
+use monch_io::{write_all_to, InputParser, Value, FORMAT_VAR};
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{self, Command, Output, Stdio};
+use std::{env, fs, thread};
+
+/// A command running a utility's binary, like `utility(env!("CARGO_BIN_EXE_cat"))`.
+///
+/// `MONCH_FORMAT` is cleared, so the utility writes CBOR and guesses the format of its input, as
+/// it would when run on its own.
+pub fn utility(binary: &str) -> Command {
+    let mut command = Command::new(binary);
+    command.env_remove(FORMAT_VAR);
+    command
+}
+
+/// Run a command with `stdin` as its input, and wait for its output.
+///
+/// The command can exit without reading all of its input, like when it fails early.
+pub fn run_with_input(command: &mut Command, stdin: Vec<u8>) -> Output {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Write from another thread, so a command writing lots of output can't block us both.
+    let mut input = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || {
+        let _ = input.write_all(&stdin);
+    });
+
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+    output
+}
+
+/// Run a command with some items as its input, in CBOR. See [`run_with_input`].
+pub fn run_with_items(command: &mut Command, items: &[Value]) -> Output {
+    let mut stdin = vec![];
+    write_all_to(&mut stdin, items).unwrap();
+    run_with_input(command, stdin)
+}
+
+/// Read every item a utility wrote, leaving out its stream header if it has one.
+pub fn items(output: &Output) -> Vec<Value> {
+    InputParser::new(&output.stdout[..])
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+/// Get a field of an item, like the `name` of an entry from `ls`. Panics if the item isn't a map,
+/// or doesn't have the field: see [`find_field`] for fields which might be missing.
+pub fn field<'a>(item: &'a Value, name: &str) -> &'a Value {
+    find_field(item, name).unwrap_or_else(|| panic!("no field '{}' in {:?}", name, item))
+}
+
+/// Get a field of an item, if it has it. Panics if the item isn't a map.
+pub fn find_field<'a>(item: &'a Value, name: &str) -> Option<&'a Value> {
+    let fields = item
+        .as_map()
+        .unwrap_or_else(|| panic!("item isn't a map: {:?}", item));
+    fields
+        .iter()
+        .find(|(k, _)| k.as_text() == Some(name))
+        .map(|(_, v)| v)
+}
+
+/// Create an empty scratch directory for one test, like `monch-cat-missing_files-1234` in the
+/// temp directory. The test should remove it with `fs::remove_dir_all` once it's done.
+pub fn scratch_dir(utility: &str, test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("monch-{}-{}-{}", utility, test, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
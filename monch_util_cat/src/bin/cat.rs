@@ -0,0 +1,198 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use monch_io::{input_stream_auto, log, output_open, try_put, Value};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The files to read, one after another. Without any, objects from stdin are passed on as
+    /// they are.
+    files: Vec<PathBuf>,
+
+    /// Write each file as one item of bytes, exactly as they are.
+    #[clap(long, conflicts_with("whole"))]
+    bytes: bool,
+
+    /// Write each file as one item of text, rather than an item for each line.
+    #[clap(long)]
+    whole: bool,
+
+    /// Write each item in a map saying where it came from, like `{file, line_no, text}`. Lines
+    /// are numbered from 1, and `line_no` is null with `--whole` or `--bytes`.
+    #[clap(long)]
+    name: bool,
+}
+
+/// How reading a file went, when nothing went wrong writing it.
+#[derive(Debug)]
+enum Outcome {
+    /// The whole file was written.
+    Done,
+
+    /// Nobody's reading our output anymore.
+    Closed,
+
+    /// The file couldn't be read, after writing what came before the error.
+    Failed(io::Error),
+}
+
+/// Turn some text into an item, replacing any invalid UTF-8 with a warning the first time.
+fn text_item(text: &[u8], file: &Path, line_no: Option<u64>, warned: &mut bool) -> Value {
+    match std::str::from_utf8(text) {
+        Ok(text) => Value::Text(text.to_string()),
+        Err(_) => {
+            if !*warned {
+                match line_no {
+                    Some(line_no) => log!(
+                        "cat: {}: line {} isn't valid UTF-8, so invalid parts were replaced",
+                        file.display(),
+                        line_no
+                    ),
+                    None => log!(
+                        "cat: {}: isn't valid UTF-8, so invalid parts were replaced",
+                        file.display()
+                    ),
+                }
+                *warned = true;
+            }
+            Value::Text(String::from_utf8_lossy(text).into_owned())
+        }
+    }
+}
+
+/// Write one item, in a map saying where it came from with `--name`. Returns `false` once nobody's
+/// reading our output.
+fn emit(args: &Args, file: &Path, line_no: Option<u64>, text: Value) -> Result<bool, Error> {
+    let written = if args.name {
+        let file = file.to_string_lossy();
+        try_put!(file: file, line_no: line_no, text: text)
+    } else {
+        try_put!(&text)
+    };
+
+    output_open(written).context("failed to write object")
+}
+
+/// Write the contents of one file, as it was asked for.
+fn cat_file(args: &Args, path: &Path, file: impl Read) -> Result<Outcome, Error> {
+    let mut input = BufReader::new(file);
+    let mut warned = false;
+
+    if args.bytes || args.whole {
+        let mut contents = vec![];
+        if let Err(e) = input.read_to_end(&mut contents) {
+            return Ok(Outcome::Failed(e));
+        }
+
+        let item = if args.bytes {
+            Value::Bytes(contents)
+        } else {
+            text_item(&contents, path, None, &mut warned)
+        };
+        return Ok(if emit(args, path, None, item)? {
+            Outcome::Done
+        } else {
+            Outcome::Closed
+        });
+    }
+
+    let mut line = vec![];
+    for line_no in 1.. {
+        line.clear();
+        match input.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => return Ok(Outcome::Failed(e)),
+        }
+
+        // Lines end in `\n` or `\r\n`, except maybe the last one.
+        let mut text = line.strip_suffix(b"\n").unwrap_or(&line);
+        text = text.strip_suffix(b"\r").unwrap_or(text);
+
+        let item = text_item(text, path, Some(line_no), &mut warned);
+        if !emit(args, path, Some(line_no), item)? {
+            return Ok(Outcome::Closed);
+        }
+    }
+
+    Ok(Outcome::Done)
+}
+
+/// Pass objects from stdin on to stdout, untouched.
+fn pass_through() -> Result<(), Error> {
+    for obj_result in input_stream_auto::<Value>() {
+        let obj = obj_result.context("failed to read object from stdin")?;
+
+        // If nobody's reading our output anymore, we're done.
+        if !output_open(try_put!(&obj)).context("failed to write object")? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    if args.files.is_empty() {
+        return pass_through();
+    }
+
+    // Keep going past files we can't read, but say so when we exit.
+    let mut failed = false;
+    for path in &args.files {
+        let outcome = match File::open(path) {
+            Ok(file) => cat_file(&args, path, file)?,
+            Err(e) => Outcome::Failed(e),
+        };
+
+        match outcome {
+            Outcome::Done => {}
+            Outcome::Closed => break,
+            Outcome::Failed(e) => {
+                log!("cat: {}: {}", path.display(), e);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let parse = |a: &[&str]| Args::try_parse_from(["cat"].iter().chain(a)).is_ok();
+        assert!(parse(&[]));
+        assert!(parse(&["a.txt", "b.txt", "--name"]));
+        assert!(parse(&["--whole", "a.txt"]));
+        assert!(!parse(&["--whole", "--bytes", "a.txt"]));
+    }
+
+    #[test]
+    fn replacing_invalid_text() {
+        let mut warned = false;
+        let path = Path::new("a.txt");
+        assert_eq!(
+            text_item(b"fine", path, Some(1), &mut warned),
+            Value::Text("fine".into())
+        );
+        assert!(!warned);
+
+        assert_eq!(
+            text_item(b"bad \xff", path, Some(2), &mut warned),
+            Value::Text("bad \u{fffd}".into())
+        );
+        assert!(warned);
+    }
+}
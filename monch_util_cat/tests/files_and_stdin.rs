@@ -0,0 +1,113 @@
+use monch_io::{cbor, write_all_to, Value};
+use monch_test::{items, run_with_input, scratch_dir, utility};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// A scratch directory for one test, with some files in it.
+fn scratch(name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+    let dir = scratch_dir("cat", name);
+    for (name, contents) in files {
+        fs::write(dir.join(name), contents).unwrap();
+    }
+    dir
+}
+
+/// Run cat in a directory, with some bytes on stdin.
+fn cat(dir: &Path, args: &[&str], stdin: Vec<u8>) -> Output {
+    let mut cat = utility(env!("CARGO_BIN_EXE_cat"));
+    run_with_input(cat.args(args).current_dir(dir), stdin)
+}
+
+fn texts(texts: &[&str]) -> Vec<Value> {
+    texts.iter().map(|t| Value::Text(t.to_string())).collect()
+}
+
+#[test]
+fn several_files() {
+    let dir = scratch(
+        "several",
+        &[("a.txt", b"one\ntwo\n"), ("b.txt", b"three\r\nfour")],
+    );
+
+    let output = cat(&dir, &["a.txt", "b.txt"], vec![]);
+    assert!(output.status.success());
+    assert_eq!(items(&output), texts(&["one", "two", "three", "four"]));
+
+    let output = cat(&dir, &["--whole", "a.txt", "b.txt"], vec![]);
+    assert_eq!(items(&output), texts(&["one\ntwo\n", "three\r\nfour"]));
+
+    let output = cat(&dir, &["--name", "b.txt", "a.txt"], vec![]);
+    assert_eq!(
+        items(&output),
+        [
+            cbor!({"file" => "b.txt", "line_no" => 1, "text" => "three"}).unwrap(),
+            cbor!({"file" => "b.txt", "line_no" => 2, "text" => "four"}).unwrap(),
+            cbor!({"file" => "a.txt", "line_no" => 1, "text" => "one"}).unwrap(),
+            cbor!({"file" => "a.txt", "line_no" => 2, "text" => "two"}).unwrap(),
+        ]
+    );
+
+    let output = cat(&dir, &["--name", "--whole", "a.txt"], vec![]);
+    assert_eq!(
+        items(&output),
+        [cbor!({"file" => "a.txt", "line_no" => null, "text" => "one\ntwo\n"}).unwrap()]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn missing_files() {
+    let dir = scratch("missing", &[("a.txt", b"one\n"), ("b.txt", b"two\n")]);
+
+    // The files that are there are still read, but the exit status says something went wrong.
+    let output = cat(&dir, &["a.txt", "nope.txt", "b.txt"], vec![]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(items(&output), texts(&["one", "two"]));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("cat: nope.txt: "), "{}", stderr);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn invalid_utf8() {
+    let dir = scratch("binary", &[("bin", b"ok\nbad \xff\nworse \xfe\n")]);
+
+    // Invalid parts are replaced, with one warning.
+    let output = cat(&dir, &["bin"], vec![]);
+    assert!(output.status.success());
+    assert_eq!(
+        items(&output),
+        texts(&["ok", "bad \u{fffd}", "worse \u{fffd}"])
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("isn't valid UTF-8").count(), 1, "{}", stderr);
+    assert!(stderr.contains("line 2"), "{}", stderr);
+
+    // ...unless the bytes are asked for as they are.
+    let output = cat(&dir, &["--bytes", "bin"], vec![]);
+    assert!(output.stderr.is_empty());
+    assert_eq!(
+        items(&output),
+        [Value::Bytes(b"ok\nbad \xff\nworse \xfe\n".to_vec())]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stdin_passes_through() {
+    let dir = scratch("stdin", &[]);
+    let objects = [
+        cbor!({"name" => "a.txt", "size" => 1}).unwrap(),
+        cbor!(["x", null]).unwrap(),
+        Value::Bytes(vec![0xff]),
+    ];
+    let mut stdin = vec![];
+    write_all_to(&mut stdin, &objects).unwrap();
+
+    let output = cat(&dir, &[], stdin);
+    assert!(output.status.success());
+    assert_eq!(items(&output), objects);
+
+    fs::remove_dir_all(dir).unwrap();
+}
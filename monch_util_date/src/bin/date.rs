@@ -0,0 +1,384 @@
+use anyhow::{anyhow, bail, Context, Error};
+use chrono::format::ParseErrorKind;
+use chrono::{
+    DateTime, FixedOffset, Local, LocalResult, NaiveDate, NaiveDateTime, Offset, SecondsFormat,
+    TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use clap::Parser;
+use monch_io::units::parse_duration;
+use monch_io::{input_stream_auto, log, output_open, try_put, Value};
+use std::process;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Describe this time, rather than now, like `2024-01-02 15:04` or `2024-01-02T15:04:05Z`.
+    #[clap(long, value_name("TIME"), conflicts_with("stdin"))]
+    parse: Option<String>,
+
+    /// Read times in this format, like `%d/%m/%Y %H:%M`, rather than trying the common ones. See
+    /// https://docs.rs/chrono/latest/chrono/format/strftime for how to write it.
+    #[clap(long, value_name("FORMAT"))]
+    format: Option<String>,
+
+    /// Read each item of text from stdin as a time, writing `{input, unix, rfc3339}` for it, or
+    /// `{input, error}` if it isn't one.
+    #[clap(long)]
+    stdin: bool,
+
+    /// Add this long to each time, like `3d2h`, or take it away, like `-90m`.
+    #[clap(long, value_name("DURATION"), allow_hyphen_values(true))]
+    add: Option<String>,
+
+    /// Use UTC for times without a time zone, and for `local`, rather than this system's zone.
+    #[clap(long, conflicts_with("tz"))]
+    utc: bool,
+
+    /// Use this time zone for times without one, and for `local`, like `Europe/Berlin` or
+    /// `+05:30`, rather than this system's zone.
+    #[clap(long, value_name("ZONE"), parse(try_from_str = parse_zone))]
+    tz: Option<Zone>,
+}
+
+/// The time zone that times without one are in, and that `local` times are written in.
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Local,
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+/// Read a time zone, by its name in the tz database, or as an offset from UTC.
+fn parse_zone(zone: &str) -> Result<Zone, String> {
+    if zone.eq_ignore_ascii_case("utc") || zone == "Z" {
+        return Ok(Zone::Fixed(
+            FixedOffset::east_opt(0).expect("UTC is a time zone"),
+        ));
+    }
+    if zone.eq_ignore_ascii_case("local") {
+        return Ok(Zone::Local);
+    }
+
+    if let Ok(time) = DateTime::parse_from_str(&format!("2000-01-01 00:00 {}", zone), "%F %R %#z") {
+        return Ok(Zone::Fixed(*time.offset()));
+    }
+    zone.parse().map(Zone::Named).map_err(|_| {
+        format!(
+            "'{}' isn't a time zone, like `Europe/Berlin` or `+05:30`",
+            zone
+        )
+    })
+}
+
+impl Zone {
+    /// The same time, in this zone.
+    fn convert(&self, time: DateTime<Utc>) -> DateTime<FixedOffset> {
+        match self {
+            Zone::Local => fixed(time.with_timezone(&Local)),
+            Zone::Fixed(offset) => time.with_timezone(offset),
+            Zone::Named(tz) => fixed(time.with_timezone(tz)),
+        }
+    }
+
+    /// Work out which time a clock in this zone means. When clocks go back and the time happens
+    /// twice, it's the first one. When clocks go forward and skip it, there's no such time.
+    fn resolve(&self, naive: &NaiveDateTime) -> Result<DateTime<FixedOffset>, Error> {
+        let time = match self {
+            Zone::Local => earliest(Local.from_local_datetime(naive)),
+            Zone::Fixed(offset) => earliest(offset.from_local_datetime(naive)),
+            Zone::Named(tz) => earliest(tz.from_local_datetime(naive)),
+        };
+        time.ok_or_else(|| anyhow!("{} is skipped when the clocks go forward", naive))
+    }
+}
+
+/// Forget which time zone a time is in, keeping only its offset.
+fn fixed<Z: TimeZone>(time: DateTime<Z>) -> DateTime<FixedOffset> {
+    let offset = time.offset().fix();
+    time.with_timezone(&offset)
+}
+
+fn earliest<Z: TimeZone>(result: LocalResult<DateTime<Z>>) -> Option<DateTime<FixedOffset>> {
+    match result {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => Some(fixed(time)),
+        LocalResult::None => None,
+    }
+}
+
+/// Formats of times that say what zone they're in.
+const ZONED_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f%z",
+    "%Y-%m-%d %H:%M:%S%.f%z",
+    "%Y-%m-%d %H:%M:%S%.f %z",
+    "%Y-%m-%dT%H:%M%z",
+    // Like in web server logs: `10/Oct/2000:13:55:36 -0700`.
+    "%d/%b/%Y:%H:%M:%S %z",
+];
+
+/// Formats of times in the zone we're using.
+const LOCAL_FORMATS: &[&str] = &[
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M",
+    "%Y-%m-%d %H:%M",
+    "%Y/%m/%d %H:%M:%S",
+    "%Y/%m/%d %H:%M",
+    "%d/%b/%Y:%H:%M:%S",
+];
+
+/// Formats of dates, which mean the midnight they start at.
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%Y/%m/%d", "%d %b %Y", "%b %d %Y"];
+
+/// Read a time in a format, if it is in that format. Times which don't say what zone they're in
+/// are in `zone`.
+fn parse_as(text: &str, format: &str, zone: Zone) -> Option<Result<DateTime<Utc>, Error>> {
+    let resolve = |naive: &NaiveDateTime| zone.resolve(naive).map(|t| t.with_timezone(&Utc));
+    if let Ok(time) = DateTime::parse_from_str(text, format) {
+        return Some(Ok(time.with_timezone(&Utc)));
+    }
+
+    // Without any time of day, it's a date, which starts at midnight.
+    match NaiveDateTime::parse_from_str(text, format) {
+        Ok(naive) => Some(resolve(&naive)),
+        Err(e) if e.kind() == ParseErrorKind::NotEnough => {
+            let date = NaiveDate::parse_from_str(text, format).ok()?;
+            Some(resolve(
+                &date.and_hms_opt(0, 0, 0).expect("days have a midnight"),
+            ))
+        }
+        Err(_) => None,
+    }
+}
+
+/// Read a time, in `format` if there is one, or in any common format otherwise, like RFC 3339,
+/// RFC 2822, `2024-01-02 15:04`, or `@1704207840` for a Unix timestamp.
+fn parse_time(text: &str, format: Option<&str>, zone: Zone) -> Result<DateTime<Utc>, Error> {
+    let text = text.trim();
+    if let Some(format) = format {
+        return parse_as(text, format, zone)
+            .unwrap_or_else(|| bail!("'{}' isn't a time in the format '{}'", text, format));
+    }
+
+    if let Some(seconds) = text.strip_prefix('@') {
+        let seconds: i64 = seconds
+            .parse()
+            .with_context(|| format!("'{}' isn't a Unix timestamp", text))?;
+        return match Utc.timestamp_opt(seconds, 0) {
+            LocalResult::Single(time) => Ok(time),
+            _ => bail!("'{}' is out of range", text),
+        };
+    }
+    // RFC 3339 times are often written with a space between the date and the time.
+    if let Ok(time) = DateTime::parse_from_rfc3339(&text.replacen(' ', "T", 1)) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(time) = DateTime::parse_from_rfc2822(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let formats = ZONED_FORMATS
+        .iter()
+        .chain(LOCAL_FORMATS)
+        .chain(DATE_FORMATS);
+    for format in formats {
+        if let Some(time) = parse_as(text, format, zone) {
+            return time;
+        }
+    }
+    bail!(
+        "'{}' isn't a time, like `2024-01-02 15:04` or `2024-01-02T15:04:05Z`",
+        text
+    )
+}
+
+/// Read a duration to add for `--add`, which is taken away if it starts with `-`.
+fn parse_offset(offset: &str) -> Result<chrono::Duration, Error> {
+    let (negative, duration) = match offset.strip_prefix('-') {
+        Some(duration) => (true, duration),
+        None => (false, offset),
+    };
+    let duration = chrono::Duration::from_std(parse_duration(duration)?)
+        .with_context(|| format!("'{}' is too long", offset))?;
+    Ok(if negative { -duration } else { duration })
+}
+
+/// Add a duration to a time, if the result is one we can represent.
+fn add(time: DateTime<Utc>, offset: Option<chrono::Duration>) -> Result<DateTime<Utc>, Error> {
+    match offset {
+        Some(offset) => time
+            .checked_add_signed(offset)
+            .ok_or_else(|| anyhow!("{} is out of range", time)),
+        None => Ok(time),
+    }
+}
+
+fn rfc3339<Z: TimeZone>(time: &DateTime<Z>) -> String
+where
+    Z::Offset: std::fmt::Display,
+{
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Write everything about a time: when it is in UTC, and in the zone we're using.
+fn describe(time: DateTime<Utc>, zone: Zone) -> Result<(), Error> {
+    let local = zone.convert(time);
+    let written = try_put!(
+        unix: time.timestamp(),
+        rfc3339: rfc3339(&time),
+        local: rfc3339(&local),
+        weekday: local.format("%A").to_string()
+    );
+    output_open(written).context("failed to write time")?;
+    Ok(())
+}
+
+/// Read each item of text from stdin as a time. Returns whether they all were.
+fn convert_stdin(args: &Args, zone: Zone, offset: Option<chrono::Duration>) -> Result<bool, Error> {
+    let mut all_ok = true;
+    for obj_result in input_stream_auto::<Value>() {
+        let obj = obj_result.context("failed to read object from stdin")?;
+        let time = match &obj {
+            Value::Text(text) => parse_time(text, args.format.as_deref(), zone),
+            _ => Err(anyhow!("expected text")),
+        };
+
+        let written = match time.and_then(|time| add(time, offset)) {
+            Ok(time) => try_put!(input: obj, unix: time.timestamp(), rfc3339: rfc3339(&time)),
+            Err(e) => {
+                all_ok = false;
+                try_put!(input: obj, error: format!("{:#}", e))
+            }
+        };
+        // If nobody's reading our output anymore, we're done.
+        if !output_open(written).context("failed to write time")? {
+            break;
+        }
+    }
+    Ok(all_ok)
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let zone = match args.tz {
+        Some(zone) => zone,
+        None if args.utc => Zone::Fixed(FixedOffset::east_opt(0).expect("UTC is a time zone")),
+        None => Zone::Local,
+    };
+    let offset = args.add.as_deref().map(parse_offset).transpose()?;
+
+    if args.stdin {
+        if !convert_stdin(&args, zone, offset)? {
+            log!("date: some items weren't times");
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let time = match &args.parse {
+        Some(text) => parse_time(text, args.format.as_deref(), zone)?,
+        None => Utc::now(),
+    };
+    describe(add(time, offset)?, zone)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc() -> Zone {
+        parse_zone("UTC").unwrap()
+    }
+
+    /// Read a time, as RFC 3339 in UTC.
+    fn parsed(text: &str, zone: Zone) -> String {
+        rfc3339(&parse_time(text, None, zone).unwrap())
+    }
+
+    #[test]
+    fn formats() {
+        let expected = "2024-01-02T15:04:05Z";
+        for text in [
+            "2024-01-02T15:04:05Z",
+            "2024-01-02T16:04:05+01:00",
+            "2024-01-02 15:04:05.000Z",
+            "2024-01-02 10:04:05 -0500",
+            "Tue, 02 Jan 2024 15:04:05 +0000",
+            "02/Jan/2024:08:04:05 -0700",
+            "2024-01-02T15:04:05",
+            "2024-01-02 15:04:05",
+            "2024/01/02 15:04:05",
+            "  2024-01-02 15:04:05\n",
+            "@1704207845",
+        ] {
+            assert_eq!(parsed(text, utc()), expected, "{}", text);
+        }
+
+        assert_eq!(parsed("2024-01-02 15:04", utc()), "2024-01-02T15:04:00Z");
+        assert_eq!(parsed("2024-01-02", utc()), "2024-01-02T00:00:00Z");
+        assert_eq!(parsed("2 Jan 2024", utc()), "2024-01-02T00:00:00Z");
+
+        let format = Some("%d.%m.%Y %H:%M");
+        let time = parse_time("02.01.2024 15:04", format, utc()).unwrap();
+        assert_eq!(rfc3339(&time), "2024-01-02T15:04:00Z");
+        assert!(parse_time("2024-01-02 15:04", format, utc()).is_err());
+
+        for text in ["", "tomorrow", "2024-13-02", "@soon", "2024-01-02 25:00"] {
+            assert!(parse_time(text, None, utc()).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn zones() {
+        let berlin = parse_zone("Europe/Berlin").unwrap();
+        assert_eq!(parsed("2024-01-02 15:04", berlin), "2024-01-02T14:04:00Z");
+        assert_eq!(parsed("2024-07-02 15:04", berlin), "2024-07-02T13:04:00Z");
+
+        // Times that say what zone they're in don't care which we're using.
+        assert_eq!(
+            parsed("2024-07-02T15:04:00Z", berlin),
+            "2024-07-02T15:04:00Z"
+        );
+
+        let kolkata = parse_zone("+05:30").unwrap();
+        assert_eq!(parsed("2024-01-02 15:04", kolkata), "2024-01-02T09:34:00Z");
+        assert!(parse_zone("Mars/Olympus_Mons").is_err());
+
+        let time = parse_time("2024-01-02T15:04:00Z", None, utc()).unwrap();
+        assert_eq!(rfc3339(&berlin.convert(time)), "2024-01-02T16:04:00+01:00");
+        assert_eq!(rfc3339(&utc().convert(time)), "2024-01-02T15:04:00Z");
+    }
+
+    #[test]
+    fn daylight_saving() {
+        let new_york = parse_zone("America/New_York").unwrap();
+
+        // Clocks went forward from 2:00 to 3:00, so 2:30 never happened.
+        let skipped = parse_time("2024-03-10 02:30", None, new_york);
+        assert!(skipped.unwrap_err().to_string().contains("skipped"));
+        assert_eq!(parsed("2024-03-10 03:30", new_york), "2024-03-10T07:30:00Z");
+
+        // Clocks went back from 2:00 to 1:00, so 1:30 happened twice, first in EDT.
+        assert_eq!(parsed("2024-11-03 01:30", new_york), "2024-11-03T05:30:00Z");
+        assert_eq!(parsed("2024-11-03 02:30", new_york), "2024-11-03T07:30:00Z");
+
+        // Adding a day across the change adds 24 hours, not a day on the clock.
+        let time = parse_time("2024-03-09 12:00", None, new_york).unwrap();
+        let later = add(time, Some(parse_offset("1d").unwrap())).unwrap();
+        assert_eq!(
+            rfc3339(&new_york.convert(later)),
+            "2024-03-10T13:00:00-04:00"
+        );
+    }
+
+    #[test]
+    fn offsets() {
+        let time = parse_time("2024-01-02T15:04:05Z", None, utc()).unwrap();
+        let added =
+            |offset: &str| rfc3339(&add(time, Some(parse_offset(offset).unwrap())).unwrap());
+        assert_eq!(added("3d2h"), "2024-01-05T17:04:05Z");
+        assert_eq!(added("-90m"), "2024-01-02T13:34:05Z");
+        assert_eq!(added("1.5s"), "2024-01-02T15:04:06Z");
+        assert!(parse_offset("later").is_err());
+        assert!(parse_offset("--1d").is_err());
+    }
+}
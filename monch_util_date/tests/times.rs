@@ -0,0 +1,92 @@
+use monch_io::{cbor, Value};
+use monch_test::{items, run_with_items, utility};
+use std::process::Output;
+
+/// Run date, with some objects on stdin. date only reads them when it's asked to.
+fn date(args: &[&str], stdin: &[Value]) -> Output {
+    let mut date = utility(env!("CARGO_BIN_EXE_date"));
+    run_with_items(date.args(args).env("TZ", "UTC"), stdin)
+}
+
+#[test]
+fn parse() {
+    let output = date(
+        &["--parse", "2024-01-02 15:04", "--tz", "Europe/Berlin"],
+        &[],
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        items(&output),
+        [cbor!({
+            "unix" => 1704204240,
+            "rfc3339" => "2024-01-02T14:04:00Z",
+            "local" => "2024-01-02T15:04:00+01:00",
+            "weekday" => "Tuesday",
+        })
+        .unwrap()]
+    );
+
+    let output = date(&["--utc", "--parse", "2024-01-02", "--add", "-1d"], &[]);
+    assert_eq!(
+        items(&output),
+        [cbor!({
+            "unix" => 1704067200,
+            "rfc3339" => "2024-01-01T00:00:00Z",
+            "local" => "2024-01-01T00:00:00Z",
+            "weekday" => "Monday",
+        })
+        .unwrap()]
+    );
+
+    assert!(!date(&["--parse", "someday"], &[]).status.success());
+    assert!(!date(&["--tz", "Nowhere/Special"], &[]).status.success());
+}
+
+#[test]
+fn now() {
+    let output = date(&[], &[]);
+    assert!(output.status.success(), "{:?}", output);
+    let found = items(&output);
+    assert_eq!(found.len(), 1);
+
+    let keys: Vec<_> = match &found[0] {
+        Value::Map(pairs) => pairs.iter().map(|(k, _)| k.clone()).collect(),
+        other => panic!("expected a map, found {:?}", other),
+    };
+    assert_eq!(
+        keys,
+        ["unix", "rfc3339", "local", "weekday"].map(|k| Value::Text(k.into()))
+    );
+}
+
+#[test]
+fn stdin() {
+    let inputs = [
+        cbor!("2024-01-02T15:04:05Z").unwrap(),
+        cbor!("02/Jan/2024:15:04:05 +0000").unwrap(),
+        cbor!("yesterday").unwrap(),
+        cbor!(12).unwrap(),
+    ];
+    let output = date(&["--stdin", "--add", "1h"], &inputs);
+    assert_eq!(output.status.code(), Some(1));
+
+    let found = items(&output);
+    let converted = |input: &Value| {
+        cbor!({"input" => input, "unix" => 1704211445, "rfc3339" => "2024-01-02T16:04:05Z"})
+            .unwrap()
+    };
+    assert_eq!(found[0], converted(&inputs[0]));
+    assert_eq!(found[1], converted(&inputs[1]));
+    assert_eq!(
+        found[2],
+        cbor!({
+            "input" => "yesterday",
+            "error" => "'yesterday' isn't a time, like `2024-01-02 15:04` or `2024-01-02T15:04:05Z`",
+        })
+        .unwrap()
+    );
+    assert_eq!(
+        found[3],
+        cbor!({"input" => 12, "error" => "expected text"}).unwrap()
+    );
+}
@@ -0,0 +1,60 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use monch_io::{diff, input_stream_auto, put, InputParser, Value, DEFAULT_MAX_ITEM_SIZE};
+use std::{fs, path::PathBuf, process};
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// A file containing the old stream of objects, to compare with the new stream on stdin
+    file: PathBuf,
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    // Read the old side of the diff from the file
+    let file = fs::File::open(&args.file)
+        .with_context(|| format!("failed to open '{}'", args.file.display()))?;
+    let old: Vec<Value> = InputParser::new_auto(file)
+        .with_max_item_size(DEFAULT_MAX_ITEM_SIZE)
+        .collect::<Result<_, _>>()
+        .with_context(|| format!("failed to read object from '{}'", args.file.display()))?;
+
+    // Read the new side from stdin
+    let new: Vec<Value> = input_stream_auto()
+        .collect::<Result<_, _>>()
+        .context("failed to read object from stdin")?;
+
+    // Compare the streams like arrays, so the paths start with each item's index.
+    let entries = diff(&Value::Array(old), &Value::Array(new));
+
+    for entry in &entries {
+        let mut record = vec![
+            (
+                Value::Text("path".into()),
+                Value::Text(entry.path.to_string()),
+            ),
+            (
+                Value::Text("kind".into()),
+                Value::Text(entry.kind.name().into()),
+            ),
+        ];
+
+        // Only include the sides that exist.
+        if let Some(old) = &entry.old {
+            record.push((Value::Text("old".into()), old.clone()));
+        }
+        if let Some(new) = &entry.new {
+            record.push((Value::Text("new".into()), new.clone()));
+        }
+
+        put!(&Value::Map(record));
+    }
+
+    // Like diff(1), exit unsuccessfully if there were differences.
+    if !entries.is_empty() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,327 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use monch_io::{log, output_open, try_put, write_header, StreamHeader};
+use std::collections::HashSet;
+use std::fs::{self, Metadata};
+use std::ops::AddAssign;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The directory to measure. Paths are written starting with it, or relative to the working
+    /// directory if it's left out.
+    root: Option<PathBuf>,
+
+    /// Write the usage of every entry this many directories down, and above. Entries in the
+    /// directory being measured are at depth 1, and at depth 0 only the total is written.
+    #[clap(short('d'), long, value_name("N"), default_value("1"))]
+    depth: usize,
+
+    /// Count the sizes of files, rather than the space they take up on disk.
+    #[clap(long)]
+    apparent_size: bool,
+}
+
+/// How much space a tree takes up.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Usage {
+    bytes: u64,
+
+    /// How many entries in the tree aren't directories.
+    files: u64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.bytes += other.bytes;
+        self.files += other.files;
+    }
+}
+
+/// The fields of each usage, in order, for the stream header.
+const FIELDS: &[&str] = &["path", "bytes", "files"];
+
+/// Measures entries, shared between every thread of the walk.
+struct Meter {
+    apparent_size: bool,
+
+    /// Files with more than one link, so each is only counted once.
+    #[cfg(unix)]
+    linked: Mutex<HashSet<(u64, u64)>>,
+
+    /// Set when anything couldn't be read, which is logged, and skipped over.
+    failed: AtomicBool,
+}
+
+impl Meter {
+    fn new(apparent_size: bool) -> Meter {
+        Meter {
+            apparent_size,
+            #[cfg(unix)]
+            linked: Mutex::new(HashSet::new()),
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Log that something couldn't be read, and remember to fail at the end.
+    fn fail(&self, message: String) {
+        log!("du: {}", message);
+        self.failed.store(true, Ordering::SeqCst);
+    }
+
+    /// The usage of an entry on its own, without anything inside it.
+    fn measure(&self, meta: &Metadata) -> Usage {
+        let files = !meta.is_dir() as u64;
+        if !self.first_link(meta) {
+            return Usage::default();
+        }
+
+        #[cfg(unix)]
+        let bytes = if self.apparent_size {
+            meta.len()
+        } else {
+            std::os::unix::fs::MetadataExt::blocks(meta) * 512
+        };
+        #[cfg(not(unix))]
+        let bytes = meta.len();
+
+        Usage { bytes, files }
+    }
+
+    /// Returns whether this is the first time we've seen a file, through any of its links.
+    #[cfg(unix)]
+    fn first_link(&self, meta: &Metadata) -> bool {
+        use std::os::unix::fs::MetadataExt;
+
+        if meta.is_dir() || meta.nlink() <= 1 {
+            return true;
+        }
+        let mut linked = self.linked.lock().expect("a walker panicked");
+        linked.insert((meta.dev(), meta.ino()))
+    }
+
+    #[cfg(not(unix))]
+    fn first_link(&self, _meta: &Metadata) -> bool {
+        true
+    }
+
+    /// Read the entries of a directory, logging any that can't be read.
+    fn entries(&self, dir: &Path) -> Vec<(PathBuf, Metadata)> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                self.fail(format!(
+                    "failed to read directory '{}': {}",
+                    dir.display(),
+                    e
+                ));
+                return vec![];
+            }
+        };
+
+        let mut found = vec![];
+        for entry in entries {
+            // Symlinks are measured themselves, rather than what they point to.
+            match entry.and_then(|entry| Ok((entry.path(), entry.metadata()?))) {
+                Ok(entry) => found.push(entry),
+                Err(e) => self.fail(format!(
+                    "failed to read an entry in '{}': {}",
+                    dir.display(),
+                    e
+                )),
+            }
+        }
+        found
+    }
+
+    /// The usage of everything inside a directory, but not the directory itself.
+    fn contents(&self, dir: &Path) -> Usage {
+        let mut usage = Usage::default();
+        let mut dirs = vec![dir.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for (path, meta) in self.entries(&dir) {
+                usage += self.measure(&meta);
+                if meta.is_dir() {
+                    dirs.push(path);
+                }
+            }
+        }
+        usage
+    }
+}
+
+/// An entry whose usage is written, and the tree of them above it, to add its usage to.
+struct Node {
+    /// The path to write for it.
+    path: String,
+
+    parent: Option<usize>,
+    usage: Usage,
+
+    /// How many of its entries, and other work, are still to be added to its usage.
+    pending: usize,
+}
+
+/// Walks the tree, measuring the directories at the deepest level we write in parallel, and
+/// writing each entry once everything inside it is measured.
+struct Walk {
+    meter: Arc<Meter>,
+    max_depth: usize,
+    nodes: Vec<Node>,
+
+    /// Where to send the directories to be measured, with the node each one is for, until every
+    /// one has been sent.
+    jobs: Option<Sender<(usize, PathBuf)>>,
+}
+
+impl Walk {
+    /// Add an entry, listing its entries if it's a directory within the depth, or having it
+    /// measured if it's one at the bottom. Returns `false` once nobody's reading our output.
+    fn add(
+        &mut self,
+        path: PathBuf,
+        display: String,
+        meta: &Metadata,
+        parent: Option<usize>,
+        depth: usize,
+    ) -> Result<bool, Error> {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            path: display,
+            parent,
+            usage: self.meter.measure(meta),
+            pending: 1,
+        });
+        if let Some(parent) = parent {
+            self.nodes[parent].pending += 1;
+        }
+
+        if !meta.is_dir() {
+            return self.finish(index);
+        }
+        if depth == self.max_depth {
+            let jobs = self.jobs.as_ref().expect("the walk is already listed");
+            jobs.send((index, path)).expect("the walkers stopped early");
+            return Ok(true);
+        }
+
+        for (child, meta) in self.meter.entries(&path) {
+            let display = Path::new(&self.nodes[index].path)
+                .join(child.file_name().expect("entries have names"))
+                .to_string_lossy()
+                .into_owned();
+            if !self.add(child, display, &meta, Some(index), depth + 1)? {
+                return Ok(false);
+            }
+        }
+        self.finish(index)
+    }
+
+    /// Count one piece of a node's work as done, and write it if it was the last, adding its usage
+    /// to its parent's. Returns `false` once nobody's reading our output.
+    fn finish(&mut self, index: usize) -> Result<bool, Error> {
+        let mut index = index;
+        loop {
+            let node = &mut self.nodes[index];
+            node.pending -= 1;
+            if node.pending > 0 {
+                return Ok(true);
+            }
+
+            // Without a root, the total is for the working directory.
+            let (usage, parent) = (node.usage, node.parent);
+            let path = if node.path.is_empty() {
+                "."
+            } else {
+                &node.path
+            };
+            let written = try_put!(path: path, bytes: usage.bytes, files: usage.files);
+            // If nobody's reading our output anymore, we're done.
+            if !output_open(written).context("failed to write usage")? {
+                return Ok(false);
+            }
+
+            match parent {
+                Some(parent) => {
+                    self.nodes[parent].usage += usage;
+                    index = parent;
+                }
+                None => return Ok(true),
+            }
+        }
+    }
+}
+
+/// Measure directories until there are no more, sending back their usage.
+fn measure(
+    meter: &Meter,
+    jobs: &Mutex<Receiver<(usize, PathBuf)>>,
+    usages: Sender<(usize, Usage)>,
+) {
+    loop {
+        // Only hold the lock while waiting, so other walkers can take jobs while this one works.
+        let job = jobs.lock().expect("a walker panicked").recv();
+        let (index, dir) = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        if usages.send((index, meter.contents(&dir))).is_err() {
+            return;
+        }
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let meter = Arc::new(Meter::new(args.apparent_size));
+
+    let root = args.root.clone().unwrap_or_else(|| PathBuf::from("."));
+    let root_meta =
+        fs::metadata(&root).with_context(|| format!("failed to read '{}'", root.display()))?;
+
+    let header = StreamHeader::new("usage", FIELDS);
+    write_header(&header).context("failed to write stream header")?;
+
+    let (job_sender, jobs) = mpsc::channel();
+    let jobs = Arc::new(Mutex::new(jobs));
+    let (usage_sender, usages) = mpsc::channel();
+    for _ in 0..num_cpus::get() {
+        let (meter, jobs, usages) = (meter.clone(), jobs.clone(), usage_sender.clone());
+        thread::spawn(move || measure(&meter, &jobs, usages));
+    }
+    drop(usage_sender);
+
+    let mut walk = Walk {
+        meter: meter.clone(),
+        max_depth: args.depth,
+        nodes: vec![],
+        jobs: Some(job_sender),
+    };
+    // Entries are written relative to the working directory, if no root was given.
+    let display = match &args.root {
+        Some(root) => root.to_string_lossy().into_owned(),
+        None => String::new(),
+    };
+    if !walk.add(root, display, &root_meta, None, 0)? {
+        return Ok(());
+    }
+
+    // Now it's just waiting for the directories at the bottom to be measured.
+    walk.jobs = None;
+    for (index, usage) in usages {
+        walk.nodes[index].usage += usage;
+        if !walk.finish(index)? {
+            return Ok(());
+        }
+    }
+
+    if meter.failed.load(Ordering::SeqCst) {
+        process::exit(1);
+    }
+    Ok(())
+}
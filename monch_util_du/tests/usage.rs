@@ -0,0 +1,186 @@
+use monch_io::Value;
+use monch_test::{items, scratch_dir, utility};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Build a tree to measure, like:
+///
+/// ```text
+/// a/x.txt           100 bytes
+/// a/sub/y.txt       5000 bytes
+/// b.txt             5 bytes
+/// c/
+/// link -> a
+/// ```
+fn tree(name: &str) -> PathBuf {
+    let dir = scratch_dir("du", name);
+    fs::create_dir_all(dir.join("a/sub")).unwrap();
+    fs::create_dir_all(dir.join("c")).unwrap();
+    fs::write(dir.join("a/x.txt"), vec![b'x'; 100]).unwrap();
+    fs::write(dir.join("a/sub/y.txt"), vec![b'y'; 5000]).unwrap();
+    fs::write(dir.join("b.txt"), "hello").unwrap();
+    #[cfg(unix)]
+    std::os::unix::fs::symlink("a", dir.join("link")).unwrap();
+    dir
+}
+
+/// Measure a tree ourselves, as `(bytes, files)`, without following symlinks.
+fn measure(path: &Path, apparent_size: bool) -> (u64, u64) {
+    let meta = fs::symlink_metadata(path).unwrap();
+
+    #[cfg(unix)]
+    let bytes = if apparent_size {
+        meta.len()
+    } else {
+        std::os::unix::fs::MetadataExt::blocks(&meta) * 512
+    };
+    #[cfg(not(unix))]
+    let bytes = meta.len();
+
+    if !meta.is_dir() {
+        return (bytes, 1);
+    }
+    fs::read_dir(path)
+        .unwrap()
+        .map(|entry| measure(&entry.unwrap().path(), apparent_size))
+        .fold((bytes, 0), |(b, f), (bytes, files)| (b + bytes, f + files))
+}
+
+/// Run du in a directory, returning its output.
+fn du(dir: &Path, args: &[&str]) -> Output {
+    utility(env!("CARGO_BIN_EXE_du"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// The paths du wrote, in order, with their `(bytes, files)`.
+fn usages(output: &Output) -> Vec<(String, (u64, u64))> {
+    assert!(output.status.success(), "{:?}", output);
+    items(output)
+        .into_iter()
+        .map(|item| {
+            let pairs = match item {
+                Value::Map(pairs) => pairs,
+                item => panic!("unexpected item {:?}", item),
+            };
+            let field = |name: &str| {
+                let key = Value::Text(name.into());
+                pairs
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, v)| v)
+                    .unwrap()
+            };
+            let number = |name: &str| u64::try_from(field(name).as_integer().unwrap()).unwrap();
+            let path = field("path").as_text().unwrap().to_string();
+            (path, (number("bytes"), number("files")))
+        })
+        .collect()
+}
+
+#[test]
+fn totals() {
+    let dir = tree("totals");
+
+    for apparent_size in [true, false] {
+        let args: &[&str] = if apparent_size {
+            &["--apparent-size"]
+        } else {
+            &[]
+        };
+        let found = usages(&du(&dir, args));
+
+        // Each entry comes before the total, in whatever order they were measured.
+        let (total, entries) = found.split_last().unwrap();
+        assert_eq!(total, &(".".to_string(), measure(&dir, apparent_size)));
+
+        let entries: BTreeMap<_, _> = entries.iter().cloned().collect();
+        let mut expected: BTreeMap<_, _> = ["a", "b.txt", "c"]
+            .iter()
+            .map(|name| (name.to_string(), measure(&dir.join(name), apparent_size)))
+            .collect();
+        if cfg!(unix) {
+            expected.insert(
+                "link".to_string(),
+                measure(&dir.join("link"), apparent_size),
+            );
+        }
+        assert_eq!(entries, expected);
+        assert_eq!(entries["a"].1, 2);
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn depth() {
+    let dir = tree("depth");
+    let size = |path: &str| measure(&dir.join(path), true);
+
+    // Everything inside a directory is written before it.
+    let found = usages(&du(&dir, &["--apparent-size", "a", "--depth", "2"]));
+    let paths: Vec<_> = found.iter().map(|(path, _)| path.as_str()).collect();
+    let position = |path: &str| paths.iter().position(|p| *p == path).unwrap();
+    assert_eq!(found.len(), 4);
+    assert!(position("a/sub/y.txt") < position("a/sub"));
+    assert_eq!(paths[3], "a");
+    assert_eq!(found[position("a/sub")].1, size("a/sub"));
+    assert_eq!(found[position("a/x.txt")].1, (100, 1));
+    assert_eq!(found[3].1, size("a"));
+
+    // At depth 0, there's only the total.
+    assert_eq!(
+        usages(&du(&dir, &["--apparent-size", "--depth", "0"])),
+        [(".".to_string(), measure(&dir, true))]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn hard_links() {
+    let dir = tree("links");
+    fs::hard_link(dir.join("a/sub/y.txt"), dir.join("c/y.txt")).unwrap();
+
+    // The file is only counted once, wherever it's found first.
+    let found = usages(&du(&dir, &["--apparent-size"]));
+    let (_, total) = found.last().unwrap();
+    let once = measure(&dir, true).0 - 5000;
+    assert_eq!(total.0, once);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failures() {
+    let dir = tree("failures");
+    let output = du(&dir, &["missing"]);
+    assert!(!output.status.success());
+
+    // Directories that can't be read are skipped, after the rest is measured.
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let locked = dir.join("c");
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        let readable = fs::read_dir(&locked).is_ok();
+        let output = du(&dir, &["--depth", "2"]);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Running as root, there's no stopping it.
+        if !readable {
+            assert_eq!(output.status.code(), Some(1));
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert!(
+                stderr.contains("failed to read directory 'c'"),
+                "{}",
+                stderr
+            );
+            assert!(!output.stdout.is_empty());
+        }
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
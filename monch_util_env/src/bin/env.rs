@@ -0,0 +1,157 @@
+use anyhow::{bail, Context, Error};
+use clap::Parser;
+use monch_io::{log, output_open, try_put, write_header, StreamHeader};
+use std::env;
+use std::io;
+use std::process::{self, Command, ExitStatus};
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Variables to write the values of, like `HOME`. With a command, variables to set for it
+    /// instead, like `LANG=C`.
+    #[clap(value_name("NAME"))]
+    vars: Vec<String>,
+
+    /// Only list variables whose names start with this.
+    #[clap(long("match"), value_name("PREFIX"), conflicts_with_all(&["vars", "command"]))]
+    prefix: Option<String>,
+
+    /// A command to run with the variables set, after `--`. It reads and writes our stdin and
+    /// stdout, and we exit with its status.
+    #[clap(last(true), value_name("COMMAND"))]
+    command: Vec<String>,
+}
+
+/// Split an assignment like `LANG=C` into the variable's name and value.
+fn parse_assignment(assignment: &str) -> Result<(&str, &str), Error> {
+    match assignment.split_once('=') {
+        Some((name, value)) if !name.is_empty() => Ok((name, value)),
+        _ => bail!("'{}' should look like 'NAME=VALUE'", assignment),
+    }
+}
+
+/// Write a map for every variable whose name starts with `prefix`, sorted by name.
+fn list(prefix: Option<&str>) -> Result<(), Error> {
+    let mut vars: Vec<(String, String)> = env::vars_os()
+        .map(|(name, value)| {
+            let name = name.to_string_lossy().into_owned();
+            (name, value.to_string_lossy().into_owned())
+        })
+        .filter(|(name, _)| name.starts_with(prefix.unwrap_or("")))
+        .collect();
+    vars.sort();
+
+    let header = StreamHeader::new("variable", &["name", "value"]);
+    write_header(&header).context("failed to write stream header")?;
+    for (name, value) in vars {
+        // If nobody's reading our output anymore, we're done.
+        if !output_open(try_put!(name: name, value: value)).context("failed to write variable")? {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the value of each variable, as text. Returns whether they were all set.
+fn show(names: &[String]) -> Result<bool, Error> {
+    let mut all_set = true;
+    for name in names {
+        if name.contains('=') {
+            bail!(
+                "'{}' sets a variable, but there's no command after `--` to set it for",
+                name
+            );
+        }
+
+        match env::var_os(name) {
+            Some(value) => {
+                let value = value.to_string_lossy();
+                if !output_open(try_put!(&value)).context("failed to write value")? {
+                    break;
+                }
+            }
+            None => {
+                log!("env: {} isn't set", name);
+                all_set = false;
+            }
+        }
+    }
+
+    Ok(all_set)
+}
+
+/// Run a command with some variables set, returning the status to exit with.
+fn run(command: &[String], vars: &[String]) -> Result<i32, Error> {
+    let assignments = vars
+        .iter()
+        .map(|var| parse_assignment(var))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (program, program_args) = command.split_first().expect("command is empty");
+    let status = Command::new(program)
+        .args(program_args)
+        .envs(assignments)
+        .status();
+
+    match status {
+        Ok(status) => Ok(exit_code(status)),
+        Err(e) => {
+            log!("env: {}: {}", program, e);
+            // Like a shell: 127 if there's no such program, 126 if it couldn't be run.
+            if e.kind() == io::ErrorKind::NotFound {
+                Ok(127)
+            } else {
+                Ok(126)
+            }
+        }
+    }
+}
+
+/// The status to exit with to pass on a child's: its exit code, or 128 plus the signal that killed
+/// it, like a shell reports.
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    if !args.command.is_empty() {
+        process::exit(run(&args.command, &args.vars)?);
+    }
+
+    if args.vars.is_empty() {
+        list(args.prefix.as_deref())
+    } else if show(&args.vars)? {
+        Ok(())
+    } else {
+        process::exit(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignments() {
+        assert_eq!(parse_assignment("LANG=C").unwrap(), ("LANG", "C"));
+        assert_eq!(parse_assignment("EMPTY=").unwrap(), ("EMPTY", ""));
+        assert_eq!(parse_assignment("EQ=a=b").unwrap(), ("EQ", "a=b"));
+        assert!(parse_assignment("LANG").is_err());
+        assert!(parse_assignment("=C").is_err());
+    }
+}
@@ -0,0 +1,91 @@
+use monch_io::Value;
+use monch_test::{items, utility};
+use std::process::Output;
+
+const ENV: &str = env!("CARGO_BIN_EXE_env");
+
+/// Run env, with a few variables of our own set.
+fn env(args: &[&str]) -> Output {
+    utility(ENV)
+        .args(args)
+        .env("MONCH_ENV_TEST_B", "two")
+        .env("MONCH_ENV_TEST_A", "one")
+        .env_remove("MONCH_ENV_TEST_UNSET")
+        .output()
+        .unwrap()
+}
+
+fn variable(name: &str, value: &str) -> Value {
+    Value::Map(vec![
+        (Value::Text("name".into()), Value::Text(name.into())),
+        (Value::Text("value".into()), Value::Text(value.into())),
+    ])
+}
+
+#[test]
+fn lists_variables() {
+    let output = env(&["--match", "MONCH_ENV_TEST_"]);
+    assert!(output.status.success());
+    assert_eq!(
+        items(&output),
+        [
+            variable("MONCH_ENV_TEST_A", "one"),
+            variable("MONCH_ENV_TEST_B", "two")
+        ]
+    );
+
+    // Everything is listed without a prefix, in order.
+    let output = env(&[]);
+    let names: Vec<String> = items(&output)
+        .iter()
+        .map(|v| v.as_map().unwrap()[0].1.as_text().unwrap().to_string())
+        .collect();
+    let mut sorted = names.clone();
+    sorted.sort();
+    assert_eq!(names, sorted);
+    assert!(names.iter().any(|name| name == "MONCH_ENV_TEST_A"));
+}
+
+#[test]
+fn shows_values() {
+    let output = env(&["MONCH_ENV_TEST_A"]);
+    assert!(output.status.success());
+    assert_eq!(items(&output), [Value::Text("one".into())]);
+
+    let output = env(&["MONCH_ENV_TEST_UNSET"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(items(&output).is_empty());
+
+    // Setting a variable needs something to set it for.
+    assert!(!env(&["MONCH_ENV_TEST_A=three"]).status.success());
+}
+
+#[test]
+fn sets_and_execs() {
+    // Use env itself as the child, to see what it was given.
+    let output = env(&[
+        "MONCH_ENV_TEST_A=three",
+        "MONCH_ENV_TEST_C=four",
+        "--",
+        ENV,
+        "--match",
+        "MONCH_ENV_TEST_",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        items(&output),
+        [
+            variable("MONCH_ENV_TEST_A", "three"),
+            variable("MONCH_ENV_TEST_B", "two"),
+            variable("MONCH_ENV_TEST_C", "four")
+        ]
+    );
+
+    // The child's status is passed on.
+    let output = env(&["MONCH_ENV_TEST_A=three", "--", ENV, "MONCH_ENV_TEST_UNSET"]);
+    assert_eq!(output.status.code(), Some(1));
+    let output = env(&["--", "monch-env-test-no-such-program"]);
+    assert_eq!(output.status.code(), Some(127));
+
+    assert!(!env(&["NOT_AN_ASSIGNMENT", "--", ENV]).status.success());
+}
@@ -0,0 +1,305 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use globset::{Glob, GlobMatcher};
+use monch_io::metadata::{dir_id, kind_name, rfc3339};
+use monch_io::units::{parse_duration, parse_size};
+use monch_io::{log, output_open, try_put, write_header, StreamHeader};
+use serde::Serialize;
+use std::fs::{self, Metadata};
+use std::path::PathBuf;
+use std::process;
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The directory to search. Paths are written starting with it, or relative to the working
+    /// directory if it's left out.
+    root: Option<PathBuf>,
+
+    /// Only find entries whose names match this glob, like '*.rs'.
+    #[clap(long, value_name("GLOB"))]
+    name: Option<String>,
+
+    /// Only find entries of this kind: `f` for files, `d` for directories, or `l` for symlinks.
+    #[clap(long("type"), value_name("KIND"), parse(try_from_str = parse_kind))]
+    kind: Option<&'static str>,
+
+    /// Only find entries of at least this size, like `1k` or `10M`.
+    #[clap(long, value_name("SIZE"), parse(try_from_str = parse_size))]
+    min_size: Option<u64>,
+
+    /// Only find entries of at most this size, like `1k` or `10M`.
+    #[clap(long, value_name("SIZE"), parse(try_from_str = parse_size))]
+    max_size: Option<u64>,
+
+    /// Only find entries modified less than this long ago, like `2d` or `1h30m`.
+    #[clap(long, value_name("DURATION"), parse(try_from_str = parse_duration))]
+    newer_than: Option<Duration>,
+
+    /// Only find entries modified more than this long ago, like `2d` or `1h30m`.
+    #[clap(long, value_name("DURATION"), parse(try_from_str = parse_duration))]
+    older_than: Option<Duration>,
+
+    /// Don't look more than this many directories down. Entries in the directory being searched
+    /// are at depth 1.
+    #[clap(long, value_name("N"))]
+    max_depth: Option<usize>,
+
+    /// Describe what symlinks point to, and search inside the directories they point to.
+    #[clap(short('L'), long)]
+    follow_symlinks: bool,
+
+    /// Find entries starting with `.`, and look inside directories starting with `.`.
+    #[clap(long)]
+    hidden: bool,
+}
+
+/// Read a kind of entry for `--type`, as the name we use for it in output.
+fn parse_kind(kind: &str) -> Result<&'static str, String> {
+    match kind {
+        "f" | "file" => Ok("File"),
+        "d" | "dir" => Ok("Dir"),
+        "l" | "symlink" => Ok("Symlink"),
+        _ => Err(format!(
+            "'{}' isn't a kind of entry: use `f`, `d` or `l`",
+            kind
+        )),
+    }
+}
+
+/// An entry which matched every filter.
+#[derive(Debug, Serialize)]
+struct Found {
+    /// Where the entry is, starting with the directory being searched.
+    path: String,
+
+    name: String,
+
+    /// One of `Dir`, `File`, `Symlink`, or `Unknown`.
+    kind: &'static str,
+
+    /// The size in bytes.
+    size: u64,
+
+    /// When the entry was last modified, as an RFC 3339 timestamp in UTC, to the second, like
+    /// `2021-11-05T14:03:09Z`.
+    modified: Option<String>,
+}
+
+/// The fields of a [`Found`], in order, for the stream header.
+const FIELDS: &[&str] = &["path", "name", "kind", "size", "modified"];
+
+/// Everything an entry has to pass to be found, worked out once before the search starts.
+struct Filter<'a> {
+    args: &'a Args,
+    pattern: Option<GlobMatcher>,
+
+    /// When the search started, which ages are measured from.
+    now: SystemTime,
+}
+
+impl<'a> Filter<'a> {
+    fn build(args: &'a Args) -> Result<Filter<'a>, Error> {
+        let pattern = match &args.name {
+            Some(pattern) => Some(
+                Glob::new(pattern)
+                    .with_context(|| format!("invalid pattern '{}'", pattern))?
+                    .compile_matcher(),
+            ),
+            None => None,
+        };
+
+        Ok(Filter {
+            args,
+            pattern,
+            now: SystemTime::now(),
+        })
+    }
+
+    /// Whether an entry passes every filter we were given.
+    fn matches(&self, name: &str, meta: &Metadata) -> bool {
+        let args = self.args;
+        if !self.pattern.as_ref().map_or(true, |p| p.is_match(name)) {
+            return false;
+        }
+        if !args.kind.map_or(true, |k| k == kind_name(meta.file_type())) {
+            return false;
+        }
+        if !args.min_size.map_or(true, |min| meta.len() >= min) {
+            return false;
+        }
+        if !args.max_size.map_or(true, |max| meta.len() <= max) {
+            return false;
+        }
+
+        // Entries whose age can't be read don't pass any test of it.
+        let age = meta
+            .modified()
+            .ok()
+            .map(|modified| self.now.duration_since(modified).unwrap_or_default());
+        let newer = args
+            .newer_than
+            .map_or(true, |d| age.map_or(false, |a| a < d));
+        let older = args
+            .older_than
+            .map_or(true, |d| age.map_or(false, |a| a > d));
+        newer && older
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let filter = Filter::build(&args)?;
+
+    let header = StreamHeader::new("entry", FIELDS);
+    write_header(&header).context("failed to write stream header")?;
+
+    let failed = walk(&args, &filter)?;
+    if failed {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Search the tree, depth first, writing each entry that matches as soon as it's found. Returns
+/// whether anything in the tree couldn't be read, which is logged, and skipped over.
+fn walk(args: &Args, filter: &Filter) -> Result<bool, Error> {
+    let root = args.root.clone().unwrap_or_default();
+    let dir = if root.as_os_str().is_empty() {
+        PathBuf::from(".")
+    } else {
+        root.clone()
+    };
+
+    // Each directory we're part way through, with where it is, how deep, and what it is, so that
+    // symlinks can't send us round in circles.
+    let entries = fs::read_dir(&dir)
+        .with_context(|| format!("failed to read directory '{}'", dir.display()))?;
+    let mut stack = vec![(entries, root, 1, dir_id(&dir))];
+    let mut failed = false;
+
+    while let Some((entries, parent, depth, _)) = stack.last_mut() {
+        let (parent, depth) = (parent.clone(), *depth);
+        let entry = match entries.next() {
+            Some(Ok(entry)) => entry,
+            Some(Err(e)) => {
+                log!(
+                    "find: failed to read an entry in '{}': {}",
+                    parent.display(),
+                    e
+                );
+                failed = true;
+                continue;
+            }
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
+
+        let name = match entry.file_name().into_string() {
+            Ok(name) => name,
+            Err(original) => {
+                log!(
+                    "find: encountered file with non-utf8 name: {}",
+                    original.to_string_lossy()
+                );
+                continue;
+            }
+        };
+
+        // Skip hidden entries, and don't look inside hidden directories either.
+        if !args.hidden && name.starts_with('.') {
+            continue;
+        }
+
+        let path = parent.join(&name);
+        let meta_result = if args.follow_symlinks {
+            // Links that don't point anywhere are still found, as links.
+            fs::metadata(entry.path()).or_else(|_| entry.metadata())
+        } else {
+            entry.metadata()
+        };
+        let meta = match meta_result {
+            Ok(meta) => meta,
+            Err(e) => {
+                log!(
+                    "find: failed to read metadata for '{}': {}",
+                    path.display(),
+                    e
+                );
+                failed = true;
+                continue;
+            }
+        };
+
+        // Look inside directories once we're done with this entry, unless that's too deep.
+        let mut next_dir = None;
+        let within_depth = args.max_depth.map_or(true, |max| depth < max);
+        if meta.is_dir() && within_depth {
+            // A directory can be found more than once through links, but not inside itself.
+            let id = dir_id(&entry.path());
+            let in_itself = id.is_some() && stack.iter().any(|(_, _, _, dir)| *dir == id);
+            if !in_itself {
+                match fs::read_dir(entry.path()) {
+                    Ok(entries) => next_dir = Some((entries, path.clone(), depth + 1, id)),
+                    Err(e) => {
+                        log!("find: failed to read directory '{}': {}", path.display(), e);
+                        failed = true;
+                    }
+                }
+            }
+        }
+
+        if filter.matches(&name, &meta) {
+            let found = Found {
+                path: path.to_string_lossy().into_owned(),
+                name,
+                kind: kind_name(meta.file_type()),
+                size: meta.len(),
+                modified: meta.modified().ok().map(rfc3339),
+            };
+
+            // If nobody's reading our output anymore, we're done.
+            if !output_open(try_put!(@obj found)).context("failed to write entry")? {
+                break;
+            }
+        }
+
+        stack.extend(next_dir);
+    }
+
+    Ok(failed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn arguments() {
+        let args = |a: &[&str]| Args::try_parse_from(["find"].iter().chain(a));
+
+        let parsed = args(&[
+            "src",
+            "--type",
+            "f",
+            "--min-size",
+            "1k",
+            "--newer-than",
+            "2d",
+        ])
+        .unwrap();
+        assert_eq!(parsed.root, Some(PathBuf::from("src")));
+        assert_eq!(parsed.kind, Some("File"));
+        assert_eq!(parsed.min_size, Some(1024));
+        assert_eq!(parsed.newer_than, Some(Duration::from_secs(2 * 86400)));
+        assert_eq!(args(&["--type", "symlink"]).unwrap().kind, Some("Symlink"));
+
+        assert!(args(&["--type", "x"]).is_err());
+        assert!(args(&["--min-size", "lots"]).is_err());
+        assert!(args(&["--older-than", "2"]).is_ok());
+        assert!(args(&["--older-than", "2 days"]).is_err());
+    }
+}
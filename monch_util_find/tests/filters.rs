@@ -0,0 +1,169 @@
+use filetime::FileTime;
+use monch_io::Value;
+use monch_test::{field, items, scratch_dir, utility};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::time::{Duration, SystemTime};
+
+/// Build a tree to search, like:
+///
+/// ```text
+/// a.rs          10 bytes
+/// big.rs        4k, from 10 days ago
+/// notes.txt     2000 bytes
+/// src/lib.rs    100 bytes, from 3 days ago
+/// src/deep/x.rs
+/// .hidden/h.rs
+/// ```
+fn tree(name: &str) -> PathBuf {
+    let dir = scratch_dir("find", name);
+    fs::create_dir_all(dir.join("src/deep")).unwrap();
+    fs::create_dir_all(dir.join(".hidden")).unwrap();
+
+    let days_ago = |days: u64| {
+        let time = SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+        FileTime::from_system_time(time)
+    };
+    let file = |path: &str, size: usize, age: Option<u64>| {
+        fs::write(dir.join(path), vec![b'x'; size]).unwrap();
+        if let Some(days) = age {
+            filetime::set_file_mtime(dir.join(path), days_ago(days)).unwrap();
+        }
+    };
+    file("a.rs", 10, None);
+    file("big.rs", 4096, Some(10));
+    file("notes.txt", 2000, None);
+    file("src/lib.rs", 100, Some(3));
+    file("src/deep/x.rs", 1, None);
+    file(".hidden/h.rs", 1, None);
+    dir
+}
+
+/// Run find in a directory, returning its output.
+fn find(dir: &Path, args: &[&str]) -> Output {
+    utility(env!("CARGO_BIN_EXE_find"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// The paths find found, sorted, since directories are read in whatever order they come in.
+fn paths(dir: &Path, args: &[&str]) -> Vec<String> {
+    let output = find(dir, args);
+    assert!(output.status.success(), "{:?}", output);
+    let mut paths: Vec<String> = items(&output)
+        .iter()
+        .map(|entry| field(entry, "path").as_text().unwrap().to_string())
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn filters_combine() {
+    let dir = tree("filters");
+    let paths = |args: &[&str]| paths(&dir, args);
+
+    assert_eq!(
+        paths(&["--name", "*.rs"]),
+        ["a.rs", "big.rs", "src/deep/x.rs", "src/lib.rs"]
+    );
+    assert_eq!(
+        paths(&["--name", "*.rs", "--hidden"]),
+        [
+            ".hidden/h.rs",
+            "a.rs",
+            "big.rs",
+            "src/deep/x.rs",
+            "src/lib.rs"
+        ]
+    );
+    assert_eq!(paths(&["--type", "d"]), ["src", "src/deep"]);
+    assert_eq!(paths(&["--type", "d", "--max-depth", "1"]), ["src"]);
+    assert_eq!(
+        paths(&["--type", "f", "--max-depth", "2"]),
+        ["a.rs", "big.rs", "notes.txt", "src/lib.rs"]
+    );
+
+    assert_eq!(
+        paths(&["--type", "f", "--min-size", "1k"]),
+        ["big.rs", "notes.txt"]
+    );
+    assert_eq!(
+        paths(&["--type", "f", "--min-size", "1k", "--max-size", "2k"]),
+        ["notes.txt"]
+    );
+    assert_eq!(
+        paths(&["--name", "*.rs", "--max-size", "10"]),
+        ["a.rs", "src/deep/x.rs"]
+    );
+
+    assert_eq!(
+        paths(&["--type", "f", "--newer-than", "2d"]),
+        ["a.rs", "notes.txt", "src/deep/x.rs"]
+    );
+    assert_eq!(
+        paths(&["--type", "f", "--older-than", "2d"]),
+        ["big.rs", "src/lib.rs"]
+    );
+    assert_eq!(
+        paths(&["--older-than", "2d", "--newer-than", "1w"]),
+        ["src/lib.rs"]
+    );
+    assert!(paths(&["--name", "*.txt", "--older-than", "2d"]).is_empty());
+
+    // Paths start with the directory being searched, when there is one.
+    assert_eq!(
+        paths(&["src", "--type", "f"]),
+        ["src/deep/x.rs", "src/lib.rs"]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn entries_are_described() {
+    let dir = tree("described");
+    let output = find(&dir, &["--name", "big.rs"]);
+    let found = items(&output);
+    assert_eq!(found.len(), 1);
+
+    let entry = &found[0];
+    assert_eq!(field(entry, "name"), &Value::Text("big.rs".into()));
+    assert_eq!(field(entry, "kind"), &Value::Text("File".into()));
+    assert_eq!(field(entry, "size"), &Value::from(4096));
+    let modified = field(entry, "modified").as_text().unwrap();
+    assert!(modified.ends_with('Z'), "{}", modified);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let dir = tree("symlinks");
+    symlink("src", dir.join("link")).unwrap();
+    symlink(".", dir.join("src/deep/loop")).unwrap();
+
+    assert_eq!(paths(&dir, &["--type", "l"]), ["link", "src/deep/loop"]);
+    assert_eq!(paths(&dir, &["--name", "x.rs"]), ["src/deep/x.rs"]);
+
+    // Following links goes inside them, but not round in circles.
+    assert!(paths(&dir, &["-L", "--type", "l"]).is_empty());
+    assert_eq!(
+        paths(&dir, &["-L", "--name", "x.rs"]),
+        ["link/deep/x.rs", "src/deep/x.rs"]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn bad_arguments() {
+    let dir = tree("bad");
+    assert!(!find(&dir, &["--min-size", "big"]).status.success());
+    assert!(!find(&dir, &["--newer-than", "yesterday"]).status.success());
+    assert!(!find(&dir, &["nope"]).status.success());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -1,30 +1,220 @@
-use anyhow::{Context, Error};
+use anyhow::{bail, Context, Error};
 use clap::Parser;
-use monch_io::{input_stream, put, DataPath, Value};
+use monch_io::convert::{cbor_to_json, json_to_cbor};
+use monch_io::{input_stream_auto, output_open, try_put, DataPath, Value};
+use std::io::{self, Write};
 
 // Note: the [`DataPath`] in [`Args`] has an implementation of [`FromStr`] that allows the [`Parser`] derive to
 // figure out how to parse it from the command line arguments.
 
 #[derive(Debug, Parser)]
 struct Args {
-    /// The path to the field to extract, like '.outerMap.innerMap.2'
-    path: DataPath,
+    /// The paths to the fields to extract, like '.outerMap.innerMap.2'. With more than one, each
+    /// item's fields are written together, in an array.
+    #[clap(required(true))]
+    paths: Vec<DataPath>,
+
+    /// Write each item's fields in a map, keyed by their paths, rather than an array.
+    #[clap(long, conflicts_with("raw"))]
+    map: bool,
+
+    /// Use this for fields which are null or missing. It's read as JSON if it can be, like `0` or
+    /// `[]`, and as text otherwise.
+    #[clap(long, value_name("VALUE"), parse(from_str = parse_literal))]
+    default: Option<Value>,
+
+    /// Fail if any of the fields are missing from an item.
+    #[clap(long)]
+    required: bool,
+
+    /// Write each field as a line of plain text, for programs that don't read objects. Fields that
+    /// aren't text are written as JSON.
+    #[clap(long)]
+    raw: bool,
+}
+
+/// Read a value given on the command line, as JSON if it is, or as text if it isn't.
+fn parse_literal(literal: &str) -> Value {
+    match serde_json::from_str(literal) {
+        Ok(json) => json_to_cbor(json),
+        Err(_) => Value::Text(literal.to_string()),
+    }
+}
+
+/// Pick out the fields from the item at `index`, in order.
+fn select(args: &Args, index: u64, obj: &Value) -> Result<Vec<Value>, Error> {
+    let mut fields = vec![];
+    for path in &args.paths {
+        let field = match path.get_ref(obj) {
+            Some(field) => field,
+            None if args.required => bail!("item {} has nothing at {}", index, path),
+            None => &Value::Null,
+        };
+
+        match (field, &args.default) {
+            (Value::Null, Some(default)) => fields.push(default.clone()),
+            _ => fields.push(field.clone()),
+        }
+    }
+
+    Ok(fields)
+}
+
+/// Put the fields picked from an item together into the object we write for it.
+fn combine(args: &Args, mut fields: Vec<Value>) -> Value {
+    if args.map {
+        let keys = args.paths.iter().map(|p| Value::Text(p.to_string()));
+        Value::Map(keys.zip(fields).collect())
+    } else if fields.len() == 1 {
+        fields.remove(0)
+    } else {
+        Value::Array(fields)
+    }
+}
+
+/// Write each field on its own line, for `--raw`.
+fn write_raw(out: &mut impl Write, fields: Vec<Value>) -> Result<(), Error> {
+    for field in fields {
+        match field {
+            Value::Text(text) => writeln!(out, "{}", text)?,
+            other => {
+                let json = cbor_to_json(other).context("failed to write field as JSON")?;
+                writeln!(out, "{}", json)?;
+            }
+        }
+    }
+
+    Ok(())
 }
 
 fn main() -> Result<(), Error> {
     let args = Args::parse();
+    let stdout = io::stdout();
 
     // Read CBOR objects from stdin
-    for obj_result in input_stream::<Value>() {
+    for (index, obj_result) in (0..).zip(input_stream_auto::<Value>()) {
         // Bail if we have an unhandled error.
         let obj = obj_result.context("failed to read object from stdin")?;
 
         // Get the data
-        let selected_data = args.path.get_from(obj);
+        let fields = select(&args, index, &obj)?;
+
+        if args.raw {
+            // If nobody's reading our output anymore, we're done.
+            match write_raw(&mut stdout.lock(), fields) {
+                Err(e) if is_broken_pipe(&e) => break,
+                written => written.context("failed to write text")?,
+            }
+            continue;
+        }
 
         // Write the data
-        put!(&selected_data);
+        // If nobody's reading our output anymore, we're done.
+        let selected_data = combine(&args, fields);
+        if !output_open(try_put!(&selected_data)).context("failed to write object")? {
+            break;
+        }
     }
 
     Ok(())
 }
+
+fn is_broken_pipe(e: &Error) -> bool {
+    match e.downcast_ref::<io::Error>() {
+        Some(e) => e.kind() == io::ErrorKind::BrokenPipe,
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monch_io::cbor;
+
+    fn args(args: &[&str]) -> Args {
+        Args::try_parse_from(["get"].iter().chain(args)).unwrap()
+    }
+
+    fn get(a: &[&str], obj: &Value) -> Result<Value, Error> {
+        let args = args(a);
+        Ok(combine(&args, select(&args, 0, obj)?))
+    }
+
+    fn file() -> Value {
+        cbor!({"name" => "a.txt", "size" => 12, "owner" => null}).unwrap()
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(get(&[".name"], &file()).unwrap(), cbor!("a.txt").unwrap());
+        assert_eq!(
+            get(&[".name", ".size"], &file()).unwrap(),
+            cbor!(["a.txt", 12]).unwrap()
+        );
+        assert_eq!(
+            get(&[".size", ".nope"], &file()).unwrap(),
+            cbor!([12, null]).unwrap()
+        );
+        assert_eq!(
+            get(&["--map", ".name", ".size"], &file()).unwrap(),
+            cbor!({".name" => "a.txt", ".size" => 12}).unwrap()
+        );
+        assert_eq!(
+            get(&["--map", "."], &cbor!(1).unwrap()).unwrap(),
+            cbor!({"." => 1}).unwrap()
+        );
+    }
+
+    #[test]
+    fn defaults() {
+        // Both null and missing fields get the default.
+        let a = ["--default", "0", ".owner", ".nope", ".size"];
+        assert_eq!(get(&a, &file()).unwrap(), cbor!([0, 0, 12]).unwrap());
+
+        let a = ["--map", "--default", "[]", ".nope"];
+        assert_eq!(get(&a, &file()).unwrap(), cbor!({".nope" => []}).unwrap());
+
+        // Anything that isn't JSON is just text.
+        let a = ["--default", "nobody", ".owner"];
+        assert_eq!(get(&a, &file()).unwrap(), cbor!("nobody").unwrap());
+        let a = ["--default", "\"0\"", ".owner"];
+        assert_eq!(get(&a, &file()).unwrap(), cbor!("0").unwrap());
+    }
+
+    #[test]
+    fn required() {
+        assert!(get(&["--required", ".owner", ".size"], &file()).is_ok());
+
+        let err = get(&["--required", ".size", ".nope"], &file()).unwrap_err();
+        assert_eq!(err.to_string(), "item 0 has nothing at .nope");
+
+        // Defaults are only for fields that are there, when they're required.
+        let a = ["--required", "--default", "0", ".nope"];
+        assert!(get(&a, &file()).is_err());
+        let a = ["--required", "--default", "0", ".owner"];
+        assert_eq!(get(&a, &file()).unwrap(), cbor!(0).unwrap());
+    }
+
+    #[test]
+    fn raw() {
+        let raw = |a: &[&str]| {
+            let args = args(a);
+            let mut out = vec![];
+            write_raw(&mut out, select(&args, 0, &file()).unwrap()).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        assert_eq!(raw(&["--raw", ".name"]), "a.txt\n");
+        assert_eq!(
+            raw(&["--raw", ".name", ".size", ".owner"]),
+            "a.txt\n12\nnull\n"
+        );
+        assert_eq!(raw(&["--raw", "--default", "-", ".owner"]), "-\n");
+        assert_eq!(
+            raw(&["--raw", "."]),
+            "{\"name\":\"a.txt\",\"size\":12,\"owner\":null}\n"
+        );
+
+        assert!(Args::try_parse_from(["get", "--raw", "--map", ".name"]).is_err());
+        assert!(Args::try_parse_from(["get"]).is_err());
+    }
+}
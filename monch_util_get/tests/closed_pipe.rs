@@ -0,0 +1,28 @@
+use monch_io::{cbor, write_all_to};
+use monch_test::utility;
+use std::process::Stdio;
+
+#[test]
+fn stops_when_output_closes() {
+    let mut child = utility(env!("CARGO_BIN_EXE_get"))
+        .arg(".name")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Nobody reads get's output.
+    drop(child.stdout.take());
+
+    // Once get stops reading, writing the rest of the input fails, which is fine.
+    let items: Vec<_> = (0..10_000).map(|i| cbor!({"name" => i}).unwrap()).collect();
+    let mut stdin = child.stdin.take().unwrap();
+    let _ = write_all_to(&mut stdin, &items);
+    drop(stdin);
+
+    // It quietly stopped, rather than failing on the closed pipe.
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
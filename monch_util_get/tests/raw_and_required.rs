@@ -0,0 +1,35 @@
+use monch_io::cbor;
+use monch_test::{run_with_items, utility};
+use std::process::Output;
+
+/// Run get over some items, returning its output.
+fn get(args: &[&str]) -> Output {
+    let items = [
+        cbor!({"name" => "a.txt", "size" => 1}).unwrap(),
+        cbor!({"name" => "b.txt"}).unwrap(),
+    ];
+    run_with_items(utility(env!("CARGO_BIN_EXE_get")).args(args), &items)
+}
+
+#[test]
+fn raw_text() {
+    let output = get(&["--raw", ".name"]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"a.txt\nb.txt\n");
+
+    let output = get(&["--raw", "--default", "?", ".name", ".size"]);
+    assert_eq!(output.stdout, b"a.txt\n1\nb.txt\n?\n");
+}
+
+#[test]
+fn required_fields() {
+    let output = get(&["--required", ".name"]);
+    assert!(output.status.success());
+
+    // The first item is written before the second is found to be missing a field.
+    let output = get(&["--required", "--raw", ".size"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"1\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("item 1 has nothing at .size"), "{}", stderr);
+}
@@ -1,44 +1,486 @@
-use anyhow::{Context, Error};
+use anyhow::{anyhow, bail, Context, Error};
 use clap::Parser;
-use monch_io::{input_stream, put, DataPath, Value, log};
+use monch_io::{output_open, try_put, DataPath, InputParser, Value, log, DEFAULT_MAX_ITEM_SIZE};
+use regex::{Regex, RegexBuilder};
+use std::{io, process};
+use std::time::{Duration, Instant};
 
 // Note: balls
 
 #[derive(Debug, Parser)]
 struct Args {
-    /// The path to the field to extract, like '.outerMap.innerMap.2'
+    /// The regex to look for, like '^fix'.
     pattern: String,
 
-    /// Pass in a field for DataPath(s).
-    #[clap(short('f'), long, default_value(""))]
-    field: DataPath,
-    
+    /// Only look in this field, like '.name'. Without it, every piece of text in the item is
+    /// searched.
+    #[clap(short('f'), long)]
+    field: Option<DataPath>,
+
+    /// Match upper and lower case letters alike.
+    #[clap(short('i'), long)]
+    ignore_case: bool,
+
+    /// Pass on the items which don't match, instead of those which do.
+    #[clap(short('v'), long)]
+    invert_match: bool,
+
+    /// Look for the pattern as plain text, rather than as a regex.
+    #[clap(short('F'), long)]
+    fixed_strings: bool,
+
+    /// Write each piece of text that matched, instead of the items they're in.
+    #[clap(short('o'), long)]
+    only_matching: bool,
+
+    /// Write how many items matched, instead of the items themselves.
+    #[clap(short('c'), long)]
+    count: bool,
+
+    /// Stop reading after this many items have matched.
+    #[clap(short('m'), long, value_name("NUM"))]
+    max_count: Option<u64>,
+
+    /// Don't write anything, and stop at the first match. Only the exit status says what happened.
+    #[clap(short('q'), long)]
+    quiet: bool,
+
+    /// Treat items without text to search as errors, rather than just warning about them.
+    #[clap(long)]
+    strict: bool,
+
+    /// Log the path to each piece of text that matched, for items that are passed on.
+    #[clap(long)]
+    paths: bool,
+
+    /// Log how quickly items are being read, every second.
+    #[clap(long)]
+    progress: bool,
 }
 
-fn main() -> Result<(), Error> {
-    let args = Args::parse();
+/// What to write for an item.
+#[derive(Debug, PartialEq)]
+enum Found {
+    /// The item itself.
+    Item,
 
-    for string_result in input_stream::<Value>() {
-        // Bail if we have an error 
-        let val = string_result.context("failed to read string from stdin")?;
+    /// Each piece of text that matched, for `--only-matching`.
+    Matches(Vec<String>),
+
+    /// Nothing at all.
+    Nothing,
+
+    /// Nothing, because the field isn't text.
+    NotText,
+}
+
+/// Compile the regex we're looking for, which is only done once, before reading anything.
+fn build_regex(args: &Args) -> Result<Regex, Error> {
+    if args.only_matching && args.invert_match {
+        bail!("--only-matching and --invert-match can't be used together");
+    }
+
+    let pattern = if args.fixed_strings {
+        regex::escape(&args.pattern)
+    } else {
+        args.pattern.clone()
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|e| anyhow!("invalid pattern '{}': {}", args.pattern, e))
+}
+
+/// Look for the regex in an item, and work out what to write for it.
+///
+/// If `--paths` is given, the path to each piece of text that matched is added to `matched`.
+fn grep_item(args: &Args, regex: &Regex, item: &Value, matched: &mut Vec<DataPath>) -> Found {
+    let field = match &args.field {
+        Some(field) => field,
+        None => return grep_all(args, regex, item, matched),
+    };
+
+    // Borrow the field, so we don't clone the entire item just to look at it.
+    let text = match field.get_ref(item).and_then(Value::as_text) {
+        Some(text) => text,
+        None => return Found::NotText,
+    };
+
+    if args.only_matching {
+        let matches: Vec<String> = regex
+            .find_iter(text)
+            .map(|m| m.as_str().to_string())
+            .collect();
+        if matches.is_empty() {
+            return Found::Nothing;
+        }
+        if args.paths {
+            matched.push(field.clone());
+        }
+        return Found::Matches(matches);
+    }
+
+    let is_match = regex.is_match(text);
+    if is_match && args.paths {
+        matched.push(field.clone());
+    }
+    if is_match != args.invert_match {
+        Found::Item
+    } else {
+        Found::Nothing
+    }
+}
+
+/// Look for the regex in every piece of text inside an item, passing it on if any of them match.
+fn grep_all(args: &Args, regex: &Regex, item: &Value, matched: &mut Vec<DataPath>) -> Found {
+    let mut found_text = false;
+    let mut is_match = false;
+    let mut matches = vec![];
+
+    walk_text(item, |path, text| {
+        found_text = true;
+
+        let hit = if args.only_matching {
+            let before = matches.len();
+            matches.extend(regex.find_iter(text).map(|m| m.as_str().to_string()));
+            matches.len() > before
+        } else {
+            regex.is_match(text)
+        };
+        if !hit {
+            return true;
+        }
+
+        is_match = true;
+        if args.paths {
+            matched.push(path.iter().cloned().collect());
+        }
+
+        // Once something's matched, the rest only matters for `-o` or `--paths`.
+        args.only_matching || args.paths
+    });
+
+    if !found_text {
+        Found::NotText
+    } else if args.only_matching {
+        if is_match {
+            Found::Matches(matches)
+        } else {
+            Found::Nothing
+        }
+    } else if is_match != args.invert_match {
+        Found::Item
+    } else {
+        Found::Nothing
+    }
+}
+
+/// Call `visit` with every piece of text inside a value, in order, along with the keys leading to
+/// it. Map values, array elements and tagged values are all looked inside, but map keys aren't.
+///
+/// This keeps its own stack rather than recursing, so deeply nested items can't overflow ours.
+/// Stops early if `visit` returns `false`.
+fn walk_text<'a>(item: &'a Value, mut visit: impl FnMut(&[Value], &'a str) -> bool) {
+    // Each entry is a value still to look at, how deep it is, and the key it's under.
+    let mut stack: Vec<(&Value, usize, Option<Value>)> = vec![(item, 0, None)];
+    let mut path: Vec<Value> = vec![];
 
-        let inner_val = args.field.get_from(val.clone());
-        let maybe_string = inner_val.as_text();
+    while let Some((value, depth, key)) = stack.pop() {
+        // Drop the keys of whatever we looked at last, down to this value's parent.
+        path.truncate(depth);
+        path.extend(key);
+        let depth = path.len();
 
-        if let Some(string) = maybe_string {
+        match value {
+            Value::Text(text) => {
+                if !visit(&path, text) {
+                    return;
+                }
+            }
+            Value::Tag(_tag, inner) => stack.push((inner, depth, None)),
 
-            if string.contains(&args.pattern) {
-                put!(&val);
+            // Children are pushed in reverse, so that they're popped off in order.
+            Value::Array(items) => {
+                let children = items.iter().enumerate().rev();
+                stack.extend(children.map(|(i, v)| (v, depth, Some(Value::from(i as u64)))));
+            }
+            Value::Map(entries) => {
+                let children = entries.iter().rev();
+                stack.extend(children.map(|(k, v)| (v, depth, Some(k.clone()))));
             }
+            _ => {}
+        }
+    }
+}
+
+/// What happened over the whole stream, which decides how we exit.
+#[derive(Debug, Default, PartialEq)]
+struct Tally {
+    /// How many items matched, or didn't with `--invert-match`.
+    selected: u64,
+
+    /// Whether anything went wrong that we carried on past, like a corrupt item.
+    failed: bool,
+}
 
+impl Tally {
+    /// Exit like grep(1): `0` if anything was selected, `1` if nothing was, and `2` if something
+    /// went wrong. Quietly finding a match is still a success, like it is for grep.
+    fn exit_code(&self, args: &Args) -> i32 {
+        if self.failed && !(args.quiet && self.selected > 0) {
+            2
+        } else if self.selected > 0 {
+            0
         } else {
+            1
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let code = match grep(&args) {
+        Ok(tally) => tally.exit_code(&args),
+        Err(e) => {
+            log!("grep: {:#}", e);
+            2
+        }
+    };
 
-            log!("grep: unexpected non-string data item passed in");
+    process::exit(code);
+}
+
+/// Read the whole input, writing whatever matches.
+fn grep(args: &Args) -> Result<Tally, Error> {
+    let regex = build_regex(args)?;
+    let mut tally = Tally::default();
+
+    let mut input = InputParser::<Value, _>::new_auto(monch_io::stdin())
+        .with_max_item_size(DEFAULT_MAX_ITEM_SIZE)
+        .resilient();
+
+    let start = Instant::now();
+    let mut last_report = start;
+
+    // Stop as soon as we've seen enough, without waiting on another item, so that whatever's
+    // writing to us finds out promptly.
+    let done = |tally: &Tally| {
+        let max = if args.quiet { Some(1) } else { args.max_count };
+        max.map_or(false, |max| tally.selected >= max)
+    };
+
+    while !done(&tally) {
+        let string_result = match input.next() {
+            Some(result) => result,
+            None => break,
+        };
+
+        if args.progress && last_report.elapsed() >= Duration::from_secs(1) {
+            last_report = Instant::now();
+            report_progress(&input, start);
+        }
+
+        // Corrupt items are skipped over, so log them and keep going.
+        if let Err(e @ monch_io::Error::Skipped { .. }) = string_result {
+            log!("grep: {}", e);
+            tally.failed = true;
+            continue;
+        }
+
+        // Bail if we have an error 
+        let val = string_result.context("failed to read string from stdin")?;
+
+        let mut matched = vec![];
+        let found = grep_item(args, &regex, &val, &mut matched);
+        match found {
+            Found::Item | Found::Matches(_) => {
+                tally.selected += 1;
+                for path in &matched {
+                    log!("grep: matched at {}", path);
+                }
+            }
+            Found::Nothing => {}
+            Found::NotText => {
+                log!("grep: unexpected non-string data item passed in");
+                tally.failed |= args.strict;
+            }
+        }
+
+        if args.quiet || args.count {
             continue;
+        }
+
+        let written = match found {
+            Found::Item => try_put!(&val),
+            Found::Matches(matches) => matches.iter().try_for_each(|m| try_put!(m)),
+            Found::Nothing | Found::NotText => Ok(()),
+        };
+
+        // If nobody's reading our output anymore, we're done.
+        if !output_open(written).context("failed to write object")? {
+            break;
+        }
+    }
+
+    if args.progress {
+        report_progress(&input, start);
+    }
+
+    if args.count && !args.quiet {
+        output_open(try_put!(&tally.selected)).context("failed to write count")?;
+    }
+
+    Ok(tally)
+}
 
+fn report_progress<R: io::Read>(input: &InputParser<Value, R>, start: Instant) {
+    let items = input.items_read();
+    let rate = items as f64 / start.elapsed().as_secs_f64();
+    log!("grep: read {} items ({:.0}/s), {} bytes", items, rate, input.bytes_read());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Args {
+        Args::try_parse_from(["grep"].iter().chain(args)).unwrap()
+    }
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
+
+    fn grep(a: &[&str], item: &Value) -> Found {
+        let args = args(a);
+        grep_item(&args, &build_regex(&args).unwrap(), item, &mut vec![])
+    }
+
+    fn paths(a: &[&str], item: &Value) -> Vec<String> {
+        let args = args(a);
+        let mut matched = vec![];
+        grep_item(&args, &build_regex(&args).unwrap(), item, &mut matched);
+        matched.iter().map(DataPath::to_string).collect()
+    }
+
+    #[test]
+    fn anchors_and_case() {
+        assert_eq!(grep(&["^fix"], &text("fix the bug")), Found::Item);
+        assert_eq!(grep(&["^fix"], &text("a fix")), Found::Nothing);
+        assert_eq!(grep(&["bug$"], &text("fix the bug")), Found::Item);
+        assert_eq!(grep(&["a.c"], &text("abc")), Found::Item);
+
+        assert_eq!(grep(&["FIX"], &text("fix")), Found::Nothing);
+        assert_eq!(grep(&["-i", "FIX"], &text("Fix")), Found::Item);
+
+        // Fixed strings don't have any special characters.
+        assert_eq!(grep(&["-F", "a.c"], &text("abc")), Found::Nothing);
+        assert_eq!(grep(&["-F", "a.c"], &text("a.c")), Found::Item);
+        assert_eq!(grep(&["-F", "-i", "A.C"], &text("a.c")), Found::Item);
+    }
+
+    #[test]
+    fn fields_and_inversion() {
+        let item = |name: &str| Value::Map(vec![(text("name"), text(name))]);
+        assert_eq!(grep(&["-f", ".name", "^a"], &item("apple")), Found::Item);
+        assert_eq!(
+            grep(&["-f", ".name", "-v", "^a"], &item("apple")),
+            Found::Nothing
+        );
+        assert_eq!(
+            grep(&["-f", ".name", "-v", "^a"], &item("banana")),
+            Found::Item
+        );
+
+        // Items without text there are never passed on, even inverted.
+        assert_eq!(
+            grep(&["-f", ".size", "-v", "^a"], &item("apple")),
+            Found::NotText
+        );
+    }
+
+    #[test]
+    fn whole_items() {
+        let item = Value::Map(vec![
+            (text("name"), text("apple")),
+            (text("size"), Value::from(3)),
+            (
+                text("tags"),
+                Value::Array(vec![text("red"), Value::Tag(0, Box::new(text("round")))]),
+            ),
+        ]);
+        assert_eq!(grep(&["^app"], &item), Found::Item);
+        assert_eq!(grep(&["^round$"], &item), Found::Item);
+        assert_eq!(grep(&["^size$"], &item), Found::Nothing);
+        assert_eq!(grep(&["-v", "^app"], &item), Found::Nothing);
+        assert_eq!(grep(&["-v", "^pear"], &item), Found::Item);
+        assert_eq!(
+            grep(&["-o", "r[a-z]+"], &item),
+            Found::Matches(vec!["red".to_string(), "round".to_string()])
+        );
+
+        assert_eq!(paths(&["--paths", "r"], &item), [".tags.0", ".tags.1"]);
+        assert_eq!(paths(&["--paths", "-f", ".name", "a"], &item), [".name"]);
+        assert!(paths(&["r"], &item).is_empty());
+
+        // Text on its own is matched just like before, but items without any text aren't.
+        assert_eq!(grep(&["^a"], &text("apple")), Found::Item);
+        assert_eq!(paths(&["--paths", "^a"], &text("apple")), ["."]);
+        assert_eq!(grep(&["-v", "^a"], &Value::from(3)), Found::NotText);
+        assert_eq!(grep(&["-v", "^a"], &Value::Array(vec![])), Found::NotText);
+    }
+
+    #[test]
+    fn deeply_nested_items() {
+        let mut item = text("needle");
+        for _ in 0..100_000 {
+            item = Value::Array(vec![Value::Null, item]);
         }
+        assert_eq!(grep(&["needle"], &item), Found::Item);
+        assert_eq!(paths(&["--paths", "needle"], &item)[0].len(), 100_000 * 2);
 
+        // Dropping a value recurses too, so take it apart one level at a time.
+        while let Value::Array(mut items) = item {
+            item = items.pop().unwrap();
+        }
     }
 
-    Ok(())
+    #[test]
+    fn only_matching() {
+        let found = |m: &[&str]| Found::Matches(m.iter().map(|s| s.to_string()).collect());
+        assert_eq!(
+            grep(&["-o", "[0-9]+"], &text("a1 b22 c333")),
+            found(&["1", "22", "333"])
+        );
+        assert_eq!(
+            grep(&["-o", "-i", "ab"], &text("ab AB aB")),
+            found(&["ab", "AB", "aB"])
+        );
+        assert_eq!(grep(&["-o", "[0-9]+"], &text("none")), Found::Nothing);
+    }
+
+    #[test]
+    fn exit_codes() {
+        let code = |a: &[&str], selected, failed| Tally { selected, failed }.exit_code(&args(a));
+        assert_eq!(code(&["a"], 3, false), 0);
+        assert_eq!(code(&["a"], 0, false), 1);
+        assert_eq!(code(&["a"], 3, true), 2);
+        assert_eq!(code(&["a"], 0, true), 2);
+
+        // Quietly finding something is enough, whatever else happened.
+        assert_eq!(code(&["-q", "a"], 1, true), 0);
+        assert_eq!(code(&["-q", "a"], 0, true), 2);
+    }
+
+    #[test]
+    fn bad_patterns() {
+        let err = build_regex(&args(&["a(b"])).unwrap_err().to_string();
+        assert!(err.starts_with("invalid pattern 'a(b': "), "{}", err);
+        assert!(err.contains("unclosed group"), "{}", err);
+
+        assert!(build_regex(&args(&["-F", "a(b"])).is_ok());
+        assert!(build_regex(&args(&["-o", "-v", "a"])).is_err());
+    }
 }
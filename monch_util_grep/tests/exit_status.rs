@@ -0,0 +1,95 @@
+use monch_io::{cbor, write_all_to, Value};
+use monch_test::{items, run_with_input, utility};
+use std::process::{Output, Stdio};
+use std::thread;
+
+/// Encode items, followed by some raw bytes, to use as grep's input.
+fn input(items: &[Value], trailer: &[u8]) -> Vec<u8> {
+    let mut input = vec![];
+    write_all_to(&mut input, items).unwrap();
+    input.extend_from_slice(trailer);
+    input
+}
+
+/// Run grep over some input, returning its output.
+fn grep(args: &[&str], stdin: Vec<u8>) -> Output {
+    run_with_input(utility(env!("CARGO_BIN_EXE_grep")).args(args), stdin)
+}
+
+fn fruit() -> Vec<Value> {
+    ["apple", "banana", "cherry", "avocado"]
+        .iter()
+        .map(|name| cbor!({ "name" => name }).unwrap())
+        .collect()
+}
+
+#[test]
+fn status_says_whether_anything_matched() {
+    let found = grep(&["^a"], input(&fruit(), &[]));
+    assert_eq!(found.status.code(), Some(0));
+    assert_eq!(items(&found).len(), 2);
+
+    let missing = grep(&["^z"], input(&fruit(), &[]));
+    assert_eq!(missing.status.code(), Some(1));
+    assert!(missing.stdout.is_empty());
+
+    // Quiet runs don't write anything, but still say whether something matched.
+    let quiet = grep(&["-q", "^a"], input(&fruit(), &[]));
+    assert_eq!(quiet.status.code(), Some(0));
+    assert!(quiet.stdout.is_empty());
+
+    let bad = grep(&["a("], input(&fruit(), &[]));
+    assert_eq!(bad.status.code(), Some(2));
+}
+
+#[test]
+fn counts() {
+    let count = |args: &[&str]| items(&grep(args, input(&fruit(), &[])));
+
+    assert_eq!(count(&["-c", "^a"]), [cbor!(2).unwrap()]);
+    assert_eq!(count(&["-c", "-v", "^a"]), [cbor!(2).unwrap()]);
+    assert_eq!(count(&["-c", "-m", "1", "^a"]), [cbor!(1).unwrap()]);
+    assert_eq!(count(&["-c", "^z"]), [cbor!(0).unwrap()]);
+}
+
+#[test]
+fn errors_only_count_when_strict() {
+    let mut with_number = fruit();
+    with_number.insert(1, cbor!(12).unwrap());
+
+    let lenient = grep(&["^a"], input(&with_number, &[]));
+    assert_eq!(lenient.status.code(), Some(0));
+    assert!(!lenient.stderr.is_empty());
+
+    let strict = grep(&["--strict", "^a"], input(&with_number, &[]));
+    assert_eq!(strict.status.code(), Some(2));
+    assert_eq!(items(&strict).len(), 2);
+
+    // Corrupt input is always an error, even after a match.
+    let corrupt = grep(&["^a"], input(&fruit(), &[0xff, 0xff]));
+    assert_eq!(corrupt.status.code(), Some(2));
+    assert_eq!(items(&corrupt).len(), 2);
+}
+
+#[test]
+fn stops_after_max_count() {
+    let mut child = utility(env!("CARGO_BIN_EXE_grep"))
+        .args(["-m", "2", "^a"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Write forever, until grep stops reading.
+    let mut stdin = child.stdin.take().unwrap();
+    let writer = thread::spawn(move || {
+        let item = cbor!({ "name" => "apple" }).unwrap();
+        while write_all_to(&mut stdin, [&item]).is_ok() {}
+    });
+
+    let output = child.wait_with_output().unwrap();
+    writer.join().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(items(&output).len(), 2);
+}
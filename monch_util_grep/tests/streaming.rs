@@ -0,0 +1,37 @@
+use monch_io::{cbor, read_one_from, write_all_to, Value};
+use monch_test::utility;
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn matches_arrive_while_input_is_open() {
+    let mut child = utility(env!("CARGO_BIN_EXE_grep"))
+        .arg("^a")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Write one match, and keep stdin open like a slow producer would.
+    let mut stdin = child.stdin.take().unwrap();
+    let item = cbor!({ "name" => "apple" }).unwrap();
+    write_all_to(&mut stdin, [&item]).unwrap();
+    stdin.flush().unwrap();
+
+    // grep's buffered output is flushed once it's waiting for more input.
+    let mut stdout = child.stdout.take().unwrap();
+    let (send, recv) = mpsc::channel();
+    thread::spawn(move || {
+        let found: Value = read_one_from(&mut stdout).unwrap();
+        send.send(found).unwrap();
+    });
+    let found = recv.recv_timeout(Duration::from_secs(10));
+
+    drop(stdin);
+    child.wait().unwrap();
+    assert_eq!(found, Ok(item));
+}
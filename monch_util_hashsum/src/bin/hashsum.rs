@@ -0,0 +1,375 @@
+use anyhow::{Context, Error};
+use clap::{ArgGroup, Parser};
+use md5::Md5;
+use monch_io::{
+    input_stream_auto, log, output_open, try_put, write_header, InputParser, StreamHeader, Value,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt::Write;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Debug, Parser)]
+#[clap(group(ArgGroup::new("algorithm").args(&["sha256", "sha512", "blake3", "md5"])))]
+struct Args {
+    /// The files to hash. Without any, each item of text from stdin is a path, like from
+    /// `ls | get .name`.
+    #[clap(conflicts_with_all(&["stdin-bytes", "check"]))]
+    paths: Vec<PathBuf>,
+
+    /// Hash with SHA-256. This is the default.
+    #[clap(long)]
+    sha256: bool,
+
+    /// Hash with SHA-512.
+    #[clap(long)]
+    sha512: bool,
+
+    /// Hash with BLAKE3.
+    #[clap(long)]
+    blake3: bool,
+
+    /// Hash with MD5, which is only good for checking against old digests, since it's broken.
+    #[clap(long)]
+    md5: bool,
+
+    /// Hash each item of bytes or text from stdin, rather than files, writing `{index, digest}`.
+    #[clap(long, conflicts_with("check"))]
+    stdin_bytes: bool,
+
+    /// Check the files in a stream of `{path, algorithm, digest}` from an earlier run still have
+    /// those digests, writing `{path, ok}` for each.
+    #[clap(long, value_name("FILE"), conflicts_with("algorithm"))]
+    check: Option<PathBuf>,
+}
+
+/// A hash function we know how to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Algorithm {
+    Sha256,
+    Sha512,
+    Blake3,
+    Md5,
+}
+
+impl Algorithm {
+    const ALL: [Algorithm; 4] = [
+        Algorithm::Sha256,
+        Algorithm::Sha512,
+        Algorithm::Blake3,
+        Algorithm::Md5,
+    ];
+
+    /// The algorithm asked for, or SHA-256 if none was.
+    fn from_args(args: &Args) -> Algorithm {
+        if args.sha512 {
+            Algorithm::Sha512
+        } else if args.blake3 {
+            Algorithm::Blake3
+        } else if args.md5 {
+            Algorithm::Md5
+        } else {
+            Algorithm::Sha256
+        }
+    }
+
+    /// The algorithm's name, as written in the `algorithm` field, like `sha256`.
+    fn name(self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha512 => "sha512",
+            Algorithm::Blake3 => "blake3",
+            Algorithm::Md5 => "md5",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Algorithm> {
+        Algorithm::ALL.iter().copied().find(|a| a.name() == name)
+    }
+
+    fn hasher(self) -> Hasher {
+        match self {
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha512 => Hasher::Sha512(Sha512::new()),
+            Algorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+            Algorithm::Md5 => Hasher::Md5(Md5::new()),
+        }
+    }
+}
+
+/// A hash being worked out, a piece at a time.
+enum Hasher {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+    Md5(Md5),
+}
+
+impl Hasher {
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Sha512(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+            Hasher::Md5(h) => h.update(data),
+        }
+    }
+
+    /// The digest of everything so far, in lowercase hex.
+    fn finish(self) -> String {
+        match self {
+            Hasher::Sha256(h) => hex(&h.finalize()),
+            Hasher::Sha512(h) => hex(&h.finalize()),
+            Hasher::Blake3(h) => hex(h.finalize().as_bytes()),
+            Hasher::Md5(h) => hex(&h.finalize()),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        write!(hex, "{:02x}", byte).unwrap();
+    }
+    hex
+}
+
+/// Hash something a chunk at a time, so big files don't have to fit in memory.
+fn hash_reader(algorithm: Algorithm, mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = algorithm.hasher();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(n) => hasher.update(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn hash_file(algorithm: Algorithm, path: &Path) -> io::Result<String> {
+    hash_reader(algorithm, File::open(path)?)
+}
+
+/// The digest of a file, as written by a run without `--check`.
+#[derive(Debug, Serialize, Deserialize)]
+struct FileDigest {
+    path: String,
+    algorithm: String,
+    digest: String,
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    let ok = if let Some(check_file) = &args.check {
+        check(check_file)?
+    } else {
+        let algorithm = Algorithm::from_args(&args);
+        if algorithm == Algorithm::Md5 {
+            log!("hashsum: warning: MD5 is broken, so its digests can be forged");
+        }
+
+        if args.stdin_bytes {
+            hash_items(algorithm)?
+        } else {
+            hash_paths(&args, algorithm)?
+        }
+    };
+
+    if !ok {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Write the digest of each file we were asked about. Returns whether every file could be read.
+fn hash_paths(args: &Args, algorithm: Algorithm) -> Result<bool, Error> {
+    let header = StreamHeader::new("digest", &["path", "algorithm", "digest"]);
+    write_header(&header).context("failed to write stream header")?;
+
+    let mut ok = true;
+    if args.paths.is_empty() {
+        for obj_result in input_stream_auto::<Value>() {
+            let obj = obj_result.context("failed to read object from stdin")?;
+            let path = match obj {
+                Value::Text(path) => PathBuf::from(path),
+                other => {
+                    log!("hashsum: expected a path as text, but got {:?}", other);
+                    ok = false;
+                    continue;
+                }
+            };
+
+            // If nobody's reading our output anymore, we're done.
+            if !write_digest(algorithm, &path, &mut ok)? {
+                break;
+            }
+        }
+    } else {
+        for path in &args.paths {
+            if !write_digest(algorithm, path, &mut ok)? {
+                break;
+            }
+        }
+    }
+
+    Ok(ok)
+}
+
+/// Write the digest of one file, or the error if it can't be read, clearing `ok`. Returns `false`
+/// once nobody's reading our output.
+fn write_digest(algorithm: Algorithm, path: &Path, ok: &mut bool) -> Result<bool, Error> {
+    let written = match hash_file(algorithm, path) {
+        Ok(digest) => {
+            let digest = FileDigest {
+                path: path.to_string_lossy().into_owned(),
+                algorithm: algorithm.name().to_string(),
+                digest,
+            };
+            try_put!(@obj digest)
+        }
+        Err(e) => {
+            log!("hashsum: {}: {}", path.display(), e);
+            *ok = false;
+            try_put!(path: path.to_string_lossy(), error: e.to_string())
+        }
+    };
+
+    output_open(written).context("failed to write digest")
+}
+
+/// Write the digest of each item of bytes or text from stdin. Returns whether every item could be
+/// hashed.
+fn hash_items(algorithm: Algorithm) -> Result<bool, Error> {
+    let mut ok = true;
+    for (index, obj_result) in input_stream_auto::<Value>().enumerate() {
+        let obj = obj_result.context("failed to read object from stdin")?;
+        let digest = match &obj {
+            Value::Bytes(bytes) => hash_reader(algorithm, &bytes[..])?,
+            Value::Text(text) => hash_reader(algorithm, text.as_bytes())?,
+            other => {
+                log!(
+                    "hashsum: item {}: expected bytes or text, but got {:?}",
+                    index,
+                    other
+                );
+                ok = false;
+                continue;
+            }
+        };
+
+        // If nobody's reading our output anymore, we're done.
+        if !output_open(try_put!(index: index, digest: digest)).context("failed to write digest")? {
+            break;
+        }
+    }
+
+    Ok(ok)
+}
+
+/// Check the digests in a file still match, writing `{path, ok}` for each. Returns whether they
+/// all did.
+fn check(check_file: &Path) -> Result<bool, Error> {
+    let file = File::open(check_file)
+        .with_context(|| format!("failed to open '{}'", check_file.display()))?;
+
+    let mut all_ok = true;
+    for record_result in InputParser::<FileDigest, _>::new_sniffing(file) {
+        let record = record_result
+            .with_context(|| format!("failed to read digest from '{}'", check_file.display()))?;
+        let path = Path::new(&record.path);
+
+        let ok = match Algorithm::from_name(&record.algorithm) {
+            Some(algorithm) => match hash_file(algorithm, path) {
+                Ok(digest) if digest.eq_ignore_ascii_case(&record.digest) => true,
+                Ok(_) => {
+                    log!("hashsum: {}: digest doesn't match", record.path);
+                    false
+                }
+                Err(e) => {
+                    log!("hashsum: {}: {}", record.path, e);
+                    false
+                }
+            },
+            None => {
+                log!(
+                    "hashsum: {}: unknown algorithm '{}'",
+                    record.path,
+                    record.algorithm
+                );
+                false
+            }
+        };
+        all_ok &= ok;
+
+        // If nobody's reading our output anymore, we're done.
+        if !output_open(try_put!(path: record.path, ok: ok)).context("failed to write result")? {
+            break;
+        }
+    }
+
+    Ok(all_ok)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Digests of the empty string, `abc`, and a million `a`s, from each algorithm's
+    /// specification.
+    const VECTORS: &[(Algorithm, &str, &str, &str)] = &[
+        (
+            Algorithm::Sha256,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0",
+        ),
+        (
+            Algorithm::Sha512,
+            "cf83e1357eefb8bdf1542850d66d8007d620e4050b5715dc83f4a921d36ce9ce\
+             47d0d13c5d85f2b0ff8318d2877eec2f63b931bd47417a81a538327af927da3e",
+            "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a\
+             2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+            "e718483d0ce769644e2e42c7bc15b4638e1f98b13b2044285632a803afa973eb\
+             de0ff244877ea60a4cb0432ce577c31beb009c5c2c49aa2e4eadb217ad8cc09b",
+        ),
+        (
+            Algorithm::Blake3,
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262",
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85",
+            "616f575a1b58d4c9797d4217b9730ae5e6eb319d76edef6549b46f4efe31ff8b",
+        ),
+        (
+            Algorithm::Md5,
+            "d41d8cd98f00b204e9800998ecf8427e",
+            "900150983cd24fb0d6963f7d28e17f72",
+            "7707d6ae4e027c70eea2a935c2296f21",
+        ),
+    ];
+
+    #[test]
+    fn vectors() {
+        let million = vec![b'a'; 1_000_000];
+        for (algorithm, empty, abc, a_million) in VECTORS {
+            let hash = |data: &[u8]| hash_reader(*algorithm, data).unwrap();
+            assert_eq!(hash(b""), *empty, "{:?}", algorithm);
+            assert_eq!(hash(b"abc"), *abc, "{:?}", algorithm);
+            assert_eq!(hash(&million), *a_million, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn names() {
+        for algorithm in Algorithm::ALL {
+            assert_eq!(Algorithm::from_name(algorithm.name()), Some(algorithm));
+        }
+        assert_eq!(Algorithm::from_name("sha1"), None);
+    }
+}
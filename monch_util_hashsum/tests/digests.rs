@@ -0,0 +1,104 @@
+use monch_io::Value;
+use monch_test::{field, items, run_with_items, scratch_dir, utility};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+const EMPTY_SHA256: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+/// Make a directory with a couple of files to hash.
+fn dir(name: &str) -> PathBuf {
+    let dir = scratch_dir("hashsum", name);
+    fs::write(dir.join("abc.txt"), "abc").unwrap();
+    fs::write(dir.join("empty.txt"), "").unwrap();
+    dir
+}
+
+/// Run hashsum in a directory, with some objects on stdin.
+fn hashsum(dir: &Path, args: &[&str], stdin: &[Value]) -> Output {
+    let mut hashsum = utility(env!("CARGO_BIN_EXE_hashsum"));
+    run_with_items(hashsum.args(args).current_dir(dir), stdin)
+}
+
+fn text(value: &str) -> Value {
+    Value::Text(value.into())
+}
+
+#[test]
+fn files() {
+    let dir = dir("files");
+    let output = hashsum(&dir, &["abc.txt", "empty.txt"], &[]);
+    assert!(output.status.success());
+    let found = items(&output);
+    assert_eq!(found.len(), 2);
+    assert_eq!(field(&found[0], "path"), &text("abc.txt"));
+    assert_eq!(field(&found[0], "algorithm"), &text("sha256"));
+    assert_eq!(field(&found[0], "digest"), &text(ABC_SHA256));
+    assert_eq!(field(&found[1], "digest"), &text(EMPTY_SHA256));
+
+    // Paths can come from stdin instead.
+    let output = hashsum(&dir, &["--md5"], &[text("abc.txt")]);
+    assert!(output.status.success());
+    let found = items(&output);
+    assert_eq!(field(&found[0], "algorithm"), &text("md5"));
+    assert_eq!(
+        field(&found[0], "digest"),
+        &text("900150983cd24fb0d6963f7d28e17f72")
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("MD5 is broken"), "{}", stderr);
+
+    // Missing files are reported, but don't stop the rest.
+    let output = hashsum(&dir, &["nope.txt", "abc.txt"], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let found = items(&output);
+    assert!(field(&found[0], "error").is_text());
+    assert_eq!(field(&found[1], "digest"), &text(ABC_SHA256));
+
+    assert!(!hashsum(&dir, &["--sha512", "--blake3", "abc.txt"], &[])
+        .status
+        .success());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stdin_bytes() {
+    let dir = dir("stdin-bytes");
+    let stdin = [Value::Bytes(b"abc".to_vec()), Value::from(1), text("")];
+    let output = hashsum(&dir, &["--stdin-bytes"], &stdin);
+    assert_eq!(output.status.code(), Some(1));
+
+    let found = items(&output);
+    assert_eq!(found.len(), 2);
+    assert_eq!(field(&found[0], "index"), &Value::from(0));
+    assert_eq!(field(&found[0], "digest"), &text(ABC_SHA256));
+    assert_eq!(field(&found[1], "index"), &Value::from(2));
+    assert_eq!(field(&found[1], "digest"), &text(EMPTY_SHA256));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn check() {
+    let dir = dir("check");
+    let output = hashsum(&dir, &["--blake3", "abc.txt", "empty.txt"], &[]);
+    assert!(output.status.success());
+    fs::write(dir.join("sums"), &output.stdout).unwrap();
+
+    let output = hashsum(&dir, &["--check", "sums"], &[]);
+    assert!(output.status.success());
+    let found = items(&output);
+    assert_eq!(field(&found[0], "path"), &text("abc.txt"));
+    assert_eq!(field(&found[0], "ok"), &Value::Bool(true));
+    assert_eq!(field(&found[1], "ok"), &Value::Bool(true));
+
+    fs::write(dir.join("empty.txt"), "not any more").unwrap();
+    let output = hashsum(&dir, &["--check", "sums"], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let found = items(&output);
+    assert_eq!(field(&found[0], "ok"), &Value::Bool(true));
+    assert_eq!(field(&found[1], "ok"), &Value::Bool(false));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("empty.txt"), "{}", stderr);
+    fs::remove_dir_all(dir).unwrap();
+}
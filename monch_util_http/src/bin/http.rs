@@ -0,0 +1,250 @@
+use anyhow::{bail, Context, Error};
+use clap::{Parser, Subcommand};
+use monch_io::convert::{cbor_to_json, json_to_cbor};
+use monch_io::units::parse_duration;
+use monch_io::{input_stream_auto, log, output_open, try_put, Value};
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use std::io::{self, Write};
+use std::process;
+use std::time::Duration;
+
+#[derive(Debug, Parser)]
+struct Args {
+    #[clap(subcommand)]
+    method: Method,
+}
+
+#[derive(Debug, Subcommand)]
+enum Method {
+    /// Fetch something
+    Get(Request),
+
+    /// Send something to be processed
+    Post(Request),
+
+    /// Send something to be stored
+    Put(Request),
+
+    /// Delete something
+    Delete(Request),
+}
+
+#[derive(Debug, clap::Args)]
+struct Request {
+    /// Where to send the request, like 'https://api.example.com/items'.
+    url: String,
+
+    /// Send the first object from stdin as the body of the request, in JSON.
+    #[clap(long)]
+    json: bool,
+
+    /// Add a header to the request, like 'Accept: text/plain'. Can be given more than once.
+    #[clap(
+        short('H'),
+        long("header"),
+        value_name("NAME:VALUE"),
+        parse(try_from_str = parse_header)
+    )]
+    headers: Vec<(String, String)>,
+
+    /// Give up if the whole request takes longer than this, like `10s`. Without it, requests time
+    /// out after 30 seconds.
+    #[clap(long, value_name("DURATION"), parse(try_from_str = parse_duration))]
+    timeout: Option<Duration>,
+
+    /// Write the body of the response as it is, rather than an object, for programs that don't
+    /// read objects.
+    #[clap(long)]
+    raw: bool,
+
+    /// Exit successfully even if the status of the response isn't a success.
+    #[clap(long)]
+    allow_error: bool,
+}
+
+impl Method {
+    fn parts(&self) -> (reqwest::Method, &Request) {
+        match self {
+            Method::Get(request) => (reqwest::Method::GET, request),
+            Method::Post(request) => (reqwest::Method::POST, request),
+            Method::Put(request) => (reqwest::Method::PUT, request),
+            Method::Delete(request) => (reqwest::Method::DELETE, request),
+        }
+    }
+}
+
+/// Split a header like `Accept: text/plain` into its name and value.
+fn parse_header(header: &str) -> Result<(String, String), String> {
+    match header.split_once(':') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!(
+            "header '{}' should look like 'NAME: VALUE'",
+            header
+        )),
+    }
+}
+
+/// Read the first object from stdin, as a JSON request body.
+fn json_body() -> Result<Vec<u8>, Error> {
+    let item = match input_stream_auto::<Value>().next() {
+        Some(item) => item.context("failed to read object from stdin")?,
+        None => bail!("--json needs an object on stdin, to send as the body"),
+    };
+
+    let json = cbor_to_json(item).context("failed to convert the body to JSON")?;
+    Ok(serde_json::to_vec(&json)?)
+}
+
+/// Put the headers of a response in a map. Headers which are given more than once have their
+/// values joined with commas.
+fn headers_value(headers: &HeaderMap) -> Value {
+    let mut fields: Vec<(Value, Value)> = vec![];
+    for (name, value) in headers {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        let existing = fields
+            .iter_mut()
+            .find(|(key, _)| key.as_text() == Some(name.as_str()));
+        match existing {
+            Some((_, Value::Text(text))) => {
+                text.push_str(", ");
+                text.push_str(&value);
+            }
+            _ => fields.push((Value::Text(name.to_string()), Value::Text(value.into()))),
+        }
+    }
+
+    Value::Map(fields)
+}
+
+/// Turn the body of a response into an object. JSON becomes the structure it describes, and
+/// anything else is text, or bytes if it isn't valid UTF-8.
+fn body_value(content_type: Option<&str>, body: Vec<u8>) -> Value {
+    if content_type.map_or(false, is_json) {
+        if let Ok(json) = serde_json::from_slice(&body) {
+            return json_to_cbor(json);
+        }
+    }
+
+    match String::from_utf8(body) {
+        Ok(text) => Value::Text(text),
+        Err(e) => Value::Bytes(e.into_bytes()),
+    }
+}
+
+/// Whether a content type, like `application/json; charset=utf-8`, is some kind of JSON.
+fn is_json(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or("").trim();
+    let mime = mime.to_ascii_lowercase();
+    mime == "application/json" || mime.ends_with("+json")
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let (method, request) = args.method.parts();
+
+    let mut client = Client::builder();
+    if let Some(timeout) = request.timeout {
+        client = client.timeout(timeout);
+    }
+    let client = client.build().context("failed to set up HTTP client")?;
+
+    let mut builder = client.request(method, &request.url);
+    for (name, value) in &request.headers {
+        builder = builder.header(name.as_str(), value.as_str());
+    }
+    if request.json {
+        builder = builder
+            .header(CONTENT_TYPE, "application/json")
+            .body(json_body()?);
+    }
+
+    let response = builder
+        .send()
+        .with_context(|| format!("failed to send request to '{}'", request.url))?;
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = response.bytes().context("failed to read response")?;
+
+    if request.raw {
+        // If nobody's reading our output anymore, that's fine.
+        match io::stdout().lock().write_all(&body) {
+            Err(e) if e.kind() != io::ErrorKind::BrokenPipe => {
+                return Err(e).context("failed to write body")
+            }
+            _ => {}
+        }
+    } else {
+        let content_type = headers.get(CONTENT_TYPE).and_then(|t| t.to_str().ok());
+        let body = body_value(content_type, body.to_vec());
+        let written = try_put!(
+            status: status.as_u16(),
+            headers: headers_value(&headers),
+            body: body
+        );
+        output_open(written).context("failed to write response")?;
+    }
+
+    if !status.is_success() && !request.allow_error {
+        log!("http: '{}' responded with {}", request.url, status);
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monch_io::cbor;
+
+    #[test]
+    fn headers() {
+        assert_eq!(
+            parse_header("Accept: text/plain"),
+            Ok(("Accept".into(), "text/plain".into()))
+        );
+        assert_eq!(
+            parse_header("X-Time:12:30"),
+            Ok(("X-Time".into(), "12:30".into()))
+        );
+        assert_eq!(parse_header("X-Empty:"), Ok(("X-Empty".into(), "".into())));
+        assert!(parse_header("Accept").is_err());
+        assert!(parse_header(": text/plain").is_err());
+
+        let mut map = HeaderMap::new();
+        map.append("set-cookie", "a=1".parse().unwrap());
+        map.append("content-length", "0".parse().unwrap());
+        map.append("set-cookie", "b=2".parse().unwrap());
+        assert_eq!(
+            headers_value(&map),
+            cbor!({"set-cookie" => "a=1, b=2", "content-length" => "0"}).unwrap()
+        );
+    }
+
+    #[test]
+    fn bodies() {
+        let json = Some("application/json; charset=utf-8");
+        assert_eq!(
+            body_value(json, br#"{"a": [1, null]}"#.to_vec()),
+            cbor!({"a" => [1, null]}).unwrap()
+        );
+        assert_eq!(
+            body_value(Some("application/problem+json"), b"1".to_vec()),
+            cbor!(1).unwrap()
+        );
+
+        // JSON is only parsed if the server says that's what it is.
+        assert_eq!(
+            body_value(Some("text/plain"), b"[1]".to_vec()),
+            cbor!("[1]").unwrap()
+        );
+        assert_eq!(body_value(None, b"[1]".to_vec()), cbor!("[1]").unwrap());
+
+        // ...and if it isn't really, it's just text.
+        assert_eq!(body_value(json, b"{oops".to_vec()), cbor!("{oops").unwrap());
+        assert_eq!(body_value(None, vec![0xff, 0]), Value::Bytes(vec![0xff, 0]));
+    }
+}
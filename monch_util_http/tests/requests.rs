@@ -0,0 +1,148 @@
+use monch_io::{cbor, Value};
+use monch_test::{field, items, run_with_items, utility};
+use std::process::Output;
+use std::thread;
+use tiny_http::{Header, Response, Server};
+
+/// Start a server for the tests to talk to, returning its address, like `http://127.0.0.1:1234`.
+///
+/// - `/items` responds with some JSON.
+/// - `/text` and `/bytes` respond with things that aren't JSON.
+/// - `/missing` responds with a 404.
+/// - `/echo` responds with JSON describing the request.
+fn serve() -> String {
+    let server = Server::http("127.0.0.1:0").unwrap();
+    let address = server.server_addr().to_ip().unwrap();
+
+    thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            let json = Header::from_bytes("Content-Type", "application/json").unwrap();
+            let response = match request.url() {
+                "/items" => Response::from_string(r#"[{"id": 1}, {"id": 2}]"#).with_header(json),
+                "/text" => Response::from_string("hello"),
+                "/bytes" => Response::from_data(vec![0xff, 0x00]),
+                "/echo" => {
+                    let mut body = String::new();
+                    request.as_reader().read_to_string(&mut body).unwrap();
+                    let header = |name: &str| {
+                        let mut headers = request.headers().iter();
+                        let found =
+                            headers.find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name));
+                        found.map_or(String::new(), |h| h.value.to_string())
+                    };
+                    let echo = format!(
+                        r#"{{"method": "{}", "body": "{}", "type": "{}", "x-test": "{}"}}"#,
+                        request.method(),
+                        body.replace('"', "\\\""),
+                        header("Content-Type"),
+                        header("X-Test"),
+                    );
+                    Response::from_string(echo).with_header(json)
+                }
+                _ => Response::from_string("nope").with_status_code(404),
+            };
+            request.respond(response).unwrap();
+        }
+    });
+
+    format!("http://{}", address)
+}
+
+/// Run http, with some objects on stdin.
+fn http(args: &[&str], stdin: &[Value]) -> Output {
+    run_with_items(utility(env!("CARGO_BIN_EXE_http")).args(args), stdin)
+}
+
+/// Read the one response http wrote.
+fn response(output: &Output) -> Value {
+    let mut responses = items(output);
+    assert_eq!(responses.len(), 1, "{:?}", responses);
+    responses.remove(0)
+}
+
+#[test]
+fn bodies() {
+    let server = serve();
+
+    let output = http(&["get", &format!("{}/items", server)], &[]);
+    assert!(output.status.success());
+    let items = response(&output);
+    assert_eq!(field(&items, "status"), &cbor!(200).unwrap());
+    assert_eq!(
+        field(&items, "body"),
+        &cbor!([{"id" => 1}, {"id" => 2}]).unwrap()
+    );
+    let headers = field(&items, "headers");
+    assert_eq!(
+        field(headers, "content-type"),
+        &cbor!("application/json").unwrap()
+    );
+
+    let output = http(&["get", &format!("{}/text", server)], &[]);
+    assert_eq!(field(&response(&output), "body"), &cbor!("hello").unwrap());
+
+    let output = http(&["get", &format!("{}/bytes", server)], &[]);
+    assert_eq!(
+        field(&response(&output), "body"),
+        &Value::Bytes(vec![0xff, 0x00])
+    );
+
+    // Raw bodies are written as they are.
+    let output = http(&["get", "--raw", &format!("{}/items", server)], &[]);
+    assert_eq!(output.stdout, br#"[{"id": 1}, {"id": 2}]"#);
+}
+
+#[test]
+fn sending() {
+    let server = serve();
+    let url = format!("{}/echo", server);
+
+    let body = [cbor!({"name" => "new"}).unwrap()];
+    let output = http(&["post", "--json", "-H", "X-Test: yes", &url], &body);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        field(&response(&output), "body"),
+        &cbor!({
+            "method" => "POST",
+            "body" => r#"{"name":"new"}"#,
+            "type" => "application/json",
+            "x-test" => "yes",
+        })
+        .unwrap()
+    );
+
+    let output = http(&["delete", &url], &[]);
+    let echo = response(&output);
+    assert_eq!(
+        field(field(&echo, "body"), "method"),
+        &cbor!("DELETE").unwrap()
+    );
+    assert_eq!(field(field(&echo, "body"), "body"), &cbor!("").unwrap());
+
+    // There has to be something to send.
+    let output = http(&["put", "--json", &url], &[]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn errors() {
+    let server = serve();
+    let url = format!("{}/missing", server);
+
+    // Failures are still written, but the exit status says something went wrong...
+    let output = http(&["get", &url], &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(field(&response(&output), "status"), &cbor!(404).unwrap());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("404"), "{}", stderr);
+
+    // ...unless that's expected.
+    let output = http(&["get", "--allow-error", &url], &[]);
+    assert!(output.status.success());
+    assert_eq!(field(&response(&output), "body"), &cbor!("nope").unwrap());
+
+    let output = http(&["get", "http://127.0.0.1:1/nothing-here"], &[]);
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
@@ -0,0 +1,319 @@
+use anyhow::{Context, Error};
+use clap::{ArgEnum, Parser};
+use monch_io::convert::json_to_cbor;
+use monch_io::{
+    canonicalize, input_stream_auto, log, output_open, try_put, DataPath, Format, InputParser,
+    Value, DEFAULT_MAX_ITEM_SIZE,
+};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::fs;
+use std::mem;
+use std::path::PathBuf;
+use std::process;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// A file containing the other stream of objects, to join with the stream on stdin, as CBOR or
+    /// JSON.
+    file: PathBuf,
+
+    /// The field to match items on, like `.id`, on both sides.
+    #[clap(
+        long,
+        value_name("PATH"),
+        conflicts_with_all(&["left", "right"]),
+        required_unless_present_all(&["left", "right"])
+    )]
+    on: Option<DataPath>,
+
+    /// The field to match items from stdin on, when it's different to the file's.
+    #[clap(long, value_name("PATH"), requires("right"))]
+    left: Option<DataPath>,
+
+    /// The field to match items from the file on, when it's different to stdin's.
+    #[clap(long, value_name("PATH"), requires("left"))]
+    right: Option<DataPath>,
+
+    /// Which items to write. With `inner`, only items that match are written. With `left`, items
+    /// from stdin that don't match anything are written too, and with `outer`, so are items from
+    /// the file. Where there's nothing to match, the other side's fields are null.
+    #[clap(short('k'), long, arg_enum, default_value("inner"), value_name("KIND"))]
+    kind: Kind,
+
+    /// Add this to the start of fields from the file that are already in the item from stdin.
+    #[clap(long, default_value("right_"))]
+    prefix: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgEnum)]
+enum Kind {
+    Inner,
+    Left,
+    Outer,
+}
+
+type Pairs = Vec<(Value, Value)>;
+
+/// The items from the file, indexed by their keys.
+struct Index {
+    items: Vec<Pairs>,
+
+    /// Where to find the items with each key, by its canonical encoding.
+    by_key: HashMap<Vec<u8>, Vec<usize>>,
+
+    /// Whether each item has been matched with something from stdin yet.
+    matched: Vec<bool>,
+
+    /// Every field the items have, in the order they were first seen.
+    fields: Vec<Value>,
+}
+
+impl Index {
+    fn new() -> Self {
+        Index {
+            items: vec![],
+            by_key: HashMap::new(),
+            matched: vec![],
+            fields: vec![],
+        }
+    }
+
+    fn insert(&mut self, key: Option<Vec<u8>>, item: Pairs) {
+        add_fields(&mut self.fields, &item);
+        if let Some(key) = key {
+            self.by_key.entry(key).or_default().push(self.items.len());
+        }
+        self.items.push(item);
+        self.matched.push(false);
+    }
+
+    /// Find every item with this key, marking them as matched.
+    fn matches(&mut self, key: Option<&[u8]>) -> Vec<usize> {
+        let found = match key.and_then(|key| self.by_key.get(key)) {
+            Some(found) => found.clone(),
+            None => return vec![],
+        };
+        for &i in &found {
+            self.matched[i] = true;
+        }
+        found
+    }
+}
+
+/// Add any fields from this item that haven't been seen before.
+fn add_fields(fields: &mut Vec<Value>, item: &[(Value, Value)]) {
+    for (k, _) in item {
+        if !fields.contains(k) {
+            fields.push(k.clone());
+        }
+    }
+}
+
+/// Find an item's key, encoded so that equal keys are equal bytes, even if they're maps with
+/// their pairs in different orders. Items without the key, or where it's null, have no key, and
+/// don't match anything.
+fn key_of(path: &DataPath, item: &Value) -> Option<Vec<u8>> {
+    let key = match path.get_ref(item)? {
+        Value::Null => return None,
+        key => canonicalize(key.clone()),
+    };
+
+    let mut bytes = vec![];
+    monch_io::ciborium::ser::into_writer(&key, &mut bytes).expect("writing to a Vec can't fail");
+    Some(bytes)
+}
+
+/// Put a prefix on a field name, so it doesn't clash with another.
+fn prefixed(field: Value, prefix: &str) -> Value {
+    match field {
+        Value::Text(name) => Value::Text(format!("{}{}", prefix, name)),
+        Value::Integer(n) => Value::Text(format!("{}{}", prefix, i128::from(n))),
+        other => other,
+    }
+}
+
+/// Merge two items into one map, with the right's fields after the left's. Either side can be
+/// missing, for items that didn't match anything, in which case all its fields are null.
+fn merge(
+    left: Option<&Pairs>,
+    left_fields: &[Value],
+    right: Option<&Pairs>,
+    right_fields: &[Value],
+    prefix: &str,
+) -> Value {
+    let nulls =
+        |fields: &[Value]| -> Pairs { fields.iter().map(|k| (k.clone(), Value::Null)).collect() };
+
+    let mut merged = left.cloned().unwrap_or_else(|| nulls(left_fields));
+    let right = right.cloned().unwrap_or_else(|| nulls(right_fields));
+    for (k, v) in right {
+        let k = if merged.iter().any(|(existing, _)| *existing == k) {
+            prefixed(k, prefix)
+        } else {
+            k
+        };
+        merged.push((k, v));
+    }
+
+    Value::Map(merged)
+}
+
+/// Read the items in the file, whether it's CBOR, JSON Lines, or a JSON document. A JSON array,
+/// like `save` writes, is read as its elements.
+fn read_file(file: Vec<u8>) -> Result<Vec<Value>, Error> {
+    let first_byte = file.iter().find(|b| !b.is_ascii_whitespace());
+    let mut items: Vec<Value> = match first_byte.map(|&b| Format::sniff(b)) {
+        Some(Format::Json) => serde_json::Deserializer::from_slice(&file)
+            .into_iter::<JsonValue>()
+            .map(|item| item.map(json_to_cbor))
+            .collect::<Result<_, _>>()?,
+        _ => InputParser::new(&file[..])
+            .with_max_item_size(DEFAULT_MAX_ITEM_SIZE)
+            .collect::<Result<_, _>>()?,
+    };
+
+    if let [Value::Array(elements)] = &mut items[..] {
+        return Ok(mem::take(elements));
+    }
+    Ok(items)
+}
+
+/// Write one item. Returns `false` once nobody's reading our output.
+fn emit(item: &Value) -> Result<bool, Error> {
+    output_open(try_put!(item)).context("failed to write object")
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let (left_path, right_path) = match (args.on, args.left, args.right) {
+        (Some(on), _, _) => (on.clone(), on),
+        (None, Some(left), Some(right)) => (left, right),
+        _ => unreachable!("clap requires a field for both sides"),
+    };
+    let mut failed = false;
+
+    // Read the whole file, so that each item from stdin can be matched as it arrives.
+    let file = fs::read(&args.file)
+        .with_context(|| format!("failed to open '{}'", args.file.display()))?;
+    let items = read_file(file)
+        .with_context(|| format!("failed to read object from '{}'", args.file.display()))?;
+
+    let mut index = Index::new();
+    for (i, item) in items.into_iter().enumerate() {
+        let key = key_of(&right_path, &item);
+        match item {
+            Value::Map(pairs) => index.insert(key, pairs),
+            _ => {
+                log!(
+                    "join: skipping item {} of '{}', which isn't a map",
+                    i,
+                    args.file.display()
+                );
+                failed = true;
+            }
+        }
+    }
+
+    let mut left_fields = vec![];
+    for (i, item) in input_stream_auto::<Value>().enumerate() {
+        let item = item.context("failed to read object from stdin")?;
+        let key = key_of(&left_path, &item);
+        let pairs = match item {
+            Value::Map(pairs) => pairs,
+            _ => {
+                log!("join: skipping item {} from stdin, which isn't a map", i);
+                failed = true;
+                continue;
+            }
+        };
+        add_fields(&mut left_fields, &pairs);
+
+        let found = index.matches(key.as_deref());
+        let mut merged: Vec<_> = found
+            .iter()
+            .map(|&i| merge(Some(&pairs), &[], Some(&index.items[i]), &[], &args.prefix))
+            .collect();
+        if found.is_empty() && args.kind != Kind::Inner {
+            merged.push(merge(Some(&pairs), &[], None, &index.fields, &args.prefix));
+        }
+
+        for item in &merged {
+            // If nobody's reading our output anymore, we're done.
+            if !emit(item)? {
+                return Ok(());
+            }
+        }
+    }
+
+    // Now that all of stdin has been read, we know which items from the file never matched.
+    if args.kind == Kind::Outer {
+        for (item, _) in index
+            .items
+            .iter()
+            .zip(&index.matched)
+            .filter(|(_, matched)| !**matched)
+        {
+            if !emit(&merge(None, &left_fields, Some(item), &[], &args.prefix))? {
+                return Ok(());
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monch_io::cbor;
+
+    fn pairs(value: Value) -> Pairs {
+        value.into_map().unwrap()
+    }
+
+    #[test]
+    fn merges() {
+        let left = pairs(cbor!({"id" => 1, "name" => "a"}).unwrap());
+        let right = pairs(cbor!({"id" => 1, "size" => 5}).unwrap());
+
+        assert_eq!(
+            merge(Some(&left), &[], Some(&right), &[], "right_"),
+            cbor!({"id" => 1, "name" => "a", "right_id" => 1, "size" => 5}).unwrap()
+        );
+
+        let right_fields = [Value::Text("id".into()), Value::Text("size".into())];
+        assert_eq!(
+            merge(Some(&left), &[], None, &right_fields, "other."),
+            cbor!({"id" => 1, "name" => "a", "other.id" => null, "size" => null}).unwrap()
+        );
+
+        let left_fields = [Value::Text("name".into())];
+        assert_eq!(
+            merge(None, &left_fields, Some(&right), &[], "right_"),
+            cbor!({"name" => null, "id" => 1, "size" => 5}).unwrap()
+        );
+    }
+
+    #[test]
+    fn keys() {
+        let path = DataPath::parse(".key");
+        let key = |value: Value| key_of(&path, &value);
+
+        let unordered = key(cbor!({"key" => {"a" => 1, "b" => 2}}).unwrap());
+        assert!(unordered.is_some());
+        assert_eq!(
+            unordered,
+            key(cbor!({"key" => {"b" => 2, "a" => 1}}).unwrap())
+        );
+        assert_ne!(
+            key(cbor!({"key" => 1}).unwrap()),
+            key(cbor!({"key" => "1"}).unwrap())
+        );
+        assert_eq!(key(cbor!({"key" => null}).unwrap()), None);
+        assert_eq!(key(cbor!({"other" => 1}).unwrap()), None);
+    }
+}
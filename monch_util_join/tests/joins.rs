@@ -0,0 +1,155 @@
+use monch_io::{cbor, Value};
+use monch_test::{items, run_with_items, scratch_dir, utility};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Write the file side of a join into a directory, as JSON like someone might have saved it.
+fn right_file(dir: &Path, json: &str) -> PathBuf {
+    let path = dir.join("right.json");
+    fs::write(&path, json).unwrap();
+    path
+}
+
+/// Run join, with some objects on stdin. join might fail before it reads them at all.
+fn join(args: &[&str], stdin: &[Value]) -> Output {
+    run_with_items(utility(env!("CARGO_BIN_EXE_join")).args(args), stdin)
+}
+
+/// Some files, owned by some users.
+fn files() -> Vec<Value> {
+    vec![
+        cbor!({"name" => "a.txt", "owner" => "alice"}).unwrap(),
+        cbor!({"name" => "b.txt", "owner" => "bob"}).unwrap(),
+        cbor!({"name" => "c.txt", "owner" => "nobody"}).unwrap(),
+    ]
+}
+
+const USERS: &str = r#"{"owner": "alice", "uid": 1000}
+{"owner": "bob", "uid": 1001}
+{"owner": "carol", "uid": 1002}
+"#;
+
+#[test]
+fn inner() {
+    let dir = scratch_dir("join", "inner");
+    let users = right_file(&dir, USERS);
+    let output = join(&[users.to_str().unwrap(), "--on", ".owner"], &files());
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        items(&output),
+        [
+            cbor!({"name" => "a.txt", "owner" => "alice", "right_owner" => "alice", "uid" => 1000})
+                .unwrap(),
+            cbor!({"name" => "b.txt", "owner" => "bob", "right_owner" => "bob", "uid" => 1001})
+                .unwrap(),
+        ]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn left() {
+    let dir = scratch_dir("join", "left");
+    let users = right_file(&dir, USERS);
+    let path = users.to_str().unwrap();
+    let output = join(&[path, "--on", ".owner", "--kind", "left"], &files());
+    assert!(output.status.success(), "{:?}", output);
+
+    let found = items(&output);
+    assert_eq!(found.len(), 3);
+    assert_eq!(
+        found[2],
+        cbor!({"name" => "c.txt", "owner" => "nobody", "right_owner" => null, "uid" => null})
+            .unwrap()
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn outer() {
+    let dir = scratch_dir("join", "outer");
+    let users = right_file(&dir, USERS);
+    let path = users.to_str().unwrap();
+    let output = join(
+        &[path, "--left", ".owner", "--right", "owner", "-k", "outer"],
+        &files(),
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    // Items from the file that never matched come last.
+    let found = items(&output);
+    assert_eq!(found.len(), 4);
+    assert_eq!(
+        found[2],
+        cbor!({"name" => "c.txt", "owner" => "nobody", "right_owner" => null, "uid" => null})
+            .unwrap()
+    );
+    assert_eq!(
+        found[3],
+        cbor!({"name" => null, "owner" => null, "right_owner" => "carol", "uid" => 1002}).unwrap()
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn duplicates() {
+    // The file can also be one JSON array, like `save` writes.
+    let dir = scratch_dir("join", "duplicates");
+    let file = right_file(
+        &dir,
+        r#"[
+            {"user": "alice", "group": "staff"},
+            {"user": "alice", "group": "wheel"},
+            {"user": "bob", "group": "staff"}
+        ]"#,
+    );
+    let path = file.to_str().unwrap();
+    let output = join(
+        &[
+            path, "--left", ".owner", "--right", ".user", "--prefix", "group_",
+        ],
+        &files(),
+    );
+    assert!(output.status.success(), "{:?}", output);
+
+    // Each match is its own item.
+    let found = items(&output);
+    let groups: Vec<_> = found
+        .iter()
+        .map(|item| {
+            let field = |name: &str| {
+                let key = Value::Text(name.into());
+                let pairs = item.as_map().unwrap();
+                pairs.iter().find(|(k, _)| *k == key).unwrap().1.clone()
+            };
+            (field("name"), field("group"))
+        })
+        .collect();
+    assert_eq!(
+        groups,
+        [("a.txt", "staff"), ("a.txt", "wheel"), ("b.txt", "staff")]
+            .map(|(name, group)| (Value::Text(name.into()), Value::Text(group.into())))
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failures() {
+    let missing = join(&["no-such-file.json", "--on", ".id"], &files());
+    assert!(!missing.status.success());
+
+    let dir = scratch_dir("join", "failures");
+    let users = right_file(&dir, USERS);
+    let path = users.to_str().unwrap();
+    assert!(!join(&[path], &files()).status.success());
+    assert!(!join(&[path, "--left", ".owner"], &files()).status.success());
+
+    // Items that aren't maps are skipped, but the rest are still joined.
+    let mut inputs = files();
+    inputs.insert(1, cbor!("not a map").unwrap());
+    let output = join(&[path, "--on", ".owner"], &inputs);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(items(&output).len(), 2);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -1,13 +1,25 @@
 use anyhow::{Context, Error};
-use clap::Parser;
-use monch_io::{log, put};
+use clap::{ArgEnum, Parser};
+use globset::Glob;
+use monch_io::metadata::{dir_id, kind_name, rfc3339};
+use monch_io::{canonicalize, log, put, write_header, StreamHeader, Value};
 use serde::Serialize;
-use std::{env, fs, path::PathBuf};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug, Parser)]
 struct Args {
+    /// The directory to list. If this is the only argument, and it looks like a glob but isn't a
+    /// directory, it's used as the pattern instead, like `ls -R '*.rs'`.
     directory: Option<PathBuf>,
 
+    /// Only list entries whose names match this glob, like '*.rs'.
+    pattern: Option<String>,
+
     /// Do not ignore directory entries starting with `.`
     #[clap(short('a'), long)]
     all: bool,
@@ -15,31 +27,242 @@ struct Args {
     /// Include file metadata
     #[clap(short('l'), long)]
     long: bool,
+
+    /// Describe what symlinks point to, rather than the links themselves
+    #[clap(short('L'), long)]
+    dereference: bool,
+
+    /// List the contents of directories, and their directories, and so on
+    #[clap(short('R'), long)]
+    recursive: bool,
+
+    /// Don't list anything more than this many directories down, with `-R`. Entries in the
+    /// directory being listed are at depth 1.
+    #[clap(long, value_name("N"), requires("recursive"))]
+    max_depth: Option<usize>,
+
+    /// What to sort each directory's entries by. Names are compared ignoring case, the largest
+    /// entries come first by size, and the newest first by modification time. With `none`, entries
+    /// are listed in whatever order the filesystem gives them, as soon as they're read.
+    #[clap(long, arg_enum, default_value("name"), value_name("KEY"))]
+    sort: SortKey,
+
+    /// Reverse the order entries are sorted in
+    #[clap(short('r'), long)]
+    reverse: bool,
+
+    /// List directories before anything else
+    #[clap(long)]
+    dirs_first: bool,
+
+    /// Write objects with a deterministic encoding, with their keys sorted
+    #[clap(long)]
+    canonical: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgEnum)]
+enum SortKey {
+    Name,
+    Size,
+    Mtime,
+    None,
 }
 
-#[derive(Serialize)]
+/// Everything `ls -l` says about a directory entry. Fields are null where they couldn't be read.
+#[derive(Debug, Default, Serialize)]
 struct DirEntry {
     name: String,
 
+    /// The path from the directory being listed, with `-R`.
     #[serde(skip_serializing_if = "Option::is_none")]
-    kind: Option<String>,
+    path: Option<String>,
+
+    /// One of `Dir`, `File`, `Symlink`, or `Unknown`.
+    kind: Option<&'static str>,
+
+    /// The size in bytes.
+    size: Option<u64>,
+
+    /// When the entry was last modified, as an RFC 3339 timestamp in UTC, to the second, like
+    /// `2021-11-05T14:03:09Z`.
+    modified: Option<String>,
+
+    /// The permission bits, in octal, like `755`.
+    #[cfg(unix)]
+    permissions: Option<String>,
+
+    /// Whether the entry can't be written to.
+    #[cfg(not(unix))]
+    readonly: Option<bool>,
+
+    #[cfg(unix)]
+    owner: Option<String>,
+
+    #[cfg(unix)]
+    group: Option<String>,
+
+    /// Where the entry points, if it's a symlink.
+    symlink_target: Option<String>,
+}
+
+/// The fields of a [`DirEntry`], in order, for the stream header. With `-R`, `path` comes after
+/// `name`.
+#[cfg(unix)]
+const LONG_FIELDS: &[&str] = &[
+    "name",
+    "kind",
+    "size",
+    "modified",
+    "permissions",
+    "owner",
+    "group",
+    "symlink_target",
+];
+#[cfg(not(unix))]
+const LONG_FIELDS: &[&str] = &[
+    "name",
+    "kind",
+    "size",
+    "modified",
+    "readonly",
+    "symlink_target",
+];
+
+/// Looks up the names of users and groups, remembering them, since a directory's entries usually
+/// all have the same few owners.
+#[cfg(unix)]
+type Owners = users::UsersCache;
+
+/// Nothing has owners to look up, off unix.
+#[cfg(not(unix))]
+#[derive(Default)]
+struct Owners;
+
+impl Args {
+    /// `ls '*.rs'` means a pattern, unless there really is a directory called that.
+    fn glob_from_directory(&mut self) {
+        if let (Some(dir), None) = (&self.directory, &self.pattern) {
+            let looks_like_glob = dir.to_string_lossy().contains(&['*', '?', '['][..]);
+            if looks_like_glob && !dir.is_dir() {
+                self.pattern = self
+                    .directory
+                    .take()
+                    .map(|d| d.to_string_lossy().into_owned());
+            }
+        }
+    }
+}
+
+/// An entry found while listing a directory.
+#[derive(Debug)]
+struct Found {
+    name: String,
+
+    /// Where it is, relative to the directory being listed.
+    path: PathBuf,
+
+    /// Where it is, relative to our working directory.
+    full_path: PathBuf,
 }
 
 fn main() -> Result<(), Error> {
-    let args = Args::parse();
+    let mut args = Args::parse();
+
+    args.glob_from_directory();
 
     // Determine the directory we're reading from
-    let dir = match args.directory {
-        Some(dir) => dir,
+    let dir = match &args.directory {
+        Some(dir) => dir.clone(),
         None => env::current_dir().context("no working directory")?,
     };
 
-    // Read the directory
-    let entries = fs::read_dir(dir).context("failed to read directory")?;
+    // Describe what's coming, for anything that wants to display it.
+    let header = if args.long {
+        let mut fields = LONG_FIELDS.to_vec();
+        if args.recursive {
+            fields.insert(1, "path");
+        }
+        StreamHeader::new("entry", &fields)
+    } else {
+        StreamHeader::new("string", &[])
+    };
+    write_header(&header).context("failed to write stream header")?;
 
-    // Iterate over the directory entries
-    for entry_result in entries {
-        let entry = entry_result.context("failed to read directory entry")?;
+    let owners = Owners::default();
+
+    walk(&args, &dir, |found| {
+        // Without `-l`, just output the filename, or where it is with `-R`.
+        if !args.long && args.recursive {
+            put!(@obj found.path.to_string_lossy());
+            return Ok(());
+        } else if !args.long {
+            put!(@obj found.name);
+            return Ok(());
+        }
+
+        // Get extended information about the file, for an object like `{name, kind, ..}`.
+        let mut described = describe(&found.full_path, found.name, args.dereference, &owners);
+        if args.recursive {
+            described.path = Some(found.path.to_string_lossy().into_owned());
+        }
+
+        if args.canonical {
+            let entry =
+                Value::serialized(&described).context("failed to encode directory entry")?;
+            put!(@obj canonicalize(entry));
+        } else {
+            put!(@obj described);
+        }
+        Ok(())
+    })
+}
+
+/// List the entries in `dir`, and with `-R`, those in the directories inside it, depth first.
+/// Each one is passed to `found` as soon as it's read, so a big tree doesn't have to be walked
+/// before anything comes out.
+///
+/// Only the directory itself has to be readable: problems further in are logged and skipped.
+fn walk(
+    args: &Args,
+    dir: &Path,
+    mut found: impl FnMut(Found) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let pattern = match &args.pattern {
+        Some(pattern) => Some(
+            Glob::new(pattern)
+                .with_context(|| format!("invalid pattern '{}'", pattern))?
+                .compile_matcher(),
+        ),
+        None => None,
+    };
+
+    // Remember every directory we've been into, so that symlinks can't send us round in circles.
+    let mut visited = HashSet::new();
+    if let Some(id) = dir_id(dir) {
+        visited.insert(id);
+    }
+
+    // Each directory we're part way through, with where it is, and how deep.
+    let root = read_entries(args, dir).context("failed to read directory")?;
+    let mut stack = vec![(root, PathBuf::new(), 1)];
+
+    while let Some((entries, parent, depth)) = stack.last_mut() {
+        let (parent, depth) = (parent.clone(), *depth);
+        let entry = match entries.next() {
+            Some(Ok(entry)) => entry,
+            Some(Err(e)) => {
+                log!(
+                    "ls: failed to read an entry in '{}': {}",
+                    parent.display(),
+                    e
+                );
+                continue;
+            }
+            None => {
+                stack.pop();
+                continue;
+            }
+        };
 
         // Get the filename, and check that it's valid Unicode.
         let filename_os = entry.file_name();
@@ -56,37 +279,412 @@ fn main() -> Result<(), Error> {
             }
         };
 
-        // Depending on the `-a` flag, skip hidden entries (starting with a dot)
+        // Depending on the `-a` flag, skip hidden entries (starting with a dot), and don't look
+        // inside hidden directories either.
         if !args.all && name.starts_with('.') {
             continue; // skip this file
         }
 
-        // Depending on if `-l` was passed, write extended information or just the filename to
-        // stdout.
-        if args.long {
-            // Write extended information about the file, in an object like `{name, kind, ..}`.
+        let path = parent.join(&name);
+        let full_path = entry.path();
 
-            let meta = entry.metadata().context("failed to read file metadata")?;
+        // Look inside directories once we're done with this entry, unless that's too deep.
+        let mut next_dir = None;
+        let within_depth = args.max_depth.map_or(true, |max| depth < max);
+        if args.recursive && within_depth && is_dir(&entry, args.dereference) {
+            let unvisited = dir_id(&full_path).map_or(true, |id| visited.insert(id));
+            if unvisited {
+                match read_entries(args, &full_path) {
+                    Ok(entries) => next_dir = Some((entries, path.clone(), depth + 1)),
+                    Err(e) => log!("ls: failed to read directory '{}': {}", path.display(), e),
+                }
+            }
+        }
 
-            // Based on file metadata, come up with a type
-            let kind = if meta.is_dir() {
-                "Dir"
-            } else if meta.is_file() {
-                "File"
-            } else {
-                "Unknown"
-            };
+        if pattern.as_ref().map_or(true, |p| p.is_match(&name)) {
+            found(Found {
+                name,
+                path,
+                full_path,
+            })?;
+        }
+
+        stack.extend(next_dir);
+    }
+
+    Ok(())
+}
+
+/// Entries in a directory, possibly still being read.
+type Entries = Box<dyn Iterator<Item = io::Result<fs::DirEntry>>>;
 
-            // Output the filename and extended information.
-            put!({
-                "name" => name,
-                "kind" => kind,
-            });
+/// Read the entries in a directory, in the order they should be listed.
+///
+/// Sorting means reading the whole directory first, so unless we're asked to sort, entries are
+/// passed on as they're read instead. Either way, this is only one directory: with `-R`, each is
+/// sorted on its own, and the listing still comes out as the tree is walked.
+fn read_entries(args: &Args, dir: &Path) -> io::Result<Entries> {
+    let read = fs::read_dir(dir)?;
+    if args.sort == SortKey::None && !args.reverse && !args.dirs_first {
+        return Ok(Box::new(read));
+    }
+
+    // Drop hidden entries before sorting, since there's no point looking at their metadata.
+    let mut entries = vec![];
+    let mut errors = vec![];
+    for entry in read {
+        match entry {
+            Ok(entry) if !args.all && entry.file_name().to_string_lossy().starts_with('.') => {}
+            Ok(entry) => entries.push(entry),
+            Err(e) => errors.push(Err(e)),
+        }
+    }
+
+    sort_entries(args, &mut entries);
+    Ok(Box::new(
+        errors.into_iter().chain(entries.into_iter().map(Ok)),
+    ))
+}
+
+/// Sort a directory's entries, as asked to by `--sort`, `--reverse` and `--dirs-first`.
+///
+/// Entries that compare equal, like those of the same size, are sorted by name. Names are compared
+/// case-insensitively, the same way regardless of locale, and then exactly, so the order is always
+/// the same.
+fn sort_entries(args: &Args, entries: &mut Vec<fs::DirEntry>) {
+    let metadata = |entry: &fs::DirEntry| {
+        if args.dereference {
+            fs::metadata(entry.path())
         } else {
-            // Output the filename and nothing else.
-            put!(&name);
+            entry.metadata()
+        }
+    };
+
+    // Work out each entry's key once, rather than on every comparison.
+    let mut keyed: Vec<_> = entries
+        .drain(..)
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let meta = match args.sort {
+                SortKey::Size | SortKey::Mtime => metadata(&entry).ok(),
+                SortKey::Name | SortKey::None => None,
+            };
+
+            // Bigger and newer entries come first, so their keys are reversed.
+            let size = meta.as_ref().map_or(0, |m| m.len());
+            let modified = meta.and_then(|m| m.modified().ok());
+            let key = match args.sort {
+                SortKey::Size => (Reverse(size), Reverse(None)),
+                SortKey::Mtime => (Reverse(0), Reverse(modified)),
+                SortKey::Name | SortKey::None => (Reverse(0), Reverse(None)),
+            };
+
+            (key, name.to_lowercase(), name, entry)
+        })
+        .collect();
+
+    if args.sort != SortKey::None {
+        keyed.sort_by(|a, b| (&a.0, &a.1, &a.2).cmp(&(&b.0, &b.1, &b.2)));
+    }
+    if args.reverse {
+        keyed.reverse();
+    }
+    entries.extend(keyed.into_iter().map(|(_, _, _, entry)| entry));
+
+    // This is stable, so the sorted order is kept among directories, and everything else.
+    if args.dirs_first {
+        entries.sort_by_key(|entry| !is_dir(entry, args.dereference));
+    }
+}
+
+/// Whether to treat an entry as a directory to look inside. Symlinks to directories only count if
+/// we're following them.
+fn is_dir(entry: &fs::DirEntry, dereference: bool) -> bool {
+    if dereference {
+        entry.path().is_dir()
+    } else {
+        entry.file_type().map_or(false, |t| t.is_dir())
+    }
+}
+
+/// Read the metadata for the entry at `path`. If that fails, the error is logged, and whatever
+/// needed it is left null, so one unreadable entry doesn't stop the whole listing.
+fn describe(path: &Path, name: String, dereference: bool, owners: &Owners) -> DirEntry {
+    let mut entry = DirEntry {
+        name,
+        ..DirEntry::default()
+    };
+
+    // Say where symlinks point, whether or not we're following them.
+    let link_meta = fs::symlink_metadata(path);
+    if let Ok(meta) = &link_meta {
+        if meta.file_type().is_symlink() {
+            let target = fs::read_link(path).ok();
+            entry.symlink_target = target.map(|t| t.to_string_lossy().into_owned());
         }
     }
 
-    Ok(())
+    let meta_result = if dereference {
+        fs::metadata(path)
+    } else {
+        link_meta
+    };
+    let meta = match meta_result {
+        Ok(meta) => meta,
+        Err(e) => {
+            log!(
+                "ls: failed to read metadata for '{}': {}",
+                path.display(),
+                e
+            );
+            return entry;
+        }
+    };
+
+    entry.kind = Some(kind_name(meta.file_type()));
+    entry.size = Some(meta.len());
+    entry.modified = meta.modified().ok().map(rfc3339);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        use users::{Groups, Users};
+
+        entry.permissions = Some(format!("{:o}", meta.mode() & 0o7777));
+        let owner = owners.get_user_by_uid(meta.uid());
+        entry.owner = owner.map(|u| u.name().to_string_lossy().into_owned());
+        let group = owners.get_group_by_gid(meta.gid());
+        entry.group = group.map(|g| g.name().to_string_lossy().into_owned());
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = owners;
+        entry.readonly = Some(meta.permissions().readonly());
+    }
+
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use std::process;
+
+    #[test]
+    fn long_entries() {
+        let dir = env::temp_dir().join(format!("monch-ls-long-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        fs::write(dir.join("file.txt"), "hello").unwrap();
+
+        let owners = Owners::default();
+        let describe = |name: &str, dereference| {
+            describe(&dir.join(name), name.to_string(), dereference, &owners)
+        };
+
+        let file = describe("file.txt", false);
+        assert_eq!(file.kind, Some("File"));
+        assert_eq!(file.size, Some(5));
+        assert_eq!(file.symlink_target, None);
+        let modified = file.modified.unwrap();
+        assert!(
+            modified.ends_with('Z') && modified.len() == 20,
+            "{}",
+            modified
+        );
+        assert!(DateTime::parse_from_rfc3339(&modified).is_ok());
+
+        assert_eq!(describe("sub", false).kind, Some("Dir"));
+
+        // Entries that can't be read are still described, as far as they can be.
+        let missing = describe("missing", false);
+        assert_eq!(missing.name, "missing");
+        assert_eq!((missing.kind, missing.size), (None, None));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{symlink, PermissionsExt};
+
+            fs::set_permissions(dir.join("file.txt"), fs::Permissions::from_mode(0o640)).unwrap();
+            let file = describe("file.txt", false);
+            assert_eq!(file.permissions.as_deref(), Some("640"));
+            assert!(file.owner.is_some() && file.group.is_some());
+
+            symlink("file.txt", dir.join("link")).unwrap();
+            let link = describe("link", false);
+            assert_eq!(link.kind, Some("Symlink"));
+            assert_eq!(link.symlink_target.as_deref(), Some("file.txt"));
+
+            // Following the link describes the file, but still says where it points.
+            let followed = describe("link", true);
+            assert_eq!(followed.kind, Some("File"));
+            assert_eq!(followed.size, Some(5));
+            assert_eq!(followed.symlink_target.as_deref(), Some("file.txt"));
+
+            // A broken link can't be followed.
+            symlink("nowhere", dir.join("broken")).unwrap();
+            assert_eq!(describe("broken", false).kind, Some("Symlink"));
+            let broken = describe("broken", true);
+            assert_eq!((broken.kind, broken.size), (None, None));
+            assert_eq!(broken.symlink_target.as_deref(), Some("nowhere"));
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recursive_walk() {
+        let dir = env::temp_dir().join(format!("monch-ls-walk-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub/deeper")).unwrap();
+        fs::create_dir_all(dir.join(".hidden")).unwrap();
+        for file in ["a.rs", "sub/b.rs", "sub/deeper/c.txt", ".hidden/d.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+
+        // Each entry's path, sorted, since the order within a directory isn't fixed.
+        let paths = |a: &[&str]| {
+            let args = Args::try_parse_from(["ls"].iter().chain(a)).unwrap();
+            let mut paths = vec![];
+            walk(&args, &dir, |found| {
+                assert_eq!(found.full_path, dir.join(&found.path));
+                paths.push(found.path.to_string_lossy().replace('\\', "/"));
+                Ok(())
+            })
+            .unwrap();
+            paths.sort();
+            paths
+        };
+
+        assert_eq!(paths(&[]), ["a.rs", "sub"]);
+        assert_eq!(
+            paths(&["-R"]),
+            ["a.rs", "sub", "sub/b.rs", "sub/deeper", "sub/deeper/c.txt"]
+        );
+        assert_eq!(
+            paths(&["-R", "--max-depth", "2"]),
+            ["a.rs", "sub", "sub/b.rs", "sub/deeper"]
+        );
+        assert_eq!(
+            paths(&["-Ra", ".", "*.rs"]),
+            [".hidden/d.rs", "a.rs", "sub/b.rs"]
+        );
+        assert_eq!(paths(&["-R", ".", "*.rs"]), ["a.rs", "sub/b.rs"]);
+
+        // Directories are always listed depth first.
+        let args = Args::try_parse_from(["ls", "-R"]).unwrap();
+        let mut order = vec![];
+        walk(&args, &dir, |found| {
+            order.push(found.path);
+            Ok(())
+        })
+        .unwrap();
+        let position = |path: &str| order.iter().position(|p| p == Path::new(path)).unwrap();
+        assert_eq!(position("sub/deeper") + 1, position("sub/deeper/c.txt"));
+
+        #[cfg(unix)]
+        {
+            // A link back up the tree is listed, but only followed once.
+            std::os::unix::fs::symlink("..", dir.join("sub/up")).unwrap();
+            assert!(paths(&["-R"]).contains(&"sub/up".to_string()));
+            assert!(!paths(&["-R"]).iter().any(|p| p.starts_with("sub/up/")));
+            let followed = paths(&["-RL"]);
+            assert!(followed.contains(&"sub/up".to_string()));
+            assert!(!followed.iter().any(|p| p.starts_with("sub/up/")));
+
+            // Links elsewhere are followed with -L.
+            let other = env::temp_dir().join(format!("monch-ls-walk-other-{}", process::id()));
+            let _ = fs::remove_dir_all(&other);
+            fs::create_dir_all(&other).unwrap();
+            fs::write(other.join("e.rs"), "").unwrap();
+            std::os::unix::fs::symlink(&other, dir.join("elsewhere")).unwrap();
+            assert_eq!(paths(&["-RL", ".", "e.rs"]), ["elsewhere/e.rs"]);
+            assert!(paths(&["-R", ".", "e.rs"]).is_empty());
+            fs::remove_dir_all(other).unwrap();
+        }
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn sorting() {
+        use filetime::{set_file_mtime, FileTime};
+
+        let dir = env::temp_dir().join(format!("monch-ls-sort-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("Dir")).unwrap();
+
+        // Each file has a different size, and was modified a day after the one before it.
+        let files = [("b.txt", 30), ("A.txt", 10), ("c.txt", 20), (".hidden", 40)];
+        for (i, (name, size)) in files.iter().enumerate() {
+            let path = dir.join(name);
+            fs::write(&path, vec![0; *size]).unwrap();
+            set_file_mtime(&path, FileTime::from_unix_time(i as i64 * 86_400, 0)).unwrap();
+        }
+        set_file_mtime(dir.join("Dir"), FileTime::from_unix_time(10 * 86_400, 0)).unwrap();
+
+        let names = |a: &[&str]| {
+            let args = Args::try_parse_from(["ls"].iter().chain(a)).unwrap();
+            let mut names = vec![];
+            walk(&args, &dir, |found| {
+                names.push(found.name);
+                Ok(())
+            })
+            .unwrap();
+            names
+        };
+
+        assert_eq!(names(&[]), ["A.txt", "b.txt", "c.txt", "Dir"]);
+        assert_eq!(names(&["--reverse"]), ["Dir", "c.txt", "b.txt", "A.txt"]);
+        assert_eq!(names(&["--dirs-first"]), ["Dir", "A.txt", "b.txt", "c.txt"]);
+        assert_eq!(
+            names(&["-r", "--dirs-first"]),
+            ["Dir", "c.txt", "b.txt", "A.txt"]
+        );
+        assert_eq!(
+            names(&["--sort", "mtime"]),
+            ["Dir", "c.txt", "A.txt", "b.txt"]
+        );
+        assert_eq!(names(&["--sort", "mtime", "-a"])[1], ".hidden");
+        assert_eq!(
+            names(&["--sort", "mtime", "-r"]),
+            ["b.txt", "A.txt", "c.txt", "Dir"]
+        );
+
+        // Directories have whatever size the filesystem gives them, so leave them out.
+        let by_size = names(&["--sort", "size", ".", "*.txt"]);
+        assert_eq!(by_size, ["b.txt", "c.txt", "A.txt"]);
+        assert_eq!(
+            names(&["--sort", "size", "-a", ".", "*[nt]"]),
+            [".hidden", "b.txt", "c.txt", "A.txt"]
+        );
+
+        let mut unsorted = names(&["--sort", "none"]);
+        unsorted.sort();
+        assert_eq!(unsorted, ["A.txt", "Dir", "b.txt", "c.txt"]);
+        assert!(Args::try_parse_from(["ls", "--sort", "color"]).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn globs_as_directories() {
+        let args = |a: &[&str]| {
+            let mut args = Args::try_parse_from(["ls"].iter().chain(a)).unwrap();
+            args.glob_from_directory();
+            (args.directory, args.pattern)
+        };
+        assert_eq!(args(&["*.rs"]), (None, Some("*.rs".to_string())));
+        assert_eq!(args(&["src"]), (Some("src".into()), None));
+        assert_eq!(
+            args(&["src", "*.rs"]),
+            (Some("src".into()), Some("*.rs".to_string()))
+        );
+
+        assert!(Args::try_parse_from(["ls", "--max-depth", "1"]).is_err());
+        let bad = Args::try_parse_from(["ls", ".", "a[b"]).unwrap();
+        assert!(walk(&bad, Path::new("."), |_| Ok(())).is_err());
+    }
 }
@@ -0,0 +1,220 @@
+use anyhow::{Context, Error};
+use clap::{ArgEnum, Parser};
+use monch_io::convert::json_to_cbor;
+use monch_io::{output_open, try_put, InputParser, Value};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+#[cfg(any(feature = "toml", feature = "yaml"))]
+use std::io::Read;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The file to read. Its extension says what format it's in: `.json`, `.cbor`, `.csv`,
+    /// `.toml`, or `.yaml`, if open was built with those features. Anything else is read as
+    /// lines of text.
+    file: PathBuf,
+
+    /// Read the file in this format, whatever its extension.
+    #[clap(long, arg_enum, value_name("FORMAT"))]
+    format: Option<Format>,
+
+    /// Write each element of a JSON or YAML array as an item of its own, rather than writing the
+    /// whole array as one item.
+    #[clap(long)]
+    explode: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgEnum)]
+enum Format {
+    Json,
+    Cbor,
+    Csv,
+    Toml,
+    Yaml,
+    Text,
+}
+
+impl Format {
+    /// Guess a file's format from its extension, or text if we don't recognize it or can't read
+    /// it.
+    fn from_path(path: &Path) -> Format {
+        let extension = path.extension().and_then(|e| e.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => Format::Json,
+            Some("cbor") => Format::Cbor,
+            Some("csv") => Format::Csv,
+            #[cfg(feature = "toml")]
+            Some("toml") => Format::Toml,
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => Format::Yaml,
+            _ => Format::Text,
+        }
+    }
+}
+
+/// Write one item. Returns `false` once nobody's reading our output.
+fn emit(item: &Value) -> Result<bool, Error> {
+    output_open(try_put!(item)).context("failed to write object")
+}
+
+/// Write a whole document, which is split into its elements if it's an array and `explode` is
+/// set.
+fn emit_document(document: Value, explode: bool) -> Result<bool, Error> {
+    match document {
+        Value::Array(elements) if explode => {
+            for element in &elements {
+                if !emit(element)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        document => emit(&document),
+    }
+}
+
+fn read_json(file: File, explode: bool) -> Result<(), Error> {
+    let json = serde_json::from_reader(BufReader::new(file))?;
+    emit_document(json_to_cbor(json), explode)?;
+    Ok(())
+}
+
+/// Pass on the items in a CBOR file, as they're read.
+fn read_cbor(file: File) -> Result<(), Error> {
+    for item in InputParser::<Value, _>::new(file) {
+        if !emit(&item?)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Write a map for each row of a CSV file, keyed by the header row. Every field is text.
+fn read_csv(file: File) -> Result<(), Error> {
+    let mut reader = csv::Reader::from_reader(file);
+    let keys: Vec<Value> = reader
+        .headers()?
+        .iter()
+        .map(|key| Value::Text(key.to_string()))
+        .collect();
+
+    for record in reader.records() {
+        let record = record?;
+        let fields = record.iter().map(|field| Value::Text(field.to_string()));
+        if !emit(&Value::Map(keys.iter().cloned().zip(fields).collect()))? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "toml")]
+fn read_toml(mut file: File) -> Result<(), Error> {
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    emit(&toml_to_cbor(toml::from_str(&text)?))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "toml"))]
+fn read_toml(_file: File) -> Result<(), Error> {
+    anyhow::bail!("open was built without the `toml` feature")
+}
+
+/// Convert a TOML value to CBOR. Dates and times become text, like `1979-05-27T07:32:00Z`.
+#[cfg(feature = "toml")]
+fn toml_to_cbor(toml: toml::Value) -> Value {
+    match toml {
+        toml::Value::String(s) => Value::Text(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::Float(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::Text(d.to_string()),
+        toml::Value::Array(a) => Value::Array(a.into_iter().map(toml_to_cbor).collect()),
+        toml::Value::Table(t) => Value::Map(
+            t.into_iter()
+                .map(|(k, v)| (Value::Text(k), toml_to_cbor(v)))
+                .collect(),
+        ),
+    }
+}
+
+/// Write each document in a YAML file.
+#[cfg(feature = "yaml")]
+fn read_yaml(mut file: File, explode: bool) -> Result<(), Error> {
+    use serde::Deserialize;
+
+    let mut text = String::new();
+    file.read_to_string(&mut text)?;
+    for document in serde_yaml::Deserializer::from_str(&text) {
+        if !emit_document(Value::deserialize(document)?, explode)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "yaml"))]
+fn read_yaml(_file: File, _explode: bool) -> Result<(), Error> {
+    anyhow::bail!("open was built without the `yaml` feature")
+}
+
+/// Write each line of a file as text, replacing any invalid UTF-8.
+fn read_lines(file: File) -> Result<(), Error> {
+    let mut reader = BufReader::new(file);
+    let mut line = vec![];
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(());
+        }
+
+        let text = line.strip_suffix(b"\n").unwrap_or(&line);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        if !emit(&Value::Text(String::from_utf8_lossy(text).into_owned()))? {
+            return Ok(());
+        }
+    }
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let format = args.format.unwrap_or_else(|| Format::from_path(&args.file));
+
+    let file = File::open(&args.file)
+        .with_context(|| format!("failed to open '{}'", args.file.display()))?;
+    let read = match format {
+        Format::Json => read_json(file, args.explode),
+        Format::Cbor => read_cbor(file),
+        Format::Csv => read_csv(file),
+        Format::Toml => read_toml(file),
+        Format::Yaml => read_yaml(file, args.explode),
+        Format::Text => read_lines(file),
+    };
+
+    read.with_context(|| format!("failed to read '{}'", args.file.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let format = |path: &str| Format::from_path(Path::new(path));
+        assert_eq!(format("deps.json"), Format::Json);
+        assert_eq!(format("dir/items.CBOR"), Format::Cbor);
+        assert_eq!(format("table.csv"), Format::Csv);
+        #[cfg(feature = "toml")]
+        assert_eq!(format("Cargo.toml"), Format::Toml);
+        #[cfg(feature = "yaml")]
+        assert_eq!(format("config.yml"), Format::Yaml);
+        #[cfg(feature = "yaml")]
+        assert_eq!(format("config.yaml"), Format::Yaml);
+        assert_eq!(format("notes.txt"), Format::Text);
+        assert_eq!(format("README"), Format::Text);
+        assert_eq!(format(".json"), Format::Text);
+    }
+}
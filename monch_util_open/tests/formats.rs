@@ -0,0 +1,147 @@
+use monch_io::convert::cbor_to_json;
+use monch_io::{cbor, Value};
+use monch_test::{scratch_dir, utility};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Run open in the fixtures directory.
+fn open(args: &[&str]) -> Output {
+    utility(env!("CARGO_BIN_EXE_open"))
+        .args(args)
+        .current_dir(fixtures())
+        .output()
+        .unwrap()
+}
+
+fn fixtures() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// The items open wrote, after checking it succeeded.
+fn items(args: &[&str]) -> Vec<Value> {
+    let output = open(args);
+    assert!(output.status.success(), "{:?}", output);
+    monch_test::items(&output)
+}
+
+#[test]
+fn json() {
+    // The file comes back out the same as it went in.
+    let found = items(&["deps.json"]);
+    assert_eq!(found.len(), 1);
+    let original: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(fixtures().join("deps.json")).unwrap()).unwrap();
+    assert_eq!(cbor_to_json(found[0].clone()).unwrap(), original);
+
+    // Arrays can be split into their elements, but other values can't.
+    let packages = serde_json::to_string(&original["packages"]).unwrap();
+    let dir = scratch_dir("open", "json");
+    fs::write(dir.join("packages.json"), packages).unwrap();
+    let path = dir.join("packages.json");
+    let found = items(&["--explode", path.to_str().unwrap()]);
+    assert_eq!(found.len(), 3);
+    assert_eq!(
+        found[1],
+        cbor!({"name" => "clap", "version" => "3.0.0-beta.5", "default" => false}).unwrap()
+    );
+    assert_eq!(items(&["--explode", "deps.json"]).len(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn cbor() {
+    assert_eq!(
+        items(&["items.cbor"]),
+        [
+            cbor!(1).unwrap(),
+            cbor!("two").unwrap(),
+            cbor!({"three" => 3}).unwrap()
+        ]
+    );
+}
+
+#[test]
+fn csv() {
+    assert_eq!(
+        items(&["rows.csv"]),
+        [
+            cbor!({"name" => "a.txt", "size" => "10", "note" => "plain"}).unwrap(),
+            cbor!({"name" => "b.txt", "size" => "", "note" => "has, a comma"}).unwrap(),
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "toml")]
+fn toml() {
+    assert_eq!(
+        items(&["config.toml"]),
+        [cbor!({
+            "limits" => {
+                "items" => 100,
+                "names" => ["a", "b"],
+                "ratio" => 0.5,
+                "strict" => true,
+            },
+            "released" => "1979-05-27T07:32:00Z",
+            "title" => "monch",
+        })
+        .unwrap()]
+    );
+}
+
+#[test]
+#[cfg(feature = "yaml")]
+fn yaml() {
+    let first = cbor!({"name" => "first", "tags" => ["a", "b"]}).unwrap();
+    let second = cbor!([1, "two", {"nested" => {"ok" => true}}]).unwrap();
+    assert_eq!(items(&["docs.yaml"]), [first.clone(), second]);
+
+    let found = items(&["--explode", "docs.yaml"]);
+    assert_eq!(found.len(), 4);
+    assert_eq!(found[0], first);
+    assert_eq!(found[2], cbor!("two").unwrap());
+}
+
+#[test]
+#[cfg(not(feature = "toml"))]
+fn toml_without_feature() {
+    // It's read as text, like any other file we can't parse.
+    let found = items(&["config.toml"]);
+    assert_eq!(found.len(), 8);
+    assert_eq!(found[0], cbor!("title = \"monch\"").unwrap());
+
+    // Unless TOML is asked for, which fails.
+    assert!(!open(&["--format", "toml", "config.toml"]).status.success());
+}
+
+#[test]
+#[cfg(not(feature = "yaml"))]
+fn yaml_without_feature() {
+    let found = items(&["docs.yaml"]);
+    assert_eq!(found[0], cbor!("name: first").unwrap());
+    assert!(!open(&["--format", "yaml", "docs.yaml"]).status.success());
+}
+
+#[test]
+fn text() {
+    let lines = [
+        cbor!("first line").unwrap(),
+        cbor!("second line").unwrap(),
+        cbor!("").unwrap(),
+        cbor!("last, without a newline").unwrap(),
+    ];
+    assert_eq!(items(&["notes.txt"]), lines);
+
+    // The extension can be overridden.
+    assert_eq!(items(&["--format", "text", "rows.csv"]).len(), 3);
+}
+
+#[test]
+fn failures() {
+    assert!(!open(&["missing.json"]).status.success());
+    assert!(!open(&["--format", "json", "notes.txt"]).status.success());
+    assert!(!open(&["--format", "csv", "notes.txt"]).status.success());
+    assert!(!open(&["--format", "xml", "notes.txt"]).status.success());
+}
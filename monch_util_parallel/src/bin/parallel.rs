@@ -0,0 +1,349 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use monch_io::convert::cbor_to_text;
+use monch_io::{flush, input_stream_auto, log, output_open, try_put, DataPath, Value};
+use std::collections::BTreeMap;
+use std::io;
+use std::process::{self, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// How many commands to run at once. Defaults to the number of CPUs.
+    #[clap(short('j'), long, value_name("N"), parse(try_from_str = parse_jobs))]
+    jobs: Option<usize>,
+
+    /// Use this field of each item as its argument, like `.name`, rather than the whole item.
+    #[clap(short('f'), long, value_name("PATH"))]
+    field: Option<DataPath>,
+
+    /// Pass arguments that aren't text as JSON, like `{"a":1}`, rather than skipping them.
+    #[clap(long)]
+    json_arg: bool,
+
+    /// Stop starting commands once one fails. Commands that are already running are finished.
+    #[clap(long)]
+    halt_on_error: bool,
+
+    /// Write results in the order their items were read, rather than as each command finishes.
+    #[clap(long)]
+    keep_order: bool,
+
+    /// The command to run for each item, after `--`. Each `{}` in it is replaced by the item's
+    /// argument. Without any, the argument is added to the end.
+    #[clap(last(true), required(true), value_name("COMMAND"))]
+    command: Vec<String>,
+}
+
+/// Parse a number of jobs, which can't be zero.
+fn parse_jobs(jobs: &str) -> Result<usize, String> {
+    match jobs.parse() {
+        Ok(0) => Err("there must be at least one job".to_string()),
+        Ok(jobs) => Ok(jobs),
+        Err(_) => Err(format!("'{}' isn't a number of jobs", jobs)),
+    }
+}
+
+/// An item to run the command for.
+struct Job {
+    /// Where the item was in the input, among the items that were run.
+    index: u64,
+
+    /// The item, as it was read.
+    input: Value,
+
+    /// The argument to run the command with.
+    arg: String,
+}
+
+/// What happened when the command was run for an item.
+struct Outcome {
+    index: u64,
+    input: Value,
+    exit_code: i32,
+    stdout: String,
+    duration_ms: u64,
+}
+
+impl Outcome {
+    fn failed(&self) -> bool {
+        self.exit_code != 0
+    }
+}
+
+/// Fill in a command for an argument: each `{}` in it is replaced by the argument, or if there
+/// aren't any, the argument is added to the end.
+fn substitute(command: &[String], arg: &str) -> Vec<String> {
+    if command.iter().any(|part| part.contains("{}")) {
+        command.iter().map(|part| part.replace("{}", arg)).collect()
+    } else {
+        let mut command = command.to_vec();
+        command.push(arg.to_string());
+        command
+    }
+}
+
+/// Find the argument for an item, or `None` if it should be skipped.
+fn argument(args: &Args, item: &Value) -> Result<Option<String>, Error> {
+    let value = match &args.field {
+        Some(path) => match path.get_ref(item) {
+            Some(value) => value,
+            None => {
+                log!("parallel: skipping an item with nothing at {}", path);
+                return Ok(None);
+            }
+        },
+        None => item,
+    };
+
+    match value {
+        Value::Text(text) => Ok(Some(text.clone())),
+        value if args.json_arg => Ok(Some(cbor_to_text(value.clone())?)),
+        _ => {
+            log!("parallel: skipping an argument that isn't text (use --json-arg to pass it as JSON)");
+            Ok(None)
+        }
+    }
+}
+
+/// Run the command for one item.
+fn run(command: &[String], job: Job) -> Outcome {
+    let command = substitute(command, &job.arg);
+    let (program, program_args) = command.split_first().expect("command is empty");
+
+    let started = Instant::now();
+    let output = Command::new(program)
+        .args(program_args)
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output();
+    let duration_ms = started.elapsed().as_millis() as u64;
+
+    let (exit_code, stdout) = match output {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+            (exit_code(output.status), stdout)
+        }
+        Err(e) => {
+            log!("parallel: {}: {}", program, e);
+            // Like a shell: 127 if there's no such program, 126 if it couldn't be run.
+            let code = if e.kind() == io::ErrorKind::NotFound {
+                127
+            } else {
+                126
+            };
+            (code, String::new())
+        }
+    };
+
+    Outcome {
+        index: job.index,
+        input: job.input,
+        exit_code,
+        stdout,
+        duration_ms,
+    }
+}
+
+/// The status to report for a command: its exit code, or 128 plus the signal that killed it, like
+/// a shell reports.
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
+}
+
+/// Read items from stdin, handing a job for each to the workers, until the input runs out or we're
+/// told to stop.
+fn feed(args: &Args, jobs: SyncSender<Job>, stop: &AtomicBool) -> Result<(), Error> {
+    let mut index = 0;
+    for item in input_stream_auto::<Value>() {
+        let item = item.context("failed to read object from stdin")?;
+        let arg = match argument(args, &item)? {
+            Some(arg) => arg,
+            None => continue,
+        };
+
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let job = Job {
+            index,
+            input: item,
+            arg,
+        };
+        if jobs.send(job).is_err() {
+            break;
+        }
+        index += 1;
+    }
+
+    Ok(())
+}
+
+/// Run jobs until there are no more, sending back what happened. With `--halt-on-error`, a
+/// failure stops every worker from starting any more.
+fn work(args: &Args, jobs: &Mutex<Receiver<Job>>, outcomes: Sender<Outcome>, stop: &AtomicBool) {
+    loop {
+        // Only hold the lock while waiting, so other workers can take jobs while this one runs.
+        let job = jobs.lock().expect("a worker panicked").recv();
+        let job = match job {
+            Ok(job) => job,
+            Err(_) => return,
+        };
+        if stop.load(Ordering::SeqCst) {
+            continue;
+        }
+
+        let outcome = run(&args.command, job);
+        if outcome.failed() && args.halt_on_error {
+            stop.store(true, Ordering::SeqCst);
+        }
+        if outcomes.send(outcome).is_err() {
+            return;
+        }
+    }
+}
+
+/// Write the result for an item. Returns `false` once nobody's reading our output.
+fn emit(outcome: Outcome) -> Result<bool, Error> {
+    let written = try_put!(
+        input: outcome.input,
+        exit_code: outcome.exit_code,
+        stdout: outcome.stdout,
+        duration_ms: outcome.duration_ms
+    );
+    // Commands can take a while, so don't hold this result back while waiting for the next.
+    output_open(written.and_then(|_| flush())).context("failed to write result")
+}
+
+fn main() -> Result<(), Error> {
+    let args = Arc::new(Args::parse());
+    let workers = args.jobs.unwrap_or_else(num_cpus::get);
+
+    // Jobs are handed over one at a time, so we only read items as fast as they're run.
+    let (job_sender, jobs) = mpsc::sync_channel::<Job>(0);
+    let jobs = Arc::new(Mutex::new(jobs));
+    let (outcome_sender, outcomes) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+
+    for _ in 0..workers {
+        let (args, jobs, stop) = (args.clone(), jobs.clone(), stop.clone());
+        let outcomes = outcome_sender.clone();
+        thread::spawn(move || work(&args, &jobs, outcomes, &stop));
+    }
+    drop(outcome_sender);
+
+    let feeder = {
+        let args = args.clone();
+        thread::spawn(move || feed(&args, job_sender, &stop))
+    };
+
+    // With --keep-order, results that finish early wait here until it's their turn.
+    let mut waiting = BTreeMap::new();
+    let mut next = 0;
+    let mut any_failed = false;
+    for outcome in outcomes {
+        any_failed |= outcome.failed();
+        let ready = if args.keep_order {
+            waiting.insert(outcome.index, outcome);
+            let mut ready = vec![];
+            while let Some(outcome) = waiting.remove(&next) {
+                ready.push(outcome);
+                next += 1;
+            }
+            ready
+        } else {
+            vec![outcome]
+        };
+
+        for outcome in ready {
+            // If nobody's reading our output anymore, we're done.
+            if !emit(outcome)? {
+                process::exit(if any_failed { 1 } else { 0 });
+            }
+        }
+    }
+
+    // After halting, some jobs are never run, so there can be gaps in the order.
+    for outcome in waiting.into_values() {
+        if !emit(outcome)? {
+            break;
+        }
+    }
+
+    feeder.join().expect("the feeder panicked")?;
+    if any_failed {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monch_io::cbor;
+
+    fn strings(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|p| p.to_string()).collect()
+    }
+
+    #[test]
+    fn substitutions() {
+        assert_eq!(
+            substitute(&strings(&["gzip", "{}"]), "a b.txt"),
+            strings(&["gzip", "a b.txt"])
+        );
+        assert_eq!(
+            substitute(&strings(&["cp", "{}", "backup/{}.bak"]), "x"),
+            strings(&["cp", "x", "backup/x.bak"])
+        );
+        assert_eq!(
+            substitute(&strings(&["echo"]), "{}"),
+            strings(&["echo", "{}"])
+        );
+    }
+
+    #[test]
+    fn arguments() {
+        let args = |extra: &[&str]| {
+            let mut argv = vec!["parallel"];
+            argv.extend_from_slice(extra);
+            argv.extend_from_slice(&["--", "echo"]);
+            Args::parse_from(argv)
+        };
+        let item = cbor!({"name" => "a.txt", "size" => 3}).unwrap();
+
+        assert_eq!(argument(&args(&[]), &item).unwrap(), None);
+        assert_eq!(
+            argument(&args(&["--json-arg"]), &item).unwrap().unwrap(),
+            r#"{"name":"a.txt","size":3}"#
+        );
+        assert_eq!(
+            argument(&args(&["-f", ".name"]), &item).unwrap().unwrap(),
+            "a.txt"
+        );
+        assert_eq!(argument(&args(&["-f", ".size"]), &item).unwrap(), None);
+        assert_eq!(
+            argument(&args(&["-f", ".size", "--json-arg"]), &item)
+                .unwrap()
+                .unwrap(),
+            "3"
+        );
+        assert_eq!(argument(&args(&["-f", ".missing"]), &item).unwrap(), None);
+    }
+}
@@ -0,0 +1,162 @@
+use monch_io::{cbor, Value};
+use monch_test::{field, items, run_with_items, scratch_dir, utility};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Make a directory with a slow command, which sleeps for as long as its argument says, and
+/// prints how many copies of itself were running when it started.
+fn dir(name: &str) -> PathBuf {
+    let dir = scratch_dir("parallel", name);
+    fs::create_dir(dir.join("running")).unwrap();
+    fs::write(
+        dir.join("slow.sh"),
+        concat!(
+            "touch running/$$\n",
+            "echo $(ls running | wc -l)\n",
+            "sleep $1\n",
+            "rm running/$$\n",
+        ),
+    )
+    .unwrap();
+    dir
+}
+
+/// Run parallel in a directory, with some objects on stdin.
+fn parallel(dir: &Path, args: &[&str], stdin: &[Value]) -> Output {
+    let mut parallel = utility(env!("CARGO_BIN_EXE_parallel"));
+    run_with_items(parallel.args(args).current_dir(dir), stdin)
+}
+
+fn texts(texts: &[&str]) -> Vec<Value> {
+    texts.iter().map(|t| Value::Text(t.to_string())).collect()
+}
+
+/// The inputs of each result, in the order they were written.
+fn inputs(results: &[Value]) -> Vec<Value> {
+    results.iter().map(|r| field(r, "input").clone()).collect()
+}
+
+#[test]
+fn concurrency() {
+    let dir = dir("concurrency");
+    let stdin = texts(&["0.2"; 6]);
+
+    for jobs in ["1", "2"] {
+        let output = parallel(&dir, &["-j", jobs, "--", "sh", "slow.sh"], &stdin);
+        assert!(output.status.success(), "{:?}", output);
+
+        let results = items(&output);
+        assert_eq!(results.len(), 6);
+        for result in &results {
+            assert_eq!(field(result, "exit_code"), &Value::from(0));
+            let running = match field(result, "stdout") {
+                Value::Text(stdout) => stdout.trim().parse::<usize>().unwrap(),
+                other => panic!("stdout isn't text: {:?}", other),
+            };
+            assert!(running <= jobs.parse().unwrap(), "{:?}", results);
+        }
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn order() {
+    let dir = dir("order");
+    let stdin = texts(&["0.6", "0.4", "0.2", "0"]);
+
+    // Results are written as they finish, which is fastest first.
+    let output = parallel(&dir, &["-j", "4", "--", "sh", "slow.sh"], &stdin);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(inputs(&items(&output)), texts(&["0", "0.2", "0.4", "0.6"]));
+
+    let output = parallel(
+        &dir,
+        &["-j", "4", "--keep-order", "--", "sh", "slow.sh"],
+        &stdin,
+    );
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(inputs(&items(&output)), stdin);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failures() {
+    let dir = dir("failures");
+    let stdin = texts(&["0", "3", "0", "0"]);
+
+    // Every item is run, but a failure is passed on.
+    let output = parallel(&dir, &["-j", "1", "--", "sh", "-c", "exit {}"], &stdin);
+    assert_eq!(output.status.code(), Some(1));
+    let codes: Vec<_> = items(&output)
+        .iter()
+        .map(|r| field(r, "exit_code").clone())
+        .collect();
+    assert_eq!(codes, [0, 3, 0, 0].map(Value::from));
+
+    // Unless it says to stop.
+    let output = parallel(
+        &dir,
+        &["-j", "1", "--halt-on-error", "--", "sh", "-c", "exit {}"],
+        &stdin,
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(inputs(&items(&output)), texts(&["0", "3"]));
+
+    // Programs that can't be run fail like they would in a shell.
+    let output = parallel(&dir, &["--", "monch-no-such-program"], &texts(&["a"]));
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(field(&items(&output)[0], "exit_code"), &Value::from(127));
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn arguments() {
+    let dir = dir("arguments");
+    let stdin = [
+        cbor!({"name" => "a b"}).unwrap(),
+        cbor!({"name" => 2}).unwrap(),
+        cbor!({"other" => "c"}).unwrap(),
+    ];
+
+    // Items that aren't text are skipped, with a warning.
+    let output = parallel(&dir, &["-j", "1", "-f", ".name", "--", "echo"], &stdin);
+    assert!(output.status.success(), "{:?}", output);
+    let found = items(&output);
+    assert_eq!(inputs(&found), [stdin[0].clone()]);
+    assert_eq!(field(&found[0], "stdout"), &Value::Text("a b\n".into()));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--json-arg"), "{}", stderr);
+
+    // Or passed as JSON.
+    let output = parallel(
+        &dir,
+        &[
+            "-j",
+            "1",
+            "--keep-order",
+            "--json-arg",
+            "--",
+            "echo",
+            "[{}]",
+        ],
+        &stdin,
+    );
+    let stdouts: Vec<_> = items(&output)
+        .iter()
+        .map(|r| field(r, "stdout").clone())
+        .collect();
+    assert_eq!(
+        stdouts,
+        texts(&[
+            "[{\"name\":\"a b\"}]\n",
+            "[{\"name\":2}]\n",
+            "[{\"other\":\"c\"}]\n"
+        ])
+    );
+
+    assert!(!parallel(&dir, &["-j", "0", "--", "echo"], &stdin)
+        .status
+        .success());
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,229 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use monch_io::metadata::rfc3339;
+use monch_io::units::parse_duration;
+use monch_io::{flush, output_open, try_put, write_header, StreamHeader};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::thread;
+use std::time::{Duration, UNIX_EPOCH};
+use sysinfo::{
+    PidExt, Process, ProcessExt, ProcessRefreshKind, RefreshKind, System, SystemExt, UserExt,
+};
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Only list processes run by this user.
+    #[clap(long, value_name("NAME"))]
+    user: Option<String>,
+
+    /// Put each process's children in a `children` array inside it, rather than listing every
+    /// process on its own.
+    #[clap(long)]
+    tree: bool,
+
+    /// List the processes again after each interval, like `2s`, until nobody's reading.
+    #[clap(long, value_name("INTERVAL"), parse(try_from_str = parse_duration))]
+    watch: Option<Duration>,
+}
+
+/// Everything we say about a process. Fields are null where they can't be found out.
+#[derive(Debug, Serialize)]
+struct ProcessInfo {
+    pid: u32,
+    ppid: Option<u32>,
+    name: String,
+
+    /// The arguments it was started with, starting with the program.
+    cmd: Vec<String>,
+
+    /// How much CPU time it used since we last looked, where 100 is all of one core.
+    cpu_percent: f32,
+
+    /// How much memory it's using, not counting swap.
+    memory_bytes: u64,
+
+    user: Option<String>,
+
+    /// When it started, as an RFC 3339 timestamp in UTC, to the second, like
+    /// `2021-11-05T14:03:09Z`.
+    start_time: Option<String>,
+
+    /// The processes it started, with `--tree`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    children: Option<Vec<ProcessInfo>>,
+}
+
+/// The fields of a [`ProcessInfo`], in order, for the stream header.
+const FIELDS: &[&str] = &[
+    "pid",
+    "ppid",
+    "name",
+    "cmd",
+    "cpu_percent",
+    "memory_bytes",
+    "user",
+    "start_time",
+];
+
+/// How long to wait between the first two looks at each process, to see how much CPU they use.
+const CPU_SAMPLE: Duration = Duration::from_millis(200);
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    let header = StreamHeader::new("process", FIELDS);
+    write_header(&header).context("failed to write stream header")?;
+
+    // CPU usage is measured between refreshes, so the first one can't say anything about it.
+    let refresh = ProcessRefreshKind::everything();
+    let mut sys =
+        System::new_with_specifics(RefreshKind::new().with_processes(refresh).with_users_list());
+    thread::sleep(CPU_SAMPLE);
+    sys.refresh_processes_specifics(refresh);
+
+    loop {
+        for process in snapshot(&args, &sys) {
+            // If nobody's reading our output anymore, we're done.
+            if !output_open(try_put!(@obj process)).context("failed to write process")? {
+                return Ok(());
+            }
+        }
+
+        match args.watch {
+            Some(interval) => {
+                // Don't hold this snapshot back while waiting for the next.
+                if !output_open(flush()).context("failed to write process")? {
+                    return Ok(());
+                }
+                thread::sleep(interval)
+            }
+            None => return Ok(()),
+        }
+        sys.refresh_processes_specifics(refresh);
+    }
+}
+
+/// Describe every process we were asked about, in order of their PIDs.
+fn snapshot(args: &Args, sys: &System) -> Vec<ProcessInfo> {
+    let mut processes: Vec<ProcessInfo> = sys
+        .processes()
+        .values()
+        .map(|process| describe(sys, process))
+        .filter(|process| match &args.user {
+            Some(user) => process.user.as_ref() == Some(user),
+            None => true,
+        })
+        .collect();
+    processes.sort_by_key(|process| process.pid);
+
+    if args.tree {
+        build_tree(processes)
+    } else {
+        processes
+    }
+}
+
+/// Describe a process, as far as this platform lets us.
+fn describe(sys: &System, process: &Process) -> ProcessInfo {
+    let user = process
+        .user_id()
+        .and_then(|uid| sys.get_user_by_id(uid))
+        .map(|user| user.name().to_string());
+
+    // Platforms that don't know when a process started say it was at the epoch.
+    let start_time = match process.start_time() {
+        0 => None,
+        seconds => Some(rfc3339(UNIX_EPOCH + Duration::from_secs(seconds))),
+    };
+
+    ProcessInfo {
+        pid: process.pid().as_u32(),
+        ppid: process.parent().map(|pid| pid.as_u32()),
+        name: process.name().to_string(),
+        cmd: process.cmd().to_vec(),
+        cpu_percent: process.cpu_usage(),
+        memory_bytes: process.memory().saturating_mul(1024),
+        user,
+        start_time,
+        children: None,
+    }
+}
+
+/// Put each process inside its parent, leaving the ones whose parents aren't in the list at the
+/// top. Children stay in the order they were given in.
+fn build_tree(processes: Vec<ProcessInfo>) -> Vec<ProcessInfo> {
+    let pids: HashSet<u32> = processes.iter().map(|process| process.pid).collect();
+
+    let mut roots = vec![];
+    let mut children: HashMap<u32, Vec<ProcessInfo>> = HashMap::new();
+    for process in processes {
+        let parent = process.ppid.filter(|ppid| pids.contains(ppid));
+        match parent {
+            Some(ppid) if ppid != process.pid => children.entry(ppid).or_default().push(process),
+            _ => roots.push(process),
+        }
+    }
+
+    fn adopt(process: &mut ProcessInfo, children: &mut HashMap<u32, Vec<ProcessInfo>>) {
+        let mut own = children.remove(&process.pid).unwrap_or_default();
+        for child in &mut own {
+            adopt(child, children);
+        }
+        process.children = Some(own);
+    }
+
+    for root in &mut roots {
+        adopt(root, &mut children);
+    }
+    roots
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, ppid: Option<u32>) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            ppid,
+            name: format!("p{}", pid),
+            cmd: vec![],
+            cpu_percent: 0.0,
+            memory_bytes: 0,
+            user: None,
+            start_time: None,
+            children: None,
+        }
+    }
+
+    /// Describe a tree as `pid(child, child)`, to compare it more easily.
+    fn shape(processes: &[ProcessInfo]) -> String {
+        let shapes: Vec<String> = processes
+            .iter()
+            .map(|p| match &p.children {
+                Some(children) if !children.is_empty() => format!("{}({})", p.pid, shape(children)),
+                _ => p.pid.to_string(),
+            })
+            .collect();
+        shapes.join(", ")
+    }
+
+    #[test]
+    fn trees() {
+        let processes = vec![
+            process(1, None),
+            process(2, Some(1)),
+            process(3, Some(2)),
+            process(4, Some(1)),
+            process(5, Some(3)),
+            process(9, Some(8)),
+            process(10, Some(10)),
+        ];
+        assert_eq!(shape(&build_tree(processes)), "1(2(3(5)), 4), 9, 10");
+
+        // Every process in the tree says what its children are, even if it has none.
+        let tree = build_tree(vec![process(1, None)]);
+        assert_eq!(tree[0].children.as_ref().map(Vec::len), Some(0));
+    }
+}
@@ -0,0 +1,77 @@
+use monch_io::Value;
+use monch_test::{field, find_field, items, utility};
+use std::process::Stdio;
+
+fn pid(process: &Value) -> u32 {
+    let pid = field(process, "pid").as_integer().unwrap();
+    u32::try_from(pid).unwrap()
+}
+
+/// Run ps, returning its own PID, and the processes it listed.
+fn ps(args: &[&str]) -> (u32, Vec<Value>) {
+    let child = utility(env!("CARGO_BIN_EXE_ps"))
+        .args(args)
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let own_pid = child.id();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    (own_pid, items(&output))
+}
+
+/// Find a process in a tree, looking through each one's children.
+fn find_in_tree(processes: &[Value], pid_wanted: u32) -> Option<&Value> {
+    let mut stack: Vec<&Value> = processes.iter().collect();
+    while let Some(process) = stack.pop() {
+        if pid(process) == pid_wanted {
+            return Some(process);
+        }
+        if let Some(Value::Array(children)) = find_field(process, "children") {
+            stack.extend(children);
+        }
+    }
+    None
+}
+
+#[test]
+fn lists_itself() {
+    let (own_pid, processes) = ps(&[]);
+    let me = processes.iter().find(|p| pid(p) == own_pid).unwrap();
+
+    let name = field(me, "name").as_text().unwrap();
+    assert!(name.starts_with("ps"), "{}", name);
+    for name in [
+        "ppid",
+        "cmd",
+        "cpu_percent",
+        "memory_bytes",
+        "user",
+        "start_time",
+    ] {
+        assert!(find_field(me, name).is_some(), "missing {}", name);
+    }
+    assert!(find_field(me, "children").is_none());
+
+    // The list is in order of PID.
+    let pids: Vec<u32> = processes.iter().map(pid).collect();
+    let mut sorted = pids.clone();
+    sorted.sort_unstable();
+    assert_eq!(pids, sorted);
+}
+
+#[test]
+fn tree_has_itself() {
+    let (own_pid, processes) = ps(&["--tree"]);
+    let me = find_in_tree(&processes, own_pid).unwrap();
+    assert_eq!(find_field(me, "children"), Some(&Value::Array(vec![])));
+
+    // It was started by this test, so it's not at the top.
+    assert!(processes.iter().all(|p| pid(p) != own_pid));
+}
+
+#[test]
+fn filters_by_user() {
+    let (_, processes) = ps(&["--user", "no-such-user-here"]);
+    assert!(processes.is_empty());
+}
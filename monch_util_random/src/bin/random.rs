@@ -0,0 +1,168 @@
+use anyhow::{bail, Context, Error};
+use clap::{Parser, Subcommand};
+use monch_io::{input_stream_auto, log, output_open, try_put, Value};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::process;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// Start from this seed, so the same command makes the same choices every time.
+    #[clap(long, value_name("SEED"), global(true))]
+    seed: Option<u64>,
+
+    #[clap(subcommand)]
+    kind: Kind,
+}
+
+#[derive(Debug, Subcommand)]
+enum Kind {
+    /// Write integers between two bounds, including the bounds themselves
+    Int {
+        #[clap(allow_hyphen_values(true))]
+        low: i64,
+
+        #[clap(allow_hyphen_values(true))]
+        high: i64,
+
+        /// How many integers to write.
+        #[clap(short('n'), long, value_name("N"), default_value("1"))]
+        count: u64,
+    },
+
+    /// Write items of bytes, each this many bytes long
+    Bytes {
+        length: usize,
+
+        /// How many items to write.
+        #[clap(short('n'), long, value_name("N"), default_value("1"))]
+        count: u64,
+    },
+
+    /// Choose items from stdin, each as likely to be chosen as any other
+    Pick {
+        /// How many items to choose. If there are fewer than this, they're all chosen.
+        #[clap(short('n'), long, value_name("N"), default_value("1"))]
+        count: usize,
+    },
+}
+
+/// Make a generator, from a seed if there is one, or from the system's entropy otherwise.
+///
+/// This is ChaCha8 rather than rand's `StdRng`, whose algorithm can change between versions of
+/// rand: a seed has to make the same choices in every build.
+fn rng(seed: Option<u64>) -> ChaCha8Rng {
+    match seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    }
+}
+
+/// Choose up to `count` items, each as likely as any other, while only holding on to `count` of
+/// them at a time. This is reservoir sampling: the `i`th item replaces one of those held with
+/// probability `count / i`.
+fn pick<T>(
+    rng: &mut impl Rng,
+    items: impl IntoIterator<Item = Result<T, Error>>,
+    count: usize,
+) -> Result<Vec<T>, Error> {
+    let mut chosen = Vec::with_capacity(count);
+    for (seen, item) in items.into_iter().enumerate() {
+        let item = item?;
+        if chosen.len() < count {
+            chosen.push(item);
+        } else {
+            let replaced = rng.gen_range(0..=seen);
+            if replaced < count {
+                chosen[replaced] = item;
+            }
+        }
+    }
+    Ok(chosen)
+}
+
+/// Write `count` items, made by `make`.
+fn write_many(count: u64, mut make: impl FnMut() -> Value) -> Result<(), Error> {
+    for _ in 0..count {
+        // If nobody's reading our output anymore, we're done.
+        if !output_open(try_put!(&make())).context("failed to write item")? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let mut rng = rng(args.seed);
+
+    match args.kind {
+        Kind::Int { low, high, count } => {
+            if low > high {
+                bail!("the low bound, {}, is above the high bound, {}", low, high);
+            }
+            write_many(count, || Value::Integer(rng.gen_range(low..=high).into()))
+        }
+
+        Kind::Bytes { length, count } => write_many(count, || {
+            let mut bytes = vec![0; length];
+            rng.fill(&mut bytes[..]);
+            Value::Bytes(bytes)
+        }),
+
+        Kind::Pick { count } => {
+            let items = input_stream_auto::<Value>()
+                .map(|item| item.context("failed to read object from stdin"));
+            let chosen = pick(&mut rng, items, count)?;
+            if chosen.is_empty() && count > 0 {
+                log!("random: there was nothing on stdin to pick from");
+                process::exit(1);
+            }
+            write_many(chosen.len() as u64, {
+                let mut chosen = chosen.into_iter();
+                move || chosen.next().expect("wrote too many items")
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(count: u32) -> impl Iterator<Item = Result<u32, Error>> {
+        (0..count).map(Ok)
+    }
+
+    #[test]
+    fn picks() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        assert_eq!(pick(&mut rng, items(0), 1).unwrap(), Vec::<u32>::new());
+        assert_eq!(pick(&mut rng, items(3), 5).unwrap(), [0, 1, 2]);
+        assert_eq!(pick(&mut rng, items(3), 0).unwrap(), Vec::<u32>::new());
+
+        let chosen = pick(&mut rng, items(100), 10).unwrap();
+        let mut distinct = chosen.clone();
+        distinct.sort_unstable();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 10);
+        assert!(chosen.iter().all(|item| *item < 100));
+
+        // Every item gets chosen sometimes, wherever it is.
+        let mut chosen = [false; 5];
+        for _ in 0..200 {
+            chosen[pick(&mut rng, items(5), 1).unwrap()[0] as usize] = true;
+        }
+        assert_eq!(chosen, [true; 5]);
+
+        let failing = vec![Ok(1), Err(anyhow::anyhow!("broken"))];
+        assert!(pick(&mut rng, failing, 1).is_err());
+    }
+
+    #[test]
+    fn seeds() {
+        let picked = |seed| pick(&mut rng(Some(seed)), items(1000), 3).unwrap();
+        assert_eq!(picked(7), picked(7));
+        assert_ne!(picked(7), picked(8));
+    }
+}
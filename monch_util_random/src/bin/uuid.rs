@@ -0,0 +1,73 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use monch_io::{output_open, try_put};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// How many UUIDs to write.
+    #[clap(short('n'), long, value_name("N"), default_value("1"))]
+    count: u64,
+
+    /// Start from this seed, so the same command writes the same UUIDs every time.
+    #[clap(long, value_name("SEED"))]
+    seed: Option<u64>,
+}
+
+/// Make a random (version 4) UUID, like `f81d4fae-7dec-41d0-a765-00a0c91e6bf6`.
+fn uuid_v4(rng: &mut impl Rng) -> String {
+    let mut bytes: [u8; 16] = rng.gen();
+
+    // Mark it as version 4, and as the variant described in RFC 4122.
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    // Like random, this uses ChaCha8 so that a seed makes the same UUIDs whatever version of rand
+    // it's built with.
+    let mut rng = match args.seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    };
+
+    for _ in 0..args.count {
+        // If nobody's reading our output anymore, we're done.
+        if !output_open(try_put!(&uuid_v4(&mut rng))).context("failed to write UUID")? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        for _ in 0..100 {
+            let uuid = uuid_v4(&mut rng);
+            let groups: Vec<_> = uuid.split('-').map(str::len).collect();
+            assert_eq!(groups, [8, 4, 4, 4, 12], "{}", uuid);
+            assert!(uuid
+                .chars()
+                .all(|c| c == '-' || c.is_ascii_digit() || ('a'..='f').contains(&c)));
+            assert_eq!(&uuid[14..15], "4");
+            assert!(matches!(&uuid[19..20], "8" | "9" | "a" | "b"), "{}", uuid);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use monch_io::Value;
+use monch_test::{items, run_with_items, utility};
+use std::collections::BTreeSet;
+use std::process::Output;
+
+/// Run one of the generators, with some objects on stdin. Only `random pick` reads them.
+fn run(exe: &str, args: &[&str], stdin: &[Value]) -> Output {
+    run_with_items(utility(exe).args(args), stdin)
+}
+
+/// Run random, returning the items it wrote after checking it succeeded.
+fn random(args: &[&str], stdin: &[Value]) -> Vec<Value> {
+    let output = run(env!("CARGO_BIN_EXE_random"), args, stdin);
+    assert!(output.status.success(), "{:?}", output);
+    items(&output)
+}
+
+/// Run uuid, returning the UUIDs it wrote.
+fn uuid(args: &[&str]) -> Vec<String> {
+    let output = run(env!("CARGO_BIN_EXE_uuid"), args, &[]);
+    assert!(output.status.success(), "{:?}", output);
+    items(&output)
+        .into_iter()
+        .map(|item| item.into_text().unwrap())
+        .collect()
+}
+
+fn integers(items: &[Value]) -> Vec<i64> {
+    items
+        .iter()
+        .map(|item| i64::try_from(item.as_integer().unwrap()).unwrap())
+        .collect()
+}
+
+#[test]
+fn ints() {
+    let found = integers(&random(&["int", "1", "6", "-n", "1000"], &[]));
+    assert_eq!(found.len(), 1000);
+    assert!(found.iter().all(|n| (1..=6).contains(n)));
+    assert_eq!(found.iter().collect::<BTreeSet<_>>().len(), 6);
+
+    let found = integers(&random(&["int", "-3", "-3", "-n", "5"], &[]));
+    assert_eq!(found, [-3; 5]);
+    assert_eq!(random(&["int", "1", "100"], &[]).len(), 1);
+
+    let output = run(env!("CARGO_BIN_EXE_random"), &["int", "10", "1"], &[]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn bytes() {
+    let found = random(&["bytes", "32", "-n", "3"], &[]);
+    assert_eq!(found.len(), 3);
+    for item in &found {
+        assert_eq!(item.as_bytes().unwrap().len(), 32);
+    }
+    assert_ne!(found[0], found[1]);
+    assert_eq!(random(&["bytes", "0"], &[]), [Value::Bytes(vec![])]);
+}
+
+#[test]
+fn picks() {
+    let inputs: Vec<Value> = (0..50)
+        .map(|n| Value::Text(format!("item {}", n)))
+        .collect();
+    let found = random(&["pick"], &inputs);
+    assert_eq!(found.len(), 1);
+    assert!(inputs.contains(&found[0]));
+
+    let found = random(&["pick", "-n", "10"], &inputs);
+    assert_eq!(found.len(), 10);
+    assert!(found.iter().all(|item| inputs.contains(item)));
+    let distinct = found
+        .iter()
+        .enumerate()
+        .all(|(i, item)| !found[..i].contains(item));
+    assert!(distinct, "{:?}", found);
+
+    assert_eq!(random(&["pick", "-n", "100"], &inputs).len(), 50);
+
+    let output = run(env!("CARGO_BIN_EXE_random"), &["pick"], &[]);
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn seeds() {
+    let inputs: Vec<Value> = (0..50).map(|n| Value::Integer(n.into())).collect();
+    for args in [
+        &["int", "0", "1000000", "-n", "10"][..],
+        &["bytes", "16"],
+        &["pick", "-n", "3"],
+    ] {
+        let seeded = |seed: &str| {
+            let mut args = args.to_vec();
+            args.extend(["--seed", seed]);
+            random(&args, &inputs)
+        };
+        assert_eq!(seeded("42"), seeded("42"), "{:?}", args);
+        assert_ne!(seeded("42"), seeded("43"), "{:?}", args);
+    }
+
+    assert_eq!(
+        uuid(&["--seed", "7", "-n", "3"]),
+        uuid(&["--seed", "7", "-n", "3"])
+    );
+    assert_ne!(uuid(&["--seed", "7"]), uuid(&["--seed", "8"]));
+
+    // The same seed makes the same choices in every build, not only every run.
+    let seeded = random(&["int", "0", "1000000", "-n", "5", "--seed", "42"], &[]);
+    assert_eq!(integers(&seeded), [681896, 950276, 427516, 627361, 288594]);
+    assert_eq!(
+        uuid(&["--seed", "7", "-n", "2"]),
+        [
+            "bb343ad3-31b9-43b6-885f-26a39b9f7e63",
+            "cd4a77dc-68af-4f8d-99f0-eb8fd67a3255"
+        ]
+    );
+}
+
+#[test]
+fn uuids() {
+    let found = uuid(&["-n", "10"]);
+    assert_eq!(found.len(), 10);
+    assert_eq!(found.iter().collect::<BTreeSet<_>>().len(), 10);
+    for id in &found {
+        assert_eq!(id.len(), 36, "{}", id);
+        assert_eq!(&id[14..15], "4", "{}", id);
+    }
+    assert_eq!(uuid(&[]).len(), 1);
+}
@@ -0,0 +1,204 @@
+use anyhow::{bail, Context, Error};
+use clap::{ArgEnum, Parser};
+use monch_io::convert::{cbor_to_json, cbor_to_text};
+use monch_io::table::CsvWriter;
+use monch_io::{ciborium, input_stream_auto, Value};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The file to write. Its extension says what format to write: `.json`, `.jsonl`, `.cbor`, or
+    /// `.csv`. Anything else gets a line of text for each object, like `to text`.
+    file: PathBuf,
+
+    /// Write in this format, whatever the file's extension.
+    #[clap(long, arg_enum, value_name("FORMAT"))]
+    format: Option<Format>,
+
+    /// Write JSON as one object on each line, rather than one array of them all.
+    #[clap(long)]
+    lines: bool,
+
+    /// Create the file's parent directories, if they don't exist yet.
+    #[clap(short('p'), long)]
+    parents: bool,
+
+    /// Replace the file if it already exists.
+    #[clap(long)]
+    force: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ArgEnum)]
+enum Format {
+    Json,
+    Cbor,
+    Csv,
+    Text,
+}
+
+impl Format {
+    /// Choose a format from a file's extension, or text if we don't recognize it. Returns whether
+    /// the extension says JSON should be written a line at a time, too.
+    fn from_path(path: &Path) -> (Format, bool) {
+        let extension = path.extension().and_then(|e| e.to_str());
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => (Format::Json, false),
+            Some("jsonl" | "ndjson") => (Format::Json, true),
+            Some("cbor") => (Format::Cbor, false),
+            Some("csv") => (Format::Csv, false),
+            _ => (Format::Text, false),
+        }
+    }
+}
+
+/// A file being written next to where it'll end up, so nobody sees it half-written. It's deleted
+/// if it's dropped before being [persisted](TempFile::persist).
+struct TempFile {
+    path: PathBuf,
+    file: Option<BufWriter<File>>,
+    persisted: bool,
+}
+
+impl TempFile {
+    /// Create a temporary file in the same directory as `target`, so it can be renamed over it.
+    fn create(target: &Path) -> Result<TempFile, Error> {
+        let name = target
+            .file_name()
+            .context("there's no file name to save to")?;
+        let temp_name = format!(".{}.{}.tmp", name.to_string_lossy(), process::id());
+        let path = target.with_file_name(temp_name);
+
+        let file = File::create(&path)
+            .with_context(|| format!("failed to create '{}'", path.display()))?;
+        Ok(TempFile {
+            path,
+            file: Some(BufWriter::new(file)),
+            persisted: false,
+        })
+    }
+
+    fn writer(&mut self) -> &mut BufWriter<File> {
+        self.file
+            .as_mut()
+            .expect("temporary file is already closed")
+    }
+
+    /// Finish writing, and move the file to `target`, replacing anything there.
+    fn persist(mut self, target: &Path) -> Result<(), Error> {
+        let file = self.file.take().expect("temporary file is already closed");
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        drop(file);
+
+        fs::rename(&self.path, target)
+            .with_context(|| format!("failed to move file into place at '{}'", target.display()))?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.persisted {
+            self.file.take();
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Write every item on stdin to `out`, in the given format.
+fn write_items(out: &mut impl Write, format: Format, lines: bool) -> Result<(), Error> {
+    let items =
+        input_stream_auto::<Value>().map(|item| item.context("failed to read object from stdin"));
+
+    match format {
+        Format::Json if lines => {
+            for item in items {
+                serde_json::to_writer(&mut *out, &cbor_to_json(item?)?)?;
+                writeln!(out)?;
+            }
+        }
+
+        // One item on each line of the array, so it's easy to read and compare.
+        Format::Json => {
+            let mut empty = true;
+            write!(out, "[")?;
+            for item in items {
+                write!(out, "{}\n  ", if empty { "" } else { "," })?;
+                serde_json::to_writer(&mut *out, &cbor_to_json(item?)?)?;
+                empty = false;
+            }
+            writeln!(out, "{}]", if empty { "" } else { "\n" })?;
+        }
+
+        Format::Cbor => {
+            for item in items {
+                ciborium::ser::into_writer(&item?, &mut *out)?;
+            }
+        }
+
+        Format::Csv => {
+            let mut writer = CsvWriter::new(out);
+            for item in items {
+                writer.write_map(item?)?;
+            }
+            writer.finish()?;
+        }
+
+        Format::Text => {
+            for item in items {
+                writeln!(out, "{}", cbor_to_text(item?)?)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let (extension_format, extension_lines) = Format::from_path(&args.file);
+    let format = args.format.unwrap_or(extension_format);
+    let lines = args.lines || (args.format.is_none() && extension_lines);
+
+    if !args.force && fs::symlink_metadata(&args.file).is_ok() {
+        bail!(
+            "'{}' already exists, so it wasn't replaced (use --force to replace it)",
+            args.file.display()
+        );
+    }
+
+    if args.parents {
+        if let Some(parent) = args.file.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed to create '{}'", parent.display()))?;
+        }
+    }
+
+    // If anything goes wrong, the temporary file is removed, and whatever was already there is
+    // left alone.
+    let mut temp = TempFile::create(&args.file)?;
+    write_items(temp.writer(), format, lines)
+        .with_context(|| format!("failed to save '{}'", args.file.display()))?;
+    temp.persist(&args.file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        let format = |path: &str| Format::from_path(Path::new(path));
+        assert_eq!(format("out.json"), (Format::Json, false));
+        assert_eq!(format("out.JSONL"), (Format::Json, true));
+        assert_eq!(format("out.ndjson"), (Format::Json, true));
+        assert_eq!(format("dir/out.cbor"), (Format::Cbor, false));
+        assert_eq!(format("listing.csv"), (Format::Csv, false));
+        assert_eq!(format("notes.txt"), (Format::Text, false));
+        assert_eq!(format("notes"), (Format::Text, false));
+    }
+}
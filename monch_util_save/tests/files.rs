@@ -0,0 +1,150 @@
+use monch_io::{cbor, write_all_to, InputParser, Value};
+use monch_test::{run_with_input, scratch_dir, utility};
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+
+/// Run save in a directory, with some bytes on stdin. save can give up before reading anything,
+/// like when the file exists.
+fn save_bytes(dir: &Path, args: &[&str], stdin: Vec<u8>) -> Output {
+    let mut save = utility(env!("CARGO_BIN_EXE_save"));
+    run_with_input(save.args(args).current_dir(dir), stdin)
+}
+
+/// Run save in a directory, with some objects on stdin.
+fn save(dir: &Path, args: &[&str], items: &[Value]) -> Output {
+    let mut stdin = vec![];
+    write_all_to(&mut stdin, items).unwrap();
+    save_bytes(dir, args, stdin)
+}
+
+fn items() -> Vec<Value> {
+    vec![
+        cbor!({"name" => "a.txt", "size" => 10, "tags" => ["x"]}).unwrap(),
+        cbor!({"name" => "b, c", "size" => null}).unwrap(),
+    ]
+}
+
+fn read(dir: &Path, name: &str) -> String {
+    fs::read_to_string(dir.join(name)).unwrap()
+}
+
+#[test]
+fn formats() {
+    let dir = scratch_dir("save", "formats");
+    let saved = |args: &[&str]| {
+        let output = save(&dir, args, &items());
+        assert!(output.status.success(), "{:?}", output);
+        assert!(output.stdout.is_empty());
+    };
+
+    saved(&["out.json"]);
+    assert_eq!(
+        read(&dir, "out.json"),
+        concat!(
+            "[\n",
+            "  {\"name\":\"a.txt\",\"size\":10,\"tags\":[\"x\"]},\n",
+            "  {\"name\":\"b, c\",\"size\":null}\n",
+            "]\n"
+        )
+    );
+
+    saved(&["--lines", "lines.json"]);
+    saved(&["out.jsonl"]);
+    let lines = concat!(
+        "{\"name\":\"a.txt\",\"size\":10,\"tags\":[\"x\"]}\n",
+        "{\"name\":\"b, c\",\"size\":null}\n",
+    );
+    assert_eq!(read(&dir, "lines.json"), lines);
+    assert_eq!(read(&dir, "out.jsonl"), lines);
+
+    saved(&["out.csv"]);
+    assert_eq!(
+        read(&dir, "out.csv"),
+        "name,size,tags\na.txt,10,\"[\"\"x\"\"]\"\n\"b, c\",,\n"
+    );
+
+    saved(&["out.txt"]);
+    assert_eq!(
+        read(&dir, "out.txt"),
+        "{\"name\":\"a.txt\",\"size\":10,\"tags\":[\"x\"]}\n{\"name\":\"b, c\",\"size\":null}\n"
+    );
+
+    // CBOR items are written as they are.
+    saved(&["out.cbor"]);
+    let cbor = fs::read(dir.join("out.cbor")).unwrap();
+    let found: Vec<Value> = InputParser::new(&cbor[..])
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(found, items());
+
+    // The extension can be overridden.
+    saved(&["--format", "csv", "table.txt"]);
+    assert_eq!(read(&dir, "table.txt"), read(&dir, "out.csv"));
+
+    // Nothing to save is still a file.
+    assert!(save(&dir, &["empty.json"], &[]).status.success());
+    assert_eq!(read(&dir, "empty.json"), "[]\n");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn existing_files() {
+    let dir = scratch_dir("save", "existing");
+    fs::write(dir.join("out.json"), "old").unwrap();
+
+    let output = save(&dir, &["out.json"], &items());
+    assert!(!output.status.success());
+    assert_eq!(read(&dir, "out.json"), "old");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("--force"), "{}", stderr);
+
+    assert!(save(&dir, &["--force", "out.json"], &items())
+        .status
+        .success());
+    assert!(read(&dir, "out.json").starts_with("[\n"));
+
+    // Parent directories are only created when asked for.
+    assert!(!save(&dir, &["new/deeper/out.json"], &items())
+        .status
+        .success());
+    assert!(!dir.join("new").exists());
+    assert!(save(&dir, &["-p", "new/deeper/out.json"], &items())
+        .status
+        .success());
+    assert!(dir.join("new/deeper/out.json").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failures_are_atomic() {
+    let dir = scratch_dir("save", "atomic");
+    fs::write(dir.join("out.json"), "old").unwrap();
+
+    // An item, followed by something that isn't CBOR.
+    let mut stdin = vec![];
+    write_all_to(&mut stdin, &items()[..1]).unwrap();
+    stdin.extend_from_slice(&[0xff, 0xff, 0xff]);
+
+    let output = save_bytes(&dir, &["--force", "out.json"], stdin.clone());
+    assert!(!output.status.success());
+    assert!(!save_bytes(&dir, &["new.csv"], stdin).status.success());
+
+    // What was there is left alone, and there's nothing else left behind.
+    assert_eq!(read(&dir, "out.json"), "old");
+    let names: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+    assert_eq!(names, ["out.json"]);
+
+    // Items which can't be written in the format fail the same way.
+    let bytes = [cbor!({"b" => Value::Bytes(vec![1])}).unwrap()];
+    assert!(!save(&dir, &["bytes.json"], &bytes).status.success());
+    assert!(!save(&dir, &["rows.csv"], &[cbor!([1]).unwrap()])
+        .status
+        .success());
+    assert!(!dir.join("bytes.json").exists());
+    assert!(!dir.join("rows.csv").exists());
+    fs::remove_dir_all(dir).unwrap();
+}
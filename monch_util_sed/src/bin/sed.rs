@@ -1,47 +1,337 @@
-use anyhow::{Context, Error};
+use anyhow::{anyhow, bail, Context, Error};
 use clap::Parser;
-use monch_io::{input_stream, put, DataPath, Value, log};
+use monch_io::{input_stream_auto, output_open, try_put, DataPath, Value};
+use regex::{Regex, RegexBuilder};
+use std::borrow::Cow;
 
 // Note: balls
 
 #[derive(Debug, Parser)]
 struct Args {
-    /// The pattern to replace
-    pattern: String,
+    /// The regex to replace, like '(\w+)@example.com'. Leave it out to use `-e` instead.
+    #[clap(
+        requires("replacement"),
+        conflicts_with("expressions"),
+        required_unless_present_any(&["expressions", "delete"])
+    )]
+    pattern: Option<String>,
 
-    // The replacement for the 
-    replacement: String,
+    /// The replacement for each match, which can refer to groups in the pattern like `$1` or
+    /// `${name}`, or the whole match with `$0`. Write `$$` for a literal `$`.
+    replacement: Option<String>,
+
+    /// Replace a pattern, like '(\w+)@example.com=>$1'. Can be given more than once, and each
+    /// replacement is made in turn. Write `\=>` for a literal `=>` in the pattern.
+    #[clap(short('e'), long("expression"), value_name("PATTERN=>REPLACEMENT"))]
+    expressions: Vec<String>,
+
+    /// Drop items which match this pattern, before anything is replaced. Can be given more than
+    /// once.
+    #[clap(short('d'), long, value_name("PATTERN"))]
+    delete: Vec<String>,
+
+    /// Only pass on items where something was replaced.
+    #[clap(long)]
+    only_matching_items: bool,
 
     /// Pass in a field for DataPath(s).
     #[clap(short('f'), long, default_value(""))]
     field: DataPath,
-    
+
+    /// Match upper and lower case letters alike.
+    #[clap(short('i'), long)]
+    ignore_case: bool,
+
+    /// Only replace the first match in each piece of text, rather than all of them.
+    #[clap(long)]
+    first: bool,
+}
+
+/// A pattern to replace, and what to replace it with.
+#[derive(Debug)]
+struct Expression {
+    regex: Regex,
+    replacement: String,
+}
+
+/// Everything sed was asked to do, which is compiled once, before reading anything.
+#[derive(Debug)]
+struct Script {
+    /// Replacements to make, in order.
+    expressions: Vec<Expression>,
+
+    /// Patterns for items to drop.
+    deletes: Vec<Regex>,
+}
+
+impl Script {
+    fn build(args: &Args) -> Result<Script, Error> {
+        let mut expressions = vec![];
+        if let (Some(pattern), Some(replacement)) = (&args.pattern, &args.replacement) {
+            expressions.push(Expression {
+                regex: build_regex(args, pattern)?,
+                replacement: replacement.clone(),
+            });
+        }
+        for expression in &args.expressions {
+            let (pattern, replacement) = parse_expression(expression)?;
+            expressions.push(Expression {
+                regex: build_regex(args, &pattern)?,
+                replacement: replacement.to_string(),
+            });
+        }
+
+        let deletes = args
+            .delete
+            .iter()
+            .map(|pattern| build_regex(args, pattern))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Script {
+            expressions,
+            deletes,
+        })
+    }
+}
+
+/// Split an expression like `(\w+)@example.com=>$1` into its pattern and replacement, at the first
+/// `=>` that isn't escaped as `\=>`.
+fn parse_expression(expression: &str) -> Result<(String, &str), Error> {
+    let mut pattern = String::new();
+    let mut rest = expression;
+
+    loop {
+        let arrow = match rest.find("=>") {
+            Some(arrow) => arrow,
+            None => bail!(
+                "expression '{}' needs a '=>' between the pattern and the replacement",
+                expression
+            ),
+        };
+
+        // An odd number of backslashes escapes the arrow, but an even number are just escaping
+        // each other, like in `a\\=>b`.
+        let before = &rest[..arrow];
+        let backslashes = before.chars().rev().take_while(|&c| c == '\\').count();
+        if backslashes % 2 == 0 {
+            pattern.push_str(before);
+            rest = &rest[arrow + 2..];
+            break;
+        }
+
+        pattern.push_str(&before[..before.len() - 1]);
+        pattern.push_str("=>");
+        rest = &rest[arrow + 2..];
+    }
+
+    if pattern.is_empty() {
+        bail!("expression '{}' has an empty pattern", expression);
+    }
+
+    Ok((pattern, rest))
+}
+
+/// Compile one of the patterns, with the flags that apply to all of them.
+fn build_regex(args: &Args, pattern: &str) -> Result<Regex, Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(args.ignore_case)
+        .build()
+        .map_err(|e| anyhow!("invalid pattern '{}': {}", pattern, e))
+}
+
+/// Make the replacements in an item's field, returning the whole item with only that field
+/// changed, or `None` if the item should be dropped.
+///
+/// Items that don't have text at the field are passed on untouched, since there's nothing to
+/// replace in them.
+fn sed_item(args: &Args, script: &Script, mut item: Value) -> Option<Value> {
+    let text = match args.field.get_ref(&item).and_then(Value::as_text) {
+        Some(text) => text,
+        None if args.only_matching_items => return None,
+        None => return Some(item),
+    };
+
+    if script.deletes.iter().any(|regex| regex.is_match(text)) {
+        return None;
+    }
+
+    // Only allocate for the expressions that actually replace something.
+    let limit = if args.first { 1 } else { 0 };
+    let mut replaced: Option<String> = None;
+    for expression in &script.expressions {
+        let current = replaced.as_deref().unwrap_or(text);
+        let replacement = expression.replacement.as_str();
+        if let Cow::Owned(new) = expression.regex.replacen(current, limit, replacement) {
+            replaced = Some(new);
+        }
+    }
+
+    match replaced {
+        Some(replaced) => {
+            args.field.set_into(&mut item, Value::Text(replaced));
+            Some(item)
+        }
+        None if args.only_matching_items => None,
+        None => Some(item),
+    }
 }
 
 fn main() -> Result<(), Error> {
     let args = Args::parse();
+    let script = Script::build(&args)?;
 
-    for string_result in input_stream::<Value>() {
-        // Bail if we have an error 
+    for string_result in input_stream_auto::<Value>() {
+        // Bail if we have an error
         let val = string_result.context("failed to read string from stdin")?;
 
-        let inner_val = args.field.get_from(val.clone());
-        let maybe_string = inner_val.as_text();
+        let replaced = match sed_item(&args, &script, val) {
+            Some(replaced) => replaced,
+            None => continue,
+        };
 
-        if let Some(string) = maybe_string {
+        // If nobody's reading our output anymore, we're done.
+        let written = try_put!(&replaced);
+        if !output_open(written).context("failed to write object")? {
+            break;
+        }
+    }
 
-            
+    Ok(())
+}
 
-            put!(&string.replace(&args.pattern, &args.replacement));
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        } else {
+    fn args(args: &[&str]) -> Args {
+        Args::try_parse_from(["sed"].iter().chain(args)).unwrap()
+    }
 
-            log!("sed: unexpected non-string data item passed in");
-            continue;
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_string())
+    }
 
-        }
+    fn sed(a: &[&str], item: Value) -> Option<Value> {
+        let args = args(a);
+        sed_item(&args, &Script::build(&args).unwrap(), item)
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!(
+            sed(&["(\\w+)@(\\w+)", "$2 at $1"], text("me@here, you@there")),
+            Some(text("here at me, there at you"))
+        );
+        assert_eq!(
+            sed(
+                &["(?P<y>\\d{4})-(?P<m>\\d{2})", "${m}/${y}"],
+                text("2021-11")
+            ),
+            Some(text("11/2021"))
+        );
+        assert_eq!(
+            sed(&["[0-9]+", "<$0>"], text("a1b22")),
+            Some(text("a<1>b<22>"))
+        );
 
+        // `$$` is a literal `$`, and groups that don't exist are empty.
+        assert_eq!(sed(&["cost", "$$5"], text("cost")), Some(text("$5")));
+        assert_eq!(sed(&["a", "[$9]"], text("a")), Some(text("[]")));
     }
 
-    Ok(())
+    #[test]
+    fn flags() {
+        assert_eq!(sed(&["a", "o"], text("banana")), Some(text("bonono")));
+        assert_eq!(
+            sed(&["--first", "a", "o"], text("banana")),
+            Some(text("bonana"))
+        );
+        assert_eq!(sed(&["A", "o"], text("banana")), Some(text("banana")));
+        assert_eq!(sed(&["-i", "A", "o"], text("bAnana")), Some(text("bonono")));
+    }
+
+    #[test]
+    fn fields() {
+        let item = |name: &str| {
+            Value::Map(vec![
+                (text("id"), Value::from(7)),
+                (text("owner"), Value::Map(vec![(text("name"), text(name))])),
+            ])
+        };
+        assert_eq!(
+            sed(
+                &["-f", ".owner.name", "^(\\w+) (\\w+)$", "$2, $1"],
+                item("Ada Lovelace")
+            ),
+            Some(item("Lovelace, Ada"))
+        );
+
+        // Items without text at the field are passed on as they are.
+        assert_eq!(
+            sed(&["-f", ".id", "7", "8"], item("Ada")),
+            Some(item("Ada"))
+        );
+        assert_eq!(sed(&["7", "8"], item("Ada")), Some(item("Ada")));
+        assert_eq!(sed(&["--only-matching-items", "7", "8"], item("Ada")), None);
+    }
+
+    #[test]
+    fn expressions_in_order() {
+        let chain = ["-e", "a=>b", "-e", "b=>c", "-e", "c=>d"];
+        assert_eq!(sed(&chain, text("abc")), Some(text("ddd")));
+
+        let reversed = ["-e", "c=>d", "-e", "b=>c", "-e", "a=>b"];
+        assert_eq!(sed(&reversed, text("abc")), Some(text("bcd")));
+
+        // Later expressions can refer to what earlier ones wrote.
+        let groups = ["-e", "(\\w+)@(\\w+)=>$2:$1", "-e", "^(\\w+):=>[$1]"];
+        assert_eq!(sed(&groups, text("me@here")), Some(text("[here]me")));
+    }
+
+    #[test]
+    fn deleting_and_only_matching() {
+        let a = ["-d", "^#", "-e", "x=>y"];
+        assert_eq!(sed(&a, text("# comment x")), None);
+        assert_eq!(sed(&a, text("x marks")), Some(text("y marks")));
+        assert_eq!(sed(&a, text("nothing")), Some(text("nothing")));
+
+        // Deleting is done before replacing.
+        assert_eq!(sed(&["-d", "^y", "-e", "x=>y"], text("x")), Some(text("y")));
+        assert_eq!(sed(&["-d", "^#"], text("kept")), Some(text("kept")));
+
+        let only = ["--only-matching-items", "-e", "x=>y"];
+        assert_eq!(sed(&only, text("x marks")), Some(text("y marks")));
+        assert_eq!(sed(&only, text("nothing")), None);
+    }
+
+    #[test]
+    fn parsing_expressions() {
+        let parse = |e| parse_expression(e).map(|(p, r)| (p, r.to_string()));
+        assert_eq!(parse("a=>b").unwrap(), ("a".into(), "b".into()));
+        assert_eq!(parse("a=>").unwrap(), ("a".into(), "".into()));
+        assert_eq!(parse("a=>b=>c").unwrap(), ("a".into(), "b=>c".into()));
+        assert_eq!(parse("a\\=>b=>c").unwrap(), ("a=>b".into(), "c".into()));
+        assert_eq!(parse("a\\\\=>b").unwrap(), ("a\\\\".into(), "b".into()));
+
+        let err = parse("a->b").unwrap_err().to_string();
+        assert!(err.contains("needs a '=>'"), "{}", err);
+        let err = parse("a\\=>b").unwrap_err().to_string();
+        assert!(err.contains("needs a '=>'"), "{}", err);
+        let err = parse("=>b").unwrap_err().to_string();
+        assert!(err.contains("empty pattern"), "{}", err);
+    }
+
+    #[test]
+    fn bad_arguments() {
+        let build = |a: &[&str]| Script::build(&args(a)).unwrap_err().to_string();
+        assert!(build(&["a(b", "c"]).starts_with("invalid pattern 'a(b': "));
+        assert!(build(&["-e", "a(b=>c"]).starts_with("invalid pattern 'a(b': "));
+        assert!(build(&["-d", "a("]).starts_with("invalid pattern 'a(': "));
+
+        let parse = |a: &[&str]| Args::try_parse_from(["sed"].iter().chain(a)).is_ok();
+        assert!(!parse(&[]));
+        assert!(!parse(&["a"]));
+        assert!(!parse(&["a", "b", "-e", "c=>d"]));
+        assert!(parse(&["-d", "a"]));
+    }
 }
@@ -0,0 +1,176 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use monch_io::metadata::{kind_name, rfc3339};
+use monch_io::{input_stream_auto, log, output_open, try_put, write_header, StreamHeader, Value};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The paths to describe. Without any, each item of text from stdin is a path, like from
+    /// `ls | get .name`. Relative paths are from the current directory.
+    paths: Vec<PathBuf>,
+
+    /// Describe what symlinks point to, rather than the links themselves.
+    #[clap(short('L'), long)]
+    dereference: bool,
+}
+
+/// Everything we say about a path. Fields are null where this platform can't tell us.
+#[derive(Debug, Serialize)]
+struct PathInfo {
+    /// The path, as it was given.
+    path: String,
+
+    /// One of `Dir`, `File`, `Symlink`, or `Unknown`.
+    kind: &'static str,
+
+    /// The size in bytes.
+    size: u64,
+
+    /// When it was last modified, accessed, and created, as RFC 3339 timestamps in UTC, to the
+    /// second, like `2021-11-05T14:03:09Z`.
+    modified: Option<String>,
+    accessed: Option<String>,
+    created: Option<String>,
+
+    /// The permission bits, in octal, like `755`.
+    #[cfg(unix)]
+    permissions: String,
+
+    /// Whether it can't be written to.
+    #[cfg(not(unix))]
+    readonly: bool,
+
+    /// The inode number, and the device it's on, which together identify a file.
+    #[cfg(unix)]
+    inode: u64,
+    #[cfg(unix)]
+    dev: u64,
+
+    /// Where it points, if it's a symlink.
+    symlink_target: Option<String>,
+}
+
+/// The fields of a [`PathInfo`], in order, for the stream header.
+#[cfg(unix)]
+const FIELDS: &[&str] = &[
+    "path",
+    "kind",
+    "size",
+    "modified",
+    "accessed",
+    "created",
+    "permissions",
+    "inode",
+    "dev",
+    "symlink_target",
+];
+#[cfg(not(unix))]
+const FIELDS: &[&str] = &[
+    "path",
+    "kind",
+    "size",
+    "modified",
+    "accessed",
+    "created",
+    "readonly",
+    "symlink_target",
+];
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    let header = StreamHeader::new("path", FIELDS);
+    write_header(&header).context("failed to write stream header")?;
+
+    let mut failed = false;
+    if args.paths.is_empty() {
+        for obj_result in input_stream_auto::<Value>() {
+            let obj = obj_result.context("failed to read object from stdin")?;
+            let path = match obj {
+                Value::Text(path) => PathBuf::from(path),
+                other => {
+                    log!("stat: expected a path as text, but got {:?}", other);
+                    failed = true;
+                    continue;
+                }
+            };
+
+            // If nobody's reading our output anymore, we're done.
+            if !stat(&args, &path, &mut failed)? {
+                break;
+            }
+        }
+    } else {
+        for path in &args.paths {
+            if !stat(&args, path, &mut failed)? {
+                break;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Write a description of one path, or the error if it can't be described, setting `failed`.
+/// Returns `false` once nobody's reading our output.
+fn stat(args: &Args, path: &Path, failed: &mut bool) -> Result<bool, Error> {
+    let written = match describe(path, args.dereference) {
+        Ok(info) => try_put!(@obj info),
+        Err(e) => {
+            log!("stat: {}: {}", path.display(), e);
+            *failed = true;
+            try_put!(path: path.to_string_lossy(), error: e.to_string())
+        }
+    };
+
+    output_open(written).context("failed to write path")
+}
+
+/// Describe a path, following it first if it's a symlink and `dereference` is set.
+fn describe(path: &Path, dereference: bool) -> io::Result<PathInfo> {
+    let meta = if dereference {
+        fs::metadata(path)?
+    } else {
+        fs::symlink_metadata(path)?
+    };
+
+    let symlink_target = if meta.file_type().is_symlink() {
+        let target = fs::read_link(path)?;
+        Some(target.to_string_lossy().into_owned())
+    } else {
+        None
+    };
+
+    Ok(PathInfo {
+        path: path.to_string_lossy().into_owned(),
+        kind: kind_name(meta.file_type()),
+        size: meta.len(),
+        modified: meta.modified().ok().map(rfc3339),
+        accessed: meta.accessed().ok().map(rfc3339),
+        created: meta.created().ok().map(rfc3339),
+        #[cfg(unix)]
+        permissions: permissions(&meta),
+        #[cfg(not(unix))]
+        readonly: meta.permissions().readonly(),
+        #[cfg(unix)]
+        inode: std::os::unix::fs::MetadataExt::ino(&meta),
+        #[cfg(unix)]
+        dev: std::os::unix::fs::MetadataExt::dev(&meta),
+        symlink_target,
+    })
+}
+
+/// The permission bits of a file, in octal, like `755`.
+#[cfg(unix)]
+fn permissions(meta: &fs::Metadata) -> String {
+    use std::os::unix::fs::MetadataExt;
+    format!("{:o}", meta.mode() & 0o7777)
+}
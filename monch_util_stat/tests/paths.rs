@@ -0,0 +1,107 @@
+use monch_io::Value;
+use monch_test::{field, find_field, items, run_with_items, scratch_dir, utility};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Make a directory with a file and a subdirectory to describe.
+fn dir(name: &str) -> PathBuf {
+    let dir = scratch_dir("stat", name);
+    fs::create_dir(dir.join("sub")).unwrap();
+    fs::write(dir.join("file.txt"), "hello").unwrap();
+    dir
+}
+
+/// Run stat in a directory, with some objects on stdin.
+fn stat(dir: &Path, args: &[&str], stdin: &[Value]) -> Output {
+    let mut stat = utility(env!("CARGO_BIN_EXE_stat"));
+    run_with_items(stat.args(args).current_dir(dir), stdin)
+}
+
+fn text(item: &Value, name: &str) -> String {
+    field(item, name).as_text().unwrap().to_string()
+}
+
+#[test]
+fn arguments() {
+    let dir = dir("arguments");
+    let output = stat(&dir, &["file.txt", "sub"], &[]);
+    assert!(output.status.success());
+
+    let found = items(&output);
+    assert_eq!(found.len(), 2);
+    let (file, sub) = (&found[0], &found[1]);
+    assert_eq!(text(file, "path"), "file.txt");
+    assert_eq!(text(file, "kind"), "File");
+    assert_eq!(find_field(file, "size"), Some(&Value::from(5)));
+    assert!(text(file, "modified").ends_with('Z'));
+    assert!(text(file, "accessed").ends_with('Z'));
+    assert!(find_field(file, "created").is_some());
+    assert_eq!(find_field(file, "symlink_target"), Some(&Value::Null));
+    assert_eq!(text(sub, "kind"), "Dir");
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        fs::set_permissions(dir.join("file.txt"), fs::Permissions::from_mode(0o640)).unwrap();
+        let file = &items(&stat(&dir, &["file.txt"], &[]))[0];
+        assert_eq!(text(file, "permissions"), "640");
+
+        let meta = fs::metadata(dir.join("file.txt")).unwrap();
+        assert_eq!(find_field(file, "inode"), Some(&Value::from(meta.ino())));
+        assert_eq!(find_field(file, "dev"), Some(&Value::from(meta.dev())));
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn stdin_paths() {
+    let dir = dir("stdin");
+    let paths = [Value::Text("sub".into()), Value::Text("file.txt".into())];
+    let output = stat(&dir, &[], &paths);
+    assert!(output.status.success());
+
+    let found = items(&output);
+    let paths: Vec<String> = found.iter().map(|item| text(item, "path")).collect();
+    assert_eq!(paths, ["sub", "file.txt"]);
+
+    // Anything but text can't be a path.
+    let output = stat(&dir, &[], &[Value::from(1), Value::Text("sub".into())]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(items(&output).len(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn missing_paths() {
+    let dir = dir("missing");
+    let output = stat(&dir, &["nope", "file.txt"], &[]);
+    assert_eq!(output.status.code(), Some(1));
+
+    // The paths that are there are still described.
+    let found = items(&output);
+    assert_eq!(found.len(), 2);
+    assert_eq!(text(&found[0], "path"), "nope");
+    assert!(find_field(&found[0], "error").is_some());
+    assert!(find_field(&found[0], "kind").is_none());
+    assert_eq!(text(&found[1], "kind"), "File");
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks() {
+    use std::os::unix::fs::symlink;
+
+    let dir = dir("symlinks");
+    symlink("file.txt", dir.join("link")).unwrap();
+
+    let link = &items(&stat(&dir, &["link"], &[]))[0];
+    assert_eq!(text(link, "kind"), "Symlink");
+    assert_eq!(text(link, "symlink_target"), "file.txt");
+
+    let followed = &items(&stat(&dir, &["-L", "link"], &[]))[0];
+    assert_eq!(text(followed, "kind"), "File");
+    assert_eq!(find_field(followed, "size"), Some(&Value::from(5)));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,99 @@
+use anyhow::{Context, Error};
+use clap::Parser;
+use monch_io::template::{self, Piece};
+use monch_io::{input_stream_auto, log, output_open, try_put, Value};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// The template to render for each item, like `"{.name} is {.size} bytes"`. Each `{.path}` is
+    /// replaced by that field of the item, and `{{` and `}}` are literal braces.
+    #[clap(required_unless_present("file"), conflicts_with("file"))]
+    template: Option<String>,
+
+    /// Read the template from this file instead. A newline at the end of it is left out.
+    #[clap(short('f'), long, value_name("PATH"))]
+    file: Option<PathBuf>,
+
+    /// Fail on items which are missing any of the template's fields, rather than leaving them
+    /// empty.
+    #[clap(long)]
+    strict: bool,
+
+    /// Write each rendered template as a line of plain text, for programs that don't read objects.
+    #[clap(long)]
+    raw: bool,
+}
+
+/// Read the template from the arguments, or from its file.
+fn read_template(args: &Args) -> Result<Vec<Piece>, Error> {
+    let text = match (&args.template, &args.file) {
+        (Some(text), _) => text.clone(),
+        (None, Some(path)) => {
+            let mut text = fs::read_to_string(path)
+                .with_context(|| format!("failed to read '{}'", path.display()))?;
+            if text.ends_with('\n') {
+                text.pop();
+                if text.ends_with('\r') {
+                    text.pop();
+                }
+            }
+            text
+        }
+        (None, None) => unreachable!("clap requires a template"),
+    };
+
+    template::parse(&text).context("invalid template")
+}
+
+/// Write a rendered template. Returns `false` once nobody's reading our output.
+fn emit(rendered: String, raw: bool) -> Result<bool, Error> {
+    if raw {
+        return match writeln!(io::stdout().lock(), "{}", rendered) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(false),
+            written => written.map(|_| true).context("failed to write text"),
+        };
+    }
+    output_open(try_put!(&rendered)).context("failed to write text")
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+    let pieces = read_template(&args)?;
+    let mut failed = false;
+
+    for (i, item) in input_stream_auto::<Value>().enumerate() {
+        let item = item.context("failed to read object from stdin")?;
+
+        let mut missing = vec![];
+        let rendered = match template::render(&pieces, &item, &mut missing) {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                log!("template: couldn't render item {}: {}", i, e);
+                failed = true;
+                continue;
+            }
+        };
+
+        for path in &missing {
+            log!("template: item {} has nothing at {}", i, path);
+        }
+        if args.strict && !missing.is_empty() {
+            failed = true;
+            continue;
+        }
+
+        // If nobody's reading our output anymore, we're done.
+        if !emit(rendered, args.raw)? {
+            break;
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use monch_io::{cbor, Value};
+use monch_test::{run_with_items, scratch_dir, utility};
+use std::fs;
+use std::process::Output;
+
+/// Run template, with some objects on stdin. template might fail before it reads them at all.
+fn template(args: &[&str], stdin: &[Value]) -> Output {
+    run_with_items(utility(env!("CARGO_BIN_EXE_template")).args(args), stdin)
+}
+
+/// Read the text items template wrote.
+fn lines(output: &Output) -> Vec<String> {
+    monch_test::items(output)
+        .into_iter()
+        .map(|item| item.into_text().unwrap())
+        .collect()
+}
+
+/// Some files, like `ls -l` lists them.
+fn files() -> Vec<Value> {
+    vec![
+        cbor!({"name" => "a.txt", "size" => 100, "owner" => {"name" => "alice"}}).unwrap(),
+        cbor!({"name" => "b.txt", "size" => 5}).unwrap(),
+        cbor!({"name" => "c", "size" => 4096, "owner" => {"name" => "bob"}}).unwrap(),
+    ]
+}
+
+#[test]
+fn items() {
+    let output = template(&["{.name} is {.size} bytes"], &files());
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        lines(&output),
+        ["a.txt is 100 bytes", "b.txt is 5 bytes", "c is 4096 bytes"]
+    );
+
+    let output = template(&["--raw", "{{{.name}}}"], &files());
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{a.txt}\n{b.txt}\n{c}\n"
+    );
+}
+
+#[test]
+fn missing() {
+    // Missing fields are left empty, with a warning.
+    let output = template(&["{.name}: {.owner.name}"], &files());
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(lines(&output), ["a.txt: alice", "b.txt: ", "c: bob"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("item 1 has nothing at .owner.name"),
+        "{}",
+        stderr
+    );
+
+    // Unless they're not allowed.
+    let output = template(&["--strict", "{.name}: {.owner.name}"], &files());
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(lines(&output), ["a.txt: alice", "c: bob"]);
+}
+
+#[test]
+fn files_and_errors() {
+    let dir = scratch_dir("template", "files");
+    let path = dir.join("template.txt");
+    fs::write(&path, "{.name}\t{.size}\n").unwrap();
+    let output = template(&["--file", path.to_str().unwrap(), "--raw"], &files());
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "a.txt\t100\nb.txt\t5\nc\t4096\n"
+    );
+    fs::remove_dir_all(dir).unwrap();
+
+    let output = template(&["{.name"], &files());
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("unterminated placeholder"), "{}", stderr);
+
+    assert!(!template(&[], &files()).status.success());
+    assert!(!template(&["--file", "no-such-template.txt"], &files())
+        .status
+        .success());
+}
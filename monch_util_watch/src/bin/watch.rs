@@ -0,0 +1,190 @@
+use anyhow::{Context, Error};
+use chrono::{DateTime, SecondsFormat, Utc};
+use clap::Parser;
+use monch_io::units::parse_duration;
+use monch_io::{
+    canonicalize, flush, output_open, try_put, write_header, InputParser, StreamHeader, Value,
+};
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// How long to wait between the start of each run, like `2s` or `500ms`.
+    #[clap(
+        short('n'),
+        long,
+        value_name("INTERVAL"),
+        default_value("2s"),
+        parse(try_from_str = parse_duration)
+    )]
+    interval: Duration,
+
+    /// Only write a snapshot when the command's output is different from the last run's.
+    #[clap(long)]
+    diff: bool,
+
+    /// Stop after running the command this many times, rather than once nobody's reading.
+    #[clap(short('c'), long, value_name("N"))]
+    count: Option<u64>,
+
+    /// The command to run, after `--`.
+    #[clap(last(true), required(true), value_name("COMMAND"))]
+    command: Vec<String>,
+}
+
+/// The fields of each snapshot, in order, for the stream header.
+const FIELDS: &[&str] = &["timestamp", "exit_code", "items"];
+
+/// Run the command once, returning the status to report for it and what it wrote.
+fn run(command: &[String]) -> Result<(i32, Vec<Value>), Error> {
+    let (program, program_args) = command.split_first().expect("command is empty");
+
+    // Ask monch programs for CBOR, whatever we were asked to write.
+    let output = Command::new(program)
+        .args(program_args)
+        .env("MONCH_FORMAT", "cbor")
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()
+        .with_context(|| format!("failed to run '{}'", program))?;
+
+    Ok((exit_code(output.status), parse_items(&output.stdout)))
+}
+
+/// The items a command wrote: CBOR items if that's what it wrote, or otherwise a string for each
+/// line of text.
+fn parse_items(stdout: &[u8]) -> Vec<Value> {
+    if !looks_like_text(stdout) {
+        let items: Result<Vec<Value>, _> = InputParser::new(stdout).collect();
+        if let Ok(items) = items {
+            return items;
+        }
+    }
+
+    String::from_utf8_lossy(stdout)
+        .lines()
+        .map(|line| Value::Text(line.to_string()))
+        .collect()
+}
+
+/// Returns whether some output is lines of text, rather than CBOR.
+///
+/// Lines of text often happen to be valid CBOR too: `1\n` is the integers -18 and 10. So output
+/// that's printable UTF-8 ending in a newline is taken to be text, which CBOR items rarely are.
+fn looks_like_text(output: &[u8]) -> bool {
+    match std::str::from_utf8(output) {
+        Ok(text) => {
+            text.ends_with('\n')
+                && text
+                    .chars()
+                    .all(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+        }
+        Err(_) => false,
+    }
+}
+
+/// The status to report for a command: its exit code, or 128 plus the signal that killed it, like
+/// a shell reports.
+fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    1
+}
+
+/// Encode items in a way that's the same whenever they're equal, to compare runs.
+fn fingerprint(items: &[Value]) -> Vec<u8> {
+    let mut bytes = vec![];
+    let items = canonicalize(Value::Array(items.to_vec()));
+    monch_io::ciborium::ser::into_writer(&items, &mut bytes).expect("writing to a Vec can't fail");
+    bytes
+}
+
+fn main() -> Result<(), Error> {
+    let args = Args::parse();
+
+    let header = StreamHeader::new("snapshot", FIELDS);
+    write_header(&header).context("failed to write stream header")?;
+
+    let mut last = None;
+    let mut runs = 0;
+    loop {
+        let started = Instant::now();
+        let timestamp =
+            DateTime::<Utc>::from(SystemTime::now()).to_rfc3339_opts(SecondsFormat::Millis, true);
+        let (exit_code, items) = run(&args.command)?;
+        runs += 1;
+
+        // A command that fails is still worth seeing, so it's written like any other run.
+        let changed = if args.diff {
+            let fingerprint = fingerprint(&items);
+            let changed = last.as_ref() != Some(&fingerprint);
+            last = Some(fingerprint);
+            changed
+        } else {
+            true
+        };
+
+        if changed {
+            // Don't hold this snapshot back while waiting for the next.
+            let written = try_put!(timestamp: timestamp, exit_code: exit_code, items: items)
+                .and_then(|_| flush());
+            // If nobody's reading our output anymore, we're done.
+            if !output_open(written).context("failed to write snapshot")? {
+                return Ok(());
+            }
+        }
+
+        if args.count.map_or(false, |count| runs >= count) {
+            return Ok(());
+        }
+        thread::sleep(args.interval.saturating_sub(started.elapsed()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use monch_io::{cbor, write_all_to};
+
+    #[test]
+    fn items() {
+        let text = |lines: &[&str]| -> Vec<Value> {
+            lines.iter().map(|l| Value::Text(l.to_string())).collect()
+        };
+        assert_eq!(parse_items(b"1\n"), text(&["1"]));
+        assert_eq!(parse_items(b"a\r\nb\n"), text(&["a", "b"]));
+        assert_eq!(parse_items(b""), text(&[]));
+
+        let values = [cbor!({"a" => 1}).unwrap(), cbor!("ehello").unwrap()];
+        let mut cbor = vec![];
+        write_all_to(&mut cbor, &values).unwrap();
+        assert_eq!(parse_items(&cbor), values);
+
+        // Output that isn't quite either is still shown.
+        assert_eq!(parse_items(b"no newline"), text(&["no newline"]));
+        assert_eq!(parse_items(b"\xff\n"), text(&["\u{fffd}"]));
+    }
+
+    #[test]
+    fn fingerprints() {
+        let a = cbor!({"a" => 1, "b" => 2}).unwrap();
+        let b = cbor!({"b" => 2, "a" => 1}).unwrap();
+        assert_eq!(fingerprint(std::slice::from_ref(&a)), fingerprint(&[b]));
+        assert_ne!(
+            fingerprint(std::slice::from_ref(&a)),
+            fingerprint(&[a.clone(), a])
+        );
+    }
+}
@@ -0,0 +1,98 @@
+use monch_io::{cbor, Value};
+use monch_test::{items, scratch_dir, utility};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+
+/// Make a directory with a script that prints how many times it's been run, and fails on its
+/// second run.
+fn dir(name: &str) -> PathBuf {
+    let dir = scratch_dir("watch", name);
+    fs::write(
+        dir.join("counter.sh"),
+        concat!(
+            "n=$(( $(cat count 2>/dev/null || echo 0) + 1 ))\n",
+            "echo $n > count\n",
+            "echo run $n\n",
+            "[ $n -ne 2 ]\n",
+        ),
+    )
+    .unwrap();
+    dir
+}
+
+/// Run watch in a directory.
+fn watch(dir: &Path, args: &[&str]) -> Output {
+    utility(env!("CARGO_BIN_EXE_watch"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+/// The snapshots watch wrote, without their timestamps, after checking it succeeded.
+fn snapshots(output: &Output) -> Vec<Value> {
+    assert!(output.status.success(), "{:?}", output);
+    items(output)
+        .into_iter()
+        .map(|item| match item {
+            Value::Map(mut pairs) => {
+                assert_eq!(pairs[0].0, Value::Text("timestamp".into()));
+                pairs.remove(0);
+                Value::Map(pairs)
+            }
+            item => panic!("unexpected item {:?}", item),
+        })
+        .collect()
+}
+
+#[test]
+fn counter() {
+    let dir = dir("counter");
+    let output = watch(&dir, &["-n", "10ms", "-c", "3", "--", "sh", "counter.sh"]);
+    assert_eq!(
+        snapshots(&output),
+        [
+            cbor!({"exit_code" => 0, "items" => ["run 1"]}).unwrap(),
+            cbor!({"exit_code" => 1, "items" => ["run 2"]}).unwrap(),
+            cbor!({"exit_code" => 0, "items" => ["run 3"]}).unwrap(),
+        ]
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn diff() {
+    let dir = dir("diff");
+    fs::write(dir.join("items.sh"), "cat items.txt\n").unwrap();
+    fs::write(dir.join("items.txt"), "same\n").unwrap();
+
+    // Only the first run is written when nothing changes.
+    let output = watch(
+        &dir,
+        &["-n", "10ms", "-c", "3", "--diff", "--", "sh", "items.sh"],
+    );
+    assert_eq!(
+        snapshots(&output),
+        [cbor!({"exit_code" => 0, "items" => ["same"]}).unwrap()]
+    );
+
+    // But every run that changes something is.
+    let output = watch(
+        &dir,
+        &["-n", "10ms", "-c", "3", "--diff", "--", "sh", "counter.sh"],
+    );
+    assert_eq!(snapshots(&output).len(), 3);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failures() {
+    let dir = dir("failures");
+    assert!(!watch(&dir, &["-c", "1", "--", "monch-no-such-program"])
+        .status
+        .success());
+    assert!(!watch(&dir, &["-n", "often", "--", "true"]).status.success());
+    assert!(!watch(&dir, &[]).status.success());
+    fs::remove_dir_all(dir).unwrap();
+}